@@ -5,6 +5,8 @@ use crate::config::{APUConfig, Config};
 use crate::hw::bus::{BusDir, Pins};
 use bitflags::bitflags;
 use crate::components::prelude::io;
+use crate::components::sgb::packet::CommandData;
+use crate::components::spc::spc::Spc;
 
 pub struct Apu {
     config: APUConfig,
@@ -26,6 +28,8 @@ pub struct Apu {
     ch3: CH3,
     ch4: CH4,
     mixer: Option<Mixer>,
+    /// The SNES sound processor, when running as a Super Game Boy.
+    spc: Option<Box<Spc>>,
 }
 
 bitflags! {
@@ -52,6 +56,34 @@ bitflags! {
     }
 }
 
+/// A copy of the APU is silent: the sound output stays where it was.
+impl Clone for Apu {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config,
+            mode: self.mode,
+            audio_enabled: self.audio_enabled,
+            is_ch_1_active: self.is_ch_1_active,
+            is_ch_2_active: self.is_ch_2_active,
+            is_ch_3_active: self.is_ch_3_active,
+            is_ch_4_active: self.is_ch_4_active,
+            div_apu: self.div_apu,
+            frame_sequencer: self.frame_sequencer,
+            left_volume: self.left_volume,
+            right_volume: self.right_volume,
+            vin_left: self.vin_left,
+            vin_right: self.vin_right,
+            panning: self.panning,
+            ch1: self.ch1.clone(),
+            ch2: self.ch2.clone(),
+            ch3: self.ch3.clone(),
+            ch4: self.ch4.clone(),
+            mixer: None,
+            spc: self.spc.clone(),
+        }
+    }
+}
+
 impl Apu {
     pub fn new(config: Config) -> Self {
         let mixer = if config.apu_config.master_enabled && !config.headless {
@@ -59,6 +91,11 @@ impl Apu {
         } else {
             None
         };
+        let spc = if config.sgb && config.mode == GBMode::DMG && config.apu_config.sgb_enabled {
+            Some(Box::new(Spc::new()))
+        } else {
+            None
+        };
 
         Self {
             config: config.apu_config,
@@ -80,6 +117,30 @@ impl Apu {
             ch3: CH3::new(),
             ch4: CH4::new(),
             mixer,
+            spc,
+        }
+    }
+
+    /// Take the sound output away, leaving the APU silent.
+    pub fn take_mixer(&mut self) -> Option<Mixer> {
+        self.mixer.take()
+    }
+
+    pub fn set_mixer(&mut self, mixer: Option<Mixer>) {
+        self.mixer = mixer;
+    }
+
+    /// SOUND: hand the effect and music codes to the SNES sound program.
+    pub fn sgb_command(&mut self, cmd: CommandData) {
+        if let Some(spc) = &mut self.spc {
+            spc.write_ports([cmd.byte(1), cmd.byte(2), cmd.byte(3), cmd.byte(4)]);
+        }
+    }
+
+    /// SOU_TRN: load and start a program on the SNES sound processor.
+    pub fn sgb_sound_upload(&mut self, data: &[u8]) {
+        if let Some(spc) = &mut self.spc {
+            spc.upload(data);
         }
     }
 
@@ -93,6 +154,11 @@ impl Apu {
         }
     }
 
+    /// A sound register or wave RAM as the CPU reads it.
+    pub fn peek(&self, a: u16) -> u8 {
+        self.read(a)
+    }
+
     fn on_div_apu_tick(&mut self) {
         if !self.audio_enabled {
             return;
@@ -164,6 +230,14 @@ impl Apu {
             self.ch4.tick_lfsr();
         }
 
+        if let Some(spc) = &mut self.spc {
+            spc.advance();
+            if let Some(mixer) = &mut self.mixer {
+                let (left, right) = spc.output();
+                mixer.feed_sgb(left, right);
+            }
+        }
+
         if self.mixer.is_some() {
             let (left, right) = self.mix();
             if let Some(mixer) = &mut self.mixer {
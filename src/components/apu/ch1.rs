@@ -4,6 +4,7 @@ use crate::components::apu::period_timer::PeriodTimer;
 use crate::components::apu::volume_envelope::VolumeEnvelope;
 use crate::components::memory::Memory;
 
+#[derive(Clone)]
 pub struct CH1 {
     pub dac_enabled: bool,
     sweep_pace: u8,
@@ -4,6 +4,7 @@ use crate::components::apu::period_timer::PeriodTimer;
 use crate::components::apu::volume_envelope::VolumeEnvelope;
 use crate::components::memory::Memory;
 
+#[derive(Clone)]
 pub struct CH2 {
     pub dac_enabled: bool,
     pub duty_cycle: DutyCycle,
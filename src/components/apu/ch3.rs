@@ -4,6 +4,7 @@ use crate::components::memory::Memory;
 use crate::components::mode::GBMode;
 use bitflags::bitflags;
 
+#[derive(Clone)]
 pub struct CH3 {
     pub dac_enabled: bool,
     pub output_level: OutputLevel,
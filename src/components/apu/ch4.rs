@@ -2,6 +2,7 @@ use crate::components::apu::length_counter::LengthCounter;
 use crate::components::apu::volume_envelope::VolumeEnvelope;
 use crate::components::memory::Memory;
 
+#[derive(Clone)]
 pub struct CH4 {
     pub dac_enabled: bool,
     clock_shift: u8,
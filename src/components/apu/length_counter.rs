@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct LengthCounter {
     pub enabled: bool,
     pub counter: u16,
@@ -14,6 +14,9 @@ const DRIFT_GAIN: f64 = 0.01;
 const MAX_DRIFT_CORRECTION: f64 = 0.005;
 const OUTPUT_GAIN: f32 = 1.0 / 960.0;
 const DC_BLOCK_HZ: f64 = 20.0;
+/// Brings full-scale 16-bit SNES samples down to roughly the Game Boy
+/// channels' loudest mix (±480).
+const SGB_SHIFT: u32 = 6;
 
 pub struct MixerProducer {
     left: BlipBuf,
@@ -125,6 +128,9 @@ impl MixerProducer {
 
 pub struct Mixer {
     producer: MixerProducer,
+    /// The SGB sound processor's latest output, held between its 32kHz
+    /// samples and summed with every Game Boy sample.
+    sgb: (i32, i32),
 }
 
 impl Mixer {
@@ -146,11 +152,18 @@ impl Mixer {
             _ => panic!("Unsupported format"),
         }
 
-        Self { producer }
+        Self {
+            producer,
+            sgb: (0, 0),
+        }
     }
 
     pub fn feed(&mut self, left: i32, right: i32) {
-        self.producer.feed(left, right);
+        self.producer.feed(left + self.sgb.0, right + self.sgb.1);
+    }
+
+    pub fn feed_sgb(&mut self, left: i16, right: i16) {
+        self.sgb = ((left as i32) >> SGB_SHIFT, (right as i32) >> SGB_SHIFT);
     }
 
     fn run_audio<T>(mut consumer: Consumer<(f32, f32)>, device: Device, config: StreamConfig)
@@ -4,6 +4,7 @@
 /// tick lets a mid-period frequency change take effect at the next reload, as
 /// on hardware. The reload is exact: a period of N signals every N ticks, with
 /// no off-by-one.
+#[derive(Clone)]
 pub struct PeriodTimer {
     counter: u16,
 }
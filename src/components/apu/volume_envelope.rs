@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct VolumeEnvelope {
     pub volume: f32,
     pub period: u16,
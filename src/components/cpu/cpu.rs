@@ -6,6 +6,7 @@ use crate::hw::bus::{BusDir, BusMaster, Pins};
 use crate::hw::interrupt::Interrupts;
 use std::collections::VecDeque;
 
+#[derive(Clone)]
 pub struct Cpu {
     pub reg: Registers,
     w: u8,
@@ -29,6 +30,8 @@ pub struct Cpu {
     /// latched. While set, the *next* M-cycle is the ISR low-byte push, and the
     /// motherboard samples live `IE & IF` at its start to pick the vector.
     isr_latch_armed: bool,
+    /// Address of the opcode fetched last: the instruction now executing.
+    opcode_pc: u16,
     micro: VecDeque<MicroOp>,
 }
 
@@ -51,6 +54,7 @@ impl Cpu {
             magic_break: false,
             isr_vector: 0,
             isr_latch_armed: false,
+            opcode_pc: 0,
             micro,
         }
     }
@@ -982,6 +986,36 @@ impl Cpu {
         true
     }
 
+    /// Address of the instruction fetched last, which runs next.
+    pub fn opcode_pc(&self) -> u16 {
+        self.opcode_pc
+    }
+
+    /// Whether the next M-cycle fetches an opcode.
+    pub fn fetching(&self) -> bool {
+        matches!(self.micro.front(), Some(MicroOp::Fetch))
+    }
+
+    /// Whether the next M-cycle reads an opcode or an operand from PC.
+    pub fn reading_code(&self) -> bool {
+        matches!(
+            self.micro.front(),
+            Some(MicroOp::Fetch | MicroOp::ImmZ | MicroOp::ImmW)
+        )
+    }
+
+    /// Abandon the instruction fetched last and carry on from `pc`, as if
+    /// `opcode` had just been fetched from it, without a bus cycle.
+    pub fn jump(&mut self, pc: u16, opcode: u8) {
+        self.micro.clear();
+        self.ir = opcode;
+        self.opcode_pc = pc;
+        self.reg.pc = pc.wrapping_add(1);
+        self.halted = false;
+        self.halt_bug = false;
+        self.decode();
+    }
+
     pub fn take_isr_latch(&mut self) -> bool {
         std::mem::take(&mut self.isr_latch_armed)
     }
@@ -1032,6 +1066,7 @@ impl Cpu {
         match self.micro.pop_front().expect("micro-op queue underflow") {
             MicroOp::Fetch => {
                 self.ir = pins.data;
+                self.opcode_pc = self.reg.pc;
 
                 if self.halt_bug {
                     self.halt_bug = false;
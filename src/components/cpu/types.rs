@@ -146,6 +146,7 @@ pub fn rot_of(y: u8) -> RotOp {
     }
 }
 
+#[derive(Clone)]
 pub enum MicroOp {
     Fetch,
     ImmZ,
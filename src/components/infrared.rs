@@ -0,0 +1,98 @@
+use crate::components::link::infrared::InfraredLink;
+use crate::components::prelude::*;
+
+const LED: u8 = 0x01;
+const NO_LIGHT: u8 = 0x02;
+const READ_ENABLE: u8 = 0xC0;
+
+/// The CGB infrared port (RP). Bit 0 drives the LED, and with both read
+/// enable bits set, bit 1 reads 0 while light from the partner's LED is
+/// coming in.
+pub struct Infrared {
+    rp: u8,
+    /// Base-clock cycles since power on; the time LED switches are sent at.
+    time: u64,
+    lit: bool,
+    link: Option<Box<dyn InfraredLink>>,
+    mode: GBMode,
+}
+
+/// A copy of the port is left pointing nowhere: the link stays where it was.
+impl Clone for Infrared {
+    fn clone(&self) -> Self {
+        Self {
+            rp: self.rp,
+            time: self.time,
+            lit: self.lit,
+            link: None,
+            mode: self.mode,
+        }
+    }
+}
+
+impl Infrared {
+    pub fn new(mode: GBMode) -> Self {
+        Self {
+            rp: 0,
+            time: 0,
+            lit: false,
+            link: None,
+            mode,
+        }
+    }
+
+    /// Point the port at another infrared port.
+    pub fn connect(&mut self, link: Box<dyn InfraredLink>) {
+        self.link = Some(link);
+    }
+
+    /// Stop pointing the port anywhere, giving back the link.
+    pub fn disconnect(&mut self) -> Option<Box<dyn InfraredLink>> {
+        self.link.take()
+    }
+
+    /// Called every base dot. Only looks at the partner while reading is
+    /// enabled, as that is the only time it shows.
+    pub fn tick(&mut self) {
+        self.time += 1;
+        if self.rp & READ_ENABLE == READ_ENABLE
+            && let Some(link) = &mut self.link
+        {
+            self.lit = link.receive(self.time);
+        }
+    }
+}
+
+impl Memory for Infrared {
+    fn read(&self, a: u16) -> u8 {
+        match a {
+            io::RP if self.mode == GBMode::DMG => 0xFF,
+            io::RP => {
+                let receiving = self.rp & READ_ENABLE == READ_ENABLE && self.lit;
+                self.rp | 0x3C | if receiving { 0x00 } else { NO_LIGHT }
+            }
+            _ => panic!("Read to unsupported Infrared address ({:#06x})!", a),
+        }
+    }
+
+    fn write(&mut self, a: u16, v: u8) {
+        match a {
+            io::RP if self.mode == GBMode::DMG => {}
+            io::RP => {
+                let led = self.rp & LED;
+                self.rp = v & (READ_ENABLE | LED);
+                if self.rp & LED != led
+                    && let Some(link) = &mut self.link
+                {
+                    link.send(self.time, v & LED != 0);
+                }
+                if self.rp & READ_ENABLE == READ_ENABLE
+                    && let Some(link) = &mut self.link
+                {
+                    self.lit = link.receive(self.time);
+                }
+            }
+            _ => panic!("Write to unsupported Infrared address ({:#06x})!", a),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::components::prelude::*;
+use crate::components::sgb::packet::{Command, CommandData, PacketReader};
 use crate::hw::interrupt::Interrupts;
 use bitflags::bitflags;
 
@@ -16,20 +17,32 @@ bitflags! {
     }
 }
 
+#[derive(Clone)]
 pub struct Joypad {
     matrix: u8,
     select: u8,
     previous_select: u8,
     pub interrupts: Interrupts,
+    /// SGB packet decoder, present when running as a Super Game Boy.
+    sgb: Option<PacketReader>,
+    sgb_command: Option<CommandData>,
+    /// Controllers enabled by MLT_REQ (1, 2 or 4).
+    players: u8,
+    /// Controller currently answering reads. Only player 1 has real input.
+    player: u8,
 }
 
 impl Joypad {
-    pub fn new() -> Self {
+    pub fn new(sgb: bool) -> Self {
         Self {
             matrix: 0xFF,
             select: 0x0F,
             previous_select: 0x0F,
             interrupts: Interrupts::empty(),
+            sgb: sgb.then(PacketReader::new),
+            sgb_command: None,
+            players: 1,
+            player: 0,
         }
     }
 
@@ -51,6 +64,35 @@ impl Joypad {
 
         self.previous_select = new_select;
     }
+
+    /// A completed SGB command, once.
+    pub fn take_sgb_command(&mut self) -> Option<CommandData> {
+        self.sgb_command.take()
+    }
+
+    fn sgb_write(&mut self, v: u8) {
+        let Some(reader) = &mut self.sgb else {
+            return;
+        };
+
+        // With multiplayer enabled, releasing P15 advances to the next
+        // controller.
+        if self.players > 1 && v & 0x20 != 0 && self.select & 0x20 == 0 {
+            self.player = (self.player + 1) & (self.players - 1);
+        }
+
+        if let Some(cmd) = reader.write(v) {
+            if cmd.command == Command::MltReq {
+                self.players = match cmd.byte(1) & 0x03 {
+                    0x01 => 2,
+                    0x03 => 4,
+                    _ => 1,
+                };
+                self.player = 0;
+            }
+            self.sgb_command = Some(cmd);
+        }
+    }
 }
 
 impl Memory for Joypad {
@@ -58,14 +100,21 @@ impl Memory for Joypad {
         match a {
             0xFF00 => {
                 let mut return_value = 0x0F;
+                // Controllers other than the first have nothing plugged in.
+                let matrix = if self.player == 0 { self.matrix } else { 0xFF };
 
                 // D-Pad
                 if (self.select & 0b0001_0000) == 0x00 {
-                    return_value &= self.matrix >> 4;
+                    return_value &= matrix >> 4;
                 }
                 // Buttons
                 if (self.select & 0b0010_0000) == 0x00 {
-                    return_value &= self.matrix & 0x0F;
+                    return_value &= matrix & 0x0F;
+                }
+                // SGB multiplayer: with neither line selected the low nibble
+                // identifies the current controller ($F for player 1).
+                if self.players > 1 && (self.select & 0x30) == 0x30 {
+                    return_value = 0x0F - self.player;
                 }
 
                 return_value |= self.select | 0xC0;
@@ -77,7 +126,10 @@ impl Memory for Joypad {
 
     fn write(&mut self, a: u16, v: u8) {
         match a {
-            0xFF00 => self.select = v & 0x30,
+            0xFF00 => {
+                self.sgb_write(v);
+                self.select = v & 0x30;
+            }
             _ => panic!("Write to unsupported Joypad address ({:#06x})!", a),
         }
 
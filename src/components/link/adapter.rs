@@ -0,0 +1,232 @@
+use crate::components::link::link::SerialLink;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+pub const PLAYERS: usize = 4;
+
+/// Header of each ping packet, followed by three STAT bytes.
+const PING_HEADER: u8 = 0xFE;
+const PING_LENGTH: usize = 4;
+/// A player answers the header (and the first STAT) with this to join.
+const ACK: u8 = 0x88;
+/// Player 1 sends a whole ping packet of these to start the game...
+const START: u8 = 0xAA;
+/// ...which the adapter acknowledges with four of these to everyone.
+const START_ACK: u8 = 0xCC;
+/// Every player sending a whole packet of these goes back to pinging.
+const RESTART: u8 = 0xFF;
+
+/// Base-clock cycles between bytes while pinging (about 1.2ms).
+const PING_PERIOD: u32 = 5120;
+
+enum Phase {
+    Ping,
+    /// Acknowledging the start, with the bytes left to send.
+    Starting(usize),
+    Transmission,
+}
+
+struct Hub {
+    phase: Phase,
+    /// Each player's shift register as last published or shifted.
+    sb: [u8; PLAYERS],
+    /// Clock pulses driven into each player, with the bit sent on each.
+    pulses: [VecDeque<bool>; PLAYERS],
+    connected: [bool; PLAYERS],
+    /// Position in the current ping packet or transmission cycle.
+    index: usize,
+    /// Transmission speed and packet size, as player 1 last asked.
+    rate: u8,
+    size: usize,
+    /// Whether player 1's ping packet so far has been all START bytes.
+    starting: bool,
+    /// Data collected from each player this cycle, and what the adapter
+    /// relays to everyone this cycle (the previous cycle's data).
+    collected: Vec<u8>,
+    relayed: Vec<u8>,
+    countdown: u32,
+}
+
+impl Hub {
+    fn period(&self) -> u32 {
+        match self.phase {
+            Phase::Ping | Phase::Starting(_) => PING_PERIOD,
+            // RATE's low nibble slows the bytes down from the fastest setting.
+            Phase::Transmission => (0x28 + (self.rate & 0x0F) as u32 * 6) * 128,
+        }
+    }
+
+    fn stat(&self, player: usize) -> u8 {
+        let connected = self
+            .connected
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c)
+            .fold(0, |mask, (p, _)| mask | (0x10 << p));
+        connected | (player as u8 + 1)
+    }
+
+    /// Clock one byte through every port at once.
+    fn transfer(&mut self) {
+        let mut received = [0; PLAYERS];
+        for (player, byte) in received.iter_mut().enumerate() {
+            let out = self.outgoing(player);
+            *byte = self.sb[player];
+            self.pulses[player].extend((0..8).rev().map(|bit| out & (1 << bit) != 0));
+            self.sb[player] = out;
+        }
+        self.incoming(received);
+    }
+
+    fn outgoing(&self, player: usize) -> u8 {
+        match self.phase {
+            Phase::Ping if self.index == 0 => PING_HEADER,
+            Phase::Ping => self.stat(player),
+            Phase::Starting(_) => START_ACK,
+            Phase::Transmission => self.relayed.get(self.index).copied().unwrap_or(0),
+        }
+    }
+
+    fn incoming(&mut self, received: [u8; PLAYERS]) {
+        match self.phase {
+            Phase::Ping => {
+                for (player, &byte) in received.iter().enumerate() {
+                    if self.index < 2 && byte == ACK {
+                        self.connected[player] = true;
+                    }
+                }
+                let first = received[0];
+                self.starting = (self.index == 0 || self.starting) && first == START;
+                match self.index {
+                    2 if first != START => self.rate = first,
+                    3 if first != START => self.size = (first as usize).clamp(1, 4),
+                    _ => {}
+                }
+
+                self.index += 1;
+                if self.index == PING_LENGTH {
+                    self.index = 0;
+                    if self.starting && self.connected[0] {
+                        self.phase = Phase::Starting(PING_LENGTH);
+                    }
+                }
+            }
+            Phase::Starting(left) => {
+                self.phase = if left > 1 {
+                    Phase::Starting(left - 1)
+                } else {
+                    self.collected = vec![0; PLAYERS * self.size];
+                    self.relayed = vec![0; PLAYERS * self.size];
+                    Phase::Transmission
+                };
+            }
+            Phase::Transmission => {
+                // Each player's packet is the first SIZE bytes of the cycle;
+                // what it sends for the rest is ignored.
+                if self.index < self.size {
+                    for (player, &byte) in received.iter().enumerate() {
+                        let byte = if self.connected[player] { byte } else { 0 };
+                        self.collected[player * self.size + self.index] = byte;
+                    }
+                }
+
+                self.index += 1;
+                if self.index == PLAYERS * self.size {
+                    self.index = 0;
+                    let restart = (0..PLAYERS).filter(|&p| self.connected[p]).all(|p| {
+                        self.collected[p * self.size..(p + 1) * self.size]
+                            .iter()
+                            .all(|&b| b == RESTART)
+                    });
+                    std::mem::swap(&mut self.collected, &mut self.relayed);
+                    if restart {
+                        self.phase = Phase::Ping;
+                        self.connected = [false; PLAYERS];
+                        self.starting = false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The DMG-07 Four Player Adapter. It clocks every connected Game Boy
+/// itself, so the games all listen on the external clock.
+///
+/// It starts out pinging: each player gets `FE` and three STAT bytes (the
+/// joined players in the high nibble, its own number in the low bits) and
+/// joins by answering `88 88`, followed by the transmission speed and
+/// packet size (only player 1's count). Once player 1 answers a whole ping
+/// with `AA`s, the adapter sends four `CC`s and moves on to relaying: every
+/// cycle of 4 × size bytes it collects each player's packet from the start
+/// of the cycle, and sends all four packets from the previous cycle back out
+/// to everyone. A cycle of `FF` packets from every player restarts the
+/// pinging.
+///
+/// The adapter runs on its own clock, driven by [`advance`](Self::advance).
+pub struct FourPlayerAdapter {
+    hub: Arc<Mutex<Hub>>,
+}
+
+/// The adapter's plug for one player.
+pub struct AdapterPort {
+    hub: Arc<Mutex<Hub>>,
+    player: usize,
+}
+
+impl FourPlayerAdapter {
+    pub fn new() -> Self {
+        Self {
+            hub: Arc::new(Mutex::new(Hub {
+                phase: Phase::Ping,
+                sb: [0; PLAYERS],
+                pulses: Default::default(),
+                connected: [false; PLAYERS],
+                index: 0,
+                rate: 0,
+                size: 1,
+                starting: false,
+                collected: Vec::new(),
+                relayed: Vec::new(),
+                countdown: PING_PERIOD,
+            })),
+        }
+    }
+
+    /// The plug for `player` (0 to 3).
+    pub fn port(&self, player: usize) -> AdapterPort {
+        assert!(player < PLAYERS, "the adapter only has {PLAYERS} ports");
+        AdapterPort {
+            hub: self.hub.clone(),
+            player,
+        }
+    }
+
+    /// Advance the adapter's clock by `cycles` base-clock cycles.
+    pub fn advance(&mut self, cycles: u32) {
+        let mut hub = self.hub.lock().unwrap();
+        let mut cycles = cycles;
+        while cycles >= hub.countdown {
+            cycles -= hub.countdown;
+            hub.transfer();
+            hub.countdown = hub.period();
+        }
+        hub.countdown -= cycles;
+    }
+}
+
+impl SerialLink for AdapterPort {
+    /// The adapter only ever drives the clock; a player trying to drive it
+    /// reads an idle line.
+    fn exchange(&mut self, _bit: bool) -> bool {
+        true
+    }
+
+    fn receive(&mut self) -> Option<bool> {
+        self.hub.lock().unwrap().pulses[self.player].pop_front()
+    }
+
+    fn publish(&mut self, sb: u8, _sc: u8, _double_speed: bool) {
+        self.hub.lock().unwrap().sb[self.player] = sb;
+    }
+}
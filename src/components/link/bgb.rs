@@ -0,0 +1,355 @@
+use crate::components::link::link::SerialLink;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const VERSION: u8 = 1;
+const SYNC1: u8 = 104;
+const SYNC2: u8 = 105;
+const SYNC3: u8 = 106;
+const STATUS: u8 = 108;
+const WANT_DISCONNECT: u8 = 109;
+
+/// Protocol version 1.4.
+const VERSION_MAJOR: u8 = 1;
+const VERSION_MINOR: u8 = 4;
+
+const STATUS_RUNNING: u8 = 0x01;
+/// SYNC1 control byte bits: a transfer on the internal clock, at the CGB's
+/// fast clock and in double speed.
+const SYNC1_CONTROL: u8 = 0x81;
+const SYNC1_FAST: u8 = 0x02;
+const SYNC1_DOUBLE_SPEED: u8 = 0x04;
+const SYNC2_CONTROL: u8 = 0x80;
+/// SYNC3 `b2` acknowledging a SYNC1 that found no transfer waiting for it.
+const SYNC3_NOT_READY: u8 = 1;
+
+/// Dots between looks at the socket.
+const POLL_DOTS: u64 = 64;
+/// Timestamp units (2MiHz) between the SYNC3 timestamps we send.
+const SYNC_INTERVAL: u32 = 0x2000;
+/// How far (in timestamp units, about a frame) we may run ahead of the peer
+/// before waiting for it.
+const MAX_LEAD: i64 = 35_112;
+/// Longest we wait for the peer to catch up or to answer a SYNC1.
+const WAIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// One eight-byte BGB packet.
+#[derive(Clone, Copy, Debug)]
+struct Packet {
+    command: u8,
+    b2: u8,
+    b3: u8,
+    b4: u8,
+    timestamp: u32,
+}
+
+impl Packet {
+    fn new(command: u8, b2: u8, b3: u8, b4: u8, timestamp: u32) -> Self {
+        Self {
+            command,
+            b2,
+            b3,
+            b4,
+            timestamp,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; 8] {
+        let t = self.timestamp.to_le_bytes();
+        [
+            self.command,
+            self.b2,
+            self.b3,
+            self.b4,
+            t[0],
+            t[1],
+            t[2],
+            t[3],
+        ]
+    }
+
+    fn from_bytes(b: [u8; 8]) -> Self {
+        Self::new(
+            b[0],
+            b[1],
+            b[2],
+            b[3],
+            u32::from_le_bytes([b[4], b[5], b[6], b[7]]),
+        )
+    }
+}
+
+/// A link cable to another emulator over TCP, speaking BGB's link protocol
+/// 1.4 (as also spoken by SameBoy and others).
+///
+/// The protocol works in whole bytes: the side on the internal clock sends
+/// SYNC1 with its byte and waits for the SYNC2 reply, and the other side's
+/// serial port then sees eight external clock pulses at once. A side with
+/// no transfer started on the external clock answers SYNC3 instead, and the
+/// byte goes nowhere. Both sides
+/// exchange timestamps (2MiHz units) with SYNC3 so neither runs more than a
+/// frame ahead.
+pub struct BgbLink {
+    stream: TcpStream,
+    packets: Receiver<Packet>,
+    connected: bool,
+    /// Serial-port dots seen, two to a base-clock dot in double speed.
+    dots: u64,
+    /// Base-clock time, in half dots (8MiHz).
+    time: u64,
+    last_sync: u32,
+    /// The peer's clock minus ours, from its first timestamp.
+    remote_offset: Option<i64>,
+    remote_time: u32,
+    /// The peer let a wait time out; we don't wait on it again until it
+    /// sends something.
+    stalled: bool,
+    status_sent: bool,
+    /// Our SB, kept up to date through publishes and transfers.
+    sb: u8,
+    /// Our SC and speed, as last published.
+    sc: u8,
+    double_speed: bool,
+    /// Bits of the partner's reply still to hand out while we clock.
+    reply: u8,
+    reply_bits: u8,
+    /// Clock pulses from a SYNC1, waiting for the serial port.
+    pulses: VecDeque<bool>,
+}
+
+impl BgbLink {
+    /// Connect to a peer listening at `addr`.
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::new(TcpStream::connect(addr)?)
+    }
+
+    /// Wait for a peer to connect on `addr`.
+    pub fn listen(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let (stream, _) = TcpListener::bind(addr)?.accept()?;
+        Self::new(stream)
+    }
+
+    /// Exchange versions with the peer on the other end of `stream`.
+    pub fn new(mut stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.write_all(&Packet::new(VERSION, VERSION_MAJOR, VERSION_MINOR, 0, 0).to_bytes())?;
+
+        let mut bytes = [0; 8];
+        stream.read_exact(&mut bytes)?;
+        let version = Packet::from_bytes(bytes);
+        if version.command != VERSION || (version.b2, version.b3) != (VERSION_MAJOR, VERSION_MINOR)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "peer does not speak BGB link protocol 1.4 (got {:?})",
+                    version
+                ),
+            ));
+        }
+
+        let (sender, packets) = channel();
+        let mut reader = stream.try_clone()?;
+        thread::spawn(move || {
+            let mut bytes = [0; 8];
+            while reader.read_exact(&mut bytes).is_ok() {
+                if sender.send(Packet::from_bytes(bytes)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut link = Self {
+            stream,
+            packets,
+            connected: true,
+            dots: 0,
+            time: 0,
+            last_sync: 0,
+            remote_offset: None,
+            stalled: false,
+            remote_time: 0,
+            status_sent: false,
+            sb: 0,
+            sc: 0,
+            double_speed: false,
+            reply: 0,
+            reply_bits: 0,
+            pulses: VecDeque::new(),
+        };
+        link.send_status();
+        Ok(link)
+    }
+
+    fn timestamp(&self) -> u32 {
+        ((self.time / 4) & 0x7FFF_FFFF) as u32
+    }
+
+    fn send(&mut self, packet: Packet) {
+        if self.connected && self.stream.write_all(&packet.to_bytes()).is_err() {
+            self.connected = false;
+        }
+    }
+
+    fn send_status(&mut self) {
+        self.send(Packet::new(STATUS, STATUS_RUNNING, 0, 0, 0));
+        self.status_sent = true;
+    }
+
+    /// React to a packet that isn't the reply we may be waiting for.
+    fn handle(&mut self, packet: Packet) {
+        self.stalled = false;
+        match packet.command {
+            // The peer is clocking us: answer with our byte, then let the
+            // serial port shift theirs in.
+            SYNC1 if self.sc & 0x81 == 0x80 => {
+                let timestamp = self.timestamp();
+                self.send(Packet::new(SYNC2, self.sb, SYNC2_CONTROL, 0, 0));
+                self.pulses
+                    .extend((0..8).rev().map(|bit| packet.b2 & (1 << bit) != 0));
+                self.sb = packet.b2;
+                self.note_time(packet.timestamp, timestamp);
+            }
+            SYNC1 => {
+                let timestamp = self.timestamp();
+                self.send(Packet::new(SYNC3, SYNC3_NOT_READY, 0, 0, 0));
+                self.note_time(packet.timestamp, timestamp);
+            }
+            SYNC3 if packet.b2 == 0 => {
+                let timestamp = self.timestamp();
+                self.note_time(packet.timestamp, timestamp);
+            }
+            STATUS if !self.status_sent => self.send_status(),
+            WANT_DISCONNECT => self.connected = false,
+            // Joypad, version and repeated status packets need no answer.
+            _ => {}
+        }
+    }
+
+    fn note_time(&mut self, remote: u32, local: u32) {
+        self.remote_offset
+            .get_or_insert(remote as i64 - local as i64);
+        self.remote_time = remote;
+    }
+
+    /// How far ahead of the peer we are, in timestamp units.
+    fn lead(&self) -> Option<i64> {
+        let offset = self.remote_offset?;
+        Some(self.timestamp() as i64 + offset - self.remote_time as i64)
+    }
+
+    fn poll(&mut self) {
+        if !self.connected {
+            return;
+        }
+
+        let timestamp = self.timestamp();
+        if timestamp.wrapping_sub(self.last_sync) & 0x7FFF_FFFF >= SYNC_INTERVAL {
+            self.last_sync = timestamp;
+            self.send(Packet::new(SYNC3, 0, 0, 0, timestamp));
+        }
+
+        loop {
+            match self.packets.try_recv() {
+                Ok(packet) => self.handle(packet),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    return;
+                }
+            }
+        }
+
+        // Too far ahead: wait for the peer's clock (or anything else) to
+        // catch up, but never hang on a peer that has stopped talking.
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        while self.connected && !self.stalled && self.lead().is_some_and(|lead| lead > MAX_LEAD) {
+            let now = Instant::now();
+            if now >= deadline {
+                self.stalled = true;
+                break;
+            }
+            match self.packets.recv_timeout(deadline - now) {
+                Ok(packet) => self.handle(packet),
+                Err(RecvTimeoutError::Timeout) => self.stalled = true,
+                Err(RecvTimeoutError::Disconnected) => self.connected = false,
+            }
+        }
+    }
+
+    /// The SYNC1 control byte for a transfer with our SC and speed.
+    fn control(&self) -> u8 {
+        let mut control = SYNC1_CONTROL;
+        if self.sc & 0x02 != 0 {
+            control |= SYNC1_FAST;
+        }
+        if self.double_speed {
+            control |= SYNC1_DOUBLE_SPEED;
+        }
+        control
+    }
+
+    /// Send a SYNC1 with our byte and wait for the peer's SYNC2.
+    fn transfer(&mut self) -> Option<u8> {
+        let timestamp = self.timestamp();
+        let control = self.control();
+        self.send(Packet::new(SYNC1, self.sb, control, 0, timestamp));
+
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        while self.connected {
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            match self.packets.recv_timeout(deadline - now) {
+                Ok(packet) if packet.command == SYNC2 => return Some(packet.b2),
+                // A peer that isn't ready acknowledges without data.
+                Ok(packet) if packet.command == SYNC3 && packet.b2 == SYNC3_NOT_READY => {
+                    return None;
+                }
+                Ok(packet) => self.handle(packet),
+                Err(RecvTimeoutError::Timeout) => return None,
+                Err(RecvTimeoutError::Disconnected) => self.connected = false,
+            }
+        }
+        None
+    }
+}
+
+impl SerialLink for BgbLink {
+    fn exchange(&mut self, _bit: bool) -> bool {
+        if self.reply_bits == 0 {
+            self.reply = self.transfer().unwrap_or(0xFF);
+            self.reply_bits = 8;
+        }
+        let received = self.reply & 0x80 != 0;
+        self.reply <<= 1;
+        self.reply_bits -= 1;
+        self.sb = (self.sb << 1) | received as u8;
+        received
+    }
+
+    fn receive(&mut self) -> Option<bool> {
+        if let Some(bit) = self.pulses.pop_front() {
+            return Some(bit);
+        }
+        self.dots += 1;
+        self.time += if self.double_speed { 1 } else { 2 };
+        if self.dots.is_multiple_of(POLL_DOTS) {
+            self.poll();
+        }
+        self.pulses.pop_front()
+    }
+
+    fn publish(&mut self, sb: u8, sc: u8, double_speed: bool) {
+        self.sb = sb;
+        self.sc = sc;
+        self.double_speed = double_speed;
+        // A new byte starts a new transfer.
+        self.reply_bits = 0;
+    }
+}
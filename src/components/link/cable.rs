@@ -0,0 +1,63 @@
+use crate::components::link::link::SerialLink;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// State shared by the two plugs.
+struct Wire {
+    /// Each side's shift register, as the other side sees it. The clock
+    /// master shifts its partner's copy as it goes, so bits it samples stay
+    /// right even before the partner has caught up with the pulses.
+    sb: [u8; 2],
+    /// Whether each side is waiting on the external clock.
+    external: [bool; 2],
+    /// Clock pulses driven into each side, with the bit sent on each.
+    pulses: [VecDeque<bool>; 2],
+}
+
+/// One plug of an in-process link cable. The two machines may run on
+/// different threads; they only need to stay within a few bits of each
+/// other in emulated time.
+pub struct CableEnd {
+    wire: Arc<Mutex<Wire>>,
+    side: usize,
+}
+
+/// A link cable connecting two serial ports in the same process.
+pub fn link_cable() -> (CableEnd, CableEnd) {
+    let wire = Arc::new(Mutex::new(Wire {
+        sb: [0; 2],
+        external: [true; 2],
+        pulses: [VecDeque::new(), VecDeque::new()],
+    }));
+    (
+        CableEnd {
+            wire: wire.clone(),
+            side: 0,
+        },
+        CableEnd { wire, side: 1 },
+    )
+}
+
+impl SerialLink for CableEnd {
+    fn exchange(&mut self, bit: bool) -> bool {
+        let mut wire = self.wire.lock().unwrap();
+        let other = 1 - self.side;
+        let received = wire.sb[other] & 0x80 != 0;
+        // A partner on its internal clock ignores ours.
+        if wire.external[other] {
+            wire.sb[other] = (wire.sb[other] << 1) | bit as u8;
+            wire.pulses[other].push_back(bit);
+        }
+        received
+    }
+
+    fn receive(&mut self) -> Option<bool> {
+        self.wire.lock().unwrap().pulses[self.side].pop_front()
+    }
+
+    fn publish(&mut self, sb: u8, sc: u8, _double_speed: bool) {
+        let mut wire = self.wire.lock().unwrap();
+        wire.sb[self.side] = sb;
+        wire.external[self.side] = sc & 0x01 == 0;
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// How many switches one side may have waiting before the oldest are taken
+/// as seen, for when the other side isn't reading (or isn't running).
+const MAX_EDGES: usize = 1024;
+
+/// What a CGB's infrared port sees of the outside world: another console's
+/// LED, or anything else that blinks.
+///
+/// Times are in base-clock cycles (4MiHz in both speed modes) as counted by
+/// the sending or receiving port, so pulse lengths survive the trip.
+pub trait InfraredLink: Send {
+    /// Our LED was switched on or off at `time`.
+    fn send(&mut self, time: u64, on: bool);
+
+    /// Whether the partner's LED is lit as of `time`.
+    fn receive(&mut self, time: u64) -> bool;
+}
+
+/// State shared by the two ends.
+struct Beams {
+    /// Each side's LED switches not yet seen by the other, oldest first.
+    edges: [VecDeque<(u64, bool)>; 2],
+    /// Each side's LED as last seen by the other.
+    lit: [bool; 2],
+}
+
+/// One side of an in-process infrared pair. The two ports see each other's
+/// LED switch at the same emulated time it happened, so the machines only
+/// need to be kept close together, as [`LinkedPair`] does.
+///
+/// [`LinkedPair`]: crate::hw::link::LinkedPair
+pub struct InfraredEnd {
+    beams: Arc<Mutex<Beams>>,
+    side: usize,
+}
+
+/// Two infrared ports facing each other.
+pub fn infrared_pair() -> (InfraredEnd, InfraredEnd) {
+    let beams = Arc::new(Mutex::new(Beams {
+        edges: [VecDeque::new(), VecDeque::new()],
+        lit: [false; 2],
+    }));
+    (
+        InfraredEnd {
+            beams: beams.clone(),
+            side: 0,
+        },
+        InfraredEnd { beams, side: 1 },
+    )
+}
+
+impl InfraredLink for InfraredEnd {
+    fn send(&mut self, time: u64, on: bool) {
+        let mut beams = self.beams.lock().unwrap();
+        let side = self.side;
+        beams.edges[side].push_back((time, on));
+        if beams.edges[side].len() > MAX_EDGES {
+            let (_, on) = beams.edges[side].pop_front().unwrap();
+            beams.lit[side] = on;
+        }
+    }
+
+    fn receive(&mut self, time: u64) -> bool {
+        let mut beams = self.beams.lock().unwrap();
+        let other = 1 - self.side;
+        while let Some(&(at, on)) = beams.edges[other].front() {
+            if at > time {
+                break;
+            }
+            beams.lit[other] = on;
+            beams.edges[other].pop_front();
+        }
+        beams.lit[other]
+    }
+}
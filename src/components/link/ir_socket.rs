@@ -0,0 +1,94 @@
+use crate::components::link::infrared::InfraredLink;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+
+/// How far behind the sender (in base-clock cycles, one frame) switches are
+/// played back. The two emulators run in bursts between frame-limiter
+/// sleeps, so this keeps a burst's pulses from arriving squashed together.
+const LATENCY: u64 = 70_224;
+
+/// An infrared port facing another emulator over TCP.
+///
+/// Each LED switch goes out as nine bytes: the sender's time (little-endian
+/// u64) and 0 or 1. The first switch received ties the sender's clock to
+/// ours, so later ones replay with their original spacing, [`LATENCY`]
+/// cycles after they happened.
+pub struct IrSocket {
+    stream: TcpStream,
+    switches: Receiver<(u64, bool)>,
+    connected: bool,
+    /// Our time minus the peer's, plus the latency.
+    offset: Option<i64>,
+    /// Switches received, in our time, waiting to be seen.
+    pending: VecDeque<(u64, bool)>,
+    lit: bool,
+}
+
+impl IrSocket {
+    /// Connect to a peer listening at `addr`.
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::new(TcpStream::connect(addr)?)
+    }
+
+    /// Wait for a peer to connect on `addr`.
+    pub fn listen(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let (stream, _) = TcpListener::bind(addr)?.accept()?;
+        Self::new(stream)
+    }
+
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let (sender, switches) = channel();
+        let mut reader = stream.try_clone()?;
+        thread::spawn(move || {
+            let mut bytes = [0; 9];
+            while reader.read_exact(&mut bytes).is_ok() {
+                let time = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+                if sender.send((time, bytes[8] != 0)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            stream,
+            switches,
+            connected: true,
+            offset: None,
+            pending: VecDeque::new(),
+            lit: false,
+        })
+    }
+}
+
+impl InfraredLink for IrSocket {
+    fn send(&mut self, time: u64, on: bool) {
+        let mut bytes = [0; 9];
+        bytes[..8].copy_from_slice(&time.to_le_bytes());
+        bytes[8] = on as u8;
+        if self.connected && self.stream.write_all(&bytes).is_err() {
+            self.connected = false;
+        }
+    }
+
+    fn receive(&mut self, time: u64) -> bool {
+        while let Ok((at, on)) = self.switches.try_recv() {
+            let offset = *self
+                .offset
+                .get_or_insert(time as i64 + LATENCY as i64 - at as i64);
+            self.pending
+                .push_back(((at as i64 + offset).max(0) as u64, on));
+        }
+        while let Some(&(at, on)) = self.pending.front() {
+            if at > time {
+                break;
+            }
+            self.lit = on;
+            self.pending.pop_front();
+        }
+        self.lit
+    }
+}
@@ -0,0 +1,22 @@
+/// One end of a link cable, as seen from a Game Boy's serial port.
+///
+/// The side running on its internal clock drives each transfer: for every
+/// clock pulse it calls [`exchange`](SerialLink::exchange), sending the bit
+/// shifted out of SB and getting back the partner's. A side waiting on the
+/// external clock picks up the pulses its partner drove with
+/// [`receive`](SerialLink::receive).
+pub trait SerialLink: Send {
+    /// Drive one clock pulse, sending `bit`. Returns the bit shifted in.
+    fn exchange(&mut self, bit: bool) -> bool;
+
+    /// The next clock pulse the partner drove into this end, with the bit it
+    /// sent. Polled every dot.
+    fn receive(&mut self) -> Option<bool> {
+        None
+    }
+
+    /// Called when the CPU writes SB or SC, or switches speed, so a partner
+    /// clocking this end knows what it will shift out and whether it is
+    /// listening at all. `sc` has the fast clock bit cleared on a DMG.
+    fn publish(&mut self, _sb: u8, _sc: u8, _double_speed: bool) {}
+}
@@ -0,0 +1,7 @@
+pub mod adapter;
+pub mod bgb;
+pub mod cable;
+pub mod infrared;
+pub mod ir_socket;
+pub mod link;
+pub mod printer;
@@ -0,0 +1,331 @@
+use crate::components::link::link::SerialLink;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const MAGIC: [u8; 2] = [0x88, 0x33];
+/// What the printer answers in the first byte after a packet.
+const DEVICE_ID: u8 = 0x81;
+
+const CMD_INIT: u8 = 0x01;
+const CMD_PRINT: u8 = 0x02;
+const CMD_DATA: u8 = 0x04;
+const CMD_STATUS: u8 = 0x0F;
+
+const STATUS_CHECKSUM: u8 = 0x01;
+const STATUS_BUSY: u8 = 0x02;
+const STATUS_FULL: u8 = 0x04;
+const STATUS_UNPROCESSED: u8 = 0x08;
+
+pub const PRINT_WIDTH: usize = 160;
+/// Bytes in one row of 20 tiles.
+const TILE_ROW_BYTES: usize = PRINT_WIDTH / 8 * 16;
+/// The printer's buffer holds one 160x144 image.
+const BUFFER_SIZE: usize = TILE_ROW_BYTES * 18;
+/// STATUS packets answered busy after a PRINT before the print is done.
+const BUSY_POLLS: u8 = 4;
+
+/// Shades of the thermal paper, lightest first.
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+/// One finished print job: 160 pixels wide, 8-bit grayscale.
+#[derive(Clone, Debug)]
+pub struct Print {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Print {
+    pub fn write_png(&self, path: &Path) -> io::Result<()> {
+        let w = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer
+            .write_image_data(&self.pixels)
+            .map_err(io::Error::other)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Magic(usize),
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    DeviceId,
+    Status,
+}
+
+/// The Game Boy Printer, plugged into the serial port in place of a second
+/// console.
+///
+/// Games talk to it in packets: `88 33`, command, compression flag, a
+/// little-endian length, the data, a little-endian checksum of everything
+/// after the magic, and two trailing bytes during which the printer answers
+/// with its ID and status. DATA packets (optionally RLE compressed) fill the
+/// buffer with tile rows, and PRINT prints them onto the paper strip. A job
+/// ends, and is handed out as a [`Print`], when a PRINT feeds paper after
+/// the image (a nonzero low margin nibble), as a Camera print or the last
+/// part of a Pokédex entry does.
+pub struct Printer {
+    state: State,
+    /// Bits of the byte coming in, and of the one going out.
+    incoming: u8,
+    bits: u8,
+    reply: u8,
+
+    command: u8,
+    compressed: bool,
+    length: usize,
+    data: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+
+    status: u8,
+    busy_polls: u8,
+    buffer: Vec<u8>,
+    /// The paper printed so far in the current job.
+    strip: Vec<u8>,
+
+    prints: Arc<Mutex<Vec<Print>>>,
+    output: Option<PathBuf>,
+}
+
+impl Printer {
+    /// A printer that keeps its prints in memory, and also writes each one
+    /// to `output` as `print_NNN.png` when given.
+    pub fn new(output: Option<PathBuf>) -> Self {
+        Self {
+            state: State::Magic(0),
+            incoming: 0,
+            bits: 0,
+            reply: 0,
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+            status: 0,
+            busy_polls: 0,
+            buffer: Vec::new(),
+            strip: Vec::new(),
+            prints: Arc::new(Mutex::new(Vec::new())),
+            output,
+        }
+    }
+
+    /// Finished print jobs. Stays valid after the printer is plugged in.
+    pub fn prints(&self) -> Arc<Mutex<Vec<Print>>> {
+        self.prints.clone()
+    }
+
+    /// Take one byte from the Game Boy; returns the byte to send back with
+    /// the next one.
+    fn byte(&mut self, v: u8) -> u8 {
+        if matches!(
+            self.state,
+            State::Command
+                | State::Compression
+                | State::LengthLow
+                | State::LengthHigh
+                | State::Data
+        ) {
+            self.checksum = self.checksum.wrapping_add(v as u16);
+        }
+
+        match self.state {
+            State::Magic(i) => {
+                self.state = if v == MAGIC[i] {
+                    if i + 1 == MAGIC.len() {
+                        State::Command
+                    } else {
+                        State::Magic(i + 1)
+                    }
+                } else {
+                    State::Magic(0)
+                };
+                if self.state == State::Command {
+                    self.checksum = 0;
+                    self.data.clear();
+                }
+            }
+            State::Command => {
+                self.command = v;
+                self.state = State::Compression;
+            }
+            State::Compression => {
+                self.compressed = v & 0x01 != 0;
+                self.state = State::LengthLow;
+            }
+            State::LengthLow => {
+                self.length = v as usize;
+                self.state = State::LengthHigh;
+            }
+            State::LengthHigh => {
+                self.length |= (v as usize) << 8;
+                self.state = if self.length > 0 {
+                    State::Data
+                } else {
+                    State::ChecksumLow
+                };
+            }
+            State::Data => {
+                self.data.push(v);
+                if self.data.len() == self.length {
+                    self.state = State::ChecksumLow;
+                }
+            }
+            State::ChecksumLow => {
+                self.received_checksum = v as u16;
+                self.state = State::ChecksumHigh;
+            }
+            State::ChecksumHigh => {
+                self.received_checksum |= (v as u16) << 8;
+                self.state = State::DeviceId;
+                return DEVICE_ID;
+            }
+            State::DeviceId => {
+                if self.received_checksum == self.checksum {
+                    self.status &= !STATUS_CHECKSUM;
+                    self.execute();
+                } else {
+                    self.status |= STATUS_CHECKSUM;
+                }
+                self.state = State::Status;
+                return self.status;
+            }
+            State::Status => self.state = State::Magic(0),
+        }
+        0x00
+    }
+
+    fn execute(&mut self) {
+        match self.command {
+            CMD_INIT => {
+                self.buffer.clear();
+                self.busy_polls = 0;
+                self.status = 0;
+            }
+            CMD_DATA => {
+                if self.compressed {
+                    decompress(&self.data, &mut self.buffer);
+                } else {
+                    self.buffer.extend_from_slice(&self.data);
+                }
+                self.buffer.truncate(BUFFER_SIZE);
+                if self.buffer.len() == BUFFER_SIZE {
+                    self.status |= STATUS_FULL;
+                }
+                if !self.buffer.is_empty() {
+                    self.status |= STATUS_UNPROCESSED;
+                }
+            }
+            CMD_PRINT => {
+                let [sheets, margins, palette, _exposure] = match self.data[..] {
+                    [a, b, c, d, ..] => [a, b, c, d],
+                    _ => return,
+                };
+                if sheets > 0 {
+                    self.print(palette);
+                }
+                self.buffer.clear();
+                self.status &= !(STATUS_FULL | STATUS_UNPROCESSED);
+                self.status |= STATUS_BUSY;
+                self.busy_polls = BUSY_POLLS;
+                if margins & 0x0F != 0 {
+                    self.cut();
+                }
+            }
+            CMD_STATUS if self.busy_polls > 0 => {
+                self.busy_polls -= 1;
+                if self.busy_polls == 0 {
+                    self.status &= !STATUS_BUSY;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Print the buffered tile rows onto the strip with `palette` (BGP
+    /// layout; 0 is taken as the default $E4).
+    fn print(&mut self, palette: u8) {
+        let palette = if palette == 0 { 0xE4 } else { palette };
+        let rows = self.buffer.len() / TILE_ROW_BYTES;
+        for y in 0..rows * 8 {
+            for x in 0..PRINT_WIDTH {
+                let tile = (y / 8) * (PRINT_WIDTH / 8) + x / 8;
+                let offset = tile * 16 + (y % 8) * 2;
+                let bit = 7 - (x % 8);
+                let lo = (self.buffer[offset] >> bit) & 1;
+                let hi = (self.buffer[offset + 1] >> bit) & 1;
+                let shade = (palette >> ((hi << 1 | lo) * 2)) & 0x03;
+                self.strip.push(SHADES[shade as usize]);
+            }
+        }
+    }
+
+    /// Feed the paper out and tear off the finished job.
+    fn cut(&mut self) {
+        if self.strip.is_empty() {
+            return;
+        }
+        let print = Print {
+            width: PRINT_WIDTH,
+            height: self.strip.len() / PRINT_WIDTH,
+            pixels: std::mem::take(&mut self.strip),
+        };
+
+        let mut prints = self.prints.lock().unwrap();
+        if let Some(dir) = &self.output {
+            let path = dir.join(format!("print_{:03}.png", prints.len()));
+            if let Err(err) = print.write_png(&path) {
+                eprintln!("Failed to write print to \"{}\": {}", path.display(), err);
+            }
+        }
+        prints.push(print);
+    }
+}
+
+/// Expand the printer's RLE: a control byte with bit 7 set repeats the next
+/// byte (control & $7F) + 2 times; otherwise (control + 1) literal bytes
+/// follow.
+fn decompress(data: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < data.len() {
+        let control = data[i];
+        i += 1;
+        if control & 0x80 != 0 {
+            let Some(&v) = data.get(i) else { break };
+            out.extend(std::iter::repeat_n(v, (control & 0x7F) as usize + 2));
+            i += 1;
+        } else {
+            let end = (i + control as usize + 1).min(data.len());
+            out.extend_from_slice(&data[i..end]);
+            i = end;
+        }
+    }
+}
+
+impl SerialLink for Printer {
+    fn exchange(&mut self, bit: bool) -> bool {
+        let out = self.reply & 0x80 != 0;
+        self.reply <<= 1;
+        self.incoming = (self.incoming << 1) | bit as u8;
+        self.bits += 1;
+        if self.bits == 8 {
+            self.bits = 0;
+            self.reply = self.byte(self.incoming);
+        }
+        out
+    }
+}
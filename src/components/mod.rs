@@ -1,11 +1,15 @@
 pub mod apu;
 pub mod cpu;
+pub mod infrared;
 pub mod joypad;
+pub mod link;
 pub mod memory;
 pub mod mode;
 pub mod ppu;
 pub mod registers;
 pub mod serial;
+pub mod sgb;
+pub mod spc;
 
 #[allow(unused_imports)]
 pub mod prelude {
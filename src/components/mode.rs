@@ -13,3 +13,31 @@ pub enum CCMode {
     GBA,
     SGB,
 }
+
+/// A Game Boy model, for starting a machine where its boot ROM would have
+/// left it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    /// The first DMG revision, whose boot ROM differs from the later ones.
+    Dmg0,
+    Dmg,
+    /// The Game Boy Pocket and Light.
+    Mgb,
+    Sgb,
+    Sgb2,
+    Cgb,
+}
+
+impl Model {
+    /// The mode it runs in.
+    pub fn mode(self) -> GBMode {
+        match self {
+            Model::Cgb => GBMode::CGB,
+            _ => GBMode::DMG,
+        }
+    }
+
+    pub fn is_sgb(self) -> bool {
+        matches!(self, Model::Sgb | Model::Sgb2)
+    }
+}
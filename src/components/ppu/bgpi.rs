@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct BGPI {
     pub address: u8,
     pub auto_increment: bool,
@@ -1,3 +1,5 @@
+use crate::components::mode::CCMode;
+
 const GB_COLOR_LUT_LEN: usize = 0x8000;
 const GAMMA: f32 = 2.2;
 const CGB_COLOR_CURVE: [u8; 32] = [
@@ -14,6 +16,7 @@ const SGB_COLOR_CURVE: [u8; 32] = [
 ];
 
 // Adapted from SameBoy Color Correction
+#[derive(Clone)]
 pub struct ColorCorrection {
     pub true_color_lut: [[u8; 3]; GB_COLOR_LUT_LEN],
     pub cgb_color_lut: [[u8; 3]; GB_COLOR_LUT_LEN],
@@ -47,6 +50,17 @@ impl ColorCorrection {
         }
     }
 
+    /// Convert a 15-bit BGR colour to RGB with the configured correction.
+    pub fn map(&self, mode: CCMode, color: u16) -> [u8; 3] {
+        let color = (color & 0x7FFF) as usize;
+        match mode {
+            CCMode::True => self.true_color_lut[color],
+            CCMode::CGB => self.cgb_color_lut[color],
+            CCMode::GBA => self.gba_color_lut[color],
+            CCMode::SGB => self.sgb_color_lut[color],
+        }
+    }
+
     fn true_color(r: u8, g: u8, b: u8) -> [u8; 3] {
         let r = ((r as u16 * 0xFF + 0xF) / 0x1F) as u8;
         let g = ((g as u16 * 0xFF + 0xF) / 0x1F) as u8;
@@ -60,6 +60,7 @@ pub struct Emit {
 
 /// BG pixel FIFO, up to two tiles deep so the fetcher runs a tile ahead of
 /// the shifter.
+#[derive(Clone)]
 struct BgFifo {
     data: [BgPixel; 16],
     head: usize,
@@ -96,6 +97,7 @@ impl BgFifo {
 /// OBJ FIFO: an 8-slot shift register aligned to the pixel about to be
 /// emitted. A sprite fetch overlays its 8 pixels; each emitted BG pixel shifts
 /// one OBJ pixel out in lockstep, backfilling transparent.
+#[derive(Clone)]
 struct ObjFifo {
     data: [ObjPixel; 8],
     head: usize,
@@ -140,6 +142,7 @@ struct SpriteFetch {
     stage: SpriteStage,
 }
 
+#[derive(Clone)]
 pub struct Pipeline {
     // BG fetcher
     step: FetchStep,
@@ -3,3 +3,4 @@ mod cc;
 mod fetcher;
 pub mod ppu;
 mod structs;
+pub mod viewer;
@@ -2,7 +2,10 @@ use crate::components::ppu::bgpi::BGPI;
 use crate::components::ppu::cc::ColorCorrection;
 use crate::components::ppu::fetcher::*;
 use crate::components::ppu::structs::*;
+use crate::components::ppu::viewer::VideoMemory;
 use crate::components::prelude::*;
+use crate::components::sgb::packet::CommandData;
+use crate::components::sgb::sgb::{SGB_SCREEN_H, SGB_SCREEN_W, Sgb};
 use crate::config::{Color, Config, PPUConfig, Palette};
 use crate::framebuffer::FramebufferWriter;
 use crate::hw::interrupt::Interrupts;
@@ -38,6 +41,7 @@ const LY_153_ROLLOVER: u32 = 4;
 /// edge position, measured against the mealybug m3 mode-2 handlers.
 const MODE2_LOOKAHEAD: u32 = 4;
 
+#[derive(Clone)]
 pub struct PPU {
     mode: GBMode,
     rom_is_cgb: bool,
@@ -88,10 +92,20 @@ pub struct PPU {
     /// but STAT still reports mode 0 (and the mode-2 STAT source does not fire):
     /// the "mode-0 readback" first-frame quirk pinned by mooneye stat_lyc_onoff.
     first_line_after_on: bool,
+    /// Super Game Boy display. When present, DMG pixels are recorded as shades
+    /// and coloured by the SGB at VBlank instead of going through `palette`.
+    sgb: Option<Box<Sgb>>,
 }
 
 impl PPU {
-    pub fn new(config: Config, framebuffer: FramebufferWriter, rom_is_cgb: bool) -> Self {
+    pub fn new(config: Config, mut framebuffer: FramebufferWriter, rom_is_cgb: bool) -> Self {
+        let sgb = if config.sgb && config.mode == GBMode::DMG {
+            framebuffer.resize(SGB_SCREEN_W, SGB_SCREEN_H);
+            Some(Box::new(Sgb::new()))
+        } else {
+            None
+        };
+
         Self {
             mode: config.mode,
             rom_is_cgb,
@@ -129,6 +143,7 @@ impl PPU {
             window_y_condition: false,
             bgp_glitch: None,
             first_line_after_on: false,
+            sgb,
         }
     }
 
@@ -196,6 +211,10 @@ impl PPU {
         } else if self.ly == 144 {
             self.ppu_mode = PPUMode::VBlank;
             self.interrupts |= Interrupts::V_BLANK;
+            if let Some(sgb) = &mut self.sgb {
+                let (cc, cc_mode) = (&self.cc, self.ppu_config.cc_mode);
+                sgb.render(&mut self.framebuffer, |c| cc.map(cc_mode, c));
+            }
             self.framebuffer.submit_frame();
             // Y condition is cleared each VBlank (Pandocs).
             self.window_y_condition = false;
@@ -372,9 +391,7 @@ impl PPU {
         } else {
             // The pixel emitted on the dot a BGP write lands shows old|new.
             let pal = self.bgp_glitch.unwrap_or(self.bgp);
-            let c = Self::grey_to_l(self.ppu_config.palette, pal, color as usize);
-            self.framebuffer
-                .set_pixel(c.r(), c.g(), c.b(), x, self.ly as usize);
+            self.set_dmg_pixel(x, pal, color as usize);
         }
     }
 
@@ -397,11 +414,17 @@ impl PPU {
             let c = (self.ocpd[pa] as u16) | ((self.ocpd[pa + 1] as u16) << 8) & 0x7FFF;
             self.set_rgb_mapped(x, c);
         } else {
-            let c = if obj.palette {
-                Self::grey_to_l(self.ppu_config.palette, self.obp1, obj.color as usize)
-            } else {
-                Self::grey_to_l(self.ppu_config.palette, self.obp0, obj.color as usize)
-            };
+            let obp = if obj.palette { self.obp1 } else { self.obp0 };
+            self.set_dmg_pixel(x, obp, obj.color as usize);
+        }
+    }
+
+    /// Write a DMG pixel of colour `i` through `palette` (BGP/OBP0/OBP1).
+    fn set_dmg_pixel(&mut self, x: usize, palette: u8, i: usize) {
+        if let Some(sgb) = &mut self.sgb {
+            sgb.set_shade(x, self.ly as usize, (palette >> (2 * i)) & 0x03);
+        } else {
+            let c = Self::grey_to_l(self.ppu_config.palette, palette, i);
             self.framebuffer
                 .set_pixel(c.r(), c.g(), c.b(), x, self.ly as usize);
         }
@@ -438,12 +461,7 @@ impl PPU {
     }
 
     fn set_rgb_mapped(&mut self, x: usize, color: u16) {
-        let color = match self.ppu_config.cc_mode {
-            CCMode::True => self.cc.true_color_lut[color as usize],
-            CCMode::CGB => self.cc.cgb_color_lut[color as usize],
-            CCMode::GBA => self.cc.gba_color_lut[color as usize],
-            CCMode::SGB => self.cc.sgb_color_lut[color as usize],
-        };
+        let color = self.cc.map(self.ppu_config.cc_mode, color);
 
         self.framebuffer
             .set_pixel(color[0], color[1], color[2], x, self.ly as usize);
@@ -453,6 +471,55 @@ impl PPU {
         self.boot_rom_enabled = false;
     }
 
+    /// Forward a command received over the SGB packet protocol.
+    pub fn sgb_command(&mut self, cmd: CommandData) {
+        if let Some(sgb) = &mut self.sgb {
+            sgb.command(cmd);
+        }
+    }
+
+    /// The data of a completed SOU_TRN, for the SGB sound processor.
+    pub fn take_sgb_sound_upload(&mut self) -> Option<Box<[u8]>> {
+        self.sgb.as_mut()?.take_sound_upload().map(|data| data as Box<[u8]>)
+    }
+
+    /// The scanline being drawn (LY, without the line-153 early rollover).
+    pub fn ly(&self) -> u8 {
+        self.ly
+    }
+
+    pub fn ppu_mode(&self) -> PPUMode {
+        self.ppu_mode
+    }
+
+    /// The dot within the current line, 0-455.
+    pub fn line_dot(&self) -> u32 {
+        self.cycle_count
+    }
+
+    /// VRAM, OAM and the palettes, for the viewers.
+    pub fn video(&self) -> VideoMemory<'_> {
+        VideoMemory {
+            vram: &self.vram,
+            oam: &self.oam,
+            bcpd: &self.bcpd,
+            ocpd: &self.ocpd,
+            lcdc: self.lcdc,
+            scy: self.scy,
+            scx: self.scx,
+            wy: self.wy,
+            wx: self.wx,
+            bgp: self.bgp,
+            obp0: self.obp0,
+            obp1: self.obp1,
+            cgb: self.mode == GBMode::CGB,
+            cgb_mode: self.mode == GBMode::CGB && self.use_cgb_mode(),
+            palette: self.ppu_config.palette,
+            cc: &self.cc,
+            cc_mode: self.ppu_config.cc_mode,
+        }
+    }
+
     /// Drain the interrupt requests and the HBlank edge produced since the last
     /// call, clearing them. Used by the peer-chip bus to build its `Ticked`
     /// result instead of reaching into the public fields.
@@ -484,6 +551,36 @@ impl PPU {
         self.oam[index as usize] = v;
     }
 
+    /// Both VRAM banks, laid end to end.
+    pub fn vram(&self) -> &[u8; 0x4000] {
+        &self.vram
+    }
+
+    pub fn vram_mut(&mut self) -> &mut [u8; 0x4000] {
+        &mut self.vram
+    }
+
+    pub fn oam(&self) -> &[u8; 0xA0] {
+        &self.oam
+    }
+
+    pub fn framebuffer(&self) -> &FramebufferWriter {
+        &self.framebuffer
+    }
+
+    pub fn framebuffer_mut(&mut self) -> &mut FramebufferWriter {
+        &mut self.framebuffer
+    }
+
+    pub fn oam_mut(&mut self) -> &mut [u8; 0xA0] {
+        &mut self.oam
+    }
+
+    /// VRAM at the current bank, whatever mode the PPU is in.
+    pub fn read_vram_direct(&self, a: u16) -> u8 {
+        self.read_vram(a, self.vram_bank)
+    }
+
     /// Unconditional VRAM write at the current bank, for the HDMA/GPDMA engine
     /// (which drives VRAM directly, not through the CPU's mode-gated port).
     pub fn write_vram_direct(&mut self, a: u16, v: u8) {
@@ -673,8 +770,12 @@ impl Memory for PPU {
 
                     match self.mode {
                         GBMode::DMG => {
-                            let color = self.ppu_config.palette.off;
-                            self.framebuffer.fill(color.r(), color.g(), color.b());
+                            if let Some(sgb) = &mut self.sgb {
+                                sgb.clear_screen();
+                            } else {
+                                let color = self.ppu_config.palette.off;
+                                self.framebuffer.fill(color.r(), color.g(), color.b());
+                            }
                         }
                         GBMode::CGB => {
                             self.framebuffer.clear();
@@ -0,0 +1,386 @@
+use crate::components::mode::CCMode;
+use crate::components::ppu::cc::ColorCorrection;
+use crate::components::ppu::structs::LCDC;
+use crate::config::Palette;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+/// Tiles per bank: $8000-$97FF.
+const TILES: usize = 384;
+/// Tiles per row of the tile sheet.
+const SHEET_COLUMNS: usize = 16;
+/// Sprites per row of the OAM sheet.
+const OAM_COLUMNS: usize = 8;
+/// Side of one palette swatch, in pixels.
+const SWATCH: usize = 8;
+
+/// What the viewport is outlined in.
+const OUTLINE: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+
+/// An RGBA image, 4 bytes per pixel, row by row.
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    /// A transparent image.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; 4 * width * height],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = 4 * (y * self.width + x);
+        self.data[i..i + 4].try_into().unwrap()
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, rgba: [u8; 4]) {
+        let i = 4 * (y * self.width + x);
+        self.data[i..i + 4].copy_from_slice(&rgba);
+    }
+
+    pub fn write_png(&self, path: &Path) -> io::Result<()> {
+        let w = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer
+            .write_image_data(&self.data)
+            .map_err(io::Error::other)
+    }
+}
+
+/// One OAM entry, decoded.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Sprite {
+    /// Position in OAM, 0-39.
+    pub index: u8,
+    /// Screen position plus 16, as stored.
+    pub y: u8,
+    /// Screen position plus 8, as stored.
+    pub x: u8,
+    pub tile: u8,
+    pub attributes: u8,
+}
+
+impl Sprite {
+    /// Drawn behind BG colours 1-3.
+    pub fn behind_bg(&self) -> bool {
+        self.attributes & 0x80 != 0
+    }
+
+    pub fn y_flip(&self) -> bool {
+        self.attributes & 0x40 != 0
+    }
+
+    pub fn x_flip(&self) -> bool {
+        self.attributes & 0x20 != 0
+    }
+
+    /// OBP0 or OBP1, in DMG mode.
+    pub fn dmg_palette(&self) -> usize {
+        (self.attributes >> 4) as usize & 1
+    }
+
+    /// The VRAM bank the tile is in, in CGB mode.
+    pub fn bank(&self) -> usize {
+        (self.attributes >> 3) as usize & 1
+    }
+
+    /// OBJ palette 0-7, in CGB mode.
+    pub fn cgb_palette(&self) -> usize {
+        self.attributes as usize & 7
+    }
+}
+
+impl fmt::Display for Sprite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}  Y={:3} X={:3}  tile=${:02X}  attr=${:02X}  {}{}{}  OBP{}  bank {}  palette {}",
+            self.index,
+            self.y,
+            self.x,
+            self.tile,
+            self.attributes,
+            if self.behind_bg() { 'P' } else { '-' },
+            if self.y_flip() { 'V' } else { '-' },
+            if self.x_flip() { 'H' } else { '-' },
+            self.dmg_palette(),
+            self.bank(),
+            self.cgb_palette()
+        )
+    }
+}
+
+/// A look at the PPU's memory and registers, for rendering what is in VRAM,
+/// OAM and the palettes. Colours are the ones the PPU would show: the
+/// configured shades for DMG, and corrected CGB colours otherwise.
+pub struct VideoMemory<'a> {
+    pub(super) vram: &'a [u8; 0x4000],
+    pub(super) oam: &'a [u8; 0xA0],
+    pub(super) bcpd: &'a [u8; 64],
+    pub(super) ocpd: &'a [u8; 64],
+    pub(super) lcdc: LCDC,
+    pub(super) scy: u8,
+    pub(super) scx: u8,
+    pub(super) wy: u8,
+    pub(super) wx: u8,
+    pub(super) bgp: u8,
+    pub(super) obp0: u8,
+    pub(super) obp1: u8,
+    /// Running on CGB hardware...
+    pub(super) cgb: bool,
+    /// ...and in CGB mode, with attributes and all eight palettes.
+    pub(super) cgb_mode: bool,
+    pub(super) palette: Palette,
+    pub(super) cc: &'a ColorCorrection,
+    pub(super) cc_mode: CCMode,
+}
+
+impl VideoMemory<'_> {
+    /// Every tile in both VRAM banks, bank 0 on the left, 16 tiles a row in
+    /// the configured DMG shades.
+    pub fn tiles(&self) -> Image {
+        let rows = TILES / SHEET_COLUMNS;
+        let mut image = Image::new(2 * SHEET_COLUMNS * 8, rows * 8);
+        for bank in 0..2 {
+            for tile in 0..TILES {
+                let left = (bank * SHEET_COLUMNS + tile % SHEET_COLUMNS) * 8;
+                let top = tile / SHEET_COLUMNS * 8;
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let color = self.tile_pixel(bank, tile * 16, x, y);
+                        image.set_pixel(left + x, top + y, self.shade(color));
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    /// The whole 256x256 background map LCDC selects, with the screen's
+    /// viewport outlined.
+    pub fn bg_map(&self) -> Image {
+        let mut image = self.map(self.lcdc.contains(LCDC::BG_TILE_MAP_AREA));
+        outline(&mut image, self.scx as usize, self.scy as usize, 160, 144);
+        image
+    }
+
+    /// The whole 256x256 window map LCDC selects, with the part on screen
+    /// outlined while the window is enabled.
+    pub fn window_map(&self) -> Image {
+        let mut image = self.map(self.lcdc.contains(LCDC::WINDOW_AREA));
+        if self.lcdc.contains(LCDC::WINDOW_ENABLE) && self.wx < 167 && self.wy < 144 {
+            // The window starts WX - 7 pixels in, cut off on the left below 7.
+            let left = 7usize.saturating_sub(self.wx as usize);
+            let width = 167 - (self.wx as usize).max(7);
+            outline(&mut image, left, 0, width, 144 - self.wy as usize);
+        }
+        image
+    }
+
+    /// The 40 OAM entries.
+    pub fn sprites(&self) -> Vec<Sprite> {
+        self.oam
+            .chunks(4)
+            .enumerate()
+            .map(|(index, entry)| Sprite {
+                index: index as u8,
+                y: entry[0],
+                x: entry[1],
+                tile: entry[2],
+                attributes: entry[3],
+            })
+            .collect()
+    }
+
+    /// Every sprite as it would be drawn, in OAM order, 8 a row in 8x16
+    /// cells; colour 0 is transparent.
+    pub fn oam(&self) -> Image {
+        let tall = self.lcdc.contains(LCDC::OBJ_SIZE);
+        let height = if tall { 16 } else { 8 };
+        let mut image = Image::new(OAM_COLUMNS * 8, 40 / OAM_COLUMNS * 16);
+        for sprite in self.sprites() {
+            let left = sprite.index as usize % OAM_COLUMNS * 8;
+            let top = sprite.index as usize / OAM_COLUMNS * 16;
+            let tile = if tall {
+                sprite.tile & 0xFE
+            } else {
+                sprite.tile
+            } as usize;
+            let bank = if self.cgb_mode { sprite.bank() } else { 0 };
+            let palette = if self.cgb_mode {
+                sprite.cgb_palette()
+            } else {
+                sprite.dmg_palette()
+            };
+            for y in 0..height {
+                let row = if sprite.y_flip() { height - 1 - y } else { y };
+                for x in 0..8 {
+                    let column = if sprite.x_flip() { 7 - x } else { x };
+                    let color = self.tile_pixel(bank, tile * 16 + row / 8 * 16, column, row % 8);
+                    if color != 0 {
+                        image.set_pixel(left + x, top + y, self.color(true, palette, color));
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    /// The eight BG palettes on the left and eight OBJ palettes on the right,
+    /// a palette a row. On DMG hardware, BGP and OBP0/OBP1 take the first
+    /// rows instead.
+    pub fn palettes(&self) -> Image {
+        let mut image = Image::new(9 * SWATCH, 8 * SWATCH);
+        for (obj, left) in [(false, 0), (true, 5 * SWATCH)] {
+            let count = match (self.cgb, obj) {
+                (true, _) => 8,
+                (false, false) => 1,
+                (false, true) => 2,
+            };
+            for palette in 0..count {
+                for color in 0..4 {
+                    let rgba = if self.cgb {
+                        self.cgb_color(self.cgb_raw(obj, palette, color))
+                    } else {
+                        self.color(obj, palette, color as u8)
+                    };
+                    for y in 0..SWATCH {
+                        for x in 0..SWATCH {
+                            image.set_pixel(left + color * SWATCH + x, palette * SWATCH + y, rgba);
+                        }
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    /// Colour `color` of CGB palette `palette` as stored: 15-bit BGR.
+    pub fn cgb_raw(&self, obj: bool, palette: usize, color: usize) -> u16 {
+        let data = if obj { self.ocpd } else { self.bcpd };
+        let i = (palette & 7) * 8 + (color & 3) * 2;
+        u16::from_le_bytes([data[i], data[i + 1]]) & 0x7FFF
+    }
+
+    /// Write every view to `dir` as `tiles.png`, `bg_map.png`,
+    /// `window_map.png`, `oam.png` and `palettes.png`, and the OAM table to
+    /// `oam.txt`.
+    pub fn dump(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        self.tiles().write_png(&dir.join("tiles.png"))?;
+        self.bg_map().write_png(&dir.join("bg_map.png"))?;
+        self.window_map().write_png(&dir.join("window_map.png"))?;
+        self.oam().write_png(&dir.join("oam.png"))?;
+        self.palettes().write_png(&dir.join("palettes.png"))?;
+        let table: String = self
+            .sprites()
+            .iter()
+            .map(|sprite| format!("{}\n", sprite))
+            .collect();
+        fs::write(dir.join("oam.txt"), table)
+    }
+
+    /// A 32x32 tile map, at $9C00 if `high` and $9800 otherwise.
+    fn map(&self, high: bool) -> Image {
+        let base = if high { 0x1C00 } else { 0x1800 };
+        let mut image = Image::new(256, 256);
+        for row in 0..32 {
+            for column in 0..32 {
+                let offset = base + row * 32 + column;
+                let index = self.vram[offset];
+                let tile = if self.lcdc.contains(LCDC::TILE_DATA_AREA) {
+                    index as usize * 16
+                } else {
+                    (0x1000 + index as i8 as isize * 16) as usize
+                };
+                let attributes = if self.cgb_mode {
+                    self.vram[0x2000 + offset]
+                } else {
+                    0
+                };
+                let bank = (attributes >> 3) as usize & 1;
+                for y in 0..8 {
+                    let line = if attributes & 0x40 != 0 { 7 - y } else { y };
+                    for x in 0..8 {
+                        let pixel = if attributes & 0x20 != 0 { 7 - x } else { x };
+                        let color = self.tile_pixel(bank, tile, pixel, line);
+                        let rgba = self.color(false, attributes as usize & 7, color);
+                        image.set_pixel(column * 8 + x, row * 8 + y, rgba);
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    /// The colour number of a pixel of the tile at `tile` bytes into `bank`.
+    fn tile_pixel(&self, bank: usize, tile: usize, x: usize, y: usize) -> u8 {
+        let address = bank * 0x2000 + tile + y * 2;
+        let (low, high) = (self.vram[address], self.vram[address + 1]);
+        let bit = 7 - x;
+        ((high >> bit) & 1) << 1 | ((low >> bit) & 1)
+    }
+
+    /// Colour `color` of a BG or OBJ palette, as the PPU would show it. In
+    /// DMG and compatibility mode, `palette` picks OBP0 or OBP1.
+    fn color(&self, obj: bool, palette: usize, color: u8) -> [u8; 4] {
+        if self.cgb_mode {
+            return self.cgb_color(self.cgb_raw(obj, palette, color as usize));
+        }
+        let register = match (obj, palette) {
+            (false, _) => self.bgp,
+            (true, 0) => self.obp0,
+            (true, _) => self.obp1,
+        };
+        let shade = (register >> (color * 2)) & 0x03;
+        if self.cgb {
+            let palette = if obj { palette & 1 } else { 0 };
+            self.cgb_color(self.cgb_raw(obj, palette, shade as usize))
+        } else {
+            self.shade(shade)
+        }
+    }
+
+    fn cgb_color(&self, color: u16) -> [u8; 4] {
+        let [r, g, b] = self.cc.map(self.cc_mode, color);
+        [r, g, b, 0xFF]
+    }
+
+    /// One of the configured DMG shades, 0 the lightest.
+    fn shade(&self, shade: u8) -> [u8; 4] {
+        let color = match shade {
+            0 => self.palette.light,
+            1 => self.palette.light_gray,
+            2 => self.palette.dark_gray,
+            _ => self.palette.dark,
+        };
+        [color.r(), color.g(), color.b(), 0xFF]
+    }
+}
+
+/// Draw a `width` by `height` rectangle's edges at `left`, `top`, wrapping
+/// around the image's edges as the PPU wraps around the map.
+fn outline(image: &mut Image, left: usize, top: usize, width: usize, height: usize) {
+    let (w, h) = (image.width, image.height);
+    for x in 0..width {
+        image.set_pixel((left + x) % w, top % h, OUTLINE);
+        image.set_pixel((left + x) % w, (top + height - 1) % h, OUTLINE);
+    }
+    for y in 0..height {
+        image.set_pixel(left % w, (top + y) % h, OUTLINE);
+        image.set_pixel((left + width - 1) % w, (top + y) % h, OUTLINE);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::components::mode::GBMode;
 use bitflags::bitflags;
+use serde::Serialize;
 use std::fmt;
 use std::fmt::Formatter;
 
@@ -168,7 +169,7 @@ pub mod io {
     pub const PCM34: u16 = 0xFF77;
 }
 
-#[derive(Debug, Copy, Clone, Eq)]
+#[derive(Debug, Copy, Clone, Eq, Serialize)]
 pub struct Registers {
     pub a: u8,
     pub f: u8,
@@ -197,6 +198,48 @@ impl PartialEq for Registers {
     }
 }
 
+/// A register or register pair, to get or set by name.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Reg {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+impl Reg {
+    /// `a`..`l`, `af`..`hl`, `sp` or `pc`, in any case.
+    pub fn parse(name: &str) -> Option<Reg> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "a" => Reg::A,
+            "f" => Reg::F,
+            "b" => Reg::B,
+            "c" => Reg::C,
+            "d" => Reg::D,
+            "e" => Reg::E,
+            "h" => Reg::H,
+            "l" => Reg::L,
+            "af" => Reg::AF,
+            "bc" => Reg::BC,
+            "de" => Reg::DE,
+            "hl" => Reg::HL,
+            "sp" => Reg::SP,
+            "pc" => Reg::PC,
+            _ => return None,
+        })
+    }
+}
+
 bitflags! {
     pub struct Flags: u8 {
         // Carry Flag
@@ -249,6 +292,47 @@ impl Registers {
         self.l = (x & 0x00FF) as u8;
     }
 
+    pub fn get(&self, reg: Reg) -> u16 {
+        match reg {
+            Reg::A => self.a as u16,
+            Reg::F => self.f as u16,
+            Reg::B => self.b as u16,
+            Reg::C => self.c as u16,
+            Reg::D => self.d as u16,
+            Reg::E => self.e as u16,
+            Reg::H => self.h as u16,
+            Reg::L => self.l as u16,
+            Reg::AF => self.get_af(),
+            Reg::BC => self.get_bc(),
+            Reg::DE => self.get_de(),
+            Reg::HL => self.get_hl(),
+            Reg::SP => self.sp,
+            Reg::PC => self.pc,
+        }
+    }
+
+    /// Set `reg`; 8-bit registers take the low byte of `x`, and the low
+    /// nibble of F stays clear.
+    pub fn set(&mut self, reg: Reg, x: u16) {
+        let byte = x as u8;
+        match reg {
+            Reg::A => self.a = byte,
+            Reg::F => self.f = byte & 0xF0,
+            Reg::B => self.b = byte,
+            Reg::C => self.c = byte,
+            Reg::D => self.d = byte,
+            Reg::E => self.e = byte,
+            Reg::H => self.h = byte,
+            Reg::L => self.l = byte,
+            Reg::AF => self.set_af(x),
+            Reg::BC => self.set_bc(x),
+            Reg::DE => self.set_de(x),
+            Reg::HL => self.set_hl(x),
+            Reg::SP => self.sp = x,
+            Reg::PC => self.pc = x,
+        }
+    }
+
     pub fn get_flag(&self, flag: Flags) -> bool {
         Flags::from_bits(self.f).unwrap().contains(flag)
     }
@@ -1,15 +1,48 @@
+use crate::components::link::link::SerialLink;
 use crate::components::prelude::*;
 use crate::hw::interrupt::Interrupts;
 use std::io::Write;
 
-// TODO: Handle serial properly
+/// Divider bit whose falling edge clocks the internal serial clock: 8192Hz,
+/// or 262144Hz with the CGB fast clock. Both double in double speed since
+/// the divider does.
+const CLOCK_BIT: u16 = 8;
+const FAST_CLOCK_BIT: u16 = 3;
+
 pub struct Serial {
     pub interrupts: Interrupts,
     sb: u8,
     sc: u8,
+    /// Bits left to shift in the current transfer; 0 when idle.
+    bits_left: u8,
+    /// Level of the divider bit last seen, to find its falling edges.
+    clock_level: bool,
+    /// SB as it was when the current transfer started.
+    sending: u8,
+    link: Option<Box<dyn SerialLink>>,
     output: Vec<u8>,
     print: bool,
     mode: GBMode,
+    double_speed: bool,
+}
+
+/// A copy of the port is left unplugged: the link stays where it was.
+impl Clone for Serial {
+    fn clone(&self) -> Self {
+        Self {
+            interrupts: self.interrupts,
+            sb: self.sb,
+            sc: self.sc,
+            bits_left: self.bits_left,
+            clock_level: self.clock_level,
+            sending: self.sending,
+            link: None,
+            output: self.output.clone(),
+            print: self.print,
+            mode: self.mode,
+            double_speed: self.double_speed,
+        }
+    }
 }
 
 impl Serial {
@@ -18,16 +51,118 @@ impl Serial {
             interrupts: Interrupts::empty(),
             sb: 0,
             sc: 0,
+            bits_left: 0,
+            clock_level: false,
+            sending: 0,
+            link: None,
             output: Vec::new(),
             print,
             mode,
+            double_speed: false,
         }
     }
 
-    /// Bytes transmitted so far (captured when a transfer is started).
+    /// Bytes transmitted so far. Captured when a transfer is started on the
+    /// internal clock, or when a partner finishes clocking one on the external
+    /// clock.
     pub fn output(&self) -> &[u8] {
         &self.output
     }
+
+    /// Plug a link cable (or peripheral) into the port.
+    pub fn connect(&mut self, link: Box<dyn SerialLink>) {
+        self.link = Some(link);
+        self.publish();
+    }
+
+    /// Unplug whatever is in the port, giving it back.
+    pub fn disconnect(&mut self) -> Option<Box<dyn SerialLink>> {
+        self.link.take()
+    }
+
+    /// Follow a speed switch, which doubles the serial clock too.
+    pub fn set_double_speed(&mut self, on: bool) {
+        self.double_speed = on;
+        self.publish();
+    }
+
+    fn publish(&mut self) {
+        let sc = if self.mode == GBMode::DMG {
+            self.sc & !0x02
+        } else {
+            self.sc
+        };
+        if let Some(link) = &mut self.link {
+            link.publish(self.sb, sc, self.double_speed);
+        }
+    }
+
+    fn internal_clock(&self) -> bool {
+        self.sc & 0x01 != 0
+    }
+
+    /// Called every dot with the system divider. With the internal clock
+    /// selected, each falling edge of the serial clock shifts one bit out of
+    /// SB and one in from the link (1s when nothing is connected). With the
+    /// external clock selected, SB shifts on the partner's pulses instead.
+    pub fn tick(&mut self, divider: u16) {
+        while let Some(bit) = self.link.as_mut().and_then(|link| link.receive()) {
+            if !self.internal_clock() {
+                self.shift(bit);
+            }
+        }
+
+        let bit = if self.mode != GBMode::DMG && self.sc & 0x02 != 0 {
+            FAST_CLOCK_BIT
+        } else {
+            CLOCK_BIT
+        };
+        let level = (divider >> bit) & 1 != 0;
+        let falling = self.clock_level && !level;
+        self.clock_level = level;
+
+        if falling && self.bits_left > 0 && self.internal_clock() {
+            let bit_out = self.sb & 0x80 != 0;
+            let bit_in = match &mut self.link {
+                Some(link) => link.exchange(bit_out),
+                None => true,
+            };
+            self.shift(bit_in);
+        }
+    }
+
+    /// Shift one bit in. An external clock shifts SB even with no transfer
+    /// started, but only a started transfer completes and interrupts.
+    fn shift(&mut self, bit_in: bool) {
+        self.sb = (self.sb << 1) | bit_in as u8;
+        if self.bits_left == 0 {
+            return;
+        }
+        self.bits_left -= 1;
+        if self.bits_left == 0 {
+            self.sc &= 0x7F;
+            self.interrupts |= Interrupts::SERIAL;
+            if !self.internal_clock() {
+                self.record(self.sending);
+            }
+        }
+    }
+
+    fn start(&mut self) {
+        self.bits_left = 8;
+        self.sending = self.sb;
+        if self.internal_clock() {
+            self.record(self.sb);
+        }
+    }
+
+    fn record(&mut self, v: u8) {
+        self.output.push(v);
+        if self.print {
+            print!("{}", v as char);
+            let _ = std::io::stdout().flush();
+        }
+    }
 }
 
 impl Memory for Serial {
@@ -47,16 +182,16 @@ impl Memory for Serial {
             0xFF01 => self.sb = v,
             0xFF02 => {
                 self.sc = v;
-                // Bit 7 starts a transfer; the byte staged in SB is what is sent.
+                // Bit 7 starts a transfer of the byte staged in SB; clearing
+                // it abandons one in progress.
                 if v & 0x80 != 0 {
-                    self.output.push(self.sb);
-                    if self.print {
-                        print!("{}", self.sb as char);
-                        let _ = std::io::stdout().flush();
-                    }
+                    self.start();
+                } else {
+                    self.bits_left = 0;
                 }
             }
             _ => panic!("Write to unsupported Serial address ({:#06x})!", a),
         }
+        self.publish();
     }
 }
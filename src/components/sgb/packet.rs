@@ -0,0 +1,148 @@
+use num_traits::FromPrimitive;
+
+/// Bytes in one SGB command packet.
+pub const PACKET_SIZE: usize = 16;
+
+/// SGB system commands, from the top five bits of a command's first byte.
+#[derive(Clone, Copy, PartialEq, Eq, FromPrimitive, Debug)]
+pub enum Command {
+    Pal01 = 0x00,
+    Pal23 = 0x01,
+    Pal03 = 0x02,
+    Pal12 = 0x03,
+    AttrBlk = 0x04,
+    AttrLin = 0x05,
+    AttrDiv = 0x06,
+    AttrChr = 0x07,
+    Sound = 0x08,
+    SouTrn = 0x09,
+    PalSet = 0x0A,
+    PalTrn = 0x0B,
+    AtrcEn = 0x0C,
+    TestEn = 0x0D,
+    IconEn = 0x0E,
+    DataSnd = 0x0F,
+    DataTrn = 0x10,
+    MltReq = 0x11,
+    Jump = 0x12,
+    ChrTrn = 0x13,
+    PctTrn = 0x14,
+    AttrTrn = 0x15,
+    AttrSet = 0x16,
+    MaskEn = 0x17,
+    ObjTrn = 0x18,
+    PalPri = 0x19,
+}
+
+/// A complete command: the raw bytes of all of its packets, concatenated.
+/// Parameters past the first packet (ATTR_BLK data sets, ATTR_CHR cells, ...)
+/// simply continue at offset 16, 32, ...
+#[derive(Clone)]
+pub struct CommandData {
+    pub command: Command,
+    pub data: Vec<u8>,
+}
+
+impl CommandData {
+    /// Parameter byte `i`, reading 0 past the end of what was sent.
+    pub fn byte(&self, i: usize) -> u8 {
+        self.data.get(i).copied().unwrap_or(0)
+    }
+
+    /// Little-endian parameter word at `i`.
+    pub fn word(&self, i: usize) -> u16 {
+        self.byte(i) as u16 | ((self.byte(i + 1) as u16) << 8)
+    }
+}
+
+/// Decodes the bit-banged packet protocol games drive through `JOYP`.
+///
+/// A transfer starts with a reset pulse (P14 and P15 both low). Each bit is
+/// then a pulse on one line — P15 low for a 1, P14 low for a 0 — followed by
+/// both lines high. 128 bits (LSB first) form a 16-byte packet, which must be
+/// followed by a 0 stop bit. The first packet of a command carries the
+/// command number and the number of packets (1-7) that make it up.
+#[derive(Clone)]
+pub struct PacketReader {
+    packet: [u8; PACKET_SIZE],
+    bits: usize,
+    receiving: bool,
+    ready: bool,
+    pending: Vec<u8>,
+    packets_left: u8,
+}
+
+impl PacketReader {
+    pub fn new() -> Self {
+        Self {
+            packet: [0; PACKET_SIZE],
+            bits: 0,
+            receiving: false,
+            ready: false,
+            pending: Vec::new(),
+            packets_left: 0,
+        }
+    }
+
+    /// Feed one write to `JOYP` (only bits 4-5 matter). Returns a command once
+    /// its final packet has been received.
+    pub fn write(&mut self, select: u8) -> Option<CommandData> {
+        match select & 0x30 {
+            0x00 => {
+                // Reset pulse: start a new packet.
+                self.packet = [0; PACKET_SIZE];
+                self.bits = 0;
+                self.receiving = true;
+                self.ready = false;
+                None
+            }
+            0x30 => {
+                self.ready = true;
+                None
+            }
+            v if self.receiving && self.ready => {
+                self.ready = false;
+                self.push_bit(v == 0x10)
+            }
+            _ => None,
+        }
+    }
+
+    fn push_bit(&mut self, bit: bool) -> Option<CommandData> {
+        if self.bits == PACKET_SIZE * 8 {
+            // Stop bit. A 1 here is a protocol error and drops the packet.
+            self.receiving = false;
+            if bit {
+                return None;
+            }
+            return self.finish_packet();
+        }
+
+        if bit {
+            self.packet[self.bits / 8] |= 1 << (self.bits % 8);
+        }
+        self.bits += 1;
+        None
+    }
+
+    fn finish_packet(&mut self) -> Option<CommandData> {
+        if self.packets_left == 0 {
+            let length = self.packet[0] & 0x07;
+            if length == 0 {
+                return None;
+            }
+            self.pending.clear();
+            self.packets_left = length;
+        }
+
+        self.pending.extend_from_slice(&self.packet);
+        self.packets_left -= 1;
+        if self.packets_left > 0 {
+            return None;
+        }
+
+        let data = std::mem::take(&mut self.pending);
+        FromPrimitive::from_u8(data[0] >> 3)
+            .map(|command| CommandData { command, data })
+    }
+}
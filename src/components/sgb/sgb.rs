@@ -0,0 +1,431 @@
+use crate::components::ppu::ppu::{SCREEN_H, SCREEN_W};
+use crate::components::sgb::packet::{Command, CommandData};
+use crate::framebuffer::FramebufferWriter;
+
+/// The SNES renders a 256×224 picture with the Game Boy screen centred in it.
+pub const SGB_SCREEN_W: usize = 256;
+pub const SGB_SCREEN_H: usize = 224;
+const SCREEN_X: usize = (SGB_SCREEN_W - SCREEN_W) / 2;
+const SCREEN_Y: usize = (SGB_SCREEN_H - SCREEN_H) / 2;
+
+/// Attributes are assigned per 8×8 cell of the Game Boy screen.
+const CELLS_W: usize = SCREEN_W / 8;
+const CELLS_H: usize = SCREEN_H / 8;
+const CELLS: usize = CELLS_W * CELLS_H;
+
+const SYSTEM_PALETTES: usize = 512;
+const ATTR_FILES: usize = 45;
+/// One attribute file packs the 360 cells at 2 bits each.
+const ATTR_FILE_SIZE: usize = CELLS / 4;
+
+const BORDER_TILES_SIZE: usize = 256 * 32;
+const BORDER_MAP_W: usize = 32;
+const BORDER_MAP_H: usize = 28;
+
+/// Size of one VRAM transfer: 256 2bpp tiles read off the displayed frame.
+const VRAM_TRANSFER_SIZE: usize = 0x1000;
+
+/// The SGB's power-on palette 0, used until the game sends its own colours.
+const DEFAULT_PALETTE: [u16; 4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+/// MASK_EN: what the SNES shows in place of the Game Boy screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mask {
+    None,
+    /// Keep showing the last frame.
+    Freeze,
+    Black,
+    /// Fill with colour 0.
+    Color0,
+}
+
+/// The VRAM transfers. Each copies 4KB out of the next frame the Game Boy
+/// displays (the game arranges the data as tiles 0-255 on screen).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Transfer {
+    /// PAL_TRN: the 512 system palettes.
+    Palettes,
+    /// CHR_TRN: border tiles $00-$7F (`false`) or $80-$FF (`true`).
+    BorderTiles(bool),
+    /// PCT_TRN: the border tilemap and palettes 4-7.
+    BorderMap,
+    /// ATTR_TRN: the 45 attribute files.
+    AttrFiles,
+    /// SOU_TRN: a program and data for the SNES sound processor.
+    Sound,
+}
+
+/// Super Game Boy display state: the four screen palettes and the attribute
+/// map assigning one to each 8×8 cell, the border, and the screen mask. The
+/// PPU hands it DMG shades instead of colours and it composes the final
+/// 256×224 picture at VBlank.
+#[derive(Clone)]
+pub struct Sgb {
+    palettes: [[u16; 4]; 4],
+    system_palettes: Box<[[u16; 4]; SYSTEM_PALETTES]>,
+    attr_map: [u8; CELLS],
+    attr_files: Box<[[u8; ATTR_FILE_SIZE]; ATTR_FILES]>,
+    border_tiles: Box<[u8; BORDER_TILES_SIZE]>,
+    border_map: Box<[u16; BORDER_MAP_W * BORDER_MAP_H]>,
+    border_palettes: [[u16; 16]; 4],
+    mask: Mask,
+    transfer: Option<Transfer>,
+    shades: Box<[u8; SCREEN_W * SCREEN_H]>,
+    /// Copy of `shades` taken when the screen was frozen.
+    frozen: Box<[u8; SCREEN_W * SCREEN_H]>,
+    /// A completed SOU_TRN, waiting to be handed to the sound processor.
+    sound_upload: Option<Box<[u8; VRAM_TRANSFER_SIZE]>>,
+}
+
+impl Sgb {
+    pub fn new() -> Self {
+        Self {
+            palettes: [DEFAULT_PALETTE; 4],
+            system_palettes: Box::new([[0; 4]; SYSTEM_PALETTES]),
+            attr_map: [0; CELLS],
+            attr_files: Box::new([[0; ATTR_FILE_SIZE]; ATTR_FILES]),
+            border_tiles: Box::new([0; BORDER_TILES_SIZE]),
+            border_map: Box::new([0; BORDER_MAP_W * BORDER_MAP_H]),
+            border_palettes: [[0; 16]; 4],
+            mask: Mask::None,
+            transfer: None,
+            shades: Box::new([0; SCREEN_W * SCREEN_H]),
+            frozen: Box::new([0; SCREEN_W * SCREEN_H]),
+            sound_upload: None,
+        }
+    }
+
+    pub fn mask(&self) -> Mask {
+        self.mask
+    }
+
+    /// Palette (0-3) assigned to the 8×8 cell at (`x`, `y`).
+    pub fn attribute(&self, x: usize, y: usize) -> u8 {
+        self.attr_map[y * CELLS_W + x]
+    }
+
+    /// Colour `i` of screen palette `palette`, as 15-bit BGR.
+    pub fn palette_color(&self, palette: usize, i: usize) -> u16 {
+        // Colour 0 is shared by all four palettes.
+        if i == 0 {
+            self.palettes[0][0]
+        } else {
+            self.palettes[palette][i]
+        }
+    }
+
+    /// Record the DMG shade (0-3, after BGP/OBP mapping) of one screen pixel.
+    #[inline]
+    pub fn set_shade(&mut self, x: usize, y: usize, shade: u8) {
+        self.shades[y * SCREEN_W + x] = shade;
+    }
+
+    /// The LCD was switched off: the SNES sees a blank screen.
+    pub fn clear_screen(&mut self) {
+        self.shades.fill(0);
+    }
+
+    /// The data of a completed SOU_TRN, once.
+    pub fn take_sound_upload(&mut self) -> Option<Box<[u8; VRAM_TRANSFER_SIZE]>> {
+        self.sound_upload.take()
+    }
+
+    pub fn command(&mut self, cmd: CommandData) {
+        match cmd.command {
+            Command::Pal01 => self.set_palettes(&cmd, 0, 1),
+            Command::Pal23 => self.set_palettes(&cmd, 2, 3),
+            Command::Pal03 => self.set_palettes(&cmd, 0, 3),
+            Command::Pal12 => self.set_palettes(&cmd, 1, 2),
+            Command::AttrBlk => self.attr_blk(&cmd),
+            Command::AttrLin => self.attr_lin(&cmd),
+            Command::AttrDiv => self.attr_div(&cmd),
+            Command::AttrChr => self.attr_chr(&cmd),
+            Command::PalSet => self.pal_set(&cmd),
+            Command::PalTrn => self.transfer = Some(Transfer::Palettes),
+            Command::ChrTrn => self.transfer = Some(Transfer::BorderTiles(cmd.byte(1) & 0x01 != 0)),
+            Command::PctTrn => self.transfer = Some(Transfer::BorderMap),
+            Command::AttrTrn => self.transfer = Some(Transfer::AttrFiles),
+            Command::SouTrn => self.transfer = Some(Transfer::Sound),
+            Command::AttrSet => {
+                let v = cmd.byte(1);
+                self.apply_attr_file((v & 0x3F) as usize);
+                if v & 0x40 != 0 {
+                    self.mask = Mask::None;
+                }
+            }
+            Command::MaskEn => self.set_mask(cmd.byte(1)),
+            // MLT_REQ is handled by the joypad; the rest have no effect on
+            // the picture.
+            _ => {}
+        }
+    }
+
+    fn set_palettes(&mut self, cmd: &CommandData, a: usize, b: usize) {
+        self.palettes[0][0] = cmd.word(1);
+        for i in 1..4 {
+            self.palettes[a][i] = cmd.word(1 + i * 2);
+            self.palettes[b][i] = cmd.word(7 + i * 2);
+        }
+    }
+
+    fn pal_set(&mut self, cmd: &CommandData) {
+        for i in 0..4 {
+            let index = (cmd.word(1 + i * 2) & 0x01FF) as usize;
+            self.palettes[i] = self.system_palettes[index];
+        }
+
+        let flags = cmd.byte(9);
+        if flags & 0x80 != 0 {
+            self.apply_attr_file((flags & 0x3F) as usize);
+        }
+        if flags & 0x40 != 0 {
+            self.mask = Mask::None;
+        }
+    }
+
+    fn set_mask(&mut self, v: u8) {
+        self.mask = match v & 0x03 {
+            0 => Mask::None,
+            1 => {
+                if self.mask != Mask::Freeze {
+                    self.frozen.copy_from_slice(&self.shades[..]);
+                }
+                Mask::Freeze
+            }
+            2 => Mask::Black,
+            _ => Mask::Color0,
+        };
+    }
+
+    fn attr_blk(&mut self, cmd: &CommandData) {
+        let sets = (cmd.byte(1) & 0x1F) as usize;
+        for set in 0..sets {
+            let base = 2 + set * 6;
+            let control = cmd.byte(base) & 0x07;
+            let palettes = cmd.byte(base + 1);
+            let (x1, y1) = (cmd.byte(base + 2) & 0x1F, cmd.byte(base + 3) & 0x1F);
+            let (x2, y2) = (cmd.byte(base + 4) & 0x1F, cmd.byte(base + 5) & 0x1F);
+
+            let inside = palettes & 0x03;
+            let outside = (palettes >> 4) & 0x03;
+            // Changing only the inside or only the outside also paints the
+            // surrounding line in the same palette.
+            let line = match control {
+                0x01 => Some(inside),
+                0x04 => Some(outside),
+                _ if control & 0x02 != 0 => Some((palettes >> 2) & 0x03),
+                _ => None,
+            };
+
+            for y in 0..CELLS_H as u8 {
+                for x in 0..CELLS_W as u8 {
+                    let palette = if x > x1 && x < x2 && y > y1 && y < y2 {
+                        (control & 0x01 != 0).then_some(inside)
+                    } else if x < x1 || x > x2 || y < y1 || y > y2 {
+                        (control & 0x04 != 0).then_some(outside)
+                    } else {
+                        line
+                    };
+                    if let Some(palette) = palette {
+                        self.attr_map[y as usize * CELLS_W + x as usize] = palette;
+                    }
+                }
+            }
+        }
+    }
+
+    fn attr_lin(&mut self, cmd: &CommandData) {
+        let count = cmd.byte(1) as usize;
+        for i in 0..count {
+            let v = cmd.byte(2 + i);
+            let line = (v & 0x1F) as usize;
+            let palette = (v >> 5) & 0x03;
+            if v & 0x80 != 0 {
+                // Horizontal line: one row of cells.
+                if line < CELLS_H {
+                    self.attr_map[line * CELLS_W..(line + 1) * CELLS_W].fill(palette);
+                }
+            } else if line < CELLS_W {
+                for y in 0..CELLS_H {
+                    self.attr_map[y * CELLS_W + line] = palette;
+                }
+            }
+        }
+    }
+
+    fn attr_div(&mut self, cmd: &CommandData) {
+        let v = cmd.byte(1);
+        let after = v & 0x03;
+        let before = (v >> 2) & 0x03;
+        let on_line = (v >> 4) & 0x03;
+        let horizontal = v & 0x40 != 0;
+        let split = (cmd.byte(2) & 0x1F) as usize;
+
+        for y in 0..CELLS_H {
+            for x in 0..CELLS_W {
+                let pos = if horizontal { y } else { x };
+                self.attr_map[y * CELLS_W + x] = match pos.cmp(&split) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on_line,
+                    std::cmp::Ordering::Greater => after,
+                };
+            }
+        }
+    }
+
+    fn attr_chr(&mut self, cmd: &CommandData) {
+        let mut x = (cmd.byte(1) & 0x1F) as usize;
+        let mut y = (cmd.byte(2) & 0x1F) as usize;
+        let count = (cmd.word(3) as usize).min(CELLS);
+        let vertical = cmd.byte(5) & 0x01 != 0;
+
+        for i in 0..count {
+            if x >= CELLS_W || y >= CELLS_H {
+                break;
+            }
+            let palette = (cmd.byte(6 + i / 4) >> (6 - 2 * (i % 4))) & 0x03;
+            self.attr_map[y * CELLS_W + x] = palette;
+
+            if vertical {
+                y += 1;
+                if y == CELLS_H {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == CELLS_W {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    fn apply_attr_file(&mut self, file: usize) {
+        if file >= ATTR_FILES {
+            return;
+        }
+        for (i, cell) in self.attr_map.iter_mut().enumerate() {
+            *cell = (self.attr_files[file][i / 4] >> (6 - 2 * (i % 4))) & 0x03;
+        }
+    }
+
+    /// Re-encode the displayed frame as the 256 2bpp tiles the game laid out
+    /// on screen, 20 per row, for a VRAM transfer.
+    fn vram_data(&self) -> Box<[u8; VRAM_TRANSFER_SIZE]> {
+        let mut data = Box::new([0u8; VRAM_TRANSFER_SIZE]);
+        for tile in 0..256 {
+            let (tx, ty) = (tile % CELLS_W, tile / CELLS_W);
+            for row in 0..8 {
+                let line = &self.shades[(ty * 8 + row) * SCREEN_W + tx * 8..][..8];
+                let (mut lo, mut hi) = (0u8, 0u8);
+                for (px, &shade) in line.iter().enumerate() {
+                    lo |= (shade & 0x01) << (7 - px);
+                    hi |= ((shade >> 1) & 0x01) << (7 - px);
+                }
+                data[tile * 16 + row * 2] = lo;
+                data[tile * 16 + row * 2 + 1] = hi;
+            }
+        }
+        data
+    }
+
+    fn run_transfer(&mut self, transfer: Transfer) {
+        let data = self.vram_data();
+        let word = |i: usize| data[i] as u16 | ((data[i + 1] as u16) << 8);
+
+        match transfer {
+            Transfer::Palettes => {
+                for (i, palette) in self.system_palettes.iter_mut().enumerate() {
+                    for (j, color) in palette.iter_mut().enumerate() {
+                        *color = word(i * 8 + j * 2);
+                    }
+                }
+            }
+            Transfer::BorderTiles(high) => {
+                let offset = if high { VRAM_TRANSFER_SIZE } else { 0 };
+                self.border_tiles[offset..offset + VRAM_TRANSFER_SIZE].copy_from_slice(&data[..]);
+            }
+            Transfer::BorderMap => {
+                for (i, entry) in self.border_map.iter_mut().enumerate() {
+                    *entry = word(i * 2);
+                }
+                for (i, palette) in self.border_palettes.iter_mut().enumerate() {
+                    for (j, color) in palette.iter_mut().enumerate() {
+                        *color = word(0x800 + i * 32 + j * 2);
+                    }
+                }
+            }
+            Transfer::AttrFiles => {
+                for (i, file) in self.attr_files.iter_mut().enumerate() {
+                    file.copy_from_slice(&data[i * ATTR_FILE_SIZE..(i + 1) * ATTR_FILE_SIZE]);
+                }
+            }
+            Transfer::Sound => self.sound_upload = Some(data),
+        }
+    }
+
+    /// Colour index (0-15) of pixel (`x`, `y`) of a 4bpp SNES border tile.
+    fn border_pixel(&self, tile: usize, x: usize, y: usize) -> usize {
+        let base = tile * 32 + y * 2;
+        let bit = 7 - x;
+        let plane = |i: usize| ((self.border_tiles[i] >> bit) & 0x01) as usize;
+        plane(base) | plane(base + 1) << 1 | plane(base + 16) << 2 | plane(base + 17) << 3
+    }
+
+    /// Called at VBlank: completes any pending VRAM transfer from the frame
+    /// just drawn, then composes the border and the coloured screen into
+    /// `framebuffer`. `map` converts a 15-bit BGR colour to RGB.
+    pub fn render(&mut self, framebuffer: &mut FramebufferWriter, map: impl Fn(u16) -> [u8; 3]) {
+        if let Some(transfer) = self.transfer.take() {
+            self.run_transfer(transfer);
+        }
+
+        let backdrop = map(self.palettes[0][0]);
+        framebuffer.fill(backdrop[0], backdrop[1], backdrop[2]);
+
+        let shades = if self.mask == Mask::Freeze {
+            &self.frozen
+        } else {
+            &self.shades
+        };
+        let black = map(0x0000);
+        for y in 0..SCREEN_H {
+            for x in 0..SCREEN_W {
+                let c = match self.mask {
+                    Mask::Black => black,
+                    Mask::Color0 => backdrop,
+                    Mask::None | Mask::Freeze => {
+                        let palette = self.attribute(x / 8, y / 8) as usize;
+                        map(self.palette_color(palette, shades[y * SCREEN_W + x] as usize))
+                    }
+                };
+                framebuffer.set_pixel(c[0], c[1], c[2], SCREEN_X + x, SCREEN_Y + y);
+            }
+        }
+
+        // The border sits above the Game Boy screen; colour 0 is transparent.
+        for ty in 0..BORDER_MAP_H {
+            for tx in 0..BORDER_MAP_W {
+                let entry = self.border_map[ty * BORDER_MAP_W + tx];
+                let tile = (entry & 0xFF) as usize;
+                let palette = ((entry >> 10) & 0x03) as usize;
+                let x_flip = entry & 0x4000 != 0;
+                let y_flip = entry & 0x8000 != 0;
+
+                for py in 0..8 {
+                    for px in 0..8 {
+                        let sx = if x_flip { 7 - px } else { px };
+                        let sy = if y_flip { 7 - py } else { py };
+                        let i = self.border_pixel(tile, sx, sy);
+                        if i != 0 {
+                            let c = map(self.border_palettes[palette][i]);
+                            framebuffer.set_pixel(c[0], c[1], c[2], tx * 8 + px, ty * 8 + py);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,453 @@
+/// Global DSP registers. Per-voice registers are `voice << 4 | n`.
+const MVOLL: usize = 0x0C;
+const MVOLR: usize = 0x1C;
+const EVOLL: usize = 0x2C;
+const EVOLR: usize = 0x3C;
+const KON: usize = 0x4C;
+const KOFF: usize = 0x5C;
+const FLG: usize = 0x6C;
+const ENDX: usize = 0x7C;
+const EFB: usize = 0x0D;
+const PMON: usize = 0x2D;
+const NON: usize = 0x3D;
+const EON: usize = 0x4D;
+const DIR: usize = 0x5D;
+const ESA: usize = 0x6D;
+const EDL: usize = 0x7D;
+
+const V_VOLL: usize = 0x0;
+const V_VOLR: usize = 0x1;
+const V_PITCHL: usize = 0x2;
+const V_PITCHH: usize = 0x3;
+const V_SRCN: usize = 0x4;
+const V_ADSR1: usize = 0x5;
+const V_ADSR2: usize = 0x6;
+const V_GAIN: usize = 0x7;
+const V_ENVX: usize = 0x8;
+const V_OUTX: usize = 0x9;
+
+const FLG_RESET: u8 = 0x80;
+const FLG_MUTE: u8 = 0x40;
+const FLG_ECHO_DISABLE: u8 = 0x20;
+
+const VOICES: usize = 8;
+const BRR_BLOCK_SIZE: u16 = 9;
+const ENV_MAX: i32 = 0x7FF;
+
+/// Samples between envelope/noise ticks for each of the 32 rates; 0 never
+/// ticks.
+#[rustfmt::skip]
+const RATES: [u32; 32] = [
+    0, 2048, 1536, 1280, 1024, 768, 640, 512,
+    384, 320, 256, 192, 160, 128, 96, 80,
+    64, 48, 40, 32, 24, 20, 16, 12,
+    10, 8, 6, 5, 4, 3, 2, 1,
+];
+
+/// Phase of each rate against the shared counter.
+#[rustfmt::skip]
+const RATE_OFFSETS: [u32; 32] = [
+    1, 0, 1040, 536, 0, 1040, 536, 0,
+    1040, 536, 0, 1040, 536, 0, 1040, 536,
+    0, 1040, 536, 0, 1040, 536, 0, 1040,
+    536, 0, 1040, 536, 0, 1040, 0, 0,
+];
+
+/// Period of the shared rate counter (a multiple of every rate).
+const COUNTER_RANGE: u32 = 2048 * 5 * 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EnvMode {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+#[derive(Clone, Copy)]
+struct Voice {
+    /// Address of the BRR block being played.
+    addr: u16,
+    header: u8,
+    /// The current block decoded, in the DSP's doubled 16-bit form.
+    block: [i16; 16],
+    block_pos: usize,
+    /// The last four samples, oldest first, for interpolation.
+    window: [i32; 4],
+    /// Pitch accumulator; the low 12 bits are the position between samples.
+    counter: u32,
+    env: i32,
+    env_mode: EnvMode,
+    /// Samples left before a keyed-on voice starts sounding.
+    kon_delay: u8,
+    /// Output after the envelope, used by the next voice's pitch modulation.
+    out: i32,
+}
+
+impl Voice {
+    fn new() -> Self {
+        Self {
+            addr: 0,
+            header: 0,
+            block: [0; 16],
+            block_pos: 0,
+            window: [0; 4],
+            counter: 0,
+            env: 0,
+            env_mode: EnvMode::Release,
+            kon_delay: 0,
+            out: 0,
+        }
+    }
+}
+
+fn clamp16(v: i32) -> i32 {
+    v.clamp(i16::MIN as i32, i16::MAX as i32)
+}
+
+/// The S-DSP: eight BRR sample voices with ADSR/GAIN envelopes, noise, pitch
+/// modulation and the FIR-filtered echo, producing one stereo sample every
+/// 32 SPC700 cycles (32kHz). Samples are generated whole rather than over
+/// the DSP's per-cycle pipeline, and interpolation is cubic rather than the
+/// hardware's Gaussian table.
+#[derive(Clone)]
+pub struct Dsp {
+    regs: [u8; 128],
+    voices: [Voice; VOICES],
+    counter: u32,
+    noise: i32,
+    echo_offset: usize,
+    /// Last eight echo samples read back, per channel.
+    echo_hist: [[i32; 2]; 8],
+    echo_hist_pos: usize,
+}
+
+impl Dsp {
+    pub fn new() -> Self {
+        let mut regs = [0; 128];
+        regs[FLG] = FLG_RESET | FLG_MUTE | FLG_ECHO_DISABLE;
+        Self {
+            regs,
+            voices: [Voice::new(); VOICES],
+            counter: 0,
+            noise: 0x4000,
+            echo_offset: 0,
+            echo_hist: [[0; 2]; 8],
+            echo_hist_pos: 0,
+        }
+    }
+
+    pub fn read(&self, a: u8) -> u8 {
+        self.regs[(a & 0x7F) as usize]
+    }
+
+    pub fn write(&mut self, a: u8, v: u8) {
+        match a as usize {
+            // Writing ENDX acknowledges every voice.
+            ENDX => self.regs[ENDX] = 0,
+            r if r < 0x80 => self.regs[r] = v,
+            _ => {}
+        }
+    }
+
+    fn voice_reg(&self, voice: usize, r: usize) -> u8 {
+        self.regs[(voice << 4) | r]
+    }
+
+    fn rate_tick(&self, rate: usize) -> bool {
+        RATES[rate] != 0 && (self.counter + RATE_OFFSETS[rate]).is_multiple_of(RATES[rate])
+    }
+
+    fn key_on(&mut self, ram: &[u8; 0x10000], v: usize) {
+        let entry = self.dir_entry(v);
+        let voice = &mut self.voices[v];
+        voice.addr = read16(ram, entry);
+        voice.block = [0; 16];
+        voice.block_pos = 0;
+        voice.window = [0; 4];
+        voice.counter = 0;
+        voice.env = 0;
+        voice.env_mode = EnvMode::Attack;
+        voice.kon_delay = 5;
+        Self::decode_block(ram, voice);
+        self.regs[ENDX] &= !(1 << v);
+    }
+
+    fn dir_entry(&self, v: usize) -> u16 {
+        ((self.regs[DIR] as u16) << 8).wrapping_add(self.voice_reg(v, V_SRCN) as u16 * 4)
+    }
+
+    /// Decode the nine-byte BRR block at `voice.addr`.
+    fn decode_block(ram: &[u8; 0x10000], voice: &mut Voice) {
+        voice.header = ram[voice.addr as usize];
+        let shift = voice.header >> 4;
+        let filter = voice.header & 0x0C;
+        let mut p1 = voice.block[15] as i32;
+        let mut p2 = voice.block[14] as i32;
+
+        for i in 0..16 {
+            let byte = ram[voice.addr.wrapping_add(1 + i as u16 / 2) as usize];
+            let nibble = if i & 1 == 0 { byte >> 4 } else { byte & 0x0F };
+            let mut s = ((nibble as i8) << 4 >> 4) as i32;
+            s = (s << shift) >> 1;
+            if shift >= 0xD {
+                s = if s < 0 { -0x800 } else { 0 };
+            }
+
+            let (q1, q2) = (p1, p2 >> 1);
+            match filter {
+                0x4 => {
+                    s += q1 >> 1;
+                    s += (-q1) >> 5;
+                }
+                0x8 => {
+                    s += q1 - q2;
+                    s += q2 >> 4;
+                    s += (q1 * -3) >> 6;
+                }
+                0xC => {
+                    s += q1 - q2;
+                    s += (q1 * -13) >> 7;
+                    s += (q2 * 3) >> 4;
+                }
+                _ => {}
+            }
+
+            let s = (clamp16(s) * 2) as i16;
+            voice.block[i] = s;
+            p2 = p1;
+            p1 = s as i32;
+        }
+    }
+
+    /// Move to the next block at the end of the current one, following the
+    /// loop point or silencing the voice when the block ended the sample.
+    fn next_block(&mut self, ram: &[u8; 0x10000], v: usize) {
+        let header = self.voices[v].header;
+        if header & 0x01 != 0 {
+            self.regs[ENDX] |= 1 << v;
+            let entry = self.dir_entry(v);
+            self.voices[v].addr = read16(ram, entry.wrapping_add(2));
+            if header & 0x02 == 0 {
+                self.voices[v].env_mode = EnvMode::Release;
+                self.voices[v].env = 0;
+            }
+        } else {
+            self.voices[v].addr = self.voices[v].addr.wrapping_add(BRR_BLOCK_SIZE);
+        }
+        self.voices[v].block_pos = 0;
+        Self::decode_block(ram, &mut self.voices[v]);
+    }
+
+    fn run_envelope(&mut self, v: usize) {
+        let adsr1 = self.voice_reg(v, V_ADSR1);
+        let adsr2 = self.voice_reg(v, V_ADSR2);
+        let gain = self.voice_reg(v, V_GAIN);
+        let mut env = self.voices[v].env;
+        let mode = self.voices[v].env_mode;
+
+        if mode == EnvMode::Release {
+            env -= 8;
+            self.voices[v].env = env.max(0);
+            return;
+        }
+
+        let (rate, next) = if adsr1 & 0x80 != 0 {
+            match mode {
+                EnvMode::Attack => {
+                    let rate = ((adsr1 & 0x0F) as usize) * 2 + 1;
+                    (rate, env + if rate == 31 { 0x400 } else { 0x20 })
+                }
+                EnvMode::Decay => {
+                    let rate = (((adsr1 >> 4) & 0x07) as usize) * 2 + 16;
+                    (rate, env - (((env - 1) >> 8) + 1))
+                }
+                _ => ((adsr2 & 0x1F) as usize, env - (((env - 1) >> 8) + 1)),
+            }
+        } else if gain & 0x80 == 0 {
+            (31, (gain & 0x7F) as i32 * 16)
+        } else {
+            let rate = (gain & 0x1F) as usize;
+            let next = match (gain >> 5) & 0x03 {
+                0 => env - 0x20,
+                1 => env - (((env - 1) >> 8) + 1),
+                2 => env + 0x20,
+                _ => env + if env < 0x600 { 0x20 } else { 0x08 },
+            };
+            (rate, next)
+        };
+
+        if !self.rate_tick(rate) {
+            return;
+        }
+
+        let voice = &mut self.voices[v];
+        if mode == EnvMode::Decay && (next >> 8) as u8 == adsr2 >> 5 {
+            voice.env_mode = EnvMode::Sustain;
+        }
+        if next > ENV_MAX && mode == EnvMode::Attack {
+            voice.env_mode = EnvMode::Decay;
+        }
+        voice.env = next.clamp(0, ENV_MAX);
+    }
+
+    /// Produce the next stereo output sample, reading BRR data and the echo
+    /// buffer from (and writing echo back to) `ram`.
+    pub fn sample(&mut self, ram: &mut [u8; 0x10000]) -> (i16, i16) {
+        self.counter = if self.counter == 0 {
+            COUNTER_RANGE - 1
+        } else {
+            self.counter - 1
+        };
+
+        let flg = self.regs[FLG];
+        if self.rate_tick((flg & 0x1F) as usize) {
+            let feedback = (self.noise << 13) ^ (self.noise << 14);
+            self.noise = (feedback & 0x4000) ^ (self.noise >> 1);
+        }
+
+        if flg & FLG_RESET != 0 {
+            for voice in self.voices.iter_mut() {
+                voice.env_mode = EnvMode::Release;
+                voice.env = 0;
+            }
+        }
+
+        let kon = std::mem::take(&mut self.regs[KON]);
+        let koff = self.regs[KOFF];
+        let mut main = [0i32; 2];
+        let mut echo_in = [0i32; 2];
+
+        for v in 0..VOICES {
+            if kon & (1 << v) != 0 {
+                self.key_on(ram, v);
+            }
+            if koff & (1 << v) != 0 {
+                self.voices[v].env_mode = EnvMode::Release;
+            }
+
+            if self.voices[v].kon_delay > 0 {
+                self.voices[v].kon_delay -= 1;
+                self.voices[v].out = 0;
+                self.regs[(v << 4) | V_OUTX] = 0;
+                continue;
+            }
+
+            let mut pitch = (self.voice_reg(v, V_PITCHL) as i32
+                | ((self.voice_reg(v, V_PITCHH) as i32 & 0x3F) << 8))
+                & 0x3FFF;
+            if v > 0 && self.regs[PMON] & (1 << v) != 0 {
+                pitch += ((self.voices[v - 1].out >> 5) * pitch) >> 10;
+            }
+
+            let sample = if self.regs[NON] & (1 << v) != 0 {
+                ((self.noise as i16) << 1) as i32
+            } else {
+                interpolate(&self.voices[v].window, self.voices[v].counter)
+            };
+
+            self.run_envelope(v);
+            let out = ((sample * self.voices[v].env) >> 11) & !1;
+            self.voices[v].out = out;
+
+            self.voices[v].counter += pitch.max(0) as u32;
+            while self.voices[v].counter >= 0x1000 {
+                self.voices[v].counter -= 0x1000;
+                if self.voices[v].block_pos == 16 {
+                    self.next_block(ram, v);
+                }
+                let voice = &mut self.voices[v];
+                voice.window.copy_within(1.., 0);
+                voice.window[3] = voice.block[voice.block_pos] as i32;
+                voice.block_pos += 1;
+            }
+
+            self.regs[(v << 4) | V_ENVX] = (self.voices[v].env >> 4) as u8;
+            self.regs[(v << 4) | V_OUTX] = (out >> 8) as u8;
+
+            for (ch, reg) in [V_VOLL, V_VOLR].into_iter().enumerate() {
+                let amp = (out * self.voice_reg(v, reg) as i8 as i32) >> 7;
+                main[ch] = clamp16(main[ch] + amp);
+                if self.regs[EON] & (1 << v) != 0 {
+                    echo_in[ch] = clamp16(echo_in[ch] + amp);
+                }
+            }
+        }
+
+        let echo_out = self.run_echo(ram, echo_in);
+
+        if flg & FLG_MUTE != 0 {
+            return (0, 0);
+        }
+        let mut output = [0i16; 2];
+        for ch in 0..2 {
+            let (mvol, evol) = if ch == 0 { (MVOLL, EVOLL) } else { (MVOLR, EVOLR) };
+            let m = (main[ch] * self.regs[mvol] as i8 as i32) >> 7;
+            let e = (echo_out[ch] * self.regs[evol] as i8 as i32) >> 7;
+            output[ch] = clamp16(m + e) as i16;
+        }
+        (output[0], output[1])
+    }
+
+    /// Read the echo buffer through the FIR filter and write back the new
+    /// input plus feedback. Returns the filtered echo.
+    fn run_echo(&mut self, ram: &mut [u8; 0x10000], input: [i32; 2]) -> [i32; 2] {
+        let length = match (self.regs[EDL] & 0x0F) as usize * 0x800 {
+            0 => 4,
+            n => n,
+        };
+        let base = ((self.regs[ESA] as usize) << 8) + self.echo_offset;
+
+        self.echo_hist_pos = (self.echo_hist_pos + 1) & 7;
+        for ch in 0..2 {
+            let a = (base + ch * 2) & 0xFFFF;
+            let v = i16::from_le_bytes([ram[a], ram[(a + 1) & 0xFFFF]]) as i32;
+            self.echo_hist[self.echo_hist_pos][ch] = v >> 1;
+        }
+
+        let mut out = [0i32; 2];
+        for (ch, o) in out.iter_mut().enumerate() {
+            let mut sum = 0;
+            for tap in 0..8 {
+                // Tap 0 applies to the oldest of the eight samples.
+                let hist = self.echo_hist[(self.echo_hist_pos + 1 + tap) & 7][ch];
+                let coef = self.regs[(tap << 4) | 0x0F] as i8 as i32;
+                sum += (hist * coef) >> 6;
+            }
+            *o = clamp16(sum) & !1;
+        }
+
+        if self.regs[FLG] & FLG_ECHO_DISABLE == 0 {
+            let efb = self.regs[EFB] as i8 as i32;
+            for ch in 0..2 {
+                let v = (clamp16(input[ch] + ((out[ch] * efb) >> 7)) & !1) as u16;
+                let a = (base + ch * 2) & 0xFFFF;
+                ram[a] = v as u8;
+                ram[(a + 1) & 0xFFFF] = (v >> 8) as u8;
+            }
+        }
+
+        self.echo_offset += 4;
+        if self.echo_offset >= length {
+            self.echo_offset = 0;
+        }
+        out
+    }
+}
+
+fn read16(ram: &[u8; 0x10000], a: u16) -> u16 {
+    ram[a as usize] as u16 | ((ram[a.wrapping_add(1) as usize] as u16) << 8)
+}
+
+/// Catmull-Rom interpolation between `w[1]` and `w[2]` at `counter`'s
+/// fractional position.
+fn interpolate(w: &[i32; 4], counter: u32) -> i32 {
+    let t = (counter & 0xFFF) as f32 / 4096.0;
+    let [p0, p1, p2, p3] = w.map(|s| s as f32);
+    let v = p1
+        + 0.5
+            * t
+            * (p2 - p0
+                + t * (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3 + t * (3.0 * (p1 - p2) + p3 - p0)));
+    clamp16(v as i32) & !1
+}
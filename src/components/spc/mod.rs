@@ -0,0 +1,3 @@
+pub mod dsp;
+pub mod spc;
+pub mod spc700;
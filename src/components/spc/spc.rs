@@ -0,0 +1,390 @@
+use crate::components::spc::dsp::Dsp;
+use crate::components::spc::spc700::{Spc700, SpcBus};
+
+/// The SNES APU runs at 1.024MHz, 125/512 of the Game Boy clock.
+const CLOCK_NUM: u32 = 125;
+const CLOCK_DEN: u32 = 512;
+/// SPC700 cycles per DSP output sample (32kHz).
+const CYCLES_PER_SAMPLE: u32 = 32;
+
+/// The boot loader mapped at $FFC0 while CONTROL bit 7 is set.
+#[rustfmt::skip]
+const IPL_ROM: [u8; 64] = [
+    0xCD, 0xEF, 0xBD, 0xE8, 0x00, 0xC6, 0x1D, 0xD0, 0xFC, 0x8F, 0xAA, 0xF4, 0x8F, 0xBB, 0xF5, 0x78,
+    0xCC, 0xF4, 0xD0, 0xFB, 0x2F, 0x19, 0xEB, 0xF4, 0xD0, 0xFC, 0x7E, 0xF4, 0xD0, 0x0B, 0xE4, 0xF5,
+    0xCB, 0xF4, 0xD7, 0x00, 0xFC, 0xD0, 0xF3, 0xAB, 0x01, 0x10, 0xEF, 0x7E, 0xF4, 0x10, 0xEB, 0xBA,
+    0xF6, 0xDA, 0x00, 0xBA, 0xF4, 0xC4, 0xF4, 0xDD, 0x5D, 0xD0, 0xDB, 0x1F, 0x00, 0x00, 0xC0, 0xFF,
+];
+
+/// Where the built-in sound driver and its tables live in SPC RAM.
+const DRIVER: u16 = 0x0200;
+/// PITCHH for each effect A code and pitch attribute, at `code << 2 | pitch`.
+const PITCH_A: u16 = 0x0300;
+/// Voice volume for each volume attribute: high, medium, low and mute.
+const VOLUME: u16 = 0x03C4;
+/// FLG (noise clock) for each effect B code and pitch attribute.
+const NOISE_B: u16 = 0x0400;
+const SAMPLE_DIR: u16 = 0x0500;
+const SQUARE_WAVE: u16 = 0x0504;
+
+const EFFECTS_A: u8 = 0x30;
+const EFFECTS_B: u8 = 0x19;
+
+/// A stand-in for the sound driver the SGB's SNES side loads at power-on,
+/// which plays SOUND's built-in effects. The original recordings aren't
+/// reproduced: each effect A code plays a fading square-wave tone on voice
+/// 0 and each effect B code a fading burst of noise on voice 1, with the
+/// pitch and volume attributes applied. A code plays when it appears in its
+/// port (so `00` in between re-triggers it) and `80` or above silences it.
+/// Direct page $10 and $12 hold the last codes seen.
+#[rustfmt::skip]
+const DRIVER_CODE: [u8; 158] = [
+    // loop
+    0xE4, 0xF4,           // MOV A,$F4
+    0x64, 0x10,           // CMP A,$10
+    0xF0, 0x46,           // BEQ effect_b
+    0xC4, 0x10,           // MOV $10,A
+    0xFD,                 // MOV Y,A
+    0xF0, 0x41,           // BEQ effect_b
+    0x30, 0x33,           // BMI stop_a
+    0x68, 0x31,           // CMP A,#$31
+    0xB0, 0x3B,           // BCS effect_b
+    0x1C,                 // ASL A
+    0x1C,                 // ASL A
+    0xC4, 0x11,           // MOV $11,A
+    0xE4, 0xF6,           // MOV A,$F6
+    0x28, 0x03,           // AND A,#$03
+    0x04, 0x11,           // OR A,$11
+    0xFD,                 // MOV Y,A
+    0xF6, 0x00, 0x03,     // MOV A,!PITCH_A+Y
+    0x8F, 0x03, 0xF2,     // MOV $F2,#V0 PITCHH
+    0xC4, 0xF3,           // MOV $F3,A
+    0xE4, 0xF6,           // MOV A,$F6
+    0x5C,                 // LSR A
+    0x5C,                 // LSR A
+    0x28, 0x03,           // AND A,#$03
+    0xFD,                 // MOV Y,A
+    0xF6, 0xC4, 0x03,     // MOV A,!VOLUME+Y
+    0x8F, 0x00, 0xF2,     // MOV $F2,#V0 VOLL
+    0xC4, 0xF3,           // MOV $F3,A
+    0x8F, 0x01, 0xF2,     // MOV $F2,#V0 VOLR
+    0xC4, 0xF3,           // MOV $F3,A
+    0x8F, 0x4C, 0xF2,     // MOV $F2,#KON
+    0x8F, 0x01, 0xF3,     // MOV $F3,#$01
+    0x2F, 0x0C,           // BRA effect_b
+    // stop_a
+    0x8F, 0x00, 0xF2,     // MOV $F2,#V0 VOLL
+    0x8F, 0x00, 0xF3,     // MOV $F3,#$00
+    0x8F, 0x01, 0xF2,     // MOV $F2,#V0 VOLR
+    0x8F, 0x00, 0xF3,     // MOV $F3,#$00
+    // effect_b
+    0xE4, 0xF5,           // MOV A,$F5
+    0x64, 0x12,           // CMP A,$12
+    0xF0, 0xAE,           // BEQ loop
+    0xC4, 0x12,           // MOV $12,A
+    0xFD,                 // MOV Y,A
+    0xF0, 0xA9,           // BEQ loop
+    0x30, 0x36,           // BMI stop_b
+    0x68, 0x1A,           // CMP A,#$1A
+    0xB0, 0xA3,           // BCS loop
+    0x1C,                 // ASL A
+    0x1C,                 // ASL A
+    0xC4, 0x11,           // MOV $11,A
+    0xE4, 0xF6,           // MOV A,$F6
+    0x9F,                 // XCN A
+    0x28, 0x03,           // AND A,#$03
+    0x04, 0x11,           // OR A,$11
+    0xFD,                 // MOV Y,A
+    0xF6, 0x00, 0x04,     // MOV A,!NOISE_B+Y
+    0x8F, 0x6C, 0xF2,     // MOV $F2,#FLG
+    0xC4, 0xF3,           // MOV $F3,A
+    0xE4, 0xF6,           // MOV A,$F6
+    0x9F,                 // XCN A
+    0x5C,                 // LSR A
+    0x5C,                 // LSR A
+    0x28, 0x03,           // AND A,#$03
+    0xFD,                 // MOV Y,A
+    0xF6, 0xC4, 0x03,     // MOV A,!VOLUME+Y
+    0x8F, 0x10, 0xF2,     // MOV $F2,#V1 VOLL
+    0xC4, 0xF3,           // MOV $F3,A
+    0x8F, 0x11, 0xF2,     // MOV $F2,#V1 VOLR
+    0xC4, 0xF3,           // MOV $F3,A
+    0x8F, 0x4C, 0xF2,     // MOV $F2,#KON
+    0x8F, 0x02, 0xF3,     // MOV $F3,#$02
+    0x5F, 0x00, 0x02,     // JMP !loop
+    // stop_b
+    0x8F, 0x10, 0xF2,     // MOV $F2,#V1 VOLL
+    0x8F, 0x00, 0xF3,     // MOV $F3,#$00
+    0x8F, 0x11, 0xF2,     // MOV $F2,#V1 VOLR
+    0x8F, 0x00, 0xF3,     // MOV $F3,#$00
+    0x5F, 0x00, 0x02,     // JMP !loop
+];
+
+const CONTROL_IPL: u8 = 0x80;
+const CONTROL_CLEAR_01: u8 = 0x10;
+const CONTROL_CLEAR_23: u8 = 0x20;
+
+/// DSP FLG with the echo buffer left alone and the noise clock stopped.
+const FLG_ECHO_OFF: u8 = 0x20;
+
+/// One of the S-SMP's three up-counting timers.
+#[derive(Clone)]
+struct Timer {
+    /// SPC700 cycles per stage-2 tick: 128 (8kHz) or 16 (64kHz).
+    period: u32,
+    divider: u32,
+    stage: u8,
+    target: u8,
+    /// Four-bit output, cleared when read.
+    out: u8,
+    enabled: bool,
+}
+
+impl Timer {
+    fn new(period: u32) -> Self {
+        Self {
+            period,
+            divider: 0,
+            stage: 0,
+            target: 0,
+            out: 0,
+            enabled: false,
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+        self.divider += cycles;
+        while self.divider >= self.period {
+            self.divider -= self.period;
+            self.stage = self.stage.wrapping_add(1);
+            // A target of 0 counts the full 256.
+            if self.stage == self.target {
+                self.stage = 0;
+                self.out = (self.out + 1) & 0x0F;
+            }
+        }
+    }
+}
+
+/// Everything the SPC700 sees on its bus: 64KB of RAM, the DSP and the
+/// $F0-$FF I/O registers.
+#[derive(Clone)]
+struct SpcIo {
+    ram: Box<[u8; 0x10000]>,
+    dsp: Dsp,
+    dsp_addr: u8,
+    control: u8,
+    /// Ports $F4-$F7 as read by the SPC700 (written by the SNES side).
+    /// Nothing on the SGB reads the SPC700's replies, so those only land in
+    /// RAM.
+    input: [u8; 4],
+    timers: [Timer; 3],
+}
+
+impl SpcIo {
+    fn write_control(&mut self, v: u8) {
+        for (i, timer) in self.timers.iter_mut().enumerate() {
+            let enable = v & (1 << i) != 0;
+            if enable && !timer.enabled {
+                timer.stage = 0;
+                timer.out = 0;
+            }
+            timer.enabled = enable;
+        }
+        if v & CONTROL_CLEAR_01 != 0 {
+            self.input[0] = 0;
+            self.input[1] = 0;
+        }
+        if v & CONTROL_CLEAR_23 != 0 {
+            self.input[2] = 0;
+            self.input[3] = 0;
+        }
+        self.control = v;
+    }
+}
+
+impl SpcBus for SpcIo {
+    fn read(&mut self, a: u16) -> u8 {
+        match a {
+            0x00F0 | 0x00F1 | 0x00FA..=0x00FC => 0x00,
+            0x00F2 => self.dsp_addr,
+            0x00F3 => self.dsp.read(self.dsp_addr),
+            0x00F4..=0x00F7 => self.input[(a - 0x00F4) as usize],
+            0x00FD..=0x00FF => {
+                let timer = &mut self.timers[(a - 0x00FD) as usize];
+                std::mem::take(&mut timer.out)
+            }
+            0xFFC0..=0xFFFF if self.control & CONTROL_IPL != 0 => {
+                IPL_ROM[(a - 0xFFC0) as usize]
+            }
+            _ => self.ram[a as usize],
+        }
+    }
+
+    fn write(&mut self, a: u16, v: u8) {
+        match a {
+            0x00F1 => self.write_control(v),
+            0x00F2 => self.dsp_addr = v,
+            0x00F3 => self.dsp.write(self.dsp_addr, v),
+            0x00FA..=0x00FC => self.timers[(a - 0x00FA) as usize].target = v,
+            _ => {}
+        }
+        // Writes always land in RAM, even beneath the I/O registers and IPL.
+        self.ram[a as usize] = v;
+    }
+}
+
+/// The SNES sound subsystem (S-SMP and S-DSP) the Super Game Boy drives
+/// through SOUND and SOU_TRN. SOU_TRN uploads a program the same way the
+/// SNES would through the IPL loader, and SOUND writes its four parameter
+/// bytes to the input ports for that program to pick up. Until a game
+/// uploads its own, a built-in driver ([`DRIVER_CODE`]) plays SOUND's
+/// effects.
+#[derive(Clone)]
+pub struct Spc {
+    cpu: Spc700,
+    io: SpcIo,
+    /// Fractional SPC700 cycles owed, in units of 1/512 of a Game Boy cycle.
+    clock: u32,
+    /// Cycles run ahead (negative) or owed (positive) to the SPC700.
+    budget: i32,
+    sample_cycles: u32,
+    output: (i16, i16),
+}
+
+impl Spc {
+    pub fn new() -> Self {
+        let mut spc = Self {
+            cpu: Spc700::new(),
+            io: SpcIo {
+                ram: Box::new([0; 0x10000]),
+                dsp: Dsp::new(),
+                dsp_addr: 0,
+                control: CONTROL_IPL | CONTROL_CLEAR_01 | CONTROL_CLEAR_23,
+                input: [0; 4],
+                timers: [Timer::new(128), Timer::new(128), Timer::new(16)],
+            },
+            clock: 0,
+            budget: 0,
+            sample_cycles: 0,
+            output: (0, 0),
+        };
+        spc.load_driver();
+        spc
+    }
+
+    /// Put the built-in driver, its tables and sample in RAM, set the DSP up
+    /// for it and start it.
+    fn load_driver(&mut self) {
+        let ram = &mut self.io.ram;
+        let at = |address: u16| address as usize;
+        ram[at(DRIVER)..][..DRIVER_CODE.len()].copy_from_slice(&DRIVER_CODE);
+
+        // Effect A codes climb in semitones over two octaves, and each pitch
+        // attribute step raises them a minor third.
+        for code in 1..=EFFECTS_A {
+            for pitch in 0..4 {
+                let semitones = ((code - 1) % 24 + 3 * pitch) as f64;
+                let pitchh = (4.0 * (semitones / 12.0).exp2()).round() as u8;
+                ram[at(PITCH_A) + (code << 2 | pitch) as usize] = pitchh;
+            }
+        }
+        ram[at(VOLUME)..][..4].copy_from_slice(&[0x7F, 0x40, 0x20, 0x00]);
+        // Effect B codes pick one of six noise clocks, which the pitch
+        // attribute raises.
+        for code in 1..=EFFECTS_B {
+            for pitch in 0..4 {
+                let rate = 0x10 + (code - 1) % 6 + 3 * pitch;
+                ram[at(NOISE_B) + (code << 2 | pitch) as usize] = FLG_ECHO_OFF | rate;
+            }
+        }
+
+        // Sample 0: a looping square wave, eight samples up and eight down.
+        ram[at(SAMPLE_DIR)..][..4].copy_from_slice(&[
+            SQUARE_WAVE as u8,
+            (SQUARE_WAVE >> 8) as u8,
+            SQUARE_WAVE as u8,
+            (SQUARE_WAVE >> 8) as u8,
+        ]);
+        ram[at(SQUARE_WAVE)..][..9]
+            .copy_from_slice(&[0xB3, 0x77, 0x77, 0x77, 0x77, 0x99, 0x99, 0x99, 0x99]);
+
+        let dsp_writes: [(u8, u8); 11] = [
+            (0x0C, 0x7F),                    // MVOLL
+            (0x1C, 0x7F),                    // MVOLR
+            (0x6C, FLG_ECHO_OFF),            // FLG: unmuted, echo writes off
+            (0x5D, (SAMPLE_DIR >> 8) as u8), // DIR
+            (0x3D, 0x02),                    // NON: voice 1 plays noise
+            (0x04, 0x00),                    // V0 SRCN
+            (0x05, 0xFF),                    // V0 ADSR1: instant attack, fast decay
+            (0x06, 0xF2),                    // V0 ADSR2: then fade out over a second or so
+            (0x14, 0x00),                    // V1 SRCN
+            (0x15, 0xFF),                    // V1 ADSR1
+            (0x16, 0xF2),                    // V1 ADSR2
+        ];
+        for (reg, value) in dsp_writes {
+            self.io.dsp.write(reg, value);
+        }
+        self.cpu.jump(DRIVER);
+    }
+
+    /// SOUND: the effect, attribute and music bytes go to ports 0-3.
+    pub fn write_ports(&mut self, ports: [u8; 4]) {
+        self.io.input = ports;
+    }
+
+    /// SOU_TRN: a series of `length, address, data` blocks (little-endian
+    /// words), ended by a zero length whose address is where to jump.
+    pub fn upload(&mut self, data: &[u8]) {
+        let word = |i: usize| {
+            data.get(i).copied().unwrap_or(0) as u16
+                | ((data.get(i + 1).copied().unwrap_or(0) as u16) << 8)
+        };
+
+        let mut i = 0;
+        while i + 4 <= data.len() {
+            let length = word(i) as usize;
+            let address = word(i + 2);
+            i += 4;
+            if length == 0 {
+                self.cpu.jump(address);
+                return;
+            }
+            for (offset, &v) in data[i..].iter().take(length).enumerate() {
+                self.io.ram[address.wrapping_add(offset as u16) as usize] = v;
+            }
+            i += length;
+        }
+    }
+
+    /// Advance by one Game Boy base-clock cycle.
+    pub fn advance(&mut self) {
+        self.clock += CLOCK_NUM;
+        if self.clock < CLOCK_DEN {
+            return;
+        }
+        self.clock -= CLOCK_DEN;
+        self.budget += 1;
+
+        while self.budget > 0 {
+            let cycles = self.cpu.step(&mut self.io);
+            self.budget -= cycles as i32;
+            for timer in self.io.timers.iter_mut() {
+                timer.tick(cycles);
+            }
+            self.sample_cycles += cycles;
+            while self.sample_cycles >= CYCLES_PER_SAMPLE {
+                self.sample_cycles -= CYCLES_PER_SAMPLE;
+                self.output = self.io.dsp.sample(&mut self.io.ram);
+            }
+        }
+    }
+
+    /// The most recent DSP output sample.
+    pub fn output(&self) -> (i16, i16) {
+        self.output
+    }
+}
@@ -0,0 +1,802 @@
+use bitflags::bitflags;
+
+bitflags! {
+    #[derive(Copy, Clone)]
+    pub struct Psw: u8 {
+        const N = 0b1000_0000;
+        const V = 0b0100_0000;
+        /// Direct page at $0100 instead of $0000.
+        const P = 0b0010_0000;
+        const B = 0b0001_0000;
+        const H = 0b0000_1000;
+        const I = 0b0000_0100;
+        const Z = 0b0000_0010;
+        const C = 0b0000_0001;
+    }
+}
+
+/// Base cycle counts per opcode. Taken branches add two.
+#[rustfmt::skip]
+const CYCLES: [u8; 256] = [
+    2, 8, 4, 5, 3, 4, 3, 6, 2, 6, 5, 4, 5, 4, 6, 8,
+    2, 8, 4, 5, 4, 5, 5, 6, 5, 5, 6, 5, 2, 2, 4, 6,
+    2, 8, 4, 5, 3, 4, 3, 6, 2, 6, 5, 4, 5, 4, 5, 4,
+    2, 8, 4, 5, 4, 5, 5, 6, 5, 5, 6, 5, 2, 2, 3, 8,
+    2, 8, 4, 5, 3, 4, 3, 6, 2, 6, 4, 4, 5, 4, 6, 6,
+    2, 8, 4, 5, 4, 5, 5, 6, 5, 5, 4, 5, 2, 2, 4, 3,
+    2, 8, 4, 5, 3, 4, 3, 6, 2, 6, 4, 4, 5, 4, 5, 5,
+    2, 8, 4, 5, 4, 5, 5, 6, 5, 5, 5, 5, 2, 2, 3, 6,
+    2, 8, 4, 5, 3, 4, 3, 6, 2, 6, 5, 4, 5, 2, 4, 5,
+    2, 8, 4, 5, 4, 5, 5, 6, 5, 5, 5, 5, 2, 2, 12, 5,
+    3, 8, 4, 5, 3, 4, 3, 6, 2, 6, 4, 4, 5, 2, 4, 4,
+    2, 8, 4, 5, 4, 5, 5, 6, 5, 5, 5, 5, 2, 2, 3, 4,
+    3, 8, 4, 5, 4, 5, 4, 7, 2, 5, 6, 4, 5, 2, 4, 9,
+    2, 8, 4, 5, 5, 6, 6, 7, 4, 5, 5, 5, 2, 2, 6, 3,
+    2, 8, 4, 5, 3, 4, 3, 6, 2, 4, 5, 3, 4, 3, 4, 3,
+    2, 8, 4, 5, 4, 5, 5, 6, 3, 4, 5, 4, 2, 2, 4, 3,
+];
+
+/// The eight-bit ALU operations shared by opcode rows $0x-$Bx.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AluOp {
+    Or,
+    And,
+    Eor,
+    Cmp,
+    Adc,
+    Sbc,
+}
+
+pub trait SpcBus {
+    fn read(&mut self, a: u16) -> u8;
+    fn write(&mut self, a: u16, v: u8);
+}
+
+/// The S-SMP's SPC700 core. Instructions execute whole; `step` returns the
+/// cycles taken so the caller can run timers and the DSP alongside.
+#[derive(Clone)]
+pub struct Spc700 {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub pc: u16,
+    pub psw: Psw,
+    /// SLEEP or STOP was executed; nothing but a reset wakes the core.
+    stopped: bool,
+}
+
+impl Spc700 {
+    pub fn new() -> Self {
+        Self {
+            a: 0,
+            x: 0,
+            y: 0,
+            sp: 0xEF,
+            pc: 0xFFC0,
+            psw: Psw::Z,
+            stopped: false,
+        }
+    }
+
+    /// Start executing at `pc` as if freshly jumped to by the IPL loader.
+    pub fn jump(&mut self, pc: u16) {
+        self.pc = pc;
+        self.stopped = false;
+    }
+
+    fn fetch(&mut self, bus: &mut impl SpcBus) -> u8 {
+        let v = bus.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        v
+    }
+
+    fn fetch16(&mut self, bus: &mut impl SpcBus) -> u16 {
+        let lo = self.fetch(bus) as u16;
+        let hi = self.fetch(bus) as u16;
+        lo | (hi << 8)
+    }
+
+    fn dp(&self, offset: u8) -> u16 {
+        if self.psw.contains(Psw::P) {
+            0x0100 | offset as u16
+        } else {
+            offset as u16
+        }
+    }
+
+    /// Little-endian word in the direct page; the high byte wraps within it.
+    fn read16_dp(&self, bus: &mut impl SpcBus, offset: u8) -> u16 {
+        let lo = bus.read(self.dp(offset)) as u16;
+        let hi = bus.read(self.dp(offset.wrapping_add(1))) as u16;
+        lo | (hi << 8)
+    }
+
+    fn write16_dp(&self, bus: &mut impl SpcBus, offset: u8, v: u16) {
+        bus.write(self.dp(offset), v as u8);
+        bus.write(self.dp(offset.wrapping_add(1)), (v >> 8) as u8);
+    }
+
+    fn read16(bus: &mut impl SpcBus, a: u16) -> u16 {
+        bus.read(a) as u16 | ((bus.read(a.wrapping_add(1)) as u16) << 8)
+    }
+
+    fn push(&mut self, bus: &mut impl SpcBus, v: u8) {
+        bus.write(0x0100 | self.sp as u16, v);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    fn pop(&mut self, bus: &mut impl SpcBus) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        bus.read(0x0100 | self.sp as u16)
+    }
+
+    fn push16(&mut self, bus: &mut impl SpcBus, v: u16) {
+        self.push(bus, (v >> 8) as u8);
+        self.push(bus, v as u8);
+    }
+
+    fn pop16(&mut self, bus: &mut impl SpcBus) -> u16 {
+        let lo = self.pop(bus) as u16;
+        let hi = self.pop(bus) as u16;
+        lo | (hi << 8)
+    }
+
+    fn ya(&self) -> u16 {
+        ((self.y as u16) << 8) | self.a as u16
+    }
+
+    fn set_ya(&mut self, v: u16) {
+        self.a = v as u8;
+        self.y = (v >> 8) as u8;
+    }
+
+    fn set_nz(&mut self, v: u8) {
+        self.psw.set(Psw::N, v & 0x80 != 0);
+        self.psw.set(Psw::Z, v == 0);
+    }
+
+    fn set_nz16(&mut self, v: u16) {
+        self.psw.set(Psw::N, v & 0x8000 != 0);
+        self.psw.set(Psw::Z, v == 0);
+    }
+
+    fn adc(&mut self, l: u8, r: u8) -> u8 {
+        let carry = self.psw.contains(Psw::C) as u16;
+        let sum = l as u16 + r as u16 + carry;
+        let result = sum as u8;
+        self.psw.set(Psw::V, (!(l ^ r) & (l ^ result) & 0x80) != 0);
+        self.psw.set(Psw::H, ((l ^ r ^ result) & 0x10) != 0);
+        self.psw.set(Psw::C, sum > 0xFF);
+        self.set_nz(result);
+        result
+    }
+
+    fn alu(&mut self, op: AluOp, l: u8, r: u8) -> u8 {
+        match op {
+            AluOp::Or => {
+                let v = l | r;
+                self.set_nz(v);
+                v
+            }
+            AluOp::And => {
+                let v = l & r;
+                self.set_nz(v);
+                v
+            }
+            AluOp::Eor => {
+                let v = l ^ r;
+                self.set_nz(v);
+                v
+            }
+            AluOp::Cmp => {
+                self.compare(l, r);
+                l
+            }
+            AluOp::Adc => self.adc(l, r),
+            AluOp::Sbc => self.adc(l, !r),
+        }
+    }
+
+    fn compare(&mut self, l: u8, r: u8) {
+        self.psw.set(Psw::C, l >= r);
+        self.set_nz(l.wrapping_sub(r));
+    }
+
+    fn asl(&mut self, v: u8) -> u8 {
+        self.psw.set(Psw::C, v & 0x80 != 0);
+        let v = v << 1;
+        self.set_nz(v);
+        v
+    }
+
+    fn rol(&mut self, v: u8) -> u8 {
+        let carry = self.psw.contains(Psw::C) as u8;
+        self.psw.set(Psw::C, v & 0x80 != 0);
+        let v = (v << 1) | carry;
+        self.set_nz(v);
+        v
+    }
+
+    fn lsr(&mut self, v: u8) -> u8 {
+        self.psw.set(Psw::C, v & 0x01 != 0);
+        let v = v >> 1;
+        self.set_nz(v);
+        v
+    }
+
+    fn ror(&mut self, v: u8) -> u8 {
+        let carry = self.psw.contains(Psw::C) as u8;
+        self.psw.set(Psw::C, v & 0x01 != 0);
+        let v = (v >> 1) | (carry << 7);
+        self.set_nz(v);
+        v
+    }
+
+    fn inc(&mut self, v: u8) -> u8 {
+        let v = v.wrapping_add(1);
+        self.set_nz(v);
+        v
+    }
+
+    fn dec(&mut self, v: u8) -> u8 {
+        let v = v.wrapping_sub(1);
+        self.set_nz(v);
+        v
+    }
+
+    /// Shift/rotate/increment group selected by the top nibble of columns
+    /// $xB and $xC.
+    fn rmw(&mut self, row: u8, v: u8) -> u8 {
+        match row >> 1 {
+            0 => self.asl(v),
+            1 => self.rol(v),
+            2 => self.lsr(v),
+            3 => self.ror(v),
+            4 => self.dec(v),
+            _ => self.inc(v),
+        }
+    }
+
+    /// Relative branch; returns the extra cycles taken.
+    fn branch(&mut self, rel: u8, taken: bool) -> u32 {
+        if taken {
+            self.pc = self.pc.wrapping_add(rel as i8 as u16);
+            2
+        } else {
+            0
+        }
+    }
+
+    /// A 13-bit address plus bit number operand used by the MOV1/AND1/...
+    /// family.
+    fn mem_bit(&mut self, bus: &mut impl SpcBus) -> (u16, u8) {
+        let operand = self.fetch16(bus);
+        (operand & 0x1FFF, (operand >> 13) as u8)
+    }
+
+    /// Operand address for the even-row ALU columns $x4-$x7.
+    fn alu_addr_even(&mut self, bus: &mut impl SpcBus, col: u8) -> u16 {
+        match col {
+            0x4 => {
+                let d = self.fetch(bus);
+                self.dp(d)
+            }
+            0x5 => self.fetch16(bus),
+            0x6 => self.dp(self.x),
+            _ => {
+                let d = self.fetch(bus).wrapping_add(self.x);
+                self.read16_dp(bus, d)
+            }
+        }
+    }
+
+    /// Operand address for the odd-row ALU columns $x4-$x7.
+    fn alu_addr_odd(&mut self, bus: &mut impl SpcBus, col: u8) -> u16 {
+        match col {
+            0x4 => {
+                let d = self.fetch(bus).wrapping_add(self.x);
+                self.dp(d)
+            }
+            0x5 => self.fetch16(bus).wrapping_add(self.x as u16),
+            0x6 => self.fetch16(bus).wrapping_add(self.y as u16),
+            _ => {
+                let d = self.fetch(bus);
+                self.read16_dp(bus, d).wrapping_add(self.y as u16)
+            }
+        }
+    }
+
+    /// Execute one instruction, returning the cycles it took.
+    pub fn step(&mut self, bus: &mut impl SpcBus) -> u32 {
+        if self.stopped {
+            return 2;
+        }
+
+        let op = self.fetch(bus);
+        let row = op >> 4;
+        let col = op & 0x0F;
+        let mut cycles = CYCLES[op as usize] as u32;
+
+        match (row, col) {
+            // ALU rows: OR, AND, EOR, CMP, ADC, SBC.
+            (0x0..=0xB, 0x4..=0x9) => {
+                let alu = [
+                    AluOp::Or,
+                    AluOp::And,
+                    AluOp::Eor,
+                    AluOp::Cmp,
+                    AluOp::Adc,
+                    AluOp::Sbc,
+                ][(row >> 1) as usize];
+                let odd = row & 1 != 0;
+                match col {
+                    0x4..=0x7 => {
+                        let addr = if odd {
+                            self.alu_addr_odd(bus, col)
+                        } else {
+                            self.alu_addr_even(bus, col)
+                        };
+                        let v = bus.read(addr);
+                        self.a = self.alu(alu, self.a, v);
+                    }
+                    0x8 if !odd => {
+                        let v = self.fetch(bus);
+                        self.a = self.alu(alu, self.a, v);
+                    }
+                    _ => {
+                        // Memory to memory: d,#i / dd,ds / (X),(Y).
+                        let (src, dst) = match (odd, col) {
+                            (true, 0x8) => {
+                                let imm = self.fetch(bus);
+                                let d = self.fetch(bus);
+                                (imm, self.dp(d))
+                            }
+                            (false, _) => {
+                                let s = self.fetch(bus);
+                                let s = bus.read(self.dp(s));
+                                let d = self.fetch(bus);
+                                (s, self.dp(d))
+                            }
+                            _ => (bus.read(self.dp(self.y)), self.dp(self.x)),
+                        };
+                        let l = bus.read(dst);
+                        let result = self.alu(alu, l, src);
+                        if alu != AluOp::Cmp {
+                            bus.write(dst, result);
+                        }
+                    }
+                }
+            }
+
+            // Stores from A.
+            (0xC, 0x4..=0x7) => {
+                let addr = self.alu_addr_even(bus, col);
+                bus.write(addr, self.a);
+            }
+            (0xD, 0x4..=0x7) => {
+                let addr = self.alu_addr_odd(bus, col);
+                bus.write(addr, self.a);
+            }
+            // Loads into A.
+            (0xE, 0x4..=0x7) => {
+                let addr = self.alu_addr_even(bus, col);
+                self.a = bus.read(addr);
+                self.set_nz(self.a);
+            }
+            (0xF, 0x4..=0x7) => {
+                let addr = self.alu_addr_odd(bus, col);
+                self.a = bus.read(addr);
+                self.set_nz(self.a);
+            }
+            (0xC, 0x8) => {
+                let v = self.fetch(bus);
+                self.compare(self.x, v);
+            }
+            (0xC, 0x9) => {
+                let addr = self.fetch16(bus);
+                bus.write(addr, self.x);
+            }
+            (0xD, 0x8) => {
+                let d = self.fetch(bus);
+                bus.write(self.dp(d), self.x);
+            }
+            (0xD, 0x9) => {
+                let d = self.fetch(bus).wrapping_add(self.y);
+                bus.write(self.dp(d), self.x);
+            }
+            (0xE, 0x8) => {
+                self.a = self.fetch(bus);
+                self.set_nz(self.a);
+            }
+            (0xE, 0x9) => {
+                let addr = self.fetch16(bus);
+                self.x = bus.read(addr);
+                self.set_nz(self.x);
+            }
+            (0xF, 0x8) => {
+                let d = self.fetch(bus);
+                self.x = bus.read(self.dp(d));
+                self.set_nz(self.x);
+            }
+            (0xF, 0x9) => {
+                let d = self.fetch(bus).wrapping_add(self.y);
+                self.x = bus.read(self.dp(d));
+                self.set_nz(self.x);
+            }
+
+            // Branches and flag operations.
+            (_, 0x0) => match row {
+                0x0 => {}
+                0x2 => self.psw.remove(Psw::P),
+                0x4 => self.psw.insert(Psw::P),
+                0x6 => self.psw.remove(Psw::C),
+                0x8 => self.psw.insert(Psw::C),
+                0xA => self.psw.insert(Psw::I),
+                0xC => self.psw.remove(Psw::I),
+                0xE => self.psw.remove(Psw::V | Psw::H),
+                _ => {
+                    let rel = self.fetch(bus);
+                    let taken = match row {
+                        0x1 => !self.psw.contains(Psw::N),
+                        0x3 => self.psw.contains(Psw::N),
+                        0x5 => !self.psw.contains(Psw::V),
+                        0x7 => self.psw.contains(Psw::V),
+                        0x9 => !self.psw.contains(Psw::C),
+                        0xB => self.psw.contains(Psw::C),
+                        0xD => !self.psw.contains(Psw::Z),
+                        _ => self.psw.contains(Psw::Z),
+                    };
+                    cycles += self.branch(rel, taken);
+                }
+            },
+
+            // TCALL n
+            (_, 0x1) => {
+                self.push16(bus, self.pc);
+                self.pc = Self::read16(bus, 0xFFDE - 2 * row as u16);
+            }
+
+            // SET1 / CLR1 d.b
+            (_, 0x2) => {
+                let d = self.fetch(bus);
+                let addr = self.dp(d);
+                let bit = 1 << (row >> 1);
+                let v = bus.read(addr);
+                bus.write(addr, if row & 1 == 0 { v | bit } else { v & !bit });
+            }
+
+            // BBS / BBC d.b,r
+            (_, 0x3) => {
+                let d = self.fetch(bus);
+                let rel = self.fetch(bus);
+                let set = bus.read(self.dp(d)) & (1 << (row >> 1)) != 0;
+                cycles += self.branch(rel, set == (row & 1 == 0));
+            }
+
+            (_, 0xA) => match row {
+                0x0 | 0x2 | 0x4 | 0x6 | 0x8 | 0xA => {
+                    let (addr, bit) = self.mem_bit(bus);
+                    let mut m = bus.read(addr) & (1 << bit) != 0;
+                    if row == 0x2 || row == 0x6 {
+                        m = !m;
+                    }
+                    let c = self.psw.contains(Psw::C);
+                    let c = match row {
+                        0x0 | 0x2 => c | m,
+                        0x4 | 0x6 => c & m,
+                        0x8 => c ^ m,
+                        _ => m,
+                    };
+                    self.psw.set(Psw::C, c);
+                }
+                0xC => {
+                    let (addr, bit) = self.mem_bit(bus);
+                    let v = bus.read(addr) & !(1 << bit);
+                    let c = self.psw.contains(Psw::C) as u8;
+                    bus.write(addr, v | (c << bit));
+                }
+                0xE => {
+                    let (addr, bit) = self.mem_bit(bus);
+                    let v = bus.read(addr);
+                    bus.write(addr, v ^ (1 << bit));
+                }
+                0x1 | 0x3 => {
+                    let d = self.fetch(bus);
+                    let w = self.read16_dp(bus, d);
+                    let w = if row == 0x1 {
+                        w.wrapping_sub(1)
+                    } else {
+                        w.wrapping_add(1)
+                    };
+                    self.write16_dp(bus, d, w);
+                    self.set_nz16(w);
+                }
+                0x5 => {
+                    let d = self.fetch(bus);
+                    let w = self.read16_dp(bus, d);
+                    let ya = self.ya();
+                    self.psw.set(Psw::C, ya >= w);
+                    self.set_nz16(ya.wrapping_sub(w));
+                }
+                0x7 | 0x9 => {
+                    let d = self.fetch(bus);
+                    let w = self.read16_dp(bus, d);
+                    let ya = self.ya();
+                    let result = if row == 0x7 {
+                        let r = ya.wrapping_add(w);
+                        self.psw.set(Psw::C, (ya as u32 + w as u32) > 0xFFFF);
+                        self.psw.set(Psw::V, (!(ya ^ w) & (ya ^ r) & 0x8000) != 0);
+                        self.psw.set(Psw::H, ((ya ^ w ^ r) & 0x1000) != 0);
+                        r
+                    } else {
+                        let r = ya.wrapping_sub(w);
+                        self.psw.set(Psw::C, ya >= w);
+                        self.psw.set(Psw::V, ((ya ^ w) & (ya ^ r) & 0x8000) != 0);
+                        self.psw.set(Psw::H, ((ya ^ w ^ r) & 0x1000) == 0);
+                        r
+                    };
+                    self.set_ya(result);
+                    self.set_nz16(result);
+                }
+                0xB => {
+                    let d = self.fetch(bus);
+                    let w = self.read16_dp(bus, d);
+                    self.set_ya(w);
+                    self.set_nz16(w);
+                }
+                0xD => {
+                    let d = self.fetch(bus);
+                    self.write16_dp(bus, d, self.ya());
+                }
+                _ => {
+                    // MOV dd,ds
+                    let s = self.fetch(bus);
+                    let v = bus.read(self.dp(s));
+                    let d = self.fetch(bus);
+                    bus.write(self.dp(d), v);
+                }
+            },
+
+            (_, 0xB) => match row {
+                0x0..=0xB => {
+                    let d = self.fetch(bus);
+                    let d = if row & 1 != 0 { d.wrapping_add(self.x) } else { d };
+                    let addr = self.dp(d);
+                    let v = bus.read(addr);
+                    let v = self.rmw(row & 0xE, v);
+                    bus.write(addr, v);
+                }
+                0xC => {
+                    let d = self.fetch(bus);
+                    bus.write(self.dp(d), self.y);
+                }
+                0xD => {
+                    let d = self.fetch(bus).wrapping_add(self.x);
+                    bus.write(self.dp(d), self.y);
+                }
+                0xE => {
+                    let d = self.fetch(bus);
+                    self.y = bus.read(self.dp(d));
+                    self.set_nz(self.y);
+                }
+                _ => {
+                    let d = self.fetch(bus).wrapping_add(self.x);
+                    self.y = bus.read(self.dp(d));
+                    self.set_nz(self.y);
+                }
+            },
+
+            (_, 0xC) => match row {
+                0x0 | 0x2 | 0x4 | 0x6 | 0x8 | 0xA => {
+                    let addr = self.fetch16(bus);
+                    let v = bus.read(addr);
+                    let v = self.rmw(row, v);
+                    bus.write(addr, v);
+                }
+                0x1 | 0x3 | 0x5 | 0x7 | 0x9 | 0xB => self.a = self.rmw(row & 0xE, self.a),
+                0xC => {
+                    let addr = self.fetch16(bus);
+                    bus.write(addr, self.y);
+                }
+                0xD => self.y = self.dec(self.y),
+                0xE => {
+                    let addr = self.fetch16(bus);
+                    self.y = bus.read(addr);
+                    self.set_nz(self.y);
+                }
+                _ => self.y = self.inc(self.y),
+            },
+
+            (_, 0xD) => match row {
+                0x0 => self.push(bus, self.psw.bits()),
+                0x1 => self.x = self.dec(self.x),
+                0x2 => self.push(bus, self.a),
+                0x3 => self.x = self.inc(self.x),
+                0x4 => self.push(bus, self.x),
+                0x5 => {
+                    self.x = self.a;
+                    self.set_nz(self.x);
+                }
+                0x6 => self.push(bus, self.y),
+                0x7 => {
+                    self.a = self.x;
+                    self.set_nz(self.a);
+                }
+                0x8 => {
+                    self.y = self.fetch(bus);
+                    self.set_nz(self.y);
+                }
+                0x9 => {
+                    self.x = self.sp;
+                    self.set_nz(self.x);
+                }
+                0xA => {
+                    let v = self.fetch(bus);
+                    self.compare(self.y, v);
+                }
+                0xB => self.sp = self.x,
+                0xC => {
+                    self.x = self.fetch(bus);
+                    self.set_nz(self.x);
+                }
+                0xD => {
+                    self.a = self.y;
+                    self.set_nz(self.a);
+                }
+                0xE => self.psw.toggle(Psw::C),
+                _ => {
+                    self.y = self.a;
+                    self.set_nz(self.y);
+                }
+            },
+
+            (_, 0xE) => match row {
+                0x0 | 0x4 => {
+                    let addr = self.fetch16(bus);
+                    let v = bus.read(addr);
+                    self.set_nz(self.a.wrapping_sub(v));
+                    bus.write(addr, if row == 0x0 { v | self.a } else { v & !self.a });
+                }
+                0x1 | 0x5 => {
+                    let addr = self.fetch16(bus);
+                    let v = bus.read(addr);
+                    let reg = if row == 0x1 { self.x } else { self.y };
+                    self.compare(reg, v);
+                }
+                0x3 | 0x7 => {
+                    let d = self.fetch(bus);
+                    let v = bus.read(self.dp(d));
+                    let reg = if row == 0x3 { self.x } else { self.y };
+                    self.compare(reg, v);
+                }
+                0x2 | 0xD => {
+                    let d = self.fetch(bus);
+                    let d = if row == 0xD { d.wrapping_add(self.x) } else { d };
+                    let rel = self.fetch(bus);
+                    let v = bus.read(self.dp(d));
+                    cycles += self.branch(rel, self.a != v);
+                }
+                0x6 => {
+                    let d = self.fetch(bus);
+                    let rel = self.fetch(bus);
+                    let addr = self.dp(d);
+                    let v = bus.read(addr).wrapping_sub(1);
+                    bus.write(addr, v);
+                    cycles += self.branch(rel, v != 0);
+                }
+                0x8 => self.psw = Psw::from_bits_retain(self.pop(bus)),
+                0x9 => self.div(),
+                0xA => self.a = self.pop(bus),
+                0xB => self.das(),
+                0xC => self.x = self.pop(bus),
+                0xE => self.y = self.pop(bus),
+                _ => {
+                    let rel = self.fetch(bus);
+                    self.y = self.y.wrapping_sub(1);
+                    cycles += self.branch(rel, self.y != 0);
+                }
+            },
+
+            (_, _) => match row {
+                0x0 => {
+                    self.push16(bus, self.pc);
+                    self.push(bus, self.psw.bits());
+                    self.psw.insert(Psw::B);
+                    self.psw.remove(Psw::I);
+                    self.pc = Self::read16(bus, 0xFFDE);
+                }
+                0x1 => {
+                    let addr = self.fetch16(bus).wrapping_add(self.x as u16);
+                    self.pc = Self::read16(bus, addr);
+                }
+                0x2 => {
+                    let rel = self.fetch(bus);
+                    self.branch(rel, true);
+                }
+                0x3 => {
+                    let addr = self.fetch16(bus);
+                    self.push16(bus, self.pc);
+                    self.pc = addr;
+                }
+                0x4 => {
+                    let u = self.fetch(bus);
+                    self.push16(bus, self.pc);
+                    self.pc = 0xFF00 | u as u16;
+                }
+                0x5 => self.pc = self.fetch16(bus),
+                0x6 => self.pc = self.pop16(bus),
+                0x7 => {
+                    self.psw = Psw::from_bits_retain(self.pop(bus));
+                    self.pc = self.pop16(bus);
+                }
+                0x8 => {
+                    let imm = self.fetch(bus);
+                    let d = self.fetch(bus);
+                    bus.write(self.dp(d), imm);
+                }
+                0x9 => {
+                    self.a = self.a.rotate_left(4);
+                    self.set_nz(self.a);
+                }
+                0xA => {
+                    bus.write(self.dp(self.x), self.a);
+                    self.x = self.x.wrapping_add(1);
+                }
+                0xB => {
+                    self.a = bus.read(self.dp(self.x));
+                    self.x = self.x.wrapping_add(1);
+                    self.set_nz(self.a);
+                }
+                0xC => {
+                    let product = self.y as u16 * self.a as u16;
+                    self.set_ya(product);
+                    self.set_nz(self.y);
+                }
+                0xD => self.daa(),
+                _ => self.stopped = true,
+            },
+        }
+
+        cycles
+    }
+
+    fn div(&mut self) {
+        let ya = self.ya() as u32;
+        let x = self.x as u32;
+        let y = self.y as u32;
+        self.psw.set(Psw::V, y >= x);
+        self.psw.set(Psw::H, (y & 0x0F) >= (x & 0x0F));
+        if y < (x << 1) {
+            self.a = (ya / x) as u8;
+            self.y = (ya % x) as u8;
+        } else {
+            // Overflowed quotients follow the hardware's iterative algorithm.
+            self.a = (255 - (ya - (x << 9)) / (256 - x)) as u8;
+            self.y = (x + (ya - (x << 9)) % (256 - x)) as u8;
+        }
+        self.set_nz(self.a);
+    }
+
+    fn daa(&mut self) {
+        if self.psw.contains(Psw::C) || self.a > 0x99 {
+            self.a = self.a.wrapping_add(0x60);
+            self.psw.insert(Psw::C);
+        }
+        if self.psw.contains(Psw::H) || (self.a & 0x0F) > 0x09 {
+            self.a = self.a.wrapping_add(0x06);
+        }
+        self.set_nz(self.a);
+    }
+
+    fn das(&mut self) {
+        if !self.psw.contains(Psw::C) || self.a > 0x99 {
+            self.a = self.a.wrapping_sub(0x60);
+            self.psw.remove(Psw::C);
+        }
+        if !self.psw.contains(Psw::H) || (self.a & 0x0F) > 0x09 {
+            self.a = self.a.wrapping_sub(0x06);
+        }
+        self.set_nz(self.a);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::components::mode::{CCMode, GBMode};
 use serde::{Deserialize, Serialize};
-use winit::keyboard::{Key, SmolStr};
+use winit::keyboard::{Key, NamedKey, SmolStr};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
@@ -12,9 +12,20 @@ pub struct Config {
     pub dmg_boot_rom: String,
     pub shader_path: String,
     pub mode: GBMode,
+    /// Run DMG mode as a Super Game Boy: SGB packets, palettes and border.
+    #[serde(default)]
+    pub sgb: bool,
     pub ppu_config: PPUConfig,
     pub apu_config: APUConfig,
     pub input: Input,
+    /// Controls for the second machine when two are linked.
+    #[serde(default = "Input::player_two")]
+    pub link_input: Input,
+    /// Controls for players three and four on the Four Player Adapter.
+    #[serde(default = "Input::player_three")]
+    pub player3_input: Input,
+    #[serde(default = "Input::player_four")]
+    pub player4_input: Input,
 }
 
 impl Default for Config {
@@ -28,9 +39,13 @@ impl Default for Config {
             dmg_boot_rom: String::default(),
             shader_path: String::default(),
             mode: GBMode::DMG,
+            sgb: false,
             ppu_config: PPUConfig::new(),
             apu_config: APUConfig::new(),
             input: Input::new(),
+            link_input: Input::player_two(),
+            player3_input: Input::player_three(),
+            player4_input: Input::player_four(),
         }
     }
 }
@@ -101,6 +116,9 @@ pub struct APUConfig {
     pub ch2_enabled: bool,
     pub ch3_enabled: bool,
     pub ch4_enabled: bool,
+    /// Play SGB SOUND/SOU_TRN audio through the emulated SNES sound processor.
+    #[serde(default = "APUConfig::default_sgb_enabled")]
+    pub sgb_enabled: bool,
 }
 
 impl APUConfig {
@@ -111,8 +129,13 @@ impl APUConfig {
             ch2_enabled: true,
             ch3_enabled: true,
             ch4_enabled: true,
+            sgb_enabled: true,
         }
     }
+
+    fn default_sgb_enabled() -> bool {
+        true
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -126,6 +149,12 @@ pub struct Input {
     pub select: Key,
     pub start: Key,
     pub screenshot: Key,
+    /// Write the VRAM viewers' PNGs.
+    #[serde(default = "Input::default_vram_dump")]
+    pub vram_dump: Key,
+    /// Run the `--script` file again.
+    #[serde(default = "Input::default_script_reload")]
+    pub script_reload: Key,
 }
 
 impl Input {
@@ -140,6 +169,64 @@ impl Input {
             select: Key::Character(SmolStr::new("c")),
             start: Key::Character(SmolStr::new("v")),
             screenshot: Key::Character(SmolStr::new("p")),
+            vram_dump: Input::default_vram_dump(),
+            script_reload: Input::default_script_reload(),
+        }
+    }
+
+    fn default_vram_dump() -> Key {
+        Key::Character(SmolStr::new("m"))
+    }
+
+    fn default_script_reload() -> Key {
+        Key::Character(SmolStr::new("r"))
+    }
+
+    pub fn player_two() -> Self {
+        Self {
+            up: Key::Named(NamedKey::ArrowUp),
+            left: Key::Named(NamedKey::ArrowLeft),
+            down: Key::Named(NamedKey::ArrowDown),
+            right: Key::Named(NamedKey::ArrowRight),
+            a: Key::Character(SmolStr::new(".")),
+            b: Key::Character(SmolStr::new(",")),
+            select: Key::Named(NamedKey::Shift),
+            start: Key::Named(NamedKey::Enter),
+            screenshot: Key::Character(SmolStr::new("p")),
+            vram_dump: Input::default_vram_dump(),
+            script_reload: Input::default_script_reload(),
+        }
+    }
+
+    pub fn player_three() -> Self {
+        Self {
+            up: Key::Character(SmolStr::new("i")),
+            left: Key::Character(SmolStr::new("j")),
+            down: Key::Character(SmolStr::new("k")),
+            right: Key::Character(SmolStr::new("l")),
+            a: Key::Character(SmolStr::new("o")),
+            b: Key::Character(SmolStr::new("u")),
+            select: Key::Character(SmolStr::new("y")),
+            start: Key::Character(SmolStr::new("h")),
+            screenshot: Key::Character(SmolStr::new("p")),
+            vram_dump: Input::default_vram_dump(),
+            script_reload: Input::default_script_reload(),
+        }
+    }
+
+    pub fn player_four() -> Self {
+        Self {
+            up: Key::Character(SmolStr::new("8")),
+            left: Key::Character(SmolStr::new("4")),
+            down: Key::Character(SmolStr::new("5")),
+            right: Key::Character(SmolStr::new("6")),
+            a: Key::Character(SmolStr::new("9")),
+            b: Key::Character(SmolStr::new("7")),
+            select: Key::Character(SmolStr::new("1")),
+            start: Key::Character(SmolStr::new("3")),
+            screenshot: Key::Character(SmolStr::new("p")),
+            vram_dump: Input::default_vram_dump(),
+            script_reload: Input::default_script_reload(),
         }
     }
 }
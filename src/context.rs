@@ -12,6 +12,7 @@ pub struct Context {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     render_output: wgpu::Texture,
+    render_size: (u32, u32),
     pub config: wgpu::SurfaceConfiguration,
     pub size: PhysicalSize<u32>,
     window: Arc<Window>,
@@ -72,11 +73,28 @@ impl Context {
         let preset = ShaderPreset::try_parse(shader_path, ShaderFeatures::NONE).unwrap();
         let chain = FilterChain::load_from_preset(preset, &device, &queue, None).unwrap();
 
-        let render_output = device.create_texture(&wgpu::TextureDescriptor {
+        let render_output = Self::create_render_output(&device, SCREEN_W as u32, SCREEN_H as u32);
+
+        Self {
+            surface,
+            device,
+            queue,
+            render_output,
+            render_size: (SCREEN_W as u32, SCREEN_H as u32),
+            config,
+            size,
+            window,
+            chain,
+            frame_count: 0,
+        }
+    }
+
+    fn create_render_output(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
             label: Some("rendertexture"),
             size: wgpu::Extent3d {
-                width: SCREEN_W as u32,
-                height: SCREEN_H as u32,
+                width,
+                height,
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
@@ -88,19 +106,7 @@ impl Context {
                 | wgpu::TextureUsages::COPY_DST
                 | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
-        });
-
-        Self {
-            surface,
-            device,
-            queue,
-            render_output,
-            config,
-            size,
-            window,
-            chain,
-            frame_count: 0,
-        }
+        })
     }
 
     pub fn window(&self) -> &Window {
@@ -118,7 +124,15 @@ impl Context {
         self.window.request_redraw();
     }
 
-    pub fn update(&mut self, rgba: &[u8]) {
+    /// Upload a `width`×`height` RGBA frame. The render target follows the
+    /// frame size, so switching to the SGB's 256×224 output recreates it.
+    pub fn update(&mut self, rgba: &[u8], width: usize, height: usize) {
+        let (width, height) = (width as u32, height as u32);
+        if self.render_size != (width, height) {
+            self.render_output = Self::create_render_output(&self.device, width, height);
+            self.render_size = (width, height);
+        }
+
         self.queue.write_texture(
             wgpu::TexelCopyTextureInfo {
                 aspect: wgpu::TextureAspect::All,
@@ -129,12 +143,12 @@ impl Context {
             &rgba,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * SCREEN_W as u32),
-                rows_per_image: Some(SCREEN_H as u32),
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
             },
             wgpu::Extent3d {
-                width: SCREEN_W as u32,
-                height: SCREEN_H as u32,
+                width,
+                height,
                 depth_or_array_layers: 1,
             },
         );
@@ -0,0 +1,143 @@
+use bitflags::bitflags;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// WRAM's eight banks followed by HRAM, $FF80-$FFFE.
+const RAM_SIZE: usize = 0x8000 + 0x80;
+
+/// How often the log is written back to its file, in base-clock cycles
+/// (about a second).
+const AUTOSAVE: u64 = 70_224 * 60;
+
+bitflags! {
+    /// How a byte was used, in the bitfield layout FCEUX and Mesen write:
+    /// bit 0 for code and bit 1 for data. Bits 2-5 are left clear for the
+    /// jump-target and bank bits those tools keep.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct CdlFlags: u8 {
+        /// Executed, as an opcode or an operand.
+        const CODE     = 0b0000_0001;
+        /// Read by a load.
+        const DATA     = 0b0000_0010;
+        /// Copied to VRAM or OAM by HDMA or OAM DMA, as FCEUX marks
+        /// bytes read by DMA in bit 6.
+        const GRAPHICS = 0b0100_0000;
+    }
+}
+
+/// A code/data log: what every ROM byte was used for, and which bytes of
+/// WRAM and HRAM ran as code.
+///
+/// The ROM log is one byte of [`CdlFlags`] per ROM byte, with nothing else,
+/// so other tools can read it as is. The RAM log is kept in a file of its
+/// own next to it, `<name>.ram.cdl`, laid out as WRAM banks 0-7 and HRAM.
+pub struct Cdl {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    path: Option<PathBuf>,
+    /// Whether a byte gained a flag since the last save.
+    dirty: bool,
+    /// When the log was last saved.
+    saved: u64,
+}
+
+impl Cdl {
+    pub fn new(rom_len: usize) -> Self {
+        Self {
+            rom: vec![0; rom_len],
+            ram: vec![0; RAM_SIZE],
+            path: None,
+            dirty: false,
+            saved: 0,
+        }
+    }
+
+    /// A log for a ROM of `rom_len` bytes saved to `path`, carrying on from
+    /// what is already there.
+    pub fn open(path: &Path, rom_len: usize) -> io::Result<Self> {
+        let mut cdl = Self::new(rom_len);
+        merge(path, &mut cdl.rom)?;
+        merge(&ram_path(path), &mut cdl.ram)?;
+        cdl.path = Some(path.to_path_buf());
+        Ok(cdl)
+    }
+
+    /// Flags for the byte at `offset` in the ROM image.
+    pub fn rom(&self, offset: usize) -> CdlFlags {
+        CdlFlags::from_bits_truncate(self.rom.get(offset).copied().unwrap_or(0))
+    }
+
+    /// Flags for the byte at `offset` in WRAM (`bank * $1000 + address`),
+    /// or `$8000 +` an offset into HRAM.
+    pub fn ram(&self, offset: usize) -> CdlFlags {
+        CdlFlags::from_bits_truncate(self.ram.get(offset).copied().unwrap_or(0))
+    }
+
+    /// The whole ROM log, as saved.
+    pub fn rom_log(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn mark_rom(&mut self, offset: usize, flags: CdlFlags) {
+        if self.rom.is_empty() {
+            return;
+        }
+        // Banks past the end of the image mirror it.
+        let len = self.rom.len();
+        mark(&mut self.rom[offset % len], flags, &mut self.dirty);
+    }
+
+    pub fn mark_ram(&mut self, offset: usize, flags: CdlFlags) {
+        if let Some(byte) = self.ram.get_mut(offset) {
+            mark(byte, flags, &mut self.dirty);
+        }
+    }
+
+    /// Write the log to its file, and the RAM log next to it.
+    pub fn save(&mut self) -> io::Result<()> {
+        if let Some(path) = &self.path {
+            fs::write(path, &self.rom)?;
+            fs::write(ram_path(path), &self.ram)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Save if anything changed and it has been a while; `cycles` is the
+    /// base clock. The machine has no shutdown hook, so this is what keeps
+    /// the file current.
+    pub fn autosave(&mut self, cycles: u64) {
+        if self.dirty && cycles >= self.saved + AUTOSAVE {
+            self.saved = cycles;
+            if let Err(err) = self.save() {
+                eprintln!("Failed to save code/data log: {}", err);
+            }
+        }
+    }
+}
+
+fn mark(byte: &mut u8, flags: CdlFlags, dirty: &mut bool) {
+    if *byte & flags.bits() != flags.bits() {
+        *byte |= flags.bits();
+        *dirty = true;
+    }
+}
+
+/// OR the flags in the file at `path`, if there is one, into `log`.
+fn merge(path: &Path, log: &mut [u8]) -> io::Result<()> {
+    let mut old = Vec::new();
+    match File::open(path) {
+        Ok(mut file) => file.read_to_end(&mut old)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    for (byte, old) in log.iter_mut().zip(old) {
+        *byte |= old;
+    }
+    Ok(())
+}
+
+fn ram_path(path: &Path) -> PathBuf {
+    path.with_extension("ram.cdl")
+}
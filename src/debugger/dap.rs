@@ -0,0 +1,671 @@
+use crate::STEP_CYCLES;
+use crate::debugger::debugger::{Debugger, IO_REGISTERS, StopReason, io_register};
+use crate::debugger::expr::Expr;
+use crate::debugger::repl::{INTERRUPTS, parse_address, set_register};
+use crate::debugger::sources::Sources;
+use crate::debugger::symbols::Symbols;
+use crate::disasm;
+use crate::hw::interrupt::Interrupts;
+use crate::hw::motherboard::Motherboard;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// The only thread there is.
+const THREAD: u64 = 1;
+
+/// `variablesReference`s of the scopes.
+const REGISTERS: u64 = 1;
+const IO: u64 = 2;
+
+/// Return addresses looked for on the stack, at most.
+const MAX_FRAMES: usize = 32;
+
+/// Read one message: a `Content-Length` header, a blank line and that many
+/// bytes of JSON. None at the end of input.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::other("missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(io::Error::other)
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// Read messages from `input` on a thread of their own. The channel closes
+/// when the client goes away.
+pub fn spawn_reader(input: impl Read + Send + 'static) -> Receiver<Value> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut input = BufReader::new(input);
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// What a request leaves the session to do.
+enum Next {
+    Stay,
+    /// Carry on with the client gone; true ends the emulator too.
+    End(bool),
+}
+
+/// A Debug Adapter Protocol server for editors, on top of a [`Debugger`].
+///
+/// Breakpoints go on source lines through the labels in `symbols`:
+/// instructions are counted on from the closest label before a line, or the
+/// breakpoint moves to the next label where that can't be done. The stack
+/// trace is PC and whatever on the stack looks like a return address, that
+/// is, follows a CALL or RST.
+pub struct Dap<W: Write> {
+    pub debugger: Debugger,
+    pub symbols: Symbols,
+    pub sources: Sources,
+    /// The cartridge ROM, to count instructions in banks not mapped in.
+    pub rom: Vec<u8>,
+    messages: Receiver<Value>,
+    output: W,
+    seq: u64,
+    running: bool,
+    stop_on_entry: bool,
+    /// Whether the session started with `launch`, rather than `attach`.
+    launched: bool,
+    /// Lines are numbered from 1, unless the client says otherwise.
+    line_base: usize,
+    /// Breakpoint ids by source file, function and instruction.
+    source_breakpoints: HashMap<PathBuf, Vec<usize>>,
+    function_breakpoints: Vec<usize>,
+    instruction_breakpoints: Vec<usize>,
+}
+
+impl<W: Write> Dap<W> {
+    pub fn new(messages: Receiver<Value>, output: W) -> Self {
+        Self {
+            debugger: Debugger::new(),
+            symbols: Symbols::new(),
+            sources: Sources::new(),
+            rom: Vec::new(),
+            messages,
+            output,
+            seq: 1,
+            running: false,
+            stop_on_entry: false,
+            launched: false,
+            line_base: 1,
+            source_breakpoints: HashMap::new(),
+            function_breakpoints: Vec::new(),
+            instruction_breakpoints: Vec::new(),
+        }
+    }
+
+    /// Serve the client until it disconnects. The machine is held until the
+    /// client is done configuring. `between` is called after every slice of
+    /// running with the T-cycles it took, to pace the machine and feed it
+    /// input; returning false ends the session.
+    ///
+    /// Returns true if the emulator should end too: the client asked to end
+    /// the debuggee, or `between` said so.
+    pub fn run(
+        &mut self,
+        mb: &mut Motherboard,
+        mut between: impl FnMut(&mut Motherboard, u64) -> bool,
+    ) -> bool {
+        loop {
+            let message = if self.running {
+                let before = self.debugger.cycles();
+                let reason = self.debugger.run(mb, STEP_CYCLES as u64);
+                if !between(mb, self.debugger.cycles() - before) {
+                    self.event("terminated", json!({}));
+                    return true;
+                }
+                if reason != StopReason::Limit {
+                    self.running = false;
+                    self.stopped(reason);
+                }
+                match self.messages.try_recv() {
+                    Ok(message) => message,
+                    Err(TryRecvError::Empty) => continue,
+                    Err(TryRecvError::Disconnected) => return false,
+                }
+            } else {
+                match self.messages.recv() {
+                    Ok(message) => message,
+                    Err(_) => return false,
+                }
+            };
+            if let Next::End(quit) = self.handle(mb, &message) {
+                return quit;
+            }
+        }
+    }
+
+    fn handle(&mut self, mb: &mut Motherboard, message: &Value) -> Next {
+        if message["type"] != "request" {
+            return Next::Stay;
+        }
+        let command = message["command"].as_str().unwrap_or("");
+        let args = &message["arguments"];
+        let mut next = Next::Stay;
+        let result = match command {
+            "initialize" => {
+                if args["linesStartAt1"] == false {
+                    self.line_base = 0;
+                }
+                Ok(capabilities())
+            }
+            "launch" | "attach" => {
+                self.launched = command == "launch";
+                self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+                for dir in args["sourceDirs"].as_array().into_iter().flatten() {
+                    if let Some(dir) = dir.as_str() {
+                        self.sources.scan(Path::new(dir));
+                    }
+                }
+                Ok(json!({}))
+            }
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setFunctionBreakpoints" => self.set_function_breakpoints(args),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(args),
+            "setExceptionBreakpoints" => {
+                self.debugger.break_on_interrupts(Interrupts::all(), false);
+                for filter in args["filters"].as_array().into_iter().flatten() {
+                    if let Some((_, bit)) = INTERRUPTS.iter().find(|(name, _)| filter == name) {
+                        self.debugger.break_on_interrupts(*bit, true);
+                    }
+                }
+                Ok(json!({}))
+            }
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    self.stopped_because("entry", json!({}));
+                } else {
+                    self.running = true;
+                }
+                Ok(json!({}))
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD, "name": "SM83" }] })),
+            "stackTrace" => Ok(self.stack_trace(mb, args)),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS, "expensive": false },
+                { "name": "IO registers", "variablesReference": IO, "expensive": false },
+            ] })),
+            "variables" => Ok(self.variables(mb, args)),
+            "setVariable" => self.set_variable(mb, args),
+            "evaluate" => {
+                let expression = args["expression"].as_str().unwrap_or("");
+                Expr::parse(expression).and_then(|e| e.eval(mb)).map(
+                    |v| json!({ "result": format!("{} (${:X})", v, v), "variablesReference": 0 }),
+                )
+            }
+            "readMemory" => self.read_memory(mb, args),
+            "disassemble" => self.disassemble(mb, args),
+            "continue" => {
+                self.debugger.resume();
+                self.running = true;
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" | "stepIn" | "stepOut" => {
+                match command {
+                    "next" => self.debugger.step_over(mb),
+                    "stepIn" => self.debugger.step(),
+                    _ => self.debugger.step_out(mb),
+                }
+                self.running = true;
+                Ok(json!({}))
+            }
+            "pause" => {
+                if self.running {
+                    self.running = false;
+                    self.debugger.resume();
+                    self.stopped_because("pause", json!({}));
+                }
+                Ok(json!({}))
+            }
+            "disconnect" => {
+                let quit = args["terminateDebuggee"].as_bool().unwrap_or(self.launched);
+                next = Next::End(quit);
+                Ok(json!({}))
+            }
+            "terminate" => {
+                next = Next::End(true);
+                Ok(json!({}))
+            }
+            _ => Err(format!("unsupported request `{}`", command)),
+        };
+
+        let mut response = json!({
+            "type": "response",
+            "request_seq": message["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(err) => response["message"] = Value::from(err),
+        }
+        self.send(response);
+        if command == "initialize" {
+            self.event("initialized", json!({}));
+        }
+        if let Next::End(_) = next {
+            self.event("terminated", json!({}));
+        }
+        next
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        let path = PathBuf::from(
+            args["source"]["path"]
+                .as_str()
+                .ok_or("missing source path")?,
+        );
+        for id in self.source_breakpoints.remove(&path).unwrap_or_default() {
+            self.debugger.remove(id);
+        }
+        let file = self
+            .sources
+            .load(&path)
+            .map_err(|err| format!("failed to read `{}`: {}", path.display(), err))?;
+
+        let mut ids = Vec::new();
+        let mut results = Vec::new();
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = breakpoint["line"].as_u64().unwrap_or(0) as usize;
+            let line = line.saturating_sub(self.line_base);
+            let condition = parse_condition(&breakpoint["condition"]);
+            let resolved = file.resolve(line, &self.symbols, &self.rom);
+            results.push(match (resolved, condition) {
+                (_, Err(err)) => json!({ "verified": false, "message": err }),
+                (None, _) => json!({
+                    "verified": false,
+                    "message": "no code from a label in the .sym file here",
+                }),
+                (Some((line, bank, address)), Ok(condition)) => {
+                    let id = self.debugger.add_breakpoint(address, Some(bank), condition);
+                    ids.push(id);
+                    json!({
+                        "id": id,
+                        "verified": true,
+                        "line": line + self.line_base,
+                        "instructionReference": reference(address),
+                    })
+                }
+            });
+        }
+        self.source_breakpoints.insert(path, ids);
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    fn set_function_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        for id in self.function_breakpoints.drain(..) {
+            self.debugger.remove(id);
+        }
+        let mut results = Vec::new();
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let name = breakpoint["name"].as_str().unwrap_or("");
+            let at = match self.symbols.lookup(name) {
+                Some((bank, address)) => Ok((Some(bank), address)),
+                None => parse_address(name).map(|address| (None, address)),
+            };
+            let (result, id) = self.add_breakpoint(at, &breakpoint["condition"]);
+            self.function_breakpoints.extend(id);
+            results.push(result);
+        }
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        for id in self.instruction_breakpoints.drain(..) {
+            self.debugger.remove(id);
+        }
+        let mut results = Vec::new();
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let offset = breakpoint["offset"].as_i64().unwrap_or(0);
+            let at = parse_reference(&breakpoint["instructionReference"])
+                .map(|address| (None, address.wrapping_add(offset as u16)));
+            let (result, id) = self.add_breakpoint(at, &breakpoint["condition"]);
+            self.instruction_breakpoints.extend(id);
+            results.push(result);
+        }
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    /// Add a function or instruction breakpoint; its id as well, if it could.
+    fn add_breakpoint(
+        &mut self,
+        at: Result<(Option<usize>, u16), String>,
+        condition: &Value,
+    ) -> (Value, Option<usize>) {
+        match (at, parse_condition(condition)) {
+            (Err(err), _) | (_, Err(err)) => (json!({ "verified": false, "message": err }), None),
+            (Ok((bank, address)), Ok(condition)) => {
+                let id = self.debugger.add_breakpoint(address, bank, condition);
+                let result = json!({ "id": id, "verified": true, "instructionReference": reference(address) });
+                (result, Some(id))
+            }
+        }
+    }
+
+    fn stack_trace(&mut self, mb: &mut Motherboard, args: &Value) -> Value {
+        let start = args["startFrame"].as_u64().unwrap_or(0) as usize;
+        let levels = match args["levels"].as_u64() {
+            Some(0) | None => MAX_FRAMES,
+            Some(levels) => levels as usize,
+        };
+        let frames: Vec<Value> = call_stack(mb)
+            .into_iter()
+            .enumerate()
+            .skip(start)
+            .take(levels)
+            .map(|(id, address)| self.frame(mb, id, address))
+            .collect();
+        json!({ "stackFrames": frames, "totalFrames": frames.len() + start })
+    }
+
+    fn frame(&self, mb: &Motherboard, id: usize, address: u16) -> Value {
+        let bank = mb.rom_bank();
+        let name = self
+            .symbols
+            .describe(bank, address)
+            .unwrap_or_else(|| format!("${:04X}", address));
+        let mut frame = json!({
+            "id": id,
+            "name": name,
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": reference(address),
+        });
+        if let Some((path, line)) = self.sources.locate(bank, address, &self.symbols, &self.rom) {
+            frame["source"] = source(path);
+            frame["line"] = json!(line + self.line_base);
+            frame["column"] = json!(self.line_base);
+        }
+        frame
+    }
+
+    fn variables(&mut self, mb: &mut Motherboard, args: &Value) -> Value {
+        let variable = |name: &str, value: String, evaluate: String| json!({ "name": name, "value": value, "evaluateName": evaluate, "variablesReference": 0 });
+        let variables: Vec<Value> = match args["variablesReference"].as_u64() {
+            Some(REGISTERS) => {
+                let regs = mb.cpu_regs();
+                let bytes = [
+                    ("A", regs.a),
+                    ("F", regs.f),
+                    ("B", regs.b),
+                    ("C", regs.c),
+                    ("D", regs.d),
+                    ("E", regs.e),
+                    ("H", regs.h),
+                    ("L", regs.l),
+                ];
+                let words = [
+                    ("AF", regs.get_af()),
+                    ("BC", regs.get_bc()),
+                    ("DE", regs.get_de()),
+                    ("HL", regs.get_hl()),
+                    ("SP", regs.sp),
+                    ("PC", mb.pc()),
+                ];
+                let flags = [("ZF", 7), ("NF", 6), ("HF", 5), ("CF", 4)];
+                let bytes = bytes
+                    .iter()
+                    .map(|(name, v)| variable(name, format!("${:02X}", v), name.to_string()));
+                let words = words
+                    .iter()
+                    .map(|(name, v)| variable(name, format!("${:04X}", v), name.to_string()));
+                let flags = flags.iter().map(|(name, bit)| {
+                    let set = (regs.f >> bit) & 1;
+                    variable(name, set.to_string(), name.to_string())
+                });
+                bytes.chain(words).chain(flags).collect()
+            }
+            Some(IO) => IO_REGISTERS
+                .iter()
+                .map(|(name, address)| {
+                    let value = format!("${:02X}", mb.peek(*address));
+                    variable(name, value, format!("[{}]", name))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        json!({ "variables": variables })
+    }
+
+    fn set_variable(&mut self, mb: &mut Motherboard, args: &Value) -> Result<Value, String> {
+        let name = args["name"].as_str().unwrap_or("");
+        let value = Expr::parse(args["value"].as_str().unwrap_or(""))?.eval(mb)?;
+        let shown = match args["variablesReference"].as_u64() {
+            Some(REGISTERS) => {
+                set_register(mb, name, value)?;
+                match name.len() {
+                    1 => format!("${:02X}", value as u8),
+                    _ if name.ends_with('F') => format!("{}", (value != 0) as u8),
+                    _ => format!("${:04X}", value as u16),
+                }
+            }
+            Some(IO) => {
+                let address =
+                    io_register(name).ok_or_else(|| format!("unknown register `{}`", name))?;
+                mb.write_bus(address, value as u8);
+                format!("${:02X}", mb.peek(address))
+            }
+            _ => return Err("unknown scope".to_string()),
+        };
+        Ok(json!({ "value": shown }))
+    }
+
+    fn read_memory(&mut self, mb: &mut Motherboard, args: &Value) -> Result<Value, String> {
+        let address = parse_reference(&args["memoryReference"])?;
+        let address = address.wrapping_add(args["offset"].as_i64().unwrap_or(0) as u16);
+        let count = args["count"].as_u64().unwrap_or(0) as usize;
+        let count = count.min(0x10000 - address as usize);
+        let data: Vec<u8> = (0..count)
+            .map(|i| mb.peek(address.wrapping_add(i as u16)))
+            .collect();
+        Ok(json!({ "address": reference(address), "data": base64(&data) }))
+    }
+
+    fn disassemble(&mut self, mb: &mut Motherboard, args: &Value) -> Result<Value, String> {
+        let address = parse_reference(&args["memoryReference"])?;
+        let address = address.wrapping_add(args["offset"].as_i64().unwrap_or(0) as u16);
+        let skip = args["instructionOffset"].as_i64().unwrap_or(0);
+        let count = args["instructionCount"].as_u64().unwrap_or(0) as usize;
+        let bank = mb.rom_bank();
+
+        // Going back, start far enough before that the decoding has lined
+        // up with the real instructions by `address`.
+        let back = skip.min(0).unsigned_abs() as usize;
+        let mut before = Vec::new();
+        let mut at = address.saturating_sub(3 * back as u16 + 8);
+        while at < address {
+            before.push(at);
+            at = at.wrapping_add(disasm::disassemble(at, |a| mb.peek(a)).size());
+        }
+        let mut addresses: Vec<u16> = before[before.len().saturating_sub(back)..].to_vec();
+        let mut at = address;
+        for _ in 0..skip.max(0) {
+            at = at.wrapping_add(disasm::disassemble(at, |a| mb.peek(a)).size());
+        }
+        while addresses.len() < count {
+            addresses.push(at);
+            at = at.wrapping_add(disasm::disassemble(at, |a| mb.peek(a)).size());
+        }
+
+        let instructions: Vec<Value> = addresses
+            .into_iter()
+            .map(|address| {
+                let mut instruction = disasm::disassemble(address, |a| mb.peek(a));
+                instruction.name_target(&self.symbols, bank);
+                let bytes: Vec<String> = instruction
+                    .bytes
+                    .iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect();
+                let mut line = json!({
+                    "address": reference(address),
+                    "instructionBytes": bytes.join(" "),
+                    "instruction": instruction.text,
+                });
+                if let Some(label) = self.symbols.label(bank, address) {
+                    line["symbol"] = json!(label);
+                }
+                if let Some((path, found)) =
+                    self.sources.locate(bank, address, &self.symbols, &self.rom)
+                {
+                    line["location"] = source(path);
+                    line["line"] = json!(found + self.line_base);
+                }
+                line
+            })
+            .collect();
+        Ok(json!({ "instructions": instructions }))
+    }
+
+    fn stopped(&mut self, reason: StopReason) {
+        match reason {
+            StopReason::Breakpoint(id) => {
+                self.stopped_because("breakpoint", json!({ "hitBreakpointIds": [id] }))
+            }
+            StopReason::Watchpoint(..) => self.stopped_because("data breakpoint", json!({})),
+            StopReason::Interrupt(bit) => {
+                let name = INTERRUPTS
+                    .iter()
+                    .find(|(_, b)| bit.contains(*b))
+                    .map_or("?", |(name, _)| name);
+                let text = format!("{} interrupt", name);
+                self.stopped_because("exception", json!({ "description": text, "text": text }))
+            }
+            StopReason::Step | StopReason::Scanline(_) => self.stopped_because("step", json!({})),
+            StopReason::Limit => {}
+        }
+    }
+
+    fn stopped_because(&mut self, reason: &str, mut body: Value) {
+        body["reason"] = json!(reason);
+        body["threadId"] = json!(THREAD);
+        body["allThreadsStopped"] = json!(true);
+        self.event("stopped", body);
+    }
+
+    fn event(&mut self, event: &str, body: Value) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn send(&mut self, mut message: Value) {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+        let _ = write_message(&mut self.output, &message);
+    }
+}
+
+fn capabilities() -> Value {
+    let filters: Vec<Value> = INTERRUPTS
+        .iter()
+        .map(|(name, _)| json!({ "filter": name, "label": format!("{} interrupt", name), "default": false }))
+        .collect();
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsFunctionBreakpoints": true,
+        "supportsConditionalBreakpoints": true,
+        "supportsInstructionBreakpoints": true,
+        "supportsSetVariable": true,
+        "supportsReadMemoryRequest": true,
+        "supportsDisassembleRequest": true,
+        "supportsTerminateRequest": true,
+        "exceptionBreakpointFilters": filters,
+    })
+}
+
+/// PC, then the return addresses on the stack, innermost first.
+fn call_stack(mb: &mut Motherboard) -> Vec<u16> {
+    let mut frames = vec![mb.pc()];
+    let mut sp = mb.cpu_regs().sp;
+    // The stack is in WRAM or HRAM; don't wander out of it into the rest.
+    let end = match sp {
+        0xC000..0xE000 => 0xE000,
+        0xFF80.. => 0xFFFF,
+        _ => sp.saturating_add(0x100),
+    };
+    while frames.len() < MAX_FRAMES && sp < end - 1 {
+        let address = u16::from_le_bytes([mb.peek(sp), mb.peek(sp.wrapping_add(1))]);
+        let call = mb.peek(address.wrapping_sub(3));
+        let rst = mb.peek(address.wrapping_sub(1));
+        if address > 3 && (matches!(call, 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC) || rst & 0xC7 == 0xC7) {
+            frames.push(address);
+        }
+        sp = sp.wrapping_add(2);
+    }
+    frames
+}
+
+/// A breakpoint's condition, if it has one.
+fn parse_condition(condition: &Value) -> Result<Option<Expr>, String> {
+    match condition.as_str() {
+        Some(text) if !text.trim().is_empty() => Expr::parse(text).map(Some),
+        _ => Ok(None),
+    }
+}
+
+fn source(path: &Path) -> Value {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string());
+    json!({ "name": name, "path": path.to_string_lossy() })
+}
+
+/// Memory and instruction references are addresses, as `0x0150`.
+fn reference(address: u16) -> String {
+    format!("0x{:04X}", address)
+}
+
+fn parse_reference(reference: &Value) -> Result<u16, String> {
+    parse_address(reference.as_str().ok_or("missing memory reference")?)
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(bits >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
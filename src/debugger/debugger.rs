@@ -0,0 +1,426 @@
+use crate::components::prelude::io;
+use crate::debugger::expr::{self, Expr};
+use crate::hw::bus::{AccessKind, BusAccess};
+use crate::hw::interrupt::Interrupts;
+use crate::hw::motherboard::Motherboard;
+
+/// IO register names usable in expressions and as addresses.
+pub const IO_REGISTERS: [(&str, u16); 60] = [
+    ("JOYP", io::JOYP),
+    ("SB", io::SB),
+    ("SC", io::SC),
+    ("DIV", io::DIV),
+    ("TIMA", io::TIMA),
+    ("TMA", io::TMA),
+    ("TAC", io::TAC),
+    ("IF", io::IF),
+    ("NR10", io::NR10),
+    ("NR11", io::NR11),
+    ("NR12", io::NR12),
+    ("NR13", io::NR13),
+    ("NR14", io::NR14),
+    ("NR21", io::NR21),
+    ("NR22", io::NR22),
+    ("NR23", io::NR23),
+    ("NR24", io::NR24),
+    ("NR30", io::NR30),
+    ("NR31", io::NR31),
+    ("NR32", io::NR32),
+    ("NR33", io::NR33),
+    ("NR34", io::NR34),
+    ("NR41", io::NR41),
+    ("NR42", io::NR42),
+    ("NR43", io::NR43),
+    ("NR44", io::NR44),
+    ("NR50", io::NR50),
+    ("NR51", io::NR51),
+    ("NR52", io::NR52),
+    ("LCDC", io::LCDC),
+    ("STAT", io::STAT),
+    ("SCY", io::SCY),
+    ("SCX", io::SCX),
+    ("LY", io::LY),
+    ("LYC", io::LYC),
+    ("DMA", io::DMA),
+    ("BGP", io::BGP),
+    ("OBP0", io::OBP0),
+    ("OBP1", io::OBP1),
+    ("WY", io::WY),
+    ("WX", io::WX),
+    ("KEY0", io::KEY0),
+    ("KEY1", io::KEY1),
+    ("VBK", io::VBK),
+    ("BANK", io::BANK),
+    ("HDMA1", io::HDMA1),
+    ("HDMA2", io::HDMA2),
+    ("HDMA3", io::HDMA3),
+    ("HDMA4", io::HDMA4),
+    ("HDMA5", io::HDMA5),
+    ("RP", io::RP),
+    ("BGPI", io::BGPI),
+    ("BGPD", io::BGPD),
+    ("OBPI", io::OBPI),
+    ("OBPD", io::OBPD),
+    ("OPRI", io::OPRI),
+    ("SVBK", io::SVBK),
+    ("PCM12", io::PCM12),
+    ("PCM34", io::PCM34),
+    ("IE", 0xFFFF),
+];
+
+/// The address of an IO register, by name (any case).
+pub fn io_register(name: &str) -> Option<u16> {
+    IO_REGISTERS
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, a)| *a)
+}
+
+/// The name of the IO register at `address`.
+pub fn io_register_name(address: u16) -> Option<&'static str> {
+    IO_REGISTERS
+        .iter()
+        .find(|(_, a)| *a == address)
+        .map(|(n, _)| *n)
+}
+
+/// Registers and flags by name, PC being the next instruction's address;
+/// then `rombank`, and IO register names, which give their address.
+impl expr::Context for Motherboard {
+    fn var(&mut self, name: &str) -> Option<u32> {
+        let regs = self.cpu_regs();
+        Some(match name.to_ascii_lowercase().as_str() {
+            "a" => regs.a as u32,
+            "f" => regs.f as u32,
+            "b" => regs.b as u32,
+            "c" => regs.c as u32,
+            "d" => regs.d as u32,
+            "e" => regs.e as u32,
+            "h" => regs.h as u32,
+            "l" => regs.l as u32,
+            "af" => regs.get_af() as u32,
+            "bc" => regs.get_bc() as u32,
+            "de" => regs.get_de() as u32,
+            "hl" => regs.get_hl() as u32,
+            "sp" => regs.sp as u32,
+            "pc" => self.pc() as u32,
+            "zf" => (regs.f >> 7 & 1) as u32,
+            "nf" => (regs.f >> 6 & 1) as u32,
+            "hf" => (regs.f >> 5 & 1) as u32,
+            "cf" => (regs.f >> 4 & 1) as u32,
+            "rombank" => self.rom_bank() as u32,
+            _ => io_register(name)? as u32,
+        })
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        self.peek(address)
+    }
+}
+
+/// The machine as it was at a watched access; adds `value`, the byte read
+/// or written, and `addr`, where.
+struct AccessContext<'a> {
+    mb: &'a mut Motherboard,
+    access: BusAccess,
+}
+
+impl expr::Context for AccessContext<'_> {
+    fn var(&mut self, name: &str) -> Option<u32> {
+        match name {
+            "value" => Some(self.access.value as u32),
+            "addr" => Some(self.access.address as u32),
+            _ => self.mb.var(name),
+        }
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        self.mb.peek(address)
+    }
+}
+
+/// Stops when an instruction at `address` is about to run. A `bank` only
+/// matters in the switchable ROM area, $4000-$7FFF.
+#[derive(Clone, Debug)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub address: u16,
+    pub bank: Option<usize>,
+    pub condition: Option<Expr>,
+}
+
+/// Stops after an instruction reads or writes `start..=end`. Opcode fetches
+/// don't count; breakpoints cover those.
+#[derive(Clone, Debug)]
+pub struct Watchpoint {
+    pub id: usize,
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+    pub condition: Option<Expr>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StopReason {
+    Breakpoint(usize),
+    Watchpoint(usize, BusAccess),
+    /// The CPU dispatched one of the interrupts broken on.
+    Interrupt(Interrupts),
+    /// A step, step over or step out finished.
+    Step,
+    Scanline(u8),
+    /// `max_cycles` ran out first; running again carries on.
+    Limit,
+}
+
+/// What `run` is heading for, besides breakpoints.
+enum Goal {
+    Continue,
+    Step,
+    Over { ret: u16, sp: u16 },
+    Out { sp: u16, opcode: u8 },
+    Scanline { ly: u8, left: bool },
+}
+
+/// Breakpoints, watchpoints and stepping on top of a [`Motherboard`].
+///
+/// Pick a goal with `step`, `step_over`, `step_out` or `run_to_scanline`
+/// (or none, to just continue), then call `run` until it stops for a
+/// reason other than [`StopReason::Limit`].
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    interrupts: Interrupts,
+    next_id: usize,
+    goal: Goal,
+    /// T-cycles run under the debugger.
+    cycles: u64,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            interrupts: Interrupts::empty(),
+            next_id: 1,
+            goal: Goal::Continue,
+            cycles: 0,
+        }
+    }
+
+    /// Break before running the instruction at `address`; returns its id.
+    pub fn add_breakpoint(
+        &mut self,
+        address: u16,
+        bank: Option<usize>,
+        condition: Option<Expr>,
+    ) -> usize {
+        let id = self.take_id();
+        self.breakpoints.push(Breakpoint {
+            id,
+            address,
+            bank,
+            condition,
+        });
+        id
+    }
+
+    /// Break after an access to `start..=end`; returns its id.
+    pub fn add_watchpoint(
+        &mut self,
+        start: u16,
+        end: u16,
+        read: bool,
+        write: bool,
+        condition: Option<Expr>,
+    ) -> usize {
+        let id = self.take_id();
+        self.watchpoints.push(Watchpoint {
+            id,
+            start,
+            end,
+            read,
+            write,
+            condition,
+        });
+        id
+    }
+
+    /// Remove a breakpoint or watchpoint; false if there was none.
+    pub fn remove(&mut self, id: usize) -> bool {
+        let count = self.breakpoints.len() + self.watchpoints.len();
+        self.breakpoints.retain(|b| b.id != id);
+        self.watchpoints.retain(|w| w.id != id);
+        count != self.breakpoints.len() + self.watchpoints.len()
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Break when the CPU dispatches any of `interrupts`, or stop breaking
+    /// on them.
+    pub fn break_on_interrupts(&mut self, interrupts: Interrupts, on: bool) {
+        self.interrupts.set(interrupts, on);
+    }
+
+    pub fn interrupt_breaks(&self) -> Interrupts {
+        self.interrupts
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Drop the goal; `run` then only stops for breakpoints, watchpoints
+    /// and interrupts.
+    pub fn resume(&mut self) {
+        self.goal = Goal::Continue;
+    }
+
+    /// Stop at the next instruction.
+    pub fn step(&mut self) {
+        self.goal = Goal::Step;
+    }
+
+    /// Like `step`, but run a CALL or RST through to its return.
+    pub fn step_over(&mut self, mb: &mut Motherboard) {
+        let pc = mb.pc();
+        let len = match mb.peek(pc) {
+            0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => 3,
+            op if op & 0xC7 == 0xC7 => 1,
+            _ => return self.step(),
+        };
+        self.goal = Goal::Over {
+            ret: pc.wrapping_add(len),
+            sp: mb.cpu_regs().sp,
+        };
+    }
+
+    /// Run until the current function returns.
+    pub fn step_out(&mut self, mb: &mut Motherboard) {
+        self.goal = Goal::Out {
+            sp: mb.cpu_regs().sp,
+            opcode: mb.peek(mb.pc()),
+        };
+    }
+
+    /// Run until the PPU next starts scanline `ly`.
+    pub fn run_to_scanline(&mut self, mb: &Motherboard, ly: u8) {
+        self.goal = Goal::Scanline {
+            ly,
+            left: mb.ly() != ly,
+        };
+    }
+
+    /// Run towards the goal for up to `max_cycles` T-cycles.
+    pub fn run(&mut self, mb: &mut Motherboard, max_cycles: u64) -> StopReason {
+        mb.record_events(!self.watchpoints.is_empty() || !self.interrupts.is_empty());
+        let mut cycles = 0;
+        let reason = loop {
+            if cycles >= max_cycles {
+                break StopReason::Limit;
+            }
+            let fetched = mb.step_mcycle();
+            cycles += 4;
+
+            if let Some(reason) = self.goal_reached(mb, fetched) {
+                break reason;
+            }
+            if fetched && let Some(reason) = self.check(mb) {
+                break reason;
+            }
+        };
+        mb.record_events(false);
+        self.cycles += cycles;
+        if reason != StopReason::Limit {
+            self.goal = Goal::Continue;
+        }
+        reason
+    }
+
+    fn take_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    fn goal_reached(&mut self, mb: &mut Motherboard, fetched: bool) -> Option<StopReason> {
+        match &mut self.goal {
+            Goal::Continue => None,
+            Goal::Step => fetched.then_some(StopReason::Step),
+            Goal::Over { ret, sp } => {
+                (fetched && mb.pc() == *ret && mb.cpu_regs().sp >= *sp).then_some(StopReason::Step)
+            }
+            Goal::Out { sp, opcode } => {
+                if !fetched {
+                    return None;
+                }
+                let returned = matches!(*opcode, 0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9);
+                if returned && mb.cpu_regs().sp > *sp {
+                    return Some(StopReason::Step);
+                }
+                *opcode = mb.peek(mb.pc());
+                None
+            }
+            Goal::Scanline { ly, left } => {
+                if mb.ly() != *ly {
+                    *left = true;
+                    None
+                } else {
+                    left.then_some(StopReason::Scanline(*ly))
+                }
+            }
+        }
+    }
+
+    /// Look for watched accesses, interrupts and breakpoints once an
+    /// instruction has finished.
+    fn check(&mut self, mb: &mut Motherboard) -> Option<StopReason> {
+        let events = mb.take_events();
+        for access in events.accesses {
+            let hit = self.watchpoints.iter().find(|w| {
+                (w.start..=w.end).contains(&access.address)
+                    && match access.kind {
+                        AccessKind::Fetch => false,
+                        AccessKind::Read => w.read,
+                        AccessKind::Write => w.write,
+                    }
+                    && w.condition.as_ref().is_none_or(|c| {
+                        c.eval(&mut AccessContext {
+                            mb: &mut *mb,
+                            access,
+                        }) != Ok(0)
+                    })
+            });
+            if let Some(w) = hit {
+                return Some(StopReason::Watchpoint(w.id, access));
+            }
+        }
+        if let Some(bit) = events
+            .interrupts
+            .iter()
+            .find(|i| self.interrupts.intersects(**i))
+        {
+            return Some(StopReason::Interrupt(*bit));
+        }
+
+        let pc = mb.pc();
+        let bank = mb.rom_bank();
+        self.breakpoints
+            .iter()
+            .find(|b| {
+                b.address == pc
+                    && (pc & 0xC000 != 0x4000 || b.bank.is_none_or(|want| want == bank))
+                    && b.condition
+                        .as_ref()
+                        .is_none_or(|c| c.eval(&mut *mb) != Ok(0))
+            })
+            .map(|b| StopReason::Breakpoint(b.id))
+    }
+}
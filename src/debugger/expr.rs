@@ -0,0 +1,285 @@
+use std::fmt;
+
+/// Where an expression gets its values from.
+pub trait Context {
+    /// A named value: a register, flag or other variable.
+    fn var(&mut self, name: &str) -> Option<u32>;
+
+    /// A byte of memory.
+    fn read(&mut self, address: u16) -> u8;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnOp {
+    Not,
+    Neg,
+    Complement,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+/// A debugger expression, as used by conditional breakpoints and `print`.
+///
+/// Numbers are decimal unless written `$FF`, `0xFF` or `%1010`. Names are
+/// registers (`a`, `hl`, `sp`, `pc`...), flags (`zf`, `nf`, `hf`, `cf`) and
+/// whatever else the [`Context`] knows. `[x]` reads the byte at `x` and
+/// `{x}` the little-endian word. Operators, loosest first: `||`, `&&`,
+/// comparisons, `|`, `^`, `&`, `+ -`, and unary `! - ~`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(u32),
+    Var(String),
+    Byte(Box<Expr>),
+    Word(Box<Expr>),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected `{}`", token)),
+        }
+    }
+
+    pub fn eval(&self, ctx: &mut dyn Context) -> Result<u32, String> {
+        Ok(match self {
+            Expr::Number(n) => *n,
+            Expr::Var(name) => ctx
+                .var(name)
+                .ok_or_else(|| format!("unknown name `{}`", name))?,
+            Expr::Byte(a) => {
+                let a = a.eval(ctx)? as u16;
+                ctx.read(a) as u32
+            }
+            Expr::Word(a) => {
+                let a = a.eval(ctx)? as u16;
+                ctx.read(a) as u32 | (ctx.read(a.wrapping_add(1)) as u32) << 8
+            }
+            Expr::Unary(op, e) => {
+                let v = e.eval(ctx)?;
+                match op {
+                    UnOp::Not => (v == 0) as u32,
+                    UnOp::Neg => v.wrapping_neg(),
+                    UnOp::Complement => !v,
+                }
+            }
+            Expr::Binary(BinOp::Or, l, r) => (l.eval(ctx)? != 0 || r.eval(ctx)? != 0) as u32,
+            Expr::Binary(BinOp::And, l, r) => (l.eval(ctx)? != 0 && r.eval(ctx)? != 0) as u32,
+            Expr::Binary(op, l, r) => {
+                let (l, r) = (l.eval(ctx)?, r.eval(ctx)?);
+                match op {
+                    BinOp::Eq => (l == r) as u32,
+                    BinOp::Ne => (l != r) as u32,
+                    BinOp::Lt => (l < r) as u32,
+                    BinOp::Le => (l <= r) as u32,
+                    BinOp::Gt => (l > r) as u32,
+                    BinOp::Ge => (l >= r) as u32,
+                    BinOp::BitOr => l | r,
+                    BinOp::BitXor => l ^ r,
+                    BinOp::BitAnd => l & r,
+                    BinOp::Add => l.wrapping_add(r),
+                    BinOp::Sub => l.wrapping_sub(r),
+                    BinOp::Or | BinOp::And => unreachable!(),
+                }
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(u32),
+    Name(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Symbol(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Longest first, so `<=` wins over `<`.
+const SYMBOLS: [&str; 21] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "|", "^", "&", "+", "-", "!", "~", "(", ")", "[",
+    "]", "{", "}",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '%' || c == '.' {
+            let end = rest[1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .map_or(rest.len(), |i| i + 1);
+            let word = &rest[..end];
+            tokens.push(match parse_number(word) {
+                Some(n) => Token::Number(n),
+                None if c.is_ascii_digit() || c == '$' || c == '%' => {
+                    return Err(format!("bad number `{}`", word));
+                }
+                None => Token::Name(word.to_string()),
+            });
+            rest = &rest[end..];
+        } else {
+            return Err(format!("unexpected `{}`", c));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// `$FF`, `0xFF`, `%1010` or decimal.
+pub fn parse_number(word: &str) -> Option<u32> {
+    if let Some(hex) = word.strip_prefix('$').or_else(|| word.strip_prefix("0x")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = word.strip_prefix('%') {
+        u32::from_str_radix(bin, 2).ok()
+    } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+        word.parse().ok()
+    } else {
+        None
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn eat(&mut self, symbol: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Symbol(s)) if *s == symbol => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[(&str, BinOp)],
+        next: fn(&mut Self) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        let mut left = next(self)?;
+        'outer: loop {
+            for (symbol, op) in ops {
+                if self.eat(symbol) {
+                    let right = next(self)?;
+                    left = Expr::Binary(*op, Box::new(left), Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        self.binary(&[("||", BinOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        self.binary(&[("&&", BinOp::And)], Self::compare)
+    }
+
+    fn compare(&mut self) -> Result<Expr, String> {
+        self.binary(
+            &[
+                ("==", BinOp::Eq),
+                ("!=", BinOp::Ne),
+                ("<=", BinOp::Le),
+                (">=", BinOp::Ge),
+                ("<", BinOp::Lt),
+                (">", BinOp::Gt),
+            ],
+            Self::bit_or,
+        )
+    }
+
+    fn bit_or(&mut self) -> Result<Expr, String> {
+        self.binary(&[("|", BinOp::BitOr)], Self::bit_xor)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, String> {
+        self.binary(&[("^", BinOp::BitXor)], Self::bit_and)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, String> {
+        self.binary(&[("&", BinOp::BitAnd)], Self::add)
+    }
+
+    fn add(&mut self) -> Result<Expr, String> {
+        self.binary(&[("+", BinOp::Add), ("-", BinOp::Sub)], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        for (symbol, op) in [("!", UnOp::Not), ("-", UnOp::Neg), ("~", UnOp::Complement)] {
+            if self.eat(symbol) {
+                return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+            }
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        for (open, close, wrap) in [
+            ("(", ")", None),
+            ("[", "]", Some(Expr::Byte as fn(Box<Expr>) -> Expr)),
+            ("{", "}", Some(Expr::Word as fn(Box<Expr>) -> Expr)),
+        ] {
+            if self.eat(open) {
+                let inner = self.or()?;
+                if !self.eat(close) {
+                    return Err(format!("missing `{}`", close));
+                }
+                return Ok(match wrap {
+                    Some(wrap) => wrap(Box::new(inner)),
+                    None => inner,
+                });
+            }
+        }
+
+        match self.tokens.get(self.pos).cloned() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(Expr::Number(n))
+            }
+            Some(Token::Name(name)) => {
+                self.pos += 1;
+                Ok(Expr::Var(name))
+            }
+            Some(token) => Err(format!("unexpected `{}`", token)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+pub mod cdl;
+pub mod dap;
+pub mod debugger;
+pub mod expr;
+pub mod profile;
+pub mod repl;
+pub mod search;
+pub mod sources;
+pub mod symbols;
+pub mod timeline;
+pub mod trace;
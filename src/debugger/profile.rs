@@ -0,0 +1,282 @@
+use crate::debugger::symbols::Symbols;
+use crate::hw::interrupt::Interrupts;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Interrupts in priority order, as their bits count up.
+const HANDLERS: [&str; 5] = ["vblank", "stat", "timer", "serial", "joypad"];
+
+/// How many addresses the report lists.
+const HOTSPOTS: usize = 50;
+
+/// A frame of the call stack the profiler keeps alongside the program's.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Entry {
+    /// A function entered by CALL or RST, by bank and address.
+    Function(usize, u16),
+    /// An interrupt handler, by bit number (0 for VBlank).
+    Interrupt(u8),
+}
+
+/// Counts the T-cycles spent at every instruction, by ROM bank and address,
+/// and where in the call stack they were spent.
+///
+/// The motherboard calls [`tick`](Self::tick) every M-cycle and
+/// [`fetched`](Self::fetched) at each instruction boundary. Calls are
+/// followed by watching CALL and RST push a return address and interrupts
+/// dispatch; a frame returns when SP rises above where it was pushed, so
+/// code that drops its return address unwinds too.
+///
+/// Cycles are the CPU's, so in double speed they tick twice as fast as the
+/// base clock.
+pub struct Profiler {
+    /// The instruction now running, as `(bank, address)`; bank 0 outside
+    /// $4000-$7FFF. None until the first boundary.
+    current: Option<(usize, u16)>,
+    opcode: u8,
+    sp: u16,
+    /// T-cycles since the last instruction boundary.
+    step_running: u64,
+    step_halted: u64,
+    /// An interrupt dispatched since the last boundary.
+    dispatched: Option<u8>,
+    /// The call stack, the key for `stacks`...
+    path: Vec<Entry>,
+    /// ...and SP just after each frame's return address was pushed; a frame
+    /// has returned once SP is above it.
+    frame_sp: Vec<u16>,
+    addresses: HashMap<(usize, u16), u64>,
+    stacks: HashMap<Vec<Entry>, u64>,
+    halted_stacks: HashMap<Vec<Entry>, u64>,
+    handlers: [u64; 5],
+    total_executing: u64,
+    total_halted: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            current: None,
+            opcode: 0,
+            sp: 0,
+            step_running: 0,
+            step_halted: 0,
+            dispatched: None,
+            path: Vec::new(),
+            frame_sp: Vec::new(),
+            addresses: HashMap::new(),
+            stacks: HashMap::new(),
+            halted_stacks: HashMap::new(),
+            handlers: [0; 5],
+            total_executing: 0,
+            total_halted: 0,
+        }
+    }
+
+    /// One M-cycle passed.
+    pub fn tick(&mut self, halted: bool) {
+        if halted {
+            self.step_halted += 4;
+        } else {
+            self.step_running += 4;
+        }
+    }
+
+    /// The CPU is dispatching `interrupt`.
+    pub fn interrupt(&mut self, interrupt: Interrupts) {
+        self.dispatched = Some(interrupt.bits().trailing_zeros() as u8);
+    }
+
+    /// The CPU fetched `opcode` at `pc` in `bank`, with SP at `sp`: the
+    /// instruction before it is done.
+    pub fn fetched(&mut self, bank: usize, pc: u16, opcode: u8, sp: u16) {
+        let bank = if (0x4000..0x8000).contains(&pc) {
+            bank
+        } else {
+            0
+        };
+
+        let running = std::mem::take(&mut self.step_running);
+        let halted = std::mem::take(&mut self.step_halted);
+        let dispatched = self.dispatched.take();
+        // Cycles from before the first boundary are part of an instruction
+        // that started before profiling did.
+        if let Some(current) = self.current {
+            match dispatched {
+                // The dispatch counts as the handler's, at its vector, though
+                // waiting halted for it doesn't.
+                Some(bit) => {
+                    self.flush(current, 0, halted);
+                    self.push(Entry::Interrupt(bit), sp);
+                    self.flush((0, pc), running, 0);
+                }
+                None => {
+                    self.flush(current, running, halted);
+                    while self.frame_sp.last().is_some_and(|&frame| frame < sp) {
+                        self.frame_sp.pop();
+                        self.path.pop();
+                    }
+                    if is_call(self.opcode) && sp == self.sp.wrapping_sub(2) {
+                        self.push(Entry::Function(bank, pc), sp);
+                    }
+                }
+            }
+        }
+
+        self.current = Some((bank, pc));
+        self.opcode = opcode;
+        self.sp = sp;
+    }
+
+    fn push(&mut self, entry: Entry, sp: u16) {
+        self.path.push(entry);
+        self.frame_sp.push(sp);
+    }
+
+    /// Put `running` and `halted` cycles down to `address` and the current
+    /// stack.
+    fn flush(&mut self, address: (usize, u16), running: u64, halted: u64) {
+        if running > 0 {
+            *self.addresses.entry(address).or_default() += running;
+            add(&mut self.stacks, &self.path, running);
+        }
+        if halted > 0 {
+            add(&mut self.halted_stacks, &self.path, halted);
+        }
+        let handler = self.path.iter().rev().find_map(|entry| match entry {
+            Entry::Interrupt(bit) => Some(*bit as usize),
+            Entry::Function(..) => None,
+        });
+        if let Some(handler) = handler {
+            self.handlers[handler] += running + halted;
+        }
+        self.total_executing += running;
+        self.total_halted += halted;
+    }
+
+    /// T-cycles spent running instructions.
+    pub fn executing(&self) -> u64 {
+        self.total_executing
+    }
+
+    /// T-cycles spent halted.
+    pub fn halted(&self) -> u64 {
+        self.total_halted
+    }
+
+    /// T-cycles spent in the instruction at `address` in `bank`.
+    pub fn cycles(&self, bank: usize, address: u16) -> u64 {
+        let bank = if (0x4000..0x8000).contains(&address) {
+            bank
+        } else {
+            0
+        };
+        self.addresses.get(&(bank, address)).copied().unwrap_or(0)
+    }
+
+    /// T-cycles spent in `interrupt`'s handler and anything it called.
+    pub fn handler(&self, interrupt: Interrupts) -> u64 {
+        self.handlers[interrupt.bits().trailing_zeros() as usize % 5]
+    }
+
+    /// A report of where the time went: totals, interrupt handlers, the
+    /// functions in `symbols` (when there are any) and the busiest
+    /// addresses, most cycles first.
+    pub fn report(&self, symbols: &Symbols) -> String {
+        let total = (self.total_executing + self.total_halted).max(1);
+        let percent = |cycles: u64| cycles as f64 * 100.0 / total as f64;
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{} T-cycles: {} executing ({:.1}%), {} halted ({:.1}%)",
+            self.total_executing + self.total_halted,
+            self.total_executing,
+            percent(self.total_executing),
+            self.total_halted,
+            percent(self.total_halted)
+        );
+
+        let _ = writeln!(out, "\nInterrupt handlers:");
+        for (name, cycles) in HANDLERS.iter().zip(self.handlers) {
+            let _ = writeln!(out, "{:>12} {:>6.2}%  {}", cycles, percent(cycles), name);
+        }
+
+        if !symbols.is_empty() {
+            let mut functions: HashMap<&str, u64> = HashMap::new();
+            for (&(bank, address), &cycles) in &self.addresses {
+                let name = symbols.function(bank, address).unwrap_or("?");
+                *functions.entry(name).or_default() += cycles;
+            }
+            let _ = writeln!(out, "\nFunctions:");
+            for (name, cycles) in sorted(functions) {
+                let _ = writeln!(out, "{:>12} {:>6.2}%  {}", cycles, percent(cycles), name);
+            }
+        }
+
+        let _ = writeln!(out, "\nHotspots:");
+        for ((bank, address), cycles) in sorted(self.addresses.clone()).take(HOTSPOTS) {
+            let _ = write!(
+                out,
+                "{:>12} {:>6.2}%  {:02X}:{:04X}",
+                cycles,
+                percent(cycles),
+                bank,
+                address
+            );
+            if let Some(name) = symbols.describe(bank, address) {
+                let _ = write!(out, "  {}", name);
+            }
+            let _ = writeln!(out);
+        }
+        out
+    }
+
+    /// Cycles by call stack in the folded format flamegraph tools read:
+    /// `frame;frame;frame cycles`, one stack per line. Time halted ends in
+    /// a `[halted]` frame.
+    pub fn folded(&self, symbols: &Symbols) -> String {
+        let name = |entry: &Entry| match *entry {
+            Entry::Function(bank, address) => match symbols.label(bank, address) {
+                Some(label) => label.to_string(),
+                None => format!("{:02X}:{:04X}", bank, address),
+            },
+            Entry::Interrupt(bit) => format!("[{}]", HANDLERS[bit as usize % 5]),
+        };
+        let mut lines: Vec<String> = Vec::new();
+        for (stacks, leaf) in [
+            (&self.stacks, None),
+            (&self.halted_stacks, Some("[halted]")),
+        ] {
+            for (path, cycles) in stacks {
+                let mut frames: Vec<String> = std::iter::once("[top]".to_string())
+                    .chain(path.iter().map(name))
+                    .collect();
+                frames.extend(leaf.map(str::to_string));
+                lines.push(format!("{} {}", frames.join(";"), cycles));
+            }
+        }
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+/// CALL, conditional CALL or RST.
+fn is_call(opcode: u8) -> bool {
+    matches!(opcode, 0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC) || opcode & 0xC7 == 0xC7
+}
+
+fn add(stacks: &mut HashMap<Vec<Entry>, u64>, path: &[Entry], cycles: u64) {
+    match stacks.get_mut(path) {
+        Some(total) => *total += cycles,
+        None => {
+            stacks.insert(path.to_vec(), cycles);
+        }
+    }
+}
+
+/// Most cycles first, ties in key order.
+fn sorted<K: Ord>(map: HashMap<K, u64>) -> impl Iterator<Item = (K, u64)> {
+    let mut items: Vec<_> = map.into_iter().collect();
+    items.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    items.into_iter()
+}
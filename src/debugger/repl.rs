@@ -0,0 +1,552 @@
+use crate::STEP_CYCLES;
+use crate::components::prelude::{Flags, Reg};
+use crate::debugger::debugger::{Debugger, StopReason, io_register};
+use crate::debugger::expr::Expr;
+use crate::debugger::search::{Compare, Search, Watch, WatchList, Width, gameshark};
+use crate::debugger::symbols::Symbols;
+use crate::disasm;
+use crate::hw::bus::AccessKind;
+use crate::hw::interrupt::Interrupts;
+use crate::hw::motherboard::Motherboard;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+const HELP: &str = "\
+c                          continue (a line of input pauses)
+s [n]                      step n instructions
+n                          step over a CALL or RST
+finish                     run until the current function returns
+line <ly>                  run until scanline ly starts
+b [bank:]<addr> [if cond]  break at addr (in ROM bank `bank`)
+w <addr>[-end] [r|w|rw] [if cond]
+                           break after a read or write (default w);
+                           `value` and `addr` name the access
+bi <interrupt>             toggle breaking on vblank, stat, timer,
+                           serial or joypad dispatch
+d <id>                     delete a breakpoint or watchpoint
+l                          list breakpoints and watchpoints
+r                          show registers
+set <reg> <expr>           set a register or flag (a..l, af..hl, sp,
+                           pc, zf, nf, hf, cf)
+x <addr> [len]             dump memory
+dis [addr] [n]             disassemble n instructions (default 10 at PC)
+poke <addr> <expr>         write memory
+search [8|16]              start a RAM search for a byte or word
+filter <cond>              keep candidates whose value now is = n, != n,
+                           < n, > n, +n or -n (from before), changed,
+                           same, up or down
+results [n]                list n candidates (default 20), with
+                           GameShark codes for their values
+display [[name=]<addr>[:16]]
+                           show a byte or word at every stop
+undisplay <addr>           stop showing it
+p <expr>                   print an expression
+sym <file>                 load labels from a .sym file
+q                          quit
+
+Addresses are hex ($ and 0x optional), IO register names or labels from
+a .sym file; a label in $4000-$7FFF breaks only in its bank. Expressions
+are decimal unless prefixed with $, 0x or %; [x] reads a byte and {x} a
+word, and registers, flags and IO register names (as addresses) can be
+used, e.g. `b 0150 if a == $10 && [LY] >= 144`.
+";
+
+pub const INTERRUPTS: [(&str, Interrupts); 5] = [
+    ("vblank", Interrupts::V_BLANK),
+    ("stat", Interrupts::LCD),
+    ("timer", Interrupts::TIMER),
+    ("serial", Interrupts::SERIAL),
+    ("joypad", Interrupts::JOYPAD),
+];
+
+/// What a command asks the REPL to do next.
+enum Next {
+    Prompt,
+    Run,
+    Steps(u32),
+    Quit,
+}
+
+/// A command line interface to a [`Debugger`], fed one command per line.
+///
+/// The machine runs in slices of [`STEP_CYCLES`] while the REPL waits for
+/// it; a line of input arriving in the meantime pauses it and is run as the
+/// next command.
+pub struct Repl<W: Write> {
+    pub debugger: Debugger,
+    /// Labels for addresses, shown in disassembly and usable as addresses.
+    pub symbols: Symbols,
+    /// Values shown with the registers whenever the machine stops.
+    pub watches: WatchList,
+    search: Option<Search>,
+    commands: Receiver<String>,
+    output: W,
+    /// The last command, repeated by an empty line.
+    last: String,
+}
+
+impl<W: Write> Repl<W> {
+    pub fn new(commands: Receiver<String>, output: W) -> Self {
+        Self {
+            debugger: Debugger::new(),
+            symbols: Symbols::new(),
+            watches: WatchList::new(),
+            search: None,
+            commands,
+            output,
+            last: String::new(),
+        }
+    }
+
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    /// Take commands until `q` or the end of input. `between` is called
+    /// after every slice of running with the T-cycles it took, to pace the
+    /// machine and feed it input; returning false quits.
+    pub fn run(
+        &mut self,
+        mb: &mut Motherboard,
+        mut between: impl FnMut(&mut Motherboard, u64) -> bool,
+    ) {
+        self.status(mb);
+        let mut pending = None;
+        loop {
+            let line = match pending.take() {
+                Some(line) => line,
+                None => {
+                    let _ = write!(self.output, "> ");
+                    let _ = self.output.flush();
+                    match self.commands.recv() {
+                        Ok(line) => line,
+                        Err(_) => return,
+                    }
+                }
+            };
+            let line = if line.trim().is_empty() {
+                self.last.clone()
+            } else {
+                self.last = line.trim().to_string();
+                self.last.clone()
+            };
+
+            let steps = match self.command(mb, &line) {
+                Ok(Next::Prompt) => continue,
+                Ok(Next::Quit) => return,
+                Ok(Next::Run) => 1,
+                Ok(Next::Steps(n)) => n,
+                Err(err) => {
+                    let _ = writeln!(self.output, "error: {}", err);
+                    continue;
+                }
+            };
+
+            for i in 0..steps {
+                if i > 0 {
+                    self.debugger.step();
+                }
+                let reason = loop {
+                    let before = self.debugger.cycles();
+                    let reason = self.debugger.run(mb, STEP_CYCLES as u64);
+                    if !between(mb, self.debugger.cycles() - before) {
+                        return;
+                    }
+                    if reason != StopReason::Limit {
+                        break reason;
+                    }
+                    match self.commands.try_recv() {
+                        Ok(line) => {
+                            self.debugger.resume();
+                            if !line.trim().is_empty() {
+                                pending = Some(line);
+                            }
+                            break StopReason::Limit;
+                        }
+                        Err(TryRecvError::Empty | TryRecvError::Disconnected) => {}
+                    }
+                };
+                if reason != StopReason::Step || i + 1 == steps {
+                    self.report(mb, reason);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn command(&mut self, mb: &mut Motherboard, line: &str) -> Result<Next, String> {
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        let mut words = args.split_whitespace();
+
+        match command {
+            "c" | "continue" => self.debugger.resume(),
+            "s" | "step" => {
+                let n = match words.next() {
+                    Some(n) => n.parse().map_err(|_| format!("bad count `{}`", n))?,
+                    None => 1,
+                };
+                self.debugger.step();
+                return Ok(Next::Steps(n));
+            }
+            "n" | "next" => self.debugger.step_over(mb),
+            "finish" | "out" => self.debugger.step_out(mb),
+            "line" => {
+                let ly = Expr::parse(args)?.eval(mb)?;
+                if ly > 153 {
+                    return Err(format!("no scanline {}", ly));
+                }
+                self.debugger.run_to_scanline(mb, ly as u8);
+            }
+            "b" | "break" => {
+                let (at, condition) = split_condition(args)?;
+                let (bank, address) = match at.split_once(':') {
+                    Some((bank, address)) => (
+                        Some(
+                            usize::from_str_radix(bank, 16)
+                                .map_err(|_| format!("bad bank `{}`", bank))?,
+                        ),
+                        self.address(address)?,
+                    ),
+                    None => match self.symbols.lookup(at) {
+                        Some((bank, address)) if (0x4000..0x8000).contains(&address) => {
+                            (Some(bank), address)
+                        }
+                        _ => (None, self.address(at)?),
+                    },
+                };
+                let id = self.debugger.add_breakpoint(address, bank, condition);
+                let name = self.name(bank.unwrap_or(0), address);
+                let _ = writeln!(self.output, "Breakpoint {} at ${:04X}{}", id, address, name);
+                return Ok(Next::Prompt);
+            }
+            "w" | "watch" => {
+                let (at, condition) = split_condition(args)?;
+                let mut words = at.split_whitespace();
+                let range = words.next().ok_or("missing address")?;
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (self.address(start)?, self.address(end)?),
+                    None => (self.address(range)?, self.address(range)?),
+                };
+                let (read, write) = match words.next() {
+                    None | Some("w") => (false, true),
+                    Some("r") => (true, false),
+                    Some("rw") => (true, true),
+                    Some(other) => return Err(format!("bad access `{}`", other)),
+                };
+                let id = self
+                    .debugger
+                    .add_watchpoint(start, end, read, write, condition);
+                let _ = writeln!(self.output, "Watchpoint {} at ${:04X}", id, start);
+                return Ok(Next::Prompt);
+            }
+            "bi" => {
+                for name in words {
+                    let (_, bit) = INTERRUPTS
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .ok_or_else(|| format!("unknown interrupt `{}`", name))?;
+                    let on = !self.debugger.interrupt_breaks().contains(*bit);
+                    self.debugger.break_on_interrupts(*bit, on);
+                }
+                let names: Vec<_> = INTERRUPTS
+                    .iter()
+                    .filter(|(_, bit)| self.debugger.interrupt_breaks().contains(*bit))
+                    .map(|(name, _)| *name)
+                    .collect();
+                let _ = writeln!(self.output, "Breaking on: {}", names.join(" "));
+                return Ok(Next::Prompt);
+            }
+            "d" | "delete" => {
+                let id = args.parse().map_err(|_| format!("bad id `{}`", args))?;
+                if !self.debugger.remove(id) {
+                    return Err(format!("no breakpoint {}", id));
+                }
+                return Ok(Next::Prompt);
+            }
+            "l" | "list" => {
+                for b in self.debugger.breakpoints() {
+                    let _ = write!(self.output, "{}: break ", b.id);
+                    if let Some(bank) = b.bank {
+                        let _ = write!(self.output, "{:02X}:", bank);
+                    }
+                    let _ = writeln!(
+                        self.output,
+                        "${:04X}{}{}",
+                        b.address,
+                        self.name(b.bank.unwrap_or(0), b.address),
+                        if b.condition.is_some() { " if ..." } else { "" }
+                    );
+                }
+                for w in self.debugger.watchpoints() {
+                    let _ = writeln!(
+                        self.output,
+                        "{}: watch ${:04X}-${:04X} {}{}{}",
+                        w.id,
+                        w.start,
+                        w.end,
+                        if w.read { "r" } else { "" },
+                        if w.write { "w" } else { "" },
+                        if w.condition.is_some() { " if ..." } else { "" }
+                    );
+                }
+                return Ok(Next::Prompt);
+            }
+            "r" | "regs" => {
+                self.status(mb);
+                return Ok(Next::Prompt);
+            }
+            "set" => {
+                let register = words.next().ok_or("missing register")?;
+                let value = Expr::parse(&args[register.len()..])?.eval(mb)?;
+                set_register(mb, register, value)?;
+                self.status(mb);
+                return Ok(Next::Prompt);
+            }
+            "x" => {
+                let address = self.address(words.next().ok_or("missing address")?)?;
+                let len = match words.next() {
+                    Some(len) => Expr::parse(len)?.eval(mb)?,
+                    None => 16,
+                };
+                for row in (0..len).step_by(16) {
+                    let row_address = address.wrapping_add(row as u16);
+                    let _ = write!(self.output, "${:04X}:", row_address);
+                    for i in row..(row + 16).min(len) {
+                        let v = mb.peek(address.wrapping_add(i as u16));
+                        let _ = write!(self.output, " {:02X}", v);
+                    }
+                    let _ = writeln!(self.output);
+                }
+                return Ok(Next::Prompt);
+            }
+            "dis" => {
+                let address = match words.next() {
+                    Some(address) => self.address(address)?,
+                    None => mb.pc(),
+                };
+                let count = match words.next() {
+                    Some(count) => Expr::parse(count)?.eval(mb)?,
+                    None => 10,
+                };
+                let bank = mb.rom_bank();
+                let mut address = address;
+                for _ in 0..count {
+                    let mut instruction = disasm::disassemble(address, |a| mb.peek(a));
+                    instruction.name_target(&self.symbols, bank);
+                    if let Some(label) = self.symbols.label(bank, address) {
+                        let _ = writeln!(self.output, "{}:", label);
+                    }
+                    let shown = if address < 0x4000 { 0 } else { bank };
+                    let _ = writeln!(
+                        self.output,
+                        "{}",
+                        disasm::line(shown, address, &instruction)
+                    );
+                    address = address.wrapping_add(instruction.size());
+                }
+                return Ok(Next::Prompt);
+            }
+            "poke" => {
+                let at = words.next().ok_or("missing address")?;
+                let address = self.address(at)?;
+                let value = Expr::parse(&args[at.len()..])?.eval(mb)?;
+                mb.poke(address, value as u8);
+                return Ok(Next::Prompt);
+            }
+            "p" | "print" => {
+                let value = Expr::parse(args)?.eval(mb)?;
+                let _ = writeln!(self.output, "{} (${:X})", value, value);
+                return Ok(Next::Prompt);
+            }
+            "search" => {
+                let width = match words.next() {
+                    None | Some("8") => Width::Byte,
+                    Some("16") => Width::Word,
+                    Some(other) => return Err(format!("bad width `{}` (8 or 16)", other)),
+                };
+                let search = Search::new(mb, width);
+                let _ = writeln!(self.output, "{} candidates", search.len());
+                self.search = Some(search);
+                return Ok(Next::Prompt);
+            }
+            "filter" => {
+                let compare = Compare::parse(args)?;
+                let search = self
+                    .search
+                    .as_mut()
+                    .ok_or("no search; start one with `search`")?;
+                let left = search.filter(mb, compare);
+                let _ = writeln!(self.output, "{} candidates", left);
+                return Ok(Next::Prompt);
+            }
+            "results" => {
+                let search = self
+                    .search
+                    .as_ref()
+                    .ok_or("no search; start one with `search`")?;
+                let n = match words.next() {
+                    Some(n) => n.parse().map_err(|_| format!("bad count `{}`", n))?,
+                    None => 20,
+                };
+                let width = search.width();
+                for c in search.candidates().iter().take(n) {
+                    let codes = gameshark(mb, c.address, c.value, width);
+                    let _ = writeln!(
+                        self.output,
+                        "${:04X}  {} (was {})  {}",
+                        c.address,
+                        width.show(c.value),
+                        width.show(c.previous),
+                        codes.join(" ")
+                    );
+                }
+                if search.len() > n {
+                    let _ = writeln!(self.output, "... and {} more", search.len() - n);
+                }
+                return Ok(Next::Prompt);
+            }
+            "display" => {
+                if !args.is_empty() {
+                    let watch = Watch::parse(args, |at| self.address(at))?;
+                    self.watches.add(watch);
+                }
+                let line = self.watches.show(mb);
+                let _ = writeln!(self.output, "{}", line);
+                return Ok(Next::Prompt);
+            }
+            "undisplay" => {
+                let address = self.address(args)?;
+                if !self.watches.remove(address) {
+                    return Err(format!("${:04X} isn't displayed", address));
+                }
+                return Ok(Next::Prompt);
+            }
+            "sym" => {
+                self.symbols = Symbols::load(Path::new(args))
+                    .map_err(|err| format!("failed to read `{}`: {}", args, err))?;
+                let _ = writeln!(self.output, "{} labels", self.symbols.len());
+                return Ok(Next::Prompt);
+            }
+            "h" | "help" => {
+                let _ = write!(self.output, "{}", HELP);
+                return Ok(Next::Prompt);
+            }
+            "q" | "quit" => return Ok(Next::Quit),
+            _ => return Err(format!("unknown command `{}` (h for help)", command)),
+        }
+        Ok(Next::Run)
+    }
+
+    /// A label, or anything [`parse_address`] takes.
+    fn address(&self, text: &str) -> Result<u16, String> {
+        match self.symbols.lookup(text) {
+            Some((_, address)) => Ok(address),
+            None => parse_address(text),
+        }
+    }
+
+    /// ` <label+offset>` for `address`, if a label comes before it.
+    fn name(&self, bank: usize, address: u16) -> String {
+        self.symbols
+            .describe(bank, address)
+            .map_or(String::new(), |name| format!(" <{}>", name))
+    }
+
+    fn report(&mut self, mb: &mut Motherboard, reason: StopReason) {
+        let _ = match reason {
+            StopReason::Breakpoint(id) => writeln!(self.output, "Breakpoint {}", id),
+            StopReason::Watchpoint(id, access) => writeln!(
+                self.output,
+                "Watchpoint {}: {} ${:02X} at ${:04X}",
+                id,
+                if access.kind == AccessKind::Write {
+                    "wrote"
+                } else {
+                    "read"
+                },
+                access.value,
+                access.address
+            ),
+            StopReason::Interrupt(bit) => {
+                let name = INTERRUPTS
+                    .iter()
+                    .find(|(_, b)| bit.contains(*b))
+                    .map_or("?", |(name, _)| name);
+                writeln!(self.output, "Interrupt {}", name)
+            }
+            StopReason::Scanline(ly) => writeln!(self.output, "Scanline {}", ly),
+            StopReason::Limit => writeln!(self.output, "Paused"),
+            StopReason::Step => Ok(()),
+        };
+        self.status(mb);
+    }
+
+    fn status(&mut self, mb: &mut Motherboard) {
+        let regs = mb.cpu_regs();
+        let pc = mb.pc();
+        let _ = write!(self.output, "PC=${:04X}", pc);
+        if (0x4000..0x8000).contains(&pc) {
+            let _ = write!(self.output, " (bank {:02X})", mb.rom_bank());
+        }
+        let name = self.name(mb.rom_bank(), pc);
+        let _ = write!(self.output, "{}", name);
+        let _ = writeln!(
+            self.output,
+            " AF=${:04X} BC=${:04X} DE=${:04X} HL=${:04X} SP=${:04X} LY={}",
+            regs.get_af(),
+            regs.get_bc(),
+            regs.get_de(),
+            regs.get_hl(),
+            regs.sp,
+            mb.ly()
+        );
+        let mut instruction = disasm::disassemble(pc, |a| mb.peek(a));
+        instruction.name_target(&self.symbols, mb.rom_bank());
+        let bank = if pc < 0x4000 { 0 } else { mb.rom_bank() };
+        let _ = writeln!(self.output, "{}", disasm::line(bank, pc, &instruction));
+        if !self.watches.is_empty() {
+            let line = self.watches.show(mb);
+            let _ = writeln!(self.output, "{}", line);
+        }
+    }
+}
+
+/// `$0150`, `0x0150`, `0150` or an IO register name.
+pub fn parse_address(text: &str) -> Result<u16, String> {
+    if let Some(address) = io_register(text) {
+        return Ok(address);
+    }
+    let hex = text
+        .strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    u16::from_str_radix(hex, 16).map_err(|_| format!("bad address `{}`", text))
+}
+
+/// Split `what if condition` into `what` and the parsed condition.
+fn split_condition(args: &str) -> Result<(&str, Option<Expr>), String> {
+    match args.split_once(" if ") {
+        Some((at, condition)) => Ok((at.trim(), Some(Expr::parse(condition)?))),
+        None => Ok((args, None)),
+    }
+}
+
+pub fn set_register(mb: &mut Motherboard, name: &str, value: u32) -> Result<(), String> {
+    let flag = match name.to_ascii_lowercase().as_str() {
+        "zf" => Flags::Z,
+        "nf" => Flags::N,
+        "hf" => Flags::H,
+        "cf" => Flags::C,
+        _ => {
+            let reg = Reg::parse(name).ok_or_else(|| format!("unknown register `{}`", name))?;
+            mb.set_register(reg, value as u16);
+            return Ok(());
+        }
+    };
+    let mut regs = mb.cpu_regs();
+    regs.set_flag(flag, value != 0);
+    mb.set_cpu_regs(regs);
+    Ok(())
+}
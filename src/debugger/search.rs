@@ -0,0 +1,302 @@
+use crate::debugger::expr::parse_number;
+use crate::hw::motherboard::Motherboard;
+use std::fmt;
+
+/// Where games keep their state: cartridge RAM, WRAM and HRAM.
+const REGIONS: [(u16, u16); 3] = [(0xA000, 0xBFFF), (0xC000, 0xDFFF), (0xFF80, 0xFFFE)];
+
+/// How big the values searched for or watched are. Words are little-endian.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Width {
+    Byte,
+    Word,
+}
+
+impl Width {
+    pub fn bytes(self) -> u16 {
+        match self {
+            Width::Byte => 1,
+            Width::Word => 2,
+        }
+    }
+
+    fn mask(self) -> u32 {
+        match self {
+            Width::Byte => 0xFF,
+            Width::Word => 0xFFFF,
+        }
+    }
+
+    /// The value at `address`, read with [`Motherboard::peek`].
+    pub fn read(self, mb: &Motherboard, address: u16) -> u32 {
+        match self {
+            Width::Byte => mb.peek(address) as u32,
+            Width::Word => {
+                u16::from_le_bytes([mb.peek(address), mb.peek(address.wrapping_add(1))]) as u32
+            }
+        }
+    }
+
+    /// `value` in hex, as wide as this.
+    pub fn show(self, value: u32) -> String {
+        match self {
+            Width::Byte => format!("${:02X}", value),
+            Width::Word => format!("${:04X}", value),
+        }
+    }
+}
+
+/// How a candidate's value now must compare with a number, or with its
+/// value at the last snapshot.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compare {
+    Equal(u32),
+    NotEqual(u32),
+    Less(u32),
+    Greater(u32),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    IncreasedBy(u32),
+    DecreasedBy(u32),
+}
+
+impl Compare {
+    /// `= n`, `!= n`, `< n`, `> n`, `+n`, `-n`, `changed`, `same`, `up` or
+    /// `down`, with numbers as for expressions.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let number = |rest: &str| {
+            let rest = rest.trim();
+            parse_number(rest).ok_or_else(|| format!("bad number `{}`", rest))
+        };
+        Ok(match text {
+            "changed" => Compare::Changed,
+            "same" | "unchanged" => Compare::Unchanged,
+            "up" | "increased" => Compare::Increased,
+            "down" | "decreased" => Compare::Decreased,
+            _ if text.starts_with("!=") => Compare::NotEqual(number(&text[2..])?),
+            _ if text.starts_with("==") => Compare::Equal(number(&text[2..])?),
+            _ if text.starts_with('=') => Compare::Equal(number(&text[1..])?),
+            _ if text.starts_with('<') => Compare::Less(number(&text[1..])?),
+            _ if text.starts_with('>') => Compare::Greater(number(&text[1..])?),
+            _ if text.starts_with('+') => Compare::IncreasedBy(number(&text[1..])?),
+            _ if text.starts_with('-') => Compare::DecreasedBy(number(&text[1..])?),
+            _ => Compare::Equal(number(text)?),
+        })
+    }
+
+    /// Whether a value that was `previous` and is `current` passes. Adding
+    /// and taking away wrap around at `width`.
+    pub fn matches(self, previous: u32, current: u32, width: Width) -> bool {
+        let mask = width.mask();
+        match self {
+            Compare::Equal(n) => current == n,
+            Compare::NotEqual(n) => current != n,
+            Compare::Less(n) => current < n,
+            Compare::Greater(n) => current > n,
+            Compare::Changed => current != previous,
+            Compare::Unchanged => current == previous,
+            Compare::Increased => current > previous,
+            Compare::Decreased => current < previous,
+            Compare::IncreasedBy(n) => current == previous.wrapping_add(n) & mask,
+            Compare::DecreasedBy(n) => current == previous.wrapping_sub(n) & mask,
+        }
+    }
+}
+
+/// An address still in the running, with its value at the last two
+/// snapshots.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Candidate {
+    pub address: u16,
+    pub value: u32,
+    pub previous: u32,
+}
+
+/// A search of RAM for the address a game keeps some value at, narrowed
+/// down by comparing snapshots: lives going down by one, a timer changing,
+/// a score being 1200.
+pub struct Search {
+    width: Width,
+    candidates: Vec<Candidate>,
+}
+
+impl Search {
+    /// Start a search with every address in RAM, snapshotted now.
+    pub fn new(mb: &Motherboard, width: Width) -> Self {
+        let candidates = REGIONS
+            .iter()
+            .flat_map(|&(start, end)| start..=end + 1 - width.bytes())
+            .map(|address| {
+                let value = width.read(mb, address);
+                Candidate {
+                    address,
+                    value,
+                    previous: value,
+                }
+            })
+            .collect();
+        Self { width, candidates }
+    }
+
+    pub fn width(&self) -> Width {
+        self.width
+    }
+
+    /// Take a snapshot and keep the candidates whose value passes `compare`;
+    /// returns how many are left.
+    pub fn filter(&mut self, mb: &Motherboard, compare: Compare) -> usize {
+        let width = self.width;
+        self.candidates.retain_mut(|candidate| {
+            let value = width.read(mb, candidate.address);
+            candidate.previous = candidate.value;
+            candidate.value = value;
+            compare.matches(candidate.previous, value, width)
+        });
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self) -> &[Candidate] {
+        &self.candidates
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+}
+
+/// GameShark codes that keep `value` at `address`, one per byte. The first
+/// byte of a code is the RAM bank: `01`, or `9X` for WRAM bank X where the
+/// CGB has banked it in at $D000.
+pub fn gameshark(mb: &Motherboard, address: u16, value: u32, width: Width) -> Vec<String> {
+    let svbk = mb.peek(0xFF70);
+    let bank = match address {
+        0xD000..=0xDFFF if svbk != 0xFF && svbk & 0x07 > 1 => 0x90 | (svbk & 0x07),
+        _ => 0x01,
+    };
+    (0..width.bytes())
+        .map(|i| {
+            let [lo, hi] = address.wrapping_add(i).to_le_bytes();
+            let byte = (value >> (8 * i)) as u8;
+            format!("{:02X}{:02X}{:02X}{:02X}", bank, byte, lo, hi)
+        })
+        .collect()
+}
+
+/// An address whose value is shown as the game runs.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Watch {
+    pub address: u16,
+    pub width: Width,
+    /// Shown instead of the address, if not empty.
+    pub name: String,
+}
+
+impl Watch {
+    /// `[name=]address[:16]`, with `address` read by `parse_address`.
+    pub fn parse(
+        spec: &str,
+        parse_address: impl Fn(&str) -> Result<u16, String>,
+    ) -> Result<Self, String> {
+        let (name, at) = match spec.split_once('=') {
+            Some((name, at)) => (name.trim(), at.trim()),
+            None => ("", spec.trim()),
+        };
+        let (at, width) = match at.split_once(':') {
+            Some((at, "16")) => (at, Width::Word),
+            Some((at, "8")) => (at, Width::Byte),
+            Some((_, width)) => return Err(format!("bad width `{}` (8 or 16)", width)),
+            None => (at, Width::Byte),
+        };
+        Ok(Self {
+            address: parse_address(at)?,
+            width,
+            name: name.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name.as_str() {
+            "" => write!(f, "${:04X}", self.address),
+            name => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Addresses to keep an eye on, logged on the frames they change.
+#[derive(Default)]
+pub struct WatchList {
+    watches: Vec<Watch>,
+    /// The values as last logged.
+    last: Vec<u32>,
+    frame: u64,
+    ly: u8,
+}
+
+impl WatchList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, watch: Watch) {
+        self.watches.push(watch);
+    }
+
+    /// Stop watching `address`; false if it wasn't watched.
+    pub fn remove(&mut self, address: u16) -> bool {
+        let before = self.watches.len();
+        self.watches.retain(|w| w.address != address);
+        self.watches.len() != before
+    }
+
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    /// Every watched value, as `name=$12 $C0A0=$0340`.
+    pub fn show(&self, mb: &Motherboard) -> String {
+        self.watches
+            .iter()
+            .map(|w| format!("{}={}", w, w.width.show(w.width.read(mb, w.address))))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Call after every instruction. At the start of each VBlank, the line
+    /// to log if a watched value changed since the last one.
+    pub fn frame(&mut self, mb: &Motherboard) -> Option<String> {
+        let ly = mb.ly();
+        let vblank = ly == 144 && self.ly != 144;
+        self.ly = ly;
+        if !vblank || self.watches.is_empty() {
+            return None;
+        }
+        self.frame += 1;
+        let values: Vec<u32> = self
+            .watches
+            .iter()
+            .map(|w| w.width.read(mb, w.address))
+            .collect();
+        if values == self.last {
+            return None;
+        }
+        self.last = values;
+        Some(format!("frame {}: {}", self.frame, self.show(mb)))
+    }
+}
@@ -0,0 +1,245 @@
+use crate::debugger::symbols::Symbols;
+use crate::disasm;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// File extensions scanned for assembly source.
+const EXTENSIONS: [&str; 6] = ["asm", "s", "inc", "z80", "sm83", "gbz80"];
+
+const MNEMONICS: [&str; 46] = [
+    "adc", "add", "and", "bit", "call", "ccf", "cp", "cpl", "daa", "dec", "di", "ei", "halt",
+    "inc", "jp", "jr", "ld", "ldd", "ldh", "ldi", "nop", "or", "pop", "push", "res", "ret", "reti",
+    "rl", "rla", "rlc", "rlca", "rr", "rra", "rrc", "rrca", "rst", "sbc", "scf", "set", "sla",
+    "sra", "srl", "stop", "sub", "swap", "xor",
+];
+
+/// An assembly source file, with the labels it defines.
+pub struct SourceFile {
+    lines: Vec<String>,
+    /// The full name of the label defined on each line that has one, with
+    /// local labels prefixed by their parent as in `.sym` files.
+    labels: HashMap<usize, String>,
+}
+
+impl SourceFile {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Find the labels in RGBDS-style source: `Name:` or `Name::` starts a
+    /// scope for `.local` labels, which may leave out the colon.
+    pub fn parse(text: &str) -> Self {
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        let mut labels = HashMap::new();
+        let mut scope = String::new();
+        for (i, line) in lines.iter().enumerate() {
+            let Some((name, _)) = split_label(line) else {
+                continue;
+            };
+            let name = match name.strip_prefix('.') {
+                Some(_) => format!("{}{}", scope, name),
+                None => {
+                    scope = name.split('.').next().unwrap_or(name).to_string();
+                    name.to_string()
+                }
+            };
+            labels.insert(i, name);
+        }
+        Self { lines, labels }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// The label defined on `line` (counting from 0).
+    pub fn label(&self, line: usize) -> Option<&str> {
+        self.labels.get(&line).map(String::as_str)
+    }
+
+    /// Where the code on `line` or the first line after it with an
+    /// instruction on it assembled to: the line, ROM bank and address.
+    ///
+    /// Addresses come from the closest label before the line in `symbols`,
+    /// counting instructions on from it in `rom`. Where that can't be
+    /// followed, past a macro or data, the next label stands in.
+    pub fn resolve(
+        &self,
+        line: usize,
+        symbols: &Symbols,
+        rom: &[u8],
+    ) -> Option<(usize, usize, u16)> {
+        let start = (0..=line.min(self.lines.len())).rev().find(|l| {
+            self.label(*l)
+                .is_some_and(|name| symbols.lookup(name).is_some())
+        });
+        if let Some(start) = start
+            && let Some(found) = self.walk(start, symbols, rom).find(|(l, ..)| *l >= line)
+        {
+            return Some(found);
+        }
+        (line..self.lines.len()).find_map(|l| {
+            let (bank, address) = symbols.lookup(self.label(l)?)?;
+            Some((l, bank, address))
+        })
+    }
+
+    /// The line `address` in `bank` assembled from, if it follows a label
+    /// in this file.
+    pub fn locate(
+        &self,
+        bank: usize,
+        address: u16,
+        symbols: &Symbols,
+        rom: &[u8],
+    ) -> Option<usize> {
+        let (name, _) = symbols.nearest(bank, address)?;
+        let start = self.labels.iter().find(|(_, n)| *n == name)?.0;
+        self.walk(*start, symbols, rom)
+            .find(|(_, _, a)| *a == address)
+            .map(|(line, ..)| line)
+            .or(Some(*start))
+    }
+
+    /// Each instruction line from the label on `start`, with the bank and
+    /// address it assembled to, until a line whose size can't be known.
+    fn walk<'a>(
+        &'a self,
+        start: usize,
+        symbols: &'a Symbols,
+        rom: &'a [u8],
+    ) -> impl Iterator<Item = (usize, usize, u16)> + 'a {
+        let (mut bank, mut address) = self
+            .label(start)
+            .and_then(|name| symbols.lookup(name))
+            .unwrap_or((0, 0));
+        let mut stopped = false;
+        self.lines[start..]
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, text)| {
+                if stopped {
+                    return None;
+                }
+                let line = start + i;
+                // Labels put the count back on track.
+                if let Some(found) = self.label(line).and_then(|name| symbols.lookup(name)) {
+                    (bank, address) = found;
+                }
+                let code = match split_label(text) {
+                    Some((_, rest)) => rest,
+                    None => text,
+                };
+                let code = code.split(';').next().unwrap_or("").trim();
+                let mnemonic = code.split_whitespace().next()?;
+                let instruction = disasm::disassemble(address, disasm::rom_reader(rom, bank));
+                let decoded = instruction.text.split_whitespace().next().unwrap_or("");
+                let known = MNEMONICS.contains(&mnemonic.to_ascii_lowercase().as_str());
+                if !known || normalize(mnemonic) != normalize(decoded) {
+                    stopped = true;
+                    return None;
+                }
+                let at = address;
+                address = address.wrapping_add(instruction.size());
+                Some((line, bank, at))
+            })
+    }
+}
+
+/// Assembly sources under some directories, to find where labels are.
+#[derive(Default)]
+pub struct Sources {
+    files: HashMap<PathBuf, SourceFile>,
+}
+
+impl Sources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load every source file under `dir`, leaving out hidden directories.
+    pub fn scan(&mut self, dir: &Path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if path.is_dir() {
+                if !hidden {
+                    self.scan(&path);
+                }
+            } else if path
+                .extension()
+                .is_some_and(|e| EXTENSIONS.iter().any(|x| e.eq_ignore_ascii_case(x)))
+            {
+                let _ = self.load(&path);
+            }
+        }
+    }
+
+    /// Load `path`, or read it again if it was loaded before.
+    pub fn load(&mut self, path: &Path) -> io::Result<&SourceFile> {
+        let file = SourceFile::load(path)?;
+        self.files.insert(path.to_path_buf(), file);
+        Ok(&self.files[path])
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&SourceFile> {
+        self.files.get(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The file and line `address` in `bank` assembled from.
+    pub fn locate(
+        &self,
+        bank: usize,
+        address: u16,
+        symbols: &Symbols,
+        rom: &[u8],
+    ) -> Option<(&Path, usize)> {
+        self.files.iter().find_map(|(path, file)| {
+            let line = file.locate(bank, address, symbols, rom)?;
+            Some((path.as_path(), line))
+        })
+    }
+}
+
+/// A label at the start of `line` and what follows it.
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    let end = trimmed
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "_.#@$".contains(c)))
+        .unwrap_or(trimmed.len());
+    let (name, rest) = trimmed.split_at(end);
+    let first = name.chars().next()?;
+    if !(first.is_ascii_alphabetic() || first == '_' || first == '.') {
+        return None;
+    }
+    match rest.strip_prefix("::").or_else(|| rest.strip_prefix(':')) {
+        Some(rest) => Some((name, rest)),
+        // A local label may go without a colon at the start of a line.
+        None if name.starts_with('.') && line.starts_with('.') => Some((name, rest)),
+        None => None,
+    }
+}
+
+/// `ldi`, `ldd` and `ldh` are `ld` in another spelling.
+fn normalize(mnemonic: &str) -> String {
+    match mnemonic.to_ascii_lowercase().as_str() {
+        "ldi" | "ldd" | "ldh" => "ld".to_string(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::{fs, io};
+
+/// Labels from a `.sym` file, as written by RGBDS (`rgblink -n`), no$gmb and
+/// wla-dx: one `bank:address name` per line, with `;` comments. In wla-dx
+/// files only the `[labels]` section is read.
+///
+/// Banks only tell labels apart in the switchable ROM area, $4000-$7FFF;
+/// elsewhere a label matches whatever bank is mapped.
+#[derive(Clone, Default)]
+pub struct Symbols {
+    labels: BTreeMap<(usize, u16), String>,
+    names: HashMap<String, (usize, u16)>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Read a `.sym` file's text, skipping lines that don't parse.
+    pub fn parse(text: &str) -> Self {
+        let mut symbols = Self::new();
+        let mut labels = true;
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if let Some(section) = line.strip_prefix('[') {
+                labels = section.trim_end_matches(']').eq_ignore_ascii_case("labels");
+                continue;
+            }
+            if !labels {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let (Some(at), Some(name)) = (words.next(), words.next()) else {
+                continue;
+            };
+            let Some((bank, address)) = at.split_once(':') else {
+                continue;
+            };
+            if let (Ok(bank), Ok(address)) = (
+                usize::from_str_radix(bank, 16),
+                u16::from_str_radix(address, 16),
+            ) {
+                symbols.insert(bank, address, name);
+            }
+        }
+        symbols
+    }
+
+    /// Add a label. The first one given for an address is the one shown.
+    pub fn insert(&mut self, bank: usize, address: u16, name: &str) {
+        self.labels
+            .entry(key(bank, address))
+            .or_insert_with(|| name.to_string());
+        self.names.insert(name.to_string(), (bank, address));
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// A label's bank and address.
+    pub fn lookup(&self, name: &str) -> Option<(usize, u16)> {
+        self.names.get(name).copied()
+    }
+
+    /// The label right at `address`, with `bank` mapped.
+    pub fn label(&self, bank: usize, address: u16) -> Option<&str> {
+        self.labels.get(&key(bank, address)).map(String::as_str)
+    }
+
+    /// The closest label at or before `address` in the same memory area,
+    /// and how far past it `address` is.
+    pub fn nearest(&self, bank: usize, address: u16) -> Option<(&str, u16)> {
+        let key = key(bank, address);
+        let (&(label_bank, label_address), name) = self.labels.range(..=key).next_back()?;
+        if label_bank != key.0 || area(label_address) != area(address) {
+            return None;
+        }
+        Some((name, address - label_address))
+    }
+
+    /// `address` as `label` or `label+offset`.
+    pub fn describe(&self, bank: usize, address: u16) -> Option<String> {
+        Some(match self.nearest(bank, address)? {
+            (name, 0) => name.to_string(),
+            (name, offset) => format!("{}+{}", name, offset),
+        })
+    }
+
+    /// The function `address` is in: the closest label before it, without
+    /// any `.local` part.
+    pub fn function(&self, bank: usize, address: u16) -> Option<&str> {
+        let (name, _) = self.nearest(bank, address)?;
+        name.split('.').next()
+    }
+}
+
+/// Bank numbers only count in the switchable ROM area.
+fn key(bank: usize, address: u16) -> (usize, u16) {
+    match address {
+        0x4000..=0x7FFF => (bank, address),
+        _ => (0, address),
+    }
+}
+
+/// Which memory area an address is in, so `label+offset` never spans two.
+fn area(address: u16) -> u8 {
+    match address {
+        0x0000..=0x3FFF => 0,
+        0x4000..=0x7FFF => 1,
+        0x8000..=0x9FFF => 2,
+        0xA000..=0xBFFF => 3,
+        0xC000..=0xCFFF => 4,
+        0xD000..=0xDFFF => 5,
+        0xFF80..=0xFFFE => 6,
+        _ => 7,
+    }
+}
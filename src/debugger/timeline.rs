@@ -0,0 +1,262 @@
+use crate::debugger::debugger::io_register_name;
+use crate::hw::interrupt::Interrupts;
+
+/// Base-clock dots per second.
+const DOTS_PER_SECOND: f64 = 4_194_304.0;
+
+/// How long an HDMA block holds the bus, in dots, at either speed.
+const HDMA_BLOCK: u64 = 32;
+
+const MODES: [&str; 4] = ["HBlank", "VBlank", "OAM scan", "Drawing"];
+
+const INTERRUPTS: [(Interrupts, &str); 5] = [
+    (Interrupts::V_BLANK, "vblank"),
+    (Interrupts::LCD, "stat"),
+    (Interrupts::TIMER, "timer"),
+    (Interrupts::SERIAL, "serial"),
+    (Interrupts::JOYPAD, "joypad"),
+];
+
+/// Tracks, as Chrome trace threads.
+const PPU_MODE: u8 = 1;
+const SCANLINE: u8 = 2;
+const INTERRUPT: u8 = 3;
+const DMA: u8 = 4;
+const REGISTERS: u8 = 5;
+const TRACKS: [(u8, &str); 5] = [
+    (PPU_MODE, "PPU mode"),
+    (SCANLINE, "Scanline"),
+    (INTERRUPT, "Interrupt requests"),
+    (DMA, "DMA"),
+    (REGISTERS, "PPU register writes"),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+    /// Interrupts requested this dot.
+    Interrupt(Interrupts),
+    /// The PPU entered a mode, numbered as in STAT.
+    Mode(u8),
+    /// LY changed.
+    Line(u8),
+    /// OAM DMA started copying from `source`...
+    OamDmaStart(u16),
+    /// ...and finished.
+    OamDmaEnd,
+    /// HDMA copied 16 bytes from `source` to `dest`.
+    Hdma { source: u16, dest: u16 },
+    /// The CPU wrote to a PPU register.
+    Write { address: u16, value: u8 },
+}
+
+/// An event and when it happened: base-clock dots since power on, and
+/// where the PPU was in the frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Record {
+    pub dots: u64,
+    pub ly: u8,
+    /// The dot within the line, 0-455.
+    pub line_dot: u32,
+    pub event: Event,
+}
+
+/// Records what happens within frames, to export as Chrome trace-event
+/// JSON for Perfetto or chrome://tracing.
+///
+/// Frames start when LY goes back to 0. The timeline skips `skip` of them,
+/// then records the next `frames`; the motherboard leaves out the boot ROM.
+pub struct Timeline {
+    skip: u32,
+    frames: u32,
+    /// Frames started so far.
+    started: u32,
+    ly: Option<u8>,
+    mode: Option<u8>,
+    records: Vec<Record>,
+    /// When recording stopped.
+    end: Option<u64>,
+}
+
+impl Timeline {
+    pub fn new(skip: u32, frames: u32) -> Self {
+        Self {
+            skip,
+            frames,
+            started: 0,
+            ly: None,
+            mode: None,
+            records: Vec::new(),
+            end: None,
+        }
+    }
+
+    /// Whether events are being recorded.
+    pub fn recording(&self) -> bool {
+        self.started > self.skip && self.end.is_none()
+    }
+
+    /// Whether every frame asked for has been recorded.
+    pub fn done(&self) -> bool {
+        self.end.is_some()
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Called every dot with the PPU's state.
+    pub fn observe(&mut self, dots: u64, ly: u8, line_dot: u32, mode: u8) {
+        if self.ly != Some(ly) {
+            if ly == 0 && self.ly.is_some() && self.end.is_none() {
+                self.started += 1;
+                if self.started > self.skip + self.frames {
+                    self.end = Some(dots);
+                } else if self.started == self.skip + 1 {
+                    // Recording starts here; open the mode span too.
+                    self.mode = None;
+                }
+            }
+            self.ly = Some(ly);
+            self.record(dots, ly, line_dot, Event::Line(ly));
+        }
+        if self.mode != Some(mode) {
+            self.mode = Some(mode);
+            self.record(dots, ly, line_dot, Event::Mode(mode));
+        }
+    }
+
+    /// Record `event`, if recording.
+    pub fn record(&mut self, dots: u64, ly: u8, line_dot: u32, event: Event) {
+        if self.recording() {
+            self.records.push(Record {
+                dots,
+                ly,
+                line_dot,
+                event,
+            });
+        }
+    }
+
+    /// The timeline in Chrome's trace-event format: PPU modes, scanlines and
+    /// DMA as spans, interrupt requests and register writes as instants,
+    /// each on a track of its own. Times are in microseconds of emulated
+    /// time; every event carries its LY and dot in the line.
+    pub fn chrome_json(&self) -> String {
+        let end = self
+            .end
+            .or(self.records.last().map(|r| r.dots))
+            .unwrap_or(0);
+        let mut events: Vec<String> = vec![
+            r#"{"ph":"M","pid":1,"name":"process_name","args":{"name":"tetsuyu"}}"#.to_string(),
+        ];
+        for (tid, name) in TRACKS {
+            events.push(format!(
+                r#"{{"ph":"M","pid":1,"tid":{},"name":"thread_name","args":{{"name":"{}"}}}}"#,
+                tid, name
+            ));
+        }
+
+        // Spans run until the next of their kind, or the end.
+        let mut open_mode: Option<(&Record, u8)> = None;
+        let mut open_line: Option<(&Record, u8)> = None;
+        let mut open_dma: Option<(&Record, u16)> = None;
+        for record in &self.records {
+            match record.event {
+                Event::Mode(mode) => {
+                    if let Some((start, mode)) = open_mode.replace((record, mode)) {
+                        events.push(span(PPU_MODE, &mode_name(mode), start, record.dots));
+                    }
+                }
+                Event::Line(ly) => {
+                    if let Some((start, ly)) = open_line.replace((record, ly)) {
+                        events.push(span(SCANLINE, &format!("LY {}", ly), start, record.dots));
+                    }
+                }
+                Event::OamDmaStart(source) => open_dma = Some((record, source)),
+                Event::OamDmaEnd => {
+                    if let Some((start, source)) = open_dma.take() {
+                        let name = format!("OAM DMA from ${:04X}", source);
+                        events.push(span(DMA, &name, start, record.dots));
+                    }
+                }
+                Event::Hdma { source, dest } => {
+                    let name = format!("HDMA ${:04X} -> ${:04X}", source, dest);
+                    events.push(span(DMA, &name, record, record.dots + HDMA_BLOCK));
+                }
+                Event::Interrupt(requested) => {
+                    for (bit, name) in INTERRUPTS {
+                        if requested.contains(bit) {
+                            events.push(instant(INTERRUPT, name, record));
+                        }
+                    }
+                }
+                Event::Write { address, value } => {
+                    let name = match io_register_name(address) {
+                        Some(name) => format!("{} = ${:02X}", name, value),
+                        None => format!("${:04X} = ${:02X}", address, value),
+                    };
+                    events.push(instant(REGISTERS, &name, record));
+                }
+            }
+        }
+        if let Some((start, mode)) = open_mode {
+            events.push(span(PPU_MODE, &mode_name(mode), start, end));
+        }
+        if let Some((start, ly)) = open_line {
+            events.push(span(SCANLINE, &format!("LY {}", ly), start, end));
+        }
+        if let Some((start, source)) = open_dma {
+            let name = format!("OAM DMA from ${:04X}", source);
+            events.push(span(DMA, &name, start, end));
+        }
+
+        let mut out = String::from("{\"traceEvents\":[\n");
+        out.push_str(&events.join(",\n"));
+        out.push_str("\n],\"displayTimeUnit\":\"ns\"}\n");
+        out
+    }
+}
+
+fn micros(dots: u64) -> f64 {
+    dots as f64 * 1_000_000.0 / DOTS_PER_SECOND
+}
+
+fn mode_name(mode: u8) -> String {
+    format!("Mode {} ({})", mode, MODES[mode as usize & 3])
+}
+
+/// A complete ("X") event from `start` to `end` dots.
+fn span(tid: u8, name: &str, start: &Record, end: u64) -> String {
+    format!(
+        r#"{{"ph":"X","pid":1,"tid":{},"name":"{}","ts":{:.3},"dur":{:.3},"args":{}}}"#,
+        tid,
+        name,
+        micros(start.dots),
+        micros(end.saturating_sub(start.dots)),
+        position(start)
+    )
+}
+
+/// A thread-scoped instant ("i") event.
+fn instant(tid: u8, name: &str, record: &Record) -> String {
+    format!(
+        r#"{{"ph":"i","s":"t","pid":1,"tid":{},"name":"{}","ts":{:.3},"args":{}}}"#,
+        tid,
+        name,
+        micros(record.dots),
+        position(record)
+    )
+}
+
+fn position(record: &Record) -> String {
+    format!(
+        r#"{{"ly":{},"dot":{},"dots":{}}}"#,
+        record.ly, record.line_dot, record.dots
+    )
+}
+
+/// Whether a CPU write to `address` goes on the timeline: the LCD, palette,
+/// VRAM bank and HDMA registers.
+pub fn is_ppu_register(address: u16) -> bool {
+    matches!(address, 0xFF40..=0xFF4B | 0xFF4F | 0xFF51..=0xFF55 | 0xFF68..=0xFF6C)
+}
@@ -0,0 +1,157 @@
+use crate::components::prelude::Registers;
+use crate::debugger::symbols::Symbols;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Base-clock cycles in a frame.
+const FRAME: u64 = 70_224;
+
+/// Which instructions get logged, and what goes on each line.
+#[derive(Clone, Debug)]
+pub struct TraceOptions {
+    /// Only instructions at `start..=end`...
+    pub start: u16,
+    pub end: u16,
+    /// ...and, in $4000-$7FFF, only from this ROM bank.
+    pub bank: Option<usize>,
+    /// Append ` CY:`, base-clock cycles since power on.
+    pub cycles: bool,
+    /// Append ` LY:`.
+    pub ly: bool,
+    /// Append ` BANK:`, the ROM bank at $4000-$7FFF.
+    pub rom_bank: bool,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        Self {
+            start: 0x0000,
+            end: 0xFFFF,
+            bank: None,
+            cycles: false,
+            ly: false,
+            rom_bank: false,
+        }
+    }
+}
+
+/// Logs every instruction in gameboy-doctor's format,
+/// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`,
+/// with the registers as they are just before it runs.
+///
+/// The motherboard leaves out the boot ROM, so a trace starts at $0100 as
+/// gameboy-doctor's own logs do.
+pub struct Tracer {
+    out: BufWriter<Box<dyn Write + Send>>,
+    options: TraceOptions,
+    /// Labels for a ` SYM:` column, when given.
+    symbols: Option<Symbols>,
+    line: Vec<u8>,
+    /// When the output was last flushed, so the file is never much more
+    /// than a frame behind.
+    flushed: u64,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write + Send>, options: TraceOptions) -> Self {
+        Self {
+            out: BufWriter::with_capacity(1 << 16, out),
+            options,
+            symbols: None,
+            line: Vec::with_capacity(128),
+            flushed: 0,
+        }
+    }
+
+    /// Trace to a new file at `path`.
+    pub fn create(path: &Path, options: TraceOptions) -> io::Result<Self> {
+        Ok(Self::new(Box::new(File::create(path)?), options))
+    }
+
+    /// Append ` SYM:`, the instruction's address as `label+offset`, to
+    /// lines with a label before them.
+    pub fn set_symbols(&mut self, symbols: Option<Symbols>) {
+        self.symbols = symbols;
+    }
+
+    /// Whether an instruction at `pc` with `bank` mapped gets logged.
+    pub fn traces(&self, pc: u16, bank: usize) -> bool {
+        (self.options.start..=self.options.end).contains(&pc)
+            && (pc & 0xC000 != 0x4000 || self.options.bank.is_none_or(|want| want == bank))
+    }
+
+    pub fn log(
+        &mut self,
+        regs: &Registers,
+        pc: u16,
+        pcmem: [u8; 4],
+        cycles: u64,
+        ly: u8,
+        bank: usize,
+    ) {
+        let line = &mut self.line;
+        line.clear();
+        for (name, v) in [
+            ("A:", regs.a),
+            (" F:", regs.f),
+            (" B:", regs.b),
+            (" C:", regs.c),
+            (" D:", regs.d),
+            (" E:", regs.e),
+            (" H:", regs.h),
+            (" L:", regs.l),
+        ] {
+            line.extend_from_slice(name.as_bytes());
+            hex8(line, v);
+        }
+        line.extend_from_slice(b" SP:");
+        hex16(line, regs.sp);
+        line.extend_from_slice(b" PC:");
+        hex16(line, pc);
+        line.extend_from_slice(b" PCMEM:");
+        for (i, v) in pcmem.into_iter().enumerate() {
+            if i > 0 {
+                line.push(b',');
+            }
+            hex8(line, v);
+        }
+        if self.options.cycles {
+            line.extend_from_slice(format!(" CY:{}", cycles).as_bytes());
+        }
+        if self.options.ly {
+            line.extend_from_slice(b" LY:");
+            hex8(line, ly);
+        }
+        if self.options.rom_bank {
+            line.extend_from_slice(format!(" BANK:{:02X}", bank).as_bytes());
+        }
+        if let Some(name) = self.symbols.as_ref().and_then(|s| s.describe(bank, pc)) {
+            line.extend_from_slice(b" SYM:");
+            line.extend_from_slice(name.as_bytes());
+        }
+        line.push(b'\n');
+        let _ = self.out.write_all(line);
+
+        if cycles >= self.flushed + FRAME {
+            self.flushed = cycles;
+            let _ = self.out.flush();
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn hex8(line: &mut Vec<u8>, v: u8) {
+    line.push(HEX[(v >> 4) as usize]);
+    line.push(HEX[(v & 0xF) as usize]);
+}
+
+fn hex16(line: &mut Vec<u8>, v: u16) {
+    hex8(line, (v >> 8) as u8);
+    hex8(line, v as u8);
+}
@@ -0,0 +1,268 @@
+use crate::debugger::symbols::Symbols;
+use std::fmt;
+
+const R8: [&str; 8] = ["b", "c", "d", "e", "h", "l", "[hl]", "a"];
+const RP: [&str; 4] = ["bc", "de", "hl", "sp"];
+const RP2: [&str; 4] = ["bc", "de", "hl", "af"];
+const CC: [&str; 4] = ["nz", "z", "nc", "c"];
+const ALU: [&str; 8] = ["add", "adc", "sub", "sbc", "and", "xor", "or", "cp"];
+const ROT: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+const ACC: [&str; 8] = ["rlca", "rrca", "rla", "rra", "daa", "cpl", "scf", "ccf"];
+
+/// One decoded SM83 instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub bytes: Vec<u8>,
+    /// RGBDS syntax, e.g. `ld a, [$C000]`.
+    pub text: String,
+    /// T-cycles taken, or for a conditional branch, taken when not branching.
+    pub cycles: u8,
+    /// T-cycles taken by a conditional branch that branches.
+    pub branch_cycles: Option<u8>,
+    /// Where a jump, call or RST goes, for labelling.
+    pub target: Option<u16>,
+}
+
+impl Instruction {
+    /// Length in bytes.
+    pub fn size(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    /// Show the target as `label` or `label+offset` from `symbols`, with
+    /// `bank` mapped at $4000-$7FFF.
+    pub fn name_target(&mut self, symbols: &Symbols, bank: usize) {
+        if let Some(target) = self.target
+            && let Some(name) = symbols.describe(bank, target)
+        {
+            self.text = self.text.replace(&format!("${:04X}", target), &name);
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Decode the instruction at `address`, reading its bytes through `read`.
+/// An opcode the SM83 doesn't have comes out as `db $XX`.
+pub fn disassemble(address: u16, mut read: impl FnMut(u16) -> u8) -> Instruction {
+    let raw = [0, 1, 2].map(|i| read(address.wrapping_add(i)));
+    let op = raw[0];
+    let n8 = raw[1];
+    let n16 = u16::from_le_bytes([raw[1], raw[2]]);
+    let e8 = n8 as i8;
+    let relative = address.wrapping_add(2).wrapping_add(e8 as u16);
+    let signed = |e: i8| {
+        if e < 0 {
+            format!("-{}", (e as i16).unsigned_abs())
+        } else {
+            format!("+{}", e)
+        }
+    };
+
+    let x = op >> 6;
+    let y = ((op >> 3) & 7) as usize;
+    let z = (op & 7) as usize;
+    let p = y >> 1;
+    let q = y & 1;
+    let hl = |r: usize, fast: u8, slow: u8| if r == 6 { slow } else { fast };
+
+    // (text, length, cycles, branch cycles, target)
+    let (text, len, cycles, branch, target): (String, u16, u8, Option<u8>, Option<u16>) = match x {
+        0 => match z {
+            0 => match y {
+                0 => ("nop".into(), 1, 4, None, None),
+                1 => (format!("ld [${:04X}], sp", n16), 3, 20, None, None),
+                2 => ("stop".into(), 2, 4, None, None),
+                3 => (format!("jr ${:04X}", relative), 2, 12, None, Some(relative)),
+                _ => (
+                    format!("jr {}, ${:04X}", CC[y - 4], relative),
+                    2,
+                    8,
+                    Some(12),
+                    Some(relative),
+                ),
+            },
+            1 if q == 0 => (format!("ld {}, ${:04X}", RP[p], n16), 3, 12, None, None),
+            1 => (format!("add hl, {}", RP[p]), 1, 8, None, None),
+            2 => {
+                let at = ["[bc]", "[de]", "[hl+]", "[hl-]"][p];
+                let text = if q == 0 {
+                    format!("ld {}, a", at)
+                } else {
+                    format!("ld a, {}", at)
+                };
+                (text, 1, 8, None, None)
+            }
+            3 => {
+                let m = if q == 0 { "inc" } else { "dec" };
+                (format!("{} {}", m, RP[p]), 1, 8, None, None)
+            }
+            4 => (format!("inc {}", R8[y]), 1, hl(y, 4, 12), None, None),
+            5 => (format!("dec {}", R8[y]), 1, hl(y, 4, 12), None, None),
+            6 => (
+                format!("ld {}, ${:02X}", R8[y], n8),
+                2,
+                hl(y, 8, 12),
+                None,
+                None,
+            ),
+            _ => (ACC[y].into(), 1, 4, None, None),
+        },
+        1 if y == 6 && z == 6 => ("halt".into(), 1, 4, None, None),
+        1 => (
+            format!("ld {}, {}", R8[y], R8[z]),
+            1,
+            if y == 6 || z == 6 { 8 } else { 4 },
+            None,
+            None,
+        ),
+        2 => (
+            format!("{} a, {}", ALU[y], R8[z]),
+            1,
+            hl(z, 4, 8),
+            None,
+            None,
+        ),
+        _ => match (z, y) {
+            (0, 0..=3) => (format!("ret {}", CC[y]), 1, 8, Some(20), None),
+            (0, 4) => (
+                format!("ldh [${:04X}], a", 0xFF00 | n8 as u16),
+                2,
+                12,
+                None,
+                None,
+            ),
+            (0, 5) => (format!("add sp, {}", e8), 2, 16, None, None),
+            (0, 6) => (
+                format!("ldh a, [${:04X}]", 0xFF00 | n8 as u16),
+                2,
+                12,
+                None,
+                None,
+            ),
+            (0, _) => (format!("ld hl, sp{}", signed(e8)), 2, 12, None, None),
+            (1, _) if q == 0 => (format!("pop {}", RP2[p]), 1, 12, None, None),
+            (1, _) => match p {
+                0 => ("ret".into(), 1, 16, None, None),
+                1 => ("reti".into(), 1, 16, None, None),
+                2 => ("jp hl".into(), 1, 4, None, None),
+                _ => ("ld sp, hl".into(), 1, 8, None, None),
+            },
+            (2, 0..=3) => (
+                format!("jp {}, ${:04X}", CC[y], n16),
+                3,
+                12,
+                Some(16),
+                Some(n16),
+            ),
+            (2, 4) => ("ldh [c], a".into(), 1, 8, None, None),
+            (2, 5) => (format!("ld [${:04X}], a", n16), 3, 16, None, None),
+            (2, 6) => ("ldh a, [c]".into(), 1, 8, None, None),
+            (2, _) => (format!("ld a, [${:04X}]", n16), 3, 16, None, None),
+            (3, 0) => (format!("jp ${:04X}", n16), 3, 16, None, Some(n16)),
+            (3, 1) => {
+                let r = n8 as usize & 7;
+                let b = (n8 >> 3) as usize & 7;
+                let (text, cycles) = match n8 >> 6 {
+                    0 => (format!("{} {}", ROT[b], R8[r]), hl(r, 8, 16)),
+                    1 => (format!("bit {}, {}", b, R8[r]), hl(r, 8, 12)),
+                    2 => (format!("res {}, {}", b, R8[r]), hl(r, 8, 16)),
+                    _ => (format!("set {}, {}", b, R8[r]), hl(r, 8, 16)),
+                };
+                (text, 2, cycles, None, None)
+            }
+            (3, 6) => ("di".into(), 1, 4, None, None),
+            (3, 7) => ("ei".into(), 1, 4, None, None),
+            (4, 0..=3) => (
+                format!("call {}, ${:04X}", CC[y], n16),
+                3,
+                12,
+                Some(24),
+                Some(n16),
+            ),
+            (5, _) if q == 0 => (format!("push {}", RP2[p]), 1, 16, None, None),
+            (5, 1) => (format!("call ${:04X}", n16), 3, 24, None, Some(n16)),
+            (6, _) => (format!("{} a, ${:02X}", ALU[y], n8), 2, 8, None, None),
+            (7, _) => {
+                let vector = (y * 8) as u16;
+                (format!("rst ${:02X}", vector), 1, 16, None, Some(vector))
+            }
+            _ => (format!("db ${:02X}", op), 1, 4, None, None),
+        },
+    };
+
+    Instruction {
+        bytes: raw[..len as usize].to_vec(),
+        text,
+        cycles,
+        branch_cycles: branch,
+        target,
+    }
+}
+
+/// Reads a ROM image the way the CPU sees it with `bank` mapped at
+/// $4000-$7FFF. Past the end of the image reads $FF.
+pub fn rom_reader(rom: &[u8], bank: usize) -> impl Fn(u16) -> u8 + '_ {
+    move |address| {
+        let offset = match address {
+            0x0000..=0x3FFF => address as usize,
+            0x4000..=0x7FFF => bank * 0x4000 + (address as usize - 0x4000),
+            _ => usize::MAX,
+        };
+        rom.get(offset).copied().unwrap_or(0xFF)
+    }
+}
+
+/// Disassemble `start..end` as a listing, one instruction per line, with
+/// `bank:address` (bank 0 below $4000), the bytes, the instruction and its
+/// cycles. Labels in `symbols` get a `label:` line of their own and name
+/// jump targets.
+pub fn listing(
+    start: u16,
+    end: u16,
+    bank: usize,
+    symbols: &Symbols,
+    mut read: impl FnMut(u16) -> u8,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut address = start;
+    while address < end {
+        let mut instruction = disassemble(address, &mut read);
+        instruction.name_target(symbols, bank);
+        if let Some(label) = symbols.label(bank, address) {
+            lines.push(format!("{}:", label));
+        }
+        let shown = if address < 0x4000 { 0 } else { bank };
+        lines.push(line(shown, address, &instruction));
+        match address.checked_add(instruction.size()) {
+            Some(next) => address = next,
+            None => break,
+        }
+    }
+    lines
+}
+
+/// `01:4000  CD 50 01  call $0150  ; 24`
+pub fn line(bank: usize, address: u16, instruction: &Instruction) -> String {
+    let bytes: Vec<_> = instruction
+        .bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    let cycles = match instruction.branch_cycles {
+        Some(taken) => format!("{}/{}", instruction.cycles, taken),
+        None => instruction.cycles.to_string(),
+    };
+    format!(
+        "{:02X}:{:04X}  {:<8}  {:<20}; {}",
+        bank,
+        address,
+        bytes.join(" "),
+        instruction.text,
+        cycles
+    )
+}
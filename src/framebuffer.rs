@@ -1,37 +1,85 @@
-use crate::components::ppu::ppu::FRAMEBUFFER_SIZE;
+use crate::components::ppu::ppu::{SCREEN_H, SCREEN_W};
+use crate::overlay::Overlay;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
 use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
 
+/// RGBA (4 bytes) per pixel
+const BYTES_PER_PIXEL: usize = 4;
+
 pub struct Frame {
-    pub data: Box<[u8; FRAMEBUFFER_SIZE]>,
+    pub width: usize,
+    pub height: usize,
+    pub data: Box<[u8]>,
 }
 
 impl Frame {
-    pub fn new() -> Self {
+    pub fn new(width: usize, height: usize) -> Self {
         Self {
-            data: Box::new([0xFF; FRAMEBUFFER_SIZE]),
+            width,
+            height,
+            data: vec![0xFF; BYTES_PER_PIXEL * width * height].into_boxed_slice(),
         }
     }
+
+    pub fn write_png(&self, path: &Path) -> io::Result<()> {
+        let w = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer
+            .write_image_data(&self.data)
+            .map_err(io::Error::other)
+    }
 }
 
+#[derive(Clone)]
 pub struct FramebufferWriter {
-    back_buffer: Box<[u8; FRAMEBUFFER_SIZE]>,
+    width: usize,
+    height: usize,
+    back_buffer: Box<[u8]>,
     frame_sender: SyncSender<Frame>,
+    /// Drawn over the next frame sent, then cleared.
+    overlay: Overlay,
 }
 
 impl FramebufferWriter {
     pub fn new(frame_sender: SyncSender<Frame>) -> Self {
         Self {
-            back_buffer: Box::new([0xFF; FRAMEBUFFER_SIZE]),
+            width: SCREEN_W,
+            height: SCREEN_H,
+            back_buffer: Frame::new(SCREEN_W, SCREEN_H).data,
             frame_sender,
+            overlay: Overlay::new(),
         }
     }
 
+    pub fn overlay_mut(&mut self) -> &mut Overlay {
+        &mut self.overlay
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Change the output size (e.g. to the 256×224 SGB screen). The back
+    /// buffer is reallocated and cleared; frames already sent keep their size.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.back_buffer = Frame::new(width, height).data;
+    }
+
     #[inline]
     pub fn set_pixel(&mut self, r: u8, g: u8, b: u8, x: usize, y: usize) {
-        const BYTES_PER_PIXEL: usize = 4;
-        const BYTES_PER_ROW: usize = BYTES_PER_PIXEL * 160;
-
-        let vertical_offset = y * BYTES_PER_ROW;
+        let vertical_offset = y * BYTES_PER_PIXEL * self.width;
         let horizontal_offset = x * BYTES_PER_PIXEL;
         let total_offset = vertical_offset + horizontal_offset;
 
@@ -42,10 +90,13 @@ impl FramebufferWriter {
     }
 
     pub fn submit_frame(&mut self) {
-        let mut new_back = Box::new([0xFF; FRAMEBUFFER_SIZE]);
-        std::mem::swap(&mut self.back_buffer, &mut new_back);
-
-        let frame = Frame { data: new_back };
+        if !self.overlay.is_empty() {
+            self.overlay
+                .draw(&mut self.back_buffer, self.width, self.height);
+            self.overlay.clear();
+        }
+        let mut frame = Frame::new(self.width, self.height);
+        std::mem::swap(&mut self.back_buffer, &mut frame.data);
 
         let _ = self.frame_sender.try_send(frame);
     }
@@ -55,8 +106,8 @@ impl FramebufferWriter {
     }
 
     pub fn fill(&mut self, r: u8, g: u8, b: u8) {
-        for y in 0..144 {
-            for x in 0..160 {
+        for y in 0..self.height {
+            for x in 0..self.width {
                 self.set_pixel(r, g, b, x, y);
             }
         }
@@ -73,7 +124,7 @@ impl FramebufferReader {
     pub fn new(frame_receiver: Receiver<Frame>) -> Self {
         Self {
             frame_receiver,
-            current_frame: Frame::new(),
+            current_frame: Frame::new(SCREEN_W, SCREEN_H),
             frames_seen: 0,
         }
     }
@@ -92,10 +143,51 @@ impl FramebufferReader {
         self.frames_seen
     }
 
+    /// Width and height of the most recent frame. Normally 160×144; 256×224
+    /// when the Super Game Boy border is being rendered.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.current_frame.width, self.current_frame.height)
+    }
+
     pub fn get_latest_frame(&mut self) -> &[u8] {
+        &self.get_latest().data[..]
+    }
+
+    /// The most recent frame along with its dimensions.
+    pub fn get_latest(&mut self) -> &Frame {
         self.poll();
-        &self.current_frame.data[..]
+        &self.current_frame
+    }
+}
+
+/// Place two frames next to each other (top-aligned) in `out`, returning the
+/// combined width and height. Used to show linked machines in one window.
+pub fn side_by_side(left: &Frame, right: &Frame, out: &mut Vec<u8>) -> (usize, usize) {
+    tile(&[left, right], 2, out)
+}
+
+/// Lay `frames` out in rows of `columns` cells, each cell as big as the
+/// largest frame, and return the combined width and height. Used to show
+/// four machines on a Four Player Adapter in a 2x2 grid.
+pub fn tile(frames: &[&Frame], columns: usize, out: &mut Vec<u8>) -> (usize, usize) {
+    let cell_w = frames.iter().map(|f| f.width).max().unwrap_or(0);
+    let cell_h = frames.iter().map(|f| f.height).max().unwrap_or(0);
+    let rows = frames.len().div_ceil(columns);
+    let columns = columns.min(frames.len());
+    let width = cell_w * columns;
+    let height = cell_h * rows;
+    out.clear();
+    out.resize(BYTES_PER_PIXEL * width * height, 0x00);
+
+    for (i, frame) in frames.iter().enumerate() {
+        let (x, y0) = ((i % columns) * cell_w, (i / columns) * cell_h);
+        let row = BYTES_PER_PIXEL * frame.width;
+        for y in 0..frame.height {
+            let start = BYTES_PER_PIXEL * ((y0 + y) * width + x);
+            out[start..start + row].copy_from_slice(&frame.data[y * row..(y + 1) * row]);
+        }
     }
+    (width, height)
 }
 
 pub fn create_framebuffer_pair() -> (FramebufferWriter, FramebufferReader) {
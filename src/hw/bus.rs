@@ -19,6 +19,7 @@ pub enum BusMaster {
 
 /// The shared traces. Exactly one master drives them per M-cycle; every chip
 /// watches them and asserts data / latches writes only on the transfer dot.
+#[derive(Clone)]
 pub struct Pins {
     pub address: u16,
     pub data: u8,
@@ -71,3 +72,28 @@ pub trait Chip {
         Ticked::default()
     }
 }
+
+/// What a CPU bus access was for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccessKind {
+    /// An opcode fetch.
+    Fetch,
+    Read,
+    Write,
+}
+
+/// One CPU bus access, as the traces showed it on the transfer dot.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BusAccess {
+    pub address: u16,
+    pub value: u8,
+    pub kind: AccessKind,
+}
+
+/// What the CPU did since the debugger last looked: its bus accesses and the
+/// interrupts it dispatched.
+#[derive(Default, Clone, Debug)]
+pub struct BusEvents {
+    pub accesses: Vec<BusAccess>,
+    pub interrupts: Vec<Interrupts>,
+}
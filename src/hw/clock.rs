@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct Clock {
     dots: u64,
     speed_phase: u8,
@@ -2,6 +2,7 @@ use super::bus::{BusDir, Chip, Pins, Ticked};
 use crate::components::mode::GBMode;
 use crate::components::prelude::io;
 
+#[derive(Clone)]
 pub struct Dma {
     mode: GBMode,
 
@@ -43,6 +44,16 @@ impl Dma {
         matches!(addr, io::DMA | io::HDMA1..=io::HDMA5)
     }
 
+    /// DMA or an HDMA register as the CPU reads it; only HDMA5 reads back,
+    /// and only on the CGB.
+    pub fn peek(&self, a: u16) -> u8 {
+        match a {
+            io::DMA => (self.oam_src >> 8) as u8,
+            io::HDMA5 if self.mode != GBMode::DMG => self.hdma_len,
+            _ => 0xFF,
+        }
+    }
+
     fn start_oam(&mut self, value: u8) {
         self.oam_pending_src = (value as u16) << 8;
         self.oam_setup = 2;
@@ -163,7 +174,7 @@ impl Chip for Dma {
         if pins.selected(Self::owns(pins.address)) {
             let cgb = self.mode != GBMode::DMG;
             match (pins.address, pins.dir) {
-                (io::DMA, BusDir::Read) => pins.data = (self.oam_src >> 8) as u8,
+                (_, BusDir::Read) => pins.data = self.peek(pins.address),
                 (io::DMA, BusDir::Write) => self.start_oam(pins.data),
                 (io::HDMA1, BusDir::Write) if cgb => {
                     self.hdma_src = (self.hdma_src & 0x00FF) | ((pins.data as u16) << 8)
@@ -178,9 +189,7 @@ impl Chip for Dma {
                 (io::HDMA4, BusDir::Write) if cgb => {
                     self.hdma_dst = (self.hdma_dst & 0xFF00) | (pins.data as u16 & 0xF0)
                 }
-                (io::HDMA5, BusDir::Read) if cgb => pins.data = self.hdma_len,
                 (io::HDMA5, BusDir::Write) if cgb => self.start_hdma(pins.data),
-                (io::HDMA1..=io::HDMA5, BusDir::Read) => pins.data = 0xFF, // DMG: inert
                 _ => {}
             }
         }
@@ -0,0 +1,164 @@
+use super::bus::{AccessKind, BusAccess};
+use super::interrupt::Interrupts;
+use super::motherboard::Motherboard;
+use std::ops::RangeInclusive;
+
+/// Called with the address of each instruction fetched there.
+pub type ExecuteHook = Box<dyn FnMut(&Motherboard, u16) + Send>;
+/// Called with each CPU bus access in range, on its transfer dot.
+pub type AccessHook = Box<dyn FnMut(&Motherboard, BusAccess) + Send>;
+/// Called at the start of each VBlank with the number of frames so far.
+pub type FrameHook = Box<dyn FnMut(&Motherboard, u64) + Send>;
+/// Called with LY each time the PPU moves on to a line.
+pub type ScanlineHook = Box<dyn FnMut(&Motherboard, u8) + Send>;
+/// Called with the interrupt the CPU has just dispatched.
+pub type InterruptHook = Box<dyn FnMut(&Motherboard, Interrupts) + Send>;
+
+/// Identifies a registered callback, for removing it again.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct HookId(u64);
+
+/// Which CPU bus accesses an access hook is called for. Opcode fetches
+/// count as reads.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    fn matches(self, kind: AccessKind) -> bool {
+        match self {
+            Access::Read => kind != AccessKind::Write,
+            Access::Write => kind == AccessKind::Write,
+        }
+    }
+}
+
+/// Rust callbacks run by the motherboard as the CPU executes, reads and
+/// writes, and as the PPU finishes lines and frames. Callbacks see the
+/// machine as it is mid-M-cycle and can't change it.
+#[derive(Default)]
+pub struct Hooks {
+    next_id: u64,
+    execute: Vec<(HookId, u16, ExecuteHook)>,
+    access: Vec<(HookId, Access, RangeInclusive<u16>, AccessHook)>,
+    frame: Vec<(HookId, FrameHook)>,
+    scanline: Vec<(HookId, ScanlineHook)>,
+    interrupt: Vec<(HookId, InterruptHook)>,
+    frames: u64,
+    ly: Option<u8>,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn id(&mut self) -> HookId {
+        self.next_id += 1;
+        HookId(self.next_id)
+    }
+
+    pub fn on_execute(&mut self, address: u16, hook: ExecuteHook) -> HookId {
+        let id = self.id();
+        self.execute.push((id, address, hook));
+        id
+    }
+
+    pub fn on_read(&mut self, range: RangeInclusive<u16>, hook: AccessHook) -> HookId {
+        let id = self.id();
+        self.access.push((id, Access::Read, range, hook));
+        id
+    }
+
+    pub fn on_write(&mut self, range: RangeInclusive<u16>, hook: AccessHook) -> HookId {
+        let id = self.id();
+        self.access.push((id, Access::Write, range, hook));
+        id
+    }
+
+    pub fn on_frame(&mut self, hook: FrameHook) -> HookId {
+        let id = self.id();
+        self.frame.push((id, hook));
+        id
+    }
+
+    pub fn on_scanline(&mut self, hook: ScanlineHook) -> HookId {
+        let id = self.id();
+        self.scanline.push((id, hook));
+        id
+    }
+
+    pub fn on_interrupt(&mut self, hook: InterruptHook) -> HookId {
+        let id = self.id();
+        self.interrupt.push((id, hook));
+        id
+    }
+
+    /// Unregister a callback; false if it wasn't registered.
+    pub fn remove(&mut self, id: HookId) -> bool {
+        let before = self.len();
+        self.execute.retain(|h| h.0 != id);
+        self.access.retain(|h| h.0 != id);
+        self.frame.retain(|h| h.0 != id);
+        self.scanline.retain(|h| h.0 != id);
+        self.interrupt.retain(|h| h.0 != id);
+        self.len() != before
+    }
+
+    pub fn len(&self) -> usize {
+        self.execute.len()
+            + self.access.len()
+            + self.frame.len()
+            + self.scanline.len()
+            + self.interrupt.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Frames started since the hooks were installed.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub(crate) fn execute(&mut self, mb: &Motherboard, pc: u16) {
+        for (_, address, hook) in &mut self.execute {
+            if *address == pc {
+                hook(mb, pc);
+            }
+        }
+    }
+
+    pub(crate) fn access(&mut self, mb: &Motherboard, access: BusAccess) {
+        for (_, kind, range, hook) in &mut self.access {
+            if kind.matches(access.kind) && range.contains(&access.address) {
+                hook(mb, access);
+            }
+        }
+    }
+
+    pub(crate) fn interrupt(&mut self, mb: &Motherboard, bit: Interrupts) {
+        for (_, hook) in &mut self.interrupt {
+            hook(mb, bit);
+        }
+    }
+
+    /// Call after every dot with what the PPU is doing now.
+    pub(crate) fn dot(&mut self, mb: &Motherboard, ly: u8, irq: Interrupts) {
+        if self.ly != Some(ly) {
+            self.ly = Some(ly);
+            for (_, hook) in &mut self.scanline {
+                hook(mb, ly);
+            }
+        }
+        if irq.contains(Interrupts::V_BLANK) {
+            self.frames += 1;
+            for (_, hook) in &mut self.frame {
+                hook(mb, self.frames);
+            }
+        }
+    }
+}
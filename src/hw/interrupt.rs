@@ -3,7 +3,7 @@ use bitflags::bitflags;
 use crate::components::prelude::io;
 
 bitflags! {
-    #[derive(Clone, Copy, PartialEq, Eq, Default)]
+    #[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
     pub struct Interrupts: u8 {
         const JOYPAD  = 0b0001_0000;
         const SERIAL  = 0b0000_1000;
@@ -32,6 +32,7 @@ impl Interrupts {
     }
 }
 
+#[derive(Clone)]
 pub struct InterruptController {
     iflag: Interrupts,
     ienable: Interrupts,
@@ -57,6 +58,14 @@ impl InterruptController {
     pub fn acknowledge(&mut self, bit: Interrupts) {
         self.iflag &= !bit;
     }
+
+    /// IF or IE as the CPU reads it.
+    pub fn peek(&self, a: u16) -> u8 {
+        match a {
+            io::IF => self.iflag.bits() | 0xE0,
+            _ => self.ienable.bits(),
+        }
+    }
 }
 
 impl Chip for InterruptController {
@@ -64,12 +73,12 @@ impl Chip for InterruptController {
         // IF/IE are pure registers; nothing free-running to advance.
         match pins.address {
             io::IF if pins.selected(true) => match pins.dir {
-                BusDir::Read => pins.data = self.iflag.bits() | 0xE0,
+                BusDir::Read => pins.data = self.peek(pins.address),
                 BusDir::Write => self.iflag = Interrupts::from_bits_truncate(pins.data),
                 BusDir::Idle => {}
             },
             0xFFFF if pins.selected(true) => match pins.dir {
-                BusDir::Read => pins.data = self.ienable.bits(),
+                BusDir::Read => pins.data = self.peek(pins.address),
                 BusDir::Write => self.ienable = Interrupts::from_bits_truncate(pins.data),
                 BusDir::Idle => {}
             },
@@ -0,0 +1,129 @@
+use super::motherboard::Motherboard;
+use crate::components::link::adapter::{FourPlayerAdapter, PLAYERS};
+use crate::components::link::cable::link_cable;
+use crate::components::link::infrared::infrared_pair;
+
+/// Two machines joined by a link cable, with their infrared ports facing
+/// each other, run in lockstep on one thread.
+pub struct LinkedPair {
+    pub a: Motherboard,
+    pub b: Motherboard,
+    /// Base-clock cycles each machine has run.
+    time: [u64; 2],
+}
+
+impl LinkedPair {
+    pub fn new(mut a: Motherboard, mut b: Motherboard) -> Self {
+        let (end_a, end_b) = link_cable();
+        a.connect_serial(Box::new(end_a));
+        b.connect_serial(Box::new(end_b));
+        let (ir_a, ir_b) = infrared_pair();
+        a.connect_infrared(Box::new(ir_a));
+        b.connect_infrared(Box::new(ir_b));
+        Self { a, b, time: [0; 2] }
+    }
+
+    /// Base-clock cycles both machines have completed.
+    pub fn cycles(&self) -> u64 {
+        self.time[0].min(self.time[1])
+    }
+
+    /// Run an M-cycle on whichever machine is behind. A double-speed M-cycle
+    /// only takes half as long, so the two stay aligned in real time.
+    pub fn step_mcycle(&mut self) {
+        let (mb, time) = if self.time[0] <= self.time[1] {
+            (&mut self.a, &mut self.time[0])
+        } else {
+            (&mut self.b, &mut self.time[1])
+        };
+        *time += if mb.double_speed() { 2 } else { 4 };
+        mb.step_mcycle();
+    }
+
+    /// Run both machines until each has done at least `cycles` more
+    /// base-clock cycles.
+    pub fn run(&mut self, cycles: u64) {
+        let end = self.cycles() + cycles;
+        while self.cycles() < end {
+            self.step_mcycle();
+        }
+    }
+
+    /// Run until `done` holds or `max_cycles` base-clock cycles elapse;
+    /// returns whether `done` was met.
+    pub fn run_until(&mut self, max_cycles: u64, mut done: impl FnMut(&Self) -> bool) -> bool {
+        let end = self.cycles() + max_cycles;
+        while self.cycles() < end {
+            if done(self) {
+                return true;
+            }
+            self.step_mcycle();
+        }
+        done(self)
+    }
+}
+
+/// Up to four machines plugged into a DMG-07 Four Player Adapter, run in
+/// lockstep on one thread.
+pub struct FourPlayer {
+    pub machines: Vec<Motherboard>,
+    adapter: FourPlayerAdapter,
+    /// Base-clock cycles each machine has run, and the adapter.
+    time: Vec<u64>,
+    adapter_time: u64,
+}
+
+impl FourPlayer {
+    pub fn new(mut machines: Vec<Motherboard>) -> Self {
+        assert!(
+            (1..=PLAYERS).contains(&machines.len()),
+            "the adapter takes 1 to {PLAYERS} players"
+        );
+        let adapter = FourPlayerAdapter::new();
+        for (player, mb) in machines.iter_mut().enumerate() {
+            mb.connect_serial(Box::new(adapter.port(player)));
+        }
+        let time = vec![0; machines.len()];
+        Self {
+            machines,
+            adapter,
+            time,
+            adapter_time: 0,
+        }
+    }
+
+    /// Base-clock cycles every machine has completed.
+    pub fn cycles(&self) -> u64 {
+        self.time.iter().copied().min().unwrap_or(0)
+    }
+
+    /// Run an M-cycle on whichever machine is furthest behind, then bring the
+    /// adapter up to the slowest machine.
+    pub fn step_mcycle(&mut self) {
+        let player = (0..self.machines.len())
+            .min_by_key(|&p| self.time[p])
+            .unwrap();
+        let mb = &mut self.machines[player];
+        self.time[player] += if mb.double_speed() { 2 } else { 4 };
+        mb.step_mcycle();
+
+        let cycles = self.cycles();
+        if cycles > self.adapter_time {
+            self.adapter.advance((cycles - self.adapter_time) as u32);
+            self.adapter_time = cycles;
+        }
+    }
+
+    /// Run until `done` holds or `max_cycles` base-clock cycles elapse;
+    /// returns whether `done` was met.
+    pub fn run_until(&mut self, max_cycles: u64, mut done: impl FnMut(&Self) -> bool) -> bool {
+        let end = self.cycles() + max_cycles;
+        while self.cycles() < end {
+            if done(self) {
+                return true;
+            }
+            self.step_mcycle();
+        }
+        done(self)
+    }
+}
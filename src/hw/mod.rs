@@ -1,7 +1,9 @@
 pub mod bus;
 pub mod clock;
 pub mod dma;
+pub mod hooks;
 pub mod interrupt;
+pub mod link;
 pub mod motherboard;
 pub mod ppu;
 pub mod sysbus;
@@ -1,6 +1,7 @@
-use super::bus::{BusDir, BusMaster, Chip, Pins, Ticked};
+use super::bus::{AccessKind, BusAccess, BusDir, BusEvents, BusMaster, Chip, Pins, Ticked};
 use super::clock::Clock;
 use super::dma::Dma;
+use super::hooks::{HookId, Hooks};
 use super::interrupt::{InterruptController, Interrupts};
 use super::ppu::Ppu;
 use super::sysbus::SystemBus;
@@ -8,10 +9,51 @@ use super::timer::Timer;
 use crate::components::apu::apu::Apu;
 use crate::components::cpu::cpu::Cpu;
 use crate::components::joypad::JoypadButton;
-use crate::components::prelude::Registers;
+use crate::components::link::infrared::InfraredLink;
+use crate::components::link::link::SerialLink;
+use crate::components::mode::Model;
+use crate::components::ppu::viewer::VideoMemory;
+use crate::components::prelude::{Reg, Registers, io};
+use crate::components::sgb::packet::Command;
 use crate::config::Config;
+use crate::debugger::cdl::{Cdl, CdlFlags};
+use crate::debugger::profile::Profiler;
+use crate::debugger::timeline::{self, Event, Timeline};
+use crate::debugger::trace::Tracer;
 use crate::framebuffer::FramebufferWriter;
 use crate::mbc::header::Header;
+use crate::overlay::Overlay;
+use std::ops::RangeInclusive;
+
+/// A block of memory to read or write whole, by bank.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Region {
+    /// $8000-$9FFF in VRAM bank 0 or 1.
+    Vram(usize),
+    /// WRAM bank 0-7; $C000-$CFFF is bank 0, and $D000-$DFFF bank 1 on the
+    /// DMG.
+    Wram(usize),
+    Oam,
+    Hram,
+    /// A bank of cartridge RAM: 8 KiB, or all 512 half-bytes of MBC2 RAM.
+    CartRam(usize),
+}
+
+/// The machine as it was at one moment, to go back to with
+/// [`Motherboard::load_state`]. What is plugged in (link cables, the sound
+/// output, the screen) and the debugging tools aren't part of it.
+#[derive(Clone)]
+pub struct SaveState {
+    cpu: Cpu,
+    clock: Clock,
+    pins: Pins,
+    ic: InterruptController,
+    timer: Timer,
+    ppu: Ppu,
+    apu: Apu,
+    dma: Dma,
+    sysbus: SystemBus,
+}
 
 pub struct Motherboard {
     cpu: Cpu,
@@ -23,6 +65,14 @@ pub struct Motherboard {
     apu: Apu,
     dma: Dma,
     sysbus: SystemBus,
+    /// CPU bus accesses and interrupt dispatches, collected while recording.
+    events: Option<Box<BusEvents>>,
+    tracer: Option<Box<Tracer>>,
+    cdl: Option<Box<Cdl>>,
+    profiler: Option<Box<Profiler>>,
+    timeline: Option<Box<Timeline>>,
+    /// Callbacks registered by embedders; `None` until the first one is.
+    hooks: Option<Box<Hooks>>,
 }
 
 impl Motherboard {
@@ -45,6 +95,12 @@ impl Motherboard {
             apu: Apu::new(config.clone()),
             dma: Dma::new(mode),
             sysbus: SystemBus::new(rom, header, &config, boot_rom),
+            events: None,
+            tracer: None,
+            cdl: None,
+            profiler: None,
+            timeline: None,
+            hooks: None,
         }
     }
 
@@ -90,6 +146,71 @@ impl Motherboard {
         Self::new(rom, header, config, boot_rom, framebuffer, rom_is_cgb)
     }
 
+    /// Start from where `model`'s boot ROM would have left the machine,
+    /// without running it: the boot ROM unmapped, the registers, LCD and
+    /// sound as it leaves them, and $0100 next. VRAM is left empty rather
+    /// than holding the logo. The divider is only known to the cycle on
+    /// the DMG and MGB; the DMG0 starts at the DIV it reads, and the
+    /// others at 0. Call it before the first step.
+    pub fn skip_boot(&mut self, model: Model) {
+        self.sysbus.disable_boot();
+        self.ppu.on_boot_rom_disabled();
+        let cgb_rom = self.peek(0x0143) & 0x80 != 0;
+
+        // The SGB's boot ROM doesn't play the chime.
+        let chime: &[(u16, u8)] = if model.is_sgb() {
+            &[]
+        } else {
+            &[(io::NR13, 0xC1), (io::NR14, 0x87)]
+        };
+        let sound = [
+            (io::NR52, 0x80),
+            (io::NR11, 0x80),
+            (io::NR12, 0xF3),
+            (io::NR51, 0xF3),
+            (io::NR50, 0x77),
+        ];
+        for &(a, v) in sound.iter().chain(chime) {
+            self.write_bus(a, v);
+        }
+        if model == Model::Cgb && cgb_rom {
+            // Every background palette white.
+            self.write_bus(io::BGPI, 0x80);
+            for i in 0..64 {
+                self.write_bus(io::BGPD, if i % 2 == 0 { 0xFF } else { 0x7F });
+            }
+        }
+        self.write_bus(io::BGP, 0xFC);
+        self.write_bus(io::LCDC, 0x91);
+        self.write_bus(io::IF, 0x01);
+
+        // The DMG and MGB boot ROMs leave the header check's flags behind.
+        let checked = if self.peek(0x014D) == 0 { 0x80 } else { 0xB0 };
+        let (af, bc, de, hl) = match model {
+            Model::Dmg0 => (0x0100, 0xFF13, 0x00C1, 0x8403),
+            Model::Dmg => (0x0100 | checked, 0x0013, 0x00D8, 0x014D),
+            Model::Mgb => (0xFF00 | checked, 0x0013, 0x00D8, 0x014D),
+            Model::Sgb => (0x0100, 0x0014, 0x0000, 0xC060),
+            Model::Sgb2 => (0xFF00, 0x0014, 0x0000, 0xC060),
+            Model::Cgb if cgb_rom => (0x1180, 0x0000, 0xFF56, 0x000D),
+            Model::Cgb => (0x1180, 0x0000, 0x0008, 0x007C),
+        };
+        let mut regs = self.cpu.regs();
+        regs.set_af(af);
+        regs.set_bc(bc);
+        regs.set_de(de);
+        regs.set_hl(hl);
+        regs.sp = 0xFFFE;
+        self.set_cpu_regs(regs);
+        self.jump(0x0100);
+
+        self.timer.preset_counter(match model {
+            Model::Dmg | Model::Mgb => 0xABCC,
+            Model::Dmg0 => 0x1800,
+            _ => 0,
+        });
+    }
+
     pub fn joypad_down(&mut self, b: JoypadButton) {
         self.sysbus.joypad_down(b);
     }
@@ -99,9 +220,90 @@ impl Motherboard {
     }
 
     /// Read a byte of CPU-addressable memory without side effects (cartridge,
-    /// WRAM, HRAM, and the sysbus-owned registers). For inspection/testing.
+    /// VRAM and OAM whatever the PPU is doing, WRAM, HRAM, and the IO
+    /// registers as the CPU reads them, without the read reaching the
+    /// chips). For inspection/testing.
     pub fn peek(&self, a: u16) -> u8 {
-        self.sysbus.peek(a)
+        match a {
+            0x8000..=0x9FFF => self.ppu.peek_vram(a),
+            0xFE00..=0xFE9F => self.ppu.oam()[a as usize - 0xFE00],
+            io::DIV..=io::TAC => self.timer.peek(a),
+            io::IF | 0xFFFF => self.ic.peek(a),
+            io::NR10..=io::WAV_END => self.apu.peek(a),
+            io::LCDC..=io::LYC | io::BGP..=io::WX | io::VBK | io::BGPI..=io::OPRI => {
+                self.ppu.peek_register(a)
+            }
+            io::DMA | io::HDMA1..=io::HDMA5 => self.dma.peek(a),
+            _ => self.sysbus.peek(a),
+        }
+    }
+
+    /// Write a byte of memory the way [`peek`](Self::peek) reads it: VRAM,
+    /// OAM, WRAM, HRAM and cartridge RAM (in the banks mapped now) are
+    /// written directly, whatever mode the PPU is in and whether or not the
+    /// RAM is enabled. IO registers are written as by the CPU, with their
+    /// side effects. ROM is left alone. None of it takes any time.
+    pub fn poke(&mut self, a: u16, v: u8) {
+        match a {
+            0x0000..=0x7FFF => {}
+            0x8000..=0x9FFF => self.ppu.write_vram_dma(a, v),
+            0xA000..=0xFDFF | 0xFF80..=0xFFFE => self.sysbus.poke(a, v),
+            0xFE00..=0xFE9F => self.ppu.write_oam(a - 0xFE00, v),
+            _ => self.write_bus(a, v),
+        }
+    }
+
+    /// `len` bytes from `start` on, read with [`peek`](Self::peek); wraps
+    /// around at $FFFF.
+    pub fn peek_range(&self, start: u16, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| self.peek(start.wrapping_add(i as u16)))
+            .collect()
+    }
+
+    /// Write `data` from `start` on with [`poke`](Self::poke).
+    pub fn poke_range(&mut self, start: u16, data: &[u8]) {
+        for (i, &v) in data.iter().enumerate() {
+            self.poke(start.wrapping_add(i as u16), v);
+        }
+    }
+
+    /// A whole bank of memory, whichever is mapped in; None for a bank
+    /// that doesn't exist.
+    pub fn region(&self, region: Region) -> Option<&[u8]> {
+        match region {
+            Region::Vram(bank) => self.ppu.vram().get(bank * 0x2000..(bank + 1) * 0x2000),
+            Region::Wram(bank) => self.sysbus.wram().get(bank * 0x1000..(bank + 1) * 0x1000),
+            Region::Oam => Some(self.ppu.oam()),
+            Region::Hram => Some(self.sysbus.hram()),
+            Region::CartRam(bank) => {
+                let ram = self.sysbus.cart_ram();
+                let size = ram.len().min(0x2000);
+                ram.get(bank * size..(bank + 1) * size)
+                    .filter(|r| !r.is_empty())
+            }
+        }
+    }
+
+    pub fn region_mut(&mut self, region: Region) -> Option<&mut [u8]> {
+        match region {
+            Region::Vram(bank) => self
+                .ppu
+                .vram_mut()
+                .get_mut(bank * 0x2000..(bank + 1) * 0x2000),
+            Region::Wram(bank) => self
+                .sysbus
+                .wram_mut()
+                .get_mut(bank * 0x1000..(bank + 1) * 0x1000),
+            Region::Oam => Some(self.ppu.oam_mut()),
+            Region::Hram => Some(self.sysbus.hram_mut()),
+            Region::CartRam(bank) => {
+                let ram = self.sysbus.cart_ram_mut();
+                let size = ram.len().min(0x2000);
+                ram.get_mut(bank * size..(bank + 1) * size)
+                    .filter(|r| !r.is_empty())
+            }
+        }
     }
 
     /// Bytes the program has transmitted over the serial port.
@@ -109,11 +311,325 @@ impl Motherboard {
         self.sysbus.serial_output()
     }
 
+    /// Plug a link cable or peripheral into the serial port.
+    pub fn connect_serial(&mut self, link: Box<dyn SerialLink>) {
+        self.sysbus.connect_serial(link);
+    }
+
+    /// Point the CGB infrared port at another one.
+    pub fn connect_infrared(&mut self, link: Box<dyn InfraredLink>) {
+        self.sysbus.connect_infrared(link);
+    }
+
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            cpu: self.cpu.clone(),
+            clock: self.clock.clone(),
+            pins: self.pins.clone(),
+            ic: self.ic.clone(),
+            timer: self.timer.clone(),
+            ppu: self.ppu.clone(),
+            apu: self.apu.clone(),
+            dma: self.dma.clone(),
+            sysbus: self.sysbus.clone(),
+        }
+    }
+
+    /// Go back to `state`, keeping what is plugged in now.
+    pub fn load_state(&mut self, state: &SaveState) {
+        let state = state.clone();
+        let mixer = self.apu.take_mixer();
+        let serial = self.sysbus.disconnect_serial();
+        let infrared = self.sysbus.disconnect_infrared();
+        let mut ppu = state.ppu;
+        std::mem::swap(ppu.framebuffer_mut(), self.ppu.framebuffer_mut());
+
+        self.cpu = state.cpu;
+        self.clock = state.clock;
+        self.pins = state.pins;
+        self.ic = state.ic;
+        self.timer = state.timer;
+        self.ppu = ppu;
+        self.apu = state.apu;
+        self.dma = state.dma;
+        self.sysbus = state.sysbus;
+
+        self.apu.set_mixer(mixer);
+        if let Some(link) = serial {
+            self.sysbus.connect_serial(link);
+        }
+        if let Some(link) = infrared {
+            self.sysbus.connect_infrared(link);
+        }
+    }
+
+    /// Shapes to draw over the next frame.
+    pub fn overlay_mut(&mut self) -> &mut Overlay {
+        self.ppu.framebuffer_mut().overlay_mut()
+    }
+
     /// CPU register snapshot.
     pub fn cpu_regs(&self) -> Registers {
         self.cpu.regs()
     }
 
+    /// Overwrite the CPU registers. A PC other than `cpu_regs().pc` is
+    /// jumped to, as by `jump`.
+    pub fn set_cpu_regs(&mut self, regs: Registers) {
+        let pc = self.cpu.reg.pc;
+        self.cpu.reg = regs;
+        if regs.pc != pc {
+            self.jump(regs.pc);
+        }
+    }
+
+    /// One register. PC is the address of the instruction that runs next,
+    /// as from [`pc`](Self::pc).
+    pub fn register(&self, reg: Reg) -> u16 {
+        match reg {
+            Reg::PC => self.pc(),
+            _ => self.cpu.regs().get(reg),
+        }
+    }
+
+    /// Set one register. Setting PC jumps there, as by
+    /// [`jump`](Self::jump).
+    pub fn set_register(&mut self, reg: Reg, value: u16) {
+        match reg {
+            Reg::PC => self.jump(value),
+            _ => {
+                let mut regs = self.cpu.regs();
+                regs.set(reg, value);
+                self.set_cpu_regs(regs);
+            }
+        }
+    }
+
+    /// Abandon the instruction already fetched and carry on from `pc`, with
+    /// its opcode taken as by [`peek`](Self::peek). No time passes: `pc()`
+    /// is `pc` straight away, and nothing else is clocked.
+    pub fn jump(&mut self, pc: u16) {
+        let opcode = self.peek(pc);
+        self.cpu.jump(pc, opcode);
+    }
+
+    /// PPU dots run since power on.
+    pub fn dots(&self) -> u64 {
+        self.clock.dots()
+    }
+
+    /// Address of the instruction that runs next. (`cpu_regs().pc` is
+    /// already past its opcode.)
+    pub fn pc(&self) -> u16 {
+        self.cpu.opcode_pc()
+    }
+
+    /// The ROM bank mapped at $4000-$7FFF.
+    pub fn rom_bank(&self) -> usize {
+        self.sysbus.rom_bank()
+    }
+
+    /// The scanline the PPU is on.
+    pub fn ly(&self) -> u8 {
+        self.ppu.ly()
+    }
+
+    /// VRAM, OAM and the palettes, to render for inspection.
+    pub fn video(&self) -> VideoMemory<'_> {
+        self.ppu.video()
+    }
+
+    /// Read any address as the CPU would see it right now, without the read
+    /// taking any time.
+    pub fn read_bus(&mut self, a: u16) -> u8 {
+        self.bus_access(a, BusDir::Read, 0xFF)
+    }
+
+    /// Write any address as the CPU would, side effects included, without
+    /// the write taking any time.
+    pub fn write_bus(&mut self, a: u16, v: u8) {
+        self.bus_access(a, BusDir::Write, v);
+    }
+
+    fn bus_access(&mut self, a: u16, dir: BusDir, v: u8) -> u8 {
+        let saved = (self.pins.address, self.pins.data, self.pins.dir);
+        self.pins.address = a;
+        self.pins.data = v;
+        self.pins.dir = dir;
+        self.pins.transfer = true;
+        let mut ticked = Ticked::default();
+        ticked.merge(self.timer.bus(&mut self.pins));
+        ticked.merge(self.ic.bus(&mut self.pins));
+        ticked.merge(self.ppu.bus(&mut self.pins));
+        ticked.merge(self.dma.bus(&mut self.pins));
+        self.apu.bus(&mut self.pins);
+        ticked.merge(self.sysbus.bus(&mut self.pins));
+        self.ic.request(ticked.irq);
+        let data = self.pins.data;
+        self.pins.transfer = false;
+        (self.pins.address, self.pins.data, self.pins.dir) = saved;
+        data
+    }
+
+    /// Start or stop collecting CPU bus accesses and interrupt dispatches.
+    pub fn record_events(&mut self, on: bool) {
+        self.events = on.then(Box::default);
+    }
+
+    /// What the CPU did since the last call, while recording.
+    pub fn take_events(&mut self) -> BusEvents {
+        match &mut self.events {
+            Some(events) => std::mem::take(events),
+            None => BusEvents::default(),
+        }
+    }
+
+    /// Log each instruction from now on, or stop logging; returns the
+    /// tracer that was logging.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        std::mem::replace(&mut self.tracer, tracer.map(Box::new)).map(|t| *t)
+    }
+
+    fn trace(&mut self) {
+        let pc = self.cpu.opcode_pc();
+        let bank = self.sysbus.rom_bank();
+        if self.sysbus.boot_rom_enabled()
+            || !self.tracer.as_ref().is_some_and(|t| t.traces(pc, bank))
+        {
+            return;
+        }
+        let pcmem = [0, 1, 2, 3].map(|i| self.peek(pc.wrapping_add(i)));
+        let cycles = self.clock.dots();
+        let ly = self.ppu.ly();
+        if let Some(tracer) = &mut self.tracer {
+            tracer.log(&self.cpu.reg, pc, pcmem, cycles, ly, bank);
+        }
+    }
+
+    /// Log what each ROM byte is used for from now on, or stop logging;
+    /// returns the log that was being kept.
+    pub fn set_cdl(&mut self, cdl: Option<Cdl>) -> Option<Cdl> {
+        std::mem::replace(&mut self.cdl, cdl.map(Box::new)).map(|c| *c)
+    }
+
+    pub fn cdl(&self) -> Option<&Cdl> {
+        self.cdl.as_deref()
+    }
+
+    /// Profile from now on, or stop profiling; returns the profiler that
+    /// was counting.
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) -> Option<Profiler> {
+        std::mem::replace(&mut self.profiler, profiler.map(Box::new)).map(|p| *p)
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    /// Record a frame timeline from now on, or stop recording; returns the
+    /// timeline that was recording.
+    pub fn set_timeline(&mut self, timeline: Option<Timeline>) -> Option<Timeline> {
+        std::mem::replace(&mut self.timeline, timeline.map(Box::new)).map(|t| *t)
+    }
+
+    pub fn timeline(&self) -> Option<&Timeline> {
+        self.timeline.as_deref()
+    }
+
+    fn hooks_mut(&mut self) -> &mut Hooks {
+        self.hooks.get_or_insert_with(Box::default)
+    }
+
+    /// Call `f` whenever the CPU fetches the instruction at `address`.
+    pub fn on_execute(
+        &mut self,
+        address: u16,
+        f: impl FnMut(&Motherboard, u16) + Send + 'static,
+    ) -> HookId {
+        self.hooks_mut().on_execute(address, Box::new(f))
+    }
+
+    /// Call `f` with every CPU read of `range`, opcode fetches included.
+    pub fn on_read(
+        &mut self,
+        range: RangeInclusive<u16>,
+        f: impl FnMut(&Motherboard, BusAccess) + Send + 'static,
+    ) -> HookId {
+        self.hooks_mut().on_read(range, Box::new(f))
+    }
+
+    /// Call `f` with every CPU write to `range`.
+    pub fn on_write(
+        &mut self,
+        range: RangeInclusive<u16>,
+        f: impl FnMut(&Motherboard, BusAccess) + Send + 'static,
+    ) -> HookId {
+        self.hooks_mut().on_write(range, Box::new(f))
+    }
+
+    /// Call `f` at the start of every VBlank.
+    pub fn on_frame(&mut self, f: impl FnMut(&Motherboard, u64) + Send + 'static) -> HookId {
+        self.hooks_mut().on_frame(Box::new(f))
+    }
+
+    /// Call `f` whenever LY changes.
+    pub fn on_scanline(&mut self, f: impl FnMut(&Motherboard, u8) + Send + 'static) -> HookId {
+        self.hooks_mut().on_scanline(Box::new(f))
+    }
+
+    /// Call `f` whenever the CPU dispatches an interrupt.
+    pub fn on_interrupt(
+        &mut self,
+        f: impl FnMut(&Motherboard, Interrupts) + Send + 'static,
+    ) -> HookId {
+        self.hooks_mut().on_interrupt(Box::new(f))
+    }
+
+    /// Unregister a callback; false if it wasn't registered.
+    pub fn remove_hook(&mut self, id: HookId) -> bool {
+        let removed = self.hooks.as_mut().is_some_and(|h| h.remove(id));
+        if self.hooks.as_ref().is_some_and(|h| h.is_empty()) {
+            self.hooks = None;
+        }
+        removed
+    }
+
+    pub fn hooks(&self) -> Option<&Hooks> {
+        self.hooks.as_deref()
+    }
+
+    /// Run `f` on the hooks, which are taken out meanwhile so callbacks can
+    /// look at the machine.
+    fn run_hooks(&mut self, f: impl FnOnce(&mut Hooks, &Motherboard)) {
+        if let Some(mut hooks) = self.hooks.take() {
+            f(&mut hooks, self);
+            self.hooks = Some(hooks);
+        }
+    }
+
+    /// Put `event` on the timeline, if one is recording.
+    fn log_timeline(&mut self, event: Event) {
+        if let Some(timeline) = &mut self.timeline {
+            let (ly, line_dot) = (self.ppu.ly(), self.ppu.line_dot());
+            timeline.record(self.clock.dots(), ly, line_dot, event);
+        }
+    }
+
+    /// Mark the byte at `a` in the code/data log, if it is ROM, or RAM
+    /// running as code.
+    fn log_cdl(&mut self, a: u16, flags: CdlFlags) {
+        let Some(cdl) = &mut self.cdl else {
+            return;
+        };
+        if let Some(offset) = self.sysbus.rom_offset(a) {
+            cdl.mark_rom(offset, flags);
+        } else if flags == CdlFlags::CODE
+            && let Some(offset) = self.sysbus.ram_offset(a)
+        {
+            cdl.mark_ram(offset, flags);
+        }
+    }
+
     /// True once the CPU has hit the test-ROM magic breakpoint (`LD B,B`).
     pub fn magic_break(&self) -> bool {
         self.cpu.magic_break()
@@ -130,22 +646,52 @@ impl Motherboard {
     pub fn step(&mut self) -> u32 {
         let mut mcycles = 0u32;
         loop {
-            let fetched = self.m_cycle();
             mcycles += 1;
-            if fetched {
+            if self.step_mcycle() {
                 break;
             }
         }
+        mcycles * 4
+    }
 
-        if self.dma.take_gpdma() {
-            self.run_gpdma();
+    /// Run a single M-cycle; returns true once it has fetched the next
+    /// instruction. Unlike `step`, this returns while the CPU is halted,
+    /// which lets linked machines run in lockstep.
+    pub fn step_mcycle(&mut self) -> bool {
+        let halted = self.cpu.is_halted();
+        let fetched = self.m_cycle();
+        // The boot ROM isn't the program's, so it isn't profiled.
+        let profiling = self.profiler.is_some() && !self.sysbus.boot_rom_enabled();
+        if profiling && let Some(profiler) = &mut self.profiler {
+            profiler.tick(halted);
         }
+        if fetched {
+            if self.dma.take_gpdma() {
+                self.run_gpdma();
+            }
 
-        if self.cpu.take_speed_switch() {
-            self.sysbus.try_speed_switch();
-        }
+            if self.cpu.take_speed_switch() {
+                self.sysbus.try_speed_switch();
+            }
 
-        mcycles * 4
+            if self.tracer.is_some() {
+                self.trace();
+            }
+
+            if let Some(cdl) = &mut self.cdl {
+                cdl.autosave(self.clock.dots());
+            }
+
+            if profiling {
+                let pc = self.cpu.opcode_pc();
+                let opcode = self.peek(pc);
+                let bank = self.sysbus.rom_bank();
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.fetched(bank, pc, opcode, self.cpu.reg.sp);
+                }
+            }
+        }
+        fetched
     }
 
     fn m_cycle(&mut self) -> bool {
@@ -173,12 +719,23 @@ impl Motherboard {
             let pending = self.ic.pending();
             if let Some(bit) = self.cpu.latch_isr_vector(pending) {
                 self.ic.acknowledge(bit);
+                if let Some(events) = &mut self.events {
+                    events.interrupts.push(bit);
+                }
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.interrupt(bit);
+                }
+                if self.hooks.is_some() {
+                    self.run_hooks(|hooks, mb| hooks.interrupt(mb, bit));
+                }
             }
         }
 
         // OAM DMA moves one byte per M-cycle, concurrent with the CPU.
         self.step_oam_dma();
 
+        let fetching = self.cpu.fetching();
+        let reading_code = self.cpu.reading_code();
         self.cpu.setup(&mut self.pins);
 
         let write_conflict =
@@ -197,12 +754,54 @@ impl Motherboard {
             self.pins.data = 0xFF;
         }
 
+        if (self.events.is_some() || self.hooks.is_some()) && self.pins.dir != BusDir::Idle {
+            let access = BusAccess {
+                address: self.pins.address,
+                value: self.pins.data,
+                kind: match self.pins.dir {
+                    BusDir::Write => AccessKind::Write,
+                    _ if fetching => AccessKind::Fetch,
+                    _ => AccessKind::Read,
+                },
+            };
+            if let Some(events) = &mut self.events {
+                events.accesses.push(access);
+            }
+            if self.hooks.is_some() {
+                self.run_hooks(|hooks, mb| hooks.access(mb, access));
+            }
+        }
+
+        if self.timeline.is_some()
+            && self.pins.dir == BusDir::Write
+            && timeline::is_ppu_register(self.pins.address)
+        {
+            self.log_timeline(Event::Write {
+                address: self.pins.address,
+                value: self.pins.data,
+            });
+        }
+
+        if self.cdl.is_some() && self.pins.dir == BusDir::Read {
+            let flags = if reading_code {
+                CdlFlags::CODE
+            } else {
+                CdlFlags::DATA
+            };
+            self.log_cdl(self.pins.address, flags);
+        }
+
         let fetched = self.cpu.complete(&self.pins);
         self.pins.transfer = false;
 
         if fetched {
             let pending = self.ic.pending();
-            self.cpu.offer_interrupt(pending);
+            // An interrupt taken now replaces the instruction just fetched.
+            let interrupted = self.cpu.offer_interrupt(pending);
+            if self.hooks.is_some() && !interrupted {
+                let pc = self.cpu.opcode_pc();
+                self.run_hooks(|hooks, mb| hooks.execute(mb, pc));
+            }
         }
         fetched
     }
@@ -224,6 +823,8 @@ impl Motherboard {
 
             ticked.merge(self.timer.advance(base_dot));
             ticked.merge(self.ppu.advance(base_dot));
+            // The serial clock is taken from the same divider as DIV.
+            self.sysbus.clock_serial(self.timer.counter());
             ticked.merge(self.sysbus.advance(base_dot));
 
             // The APU is a base-clock device: advance its frame sequencer and
@@ -233,6 +834,21 @@ impl Motherboard {
                     .advance(self.timer.div(), self.sysbus.double_speed());
             }
 
+            if let Some(timeline) = &mut self.timeline
+                && !self.sysbus.boot_rom_enabled()
+            {
+                let (dots, ly, line_dot) = (self.clock.dots(), self.ppu.ly(), self.ppu.line_dot());
+                timeline.observe(dots, ly, line_dot, self.ppu.mode());
+                if !ticked.irq.is_empty() {
+                    timeline.record(dots, ly, line_dot, Event::Interrupt(ticked.irq));
+                }
+            }
+
+            if self.hooks.is_some() {
+                let ly = self.ppu.ly();
+                self.run_hooks(|hooks, mb| hooks.dot(mb, ly, ticked.irq));
+            }
+
             self.ic.request(ticked.irq);
             if ticked.hblank_edge {
                 self.step_hdma_hblank();
@@ -243,6 +859,19 @@ impl Motherboard {
         if self.sysbus.take_boot_disabled() {
             self.ppu.on_boot_rom_disabled();
         }
+
+        // SGB commands arrive through JOYP writes; the PPU owns the SGB display
+        // and the APU the SNES sound processor.
+        if let Some(cmd) = self.sysbus.take_sgb_command() {
+            if cmd.command == Command::Sound {
+                self.apu.sgb_command(cmd);
+            } else {
+                self.ppu.sgb_command(cmd);
+            }
+        }
+        if let Some(data) = self.ppu.take_sgb_sound_upload() {
+            self.apu.sgb_sound_upload(&data);
+        }
     }
 
     fn idle_mcycle(&mut self) {
@@ -262,15 +891,24 @@ impl Motherboard {
         self.sysbus.bus(&mut self.pins);
         self.pins.transfer = false;
         self.pins.master = BusMaster::Cpu;
+        if self.cdl.is_some() {
+            self.log_cdl(addr, CdlFlags::GRAPHICS);
+        }
         self.pins.data
     }
 
     fn step_oam_dma(&mut self) {
         if let Some(src) = self.dma.oam_next() {
+            if self.dma.oam_progress == 0 {
+                self.log_timeline(Event::OamDmaStart(src));
+            }
             let byte = self.bus_read(src, BusMaster::OamDma);
             let offset = self.dma.oam_progress;
             self.ppu.write_oam(offset, byte);
             self.dma.oam_feed(byte);
+            if self.dma.oam_progress == 0xA0 {
+                self.log_timeline(Event::OamDmaEnd);
+            }
         }
     }
 
@@ -296,6 +934,8 @@ impl Motherboard {
 
     fn hdma_copy_block(&mut self) {
         let pairs = self.dma.hdma_block();
+        let (source, dest) = pairs[0];
+        self.log_timeline(Event::Hdma { source, dest });
         for (src, dst) in pairs {
             let byte = self.bus_read(src, BusMaster::OamDma);
             self.ppu.write_vram_dma(dst, byte);
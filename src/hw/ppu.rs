@@ -2,9 +2,12 @@ use super::bus::{BusDir, Chip, Pins, Ticked};
 use super::interrupt::Interrupts;
 use crate::components::memory::Memory;
 use crate::components::ppu::ppu::{OamGlitch, PPU as CorePpu};
+use crate::components::ppu::viewer::VideoMemory;
+use crate::components::sgb::packet::CommandData;
 use crate::config::Config;
 use crate::framebuffer::FramebufferWriter;
 
+#[derive(Clone)]
 pub struct Ppu {
     core: Box<CorePpu>,
 }
@@ -26,6 +29,11 @@ impl Ppu {
             | 0xFF68..=0xFF6C)
     }
 
+    /// One of the PPU's registers as the CPU reads it.
+    pub fn peek_register(&self, a: u16) -> u8 {
+        self.core.read(a)
+    }
+
     /// OAM write port for the OAM-DMA engine, which drives OAM while holding
     /// the bus as master (unconditional, bypassing CPU mode-gating).
     pub fn write_oam(&mut self, index: u16, value: u8) {
@@ -37,6 +45,37 @@ impl Ppu {
         self.core.write_vram_direct(addr, value);
     }
 
+    /// VRAM in the current bank, whatever mode the PPU is in.
+    pub fn peek_vram(&self, addr: u16) -> u8 {
+        self.core.read_vram_direct(addr)
+    }
+
+    /// Both VRAM banks, whatever mode the PPU is in.
+    pub fn vram(&self) -> &[u8; 0x4000] {
+        self.core.vram()
+    }
+
+    pub fn vram_mut(&mut self) -> &mut [u8; 0x4000] {
+        self.core.vram_mut()
+    }
+
+    /// OAM, whatever mode the PPU is in.
+    pub fn oam(&self) -> &[u8; 0xA0] {
+        self.core.oam()
+    }
+
+    pub fn framebuffer(&self) -> &FramebufferWriter {
+        self.core.framebuffer()
+    }
+
+    pub fn framebuffer_mut(&mut self) -> &mut FramebufferWriter {
+        self.core.framebuffer_mut()
+    }
+
+    pub fn oam_mut(&mut self) -> &mut [u8; 0xA0] {
+        self.core.oam_mut()
+    }
+
     /// DMG OAM corruption: a CPU access through the OAM region during mode 2
     /// glitches the row being scanned. The kind (write/read/read-increase) is
     /// decided by the CPU M-cycle. No-op outside that window.
@@ -52,6 +91,34 @@ impl Ppu {
     pub fn on_boot_rom_disabled(&mut self) {
         self.core.disable_boot_rom();
     }
+
+    /// Forwarded from the joypad: an SGB command for the SGB display.
+    pub fn sgb_command(&mut self, cmd: CommandData) {
+        self.core.sgb_command(cmd);
+    }
+
+    pub fn ly(&self) -> u8 {
+        self.core.ly()
+    }
+
+    /// The mode as STAT reports it, 0-3.
+    pub fn mode(&self) -> u8 {
+        self.core.ppu_mode() as u8
+    }
+
+    /// The dot within the current line, 0-455.
+    pub fn line_dot(&self) -> u32 {
+        self.core.line_dot()
+    }
+
+    pub fn video(&self) -> VideoMemory<'_> {
+        self.core.video()
+    }
+
+    /// A completed SOU_TRN, which the motherboard hands to the APU.
+    pub fn take_sgb_sound_upload(&mut self) -> Option<Box<[u8]>> {
+        self.core.take_sgb_sound_upload()
+    }
 }
 
 impl Chip for Ppu {
@@ -1,18 +1,24 @@
 use super::bus::{BusDir, Chip, Pins, Ticked};
 use super::interrupt::Interrupts;
+use crate::components::infrared::Infrared;
 use crate::components::joypad::{Joypad, JoypadButton};
+use crate::components::link::infrared::InfraredLink;
+use crate::components::link::link::SerialLink;
 use crate::components::memory::Memory;
 use crate::components::mode::GBMode;
 use crate::components::registers::io;
 use crate::components::serial::Serial;
+use crate::components::sgb::packet::CommandData;
 use crate::config::Config;
 use crate::mbc::header::Header;
 use crate::mbc::mode::{MBC, MBCMode};
 use crate::mbc::prelude::*;
 
+#[derive(Clone)]
 pub struct SystemBus {
     mbc: Box<dyn MBC + 'static>,
     serial: Serial,
+    infrared: Infrared,
     joypad: Joypad,
     wram: [u8; 0x8000],
     hram: [u8; 0x7F],
@@ -23,7 +29,6 @@ pub struct SystemBus {
     mode: GBMode,
     double_speed: bool,
     key1_armed: bool,
-    rp: u8,
 }
 
 impl SystemBus {
@@ -40,7 +45,9 @@ impl SystemBus {
         Self {
             mbc,
             serial: Serial::new(config.print_serial, config.mode),
-            joypad: Joypad::new(),
+            infrared: Infrared::new(config.mode),
+            // The SGB only listens for packets from carts that declare support.
+            joypad: Joypad::new(config.sgb && config.mode == GBMode::DMG && header.sgb_flag),
             wram: [0; 0x8000],
             hram: [0; 0x7F],
             wram_bank: 0x01,
@@ -50,7 +57,6 @@ impl SystemBus {
             mode: config.mode,
             double_speed: false,
             key1_armed: false,
-            rp: 0,
         }
     }
 
@@ -63,6 +69,7 @@ impl SystemBus {
         if self.key1_armed {
             self.double_speed = !self.double_speed;
             self.key1_armed = false;
+            self.serial.set_double_speed(self.double_speed);
         }
     }
 
@@ -79,14 +86,119 @@ impl SystemBus {
         self.joypad.up(b);
     }
 
+    /// A completed SGB command, once; the motherboard forwards it to the PPU.
+    pub fn take_sgb_command(&mut self) -> Option<CommandData> {
+        self.joypad.take_sgb_command()
+    }
+
     pub fn peek(&self, a: u16) -> u8 {
         self.do_read(a)
     }
 
+    /// Write WRAM, HRAM or cartridge RAM (enabled or not) directly. Other
+    /// addresses are left alone.
+    pub fn poke(&mut self, a: u16, v: u8) {
+        match a {
+            0xA000..=0xBFFF => {
+                if let Some(offset) = self.mbc.ram_offset(a) {
+                    self.mbc.ram_mut()[offset] = v;
+                }
+            }
+            0xC000..=0xFDFF | 0xFF80..=0xFFFE => self.do_write(a, v),
+            _ => {}
+        }
+    }
+
+    /// All eight WRAM banks, laid end to end. The DMG only has the first two.
+    pub fn wram(&self) -> &[u8; 0x8000] {
+        &self.wram
+    }
+
+    pub fn wram_mut(&mut self) -> &mut [u8; 0x8000] {
+        &mut self.wram
+    }
+
+    pub fn hram(&self) -> &[u8; 0x7F] {
+        &self.hram
+    }
+
+    pub fn hram_mut(&mut self) -> &mut [u8; 0x7F] {
+        &mut self.hram
+    }
+
+    /// Cartridge RAM, banks laid end to end.
+    pub fn cart_ram(&self) -> &[u8] {
+        self.mbc.ram()
+    }
+
+    pub fn cart_ram_mut(&mut self) -> &mut [u8] {
+        self.mbc.ram_mut()
+    }
+
     pub fn disable_boot(&mut self) {
         self.boot_rom_enabled = false;
     }
 
+    /// Whether the boot ROM still overlays the cartridge.
+    pub fn boot_rom_enabled(&self) -> bool {
+        self.boot_rom_enabled
+    }
+
+    /// The ROM bank mapped at $4000-$7FFF.
+    pub fn rom_bank(&self) -> usize {
+        self.mbc.rom_bank()
+    }
+
+    /// Where `a` lands in the ROM image, unless the boot ROM covers it or
+    /// it isn't ROM.
+    pub fn rom_offset(&self, a: u16) -> Option<usize> {
+        let boot = self.boot_rom_enabled
+            && (a <= 0x00FF || (self.mode != GBMode::DMG && (0x0200..=0x08FF).contains(&a)));
+        match a {
+            _ if boot => None,
+            0x0000..=0x3FFF => Some(a as usize),
+            0x4000..=0x7FFF => Some(self.mbc.rom_bank() * 0x4000 + a as usize - 0x4000),
+            _ => None,
+        }
+    }
+
+    /// Where `a` lands in WRAM (banks laid end to end), or at `$8000 +` its
+    /// offset in HRAM.
+    pub fn ram_offset(&self, a: u16) -> Option<usize> {
+        let bank = self.wram_bank.max(1);
+        match a {
+            0xC000..=0xCFFF => Some(a as usize - 0xC000),
+            0xD000..=0xDFFF => Some(a as usize - 0xD000 + 0x1000 * bank),
+            0xE000..=0xEFFF => Some(a as usize - 0xE000),
+            0xF000..=0xFDFF => Some(a as usize - 0xF000 + 0x1000 * bank),
+            0xFF80..=0xFFFE => Some(a as usize - 0xFF80 + 0x8000),
+            _ => None,
+        }
+    }
+
+    /// Plug a link cable or peripheral into the serial port.
+    pub fn connect_serial(&mut self, link: Box<dyn SerialLink>) {
+        self.serial.connect(link);
+    }
+
+    pub fn disconnect_serial(&mut self) -> Option<Box<dyn SerialLink>> {
+        self.serial.disconnect()
+    }
+
+    /// Clock the serial port from the system divider; called every dot.
+    pub fn clock_serial(&mut self, divider: u16) {
+        self.serial.tick(divider);
+    }
+
+    /// Point the CGB infrared port at another one.
+    pub fn connect_infrared(&mut self, link: Box<dyn InfraredLink>) {
+        self.infrared.connect(link);
+    }
+
+    pub fn disconnect_infrared(&mut self) -> Option<Box<dyn InfraredLink>> {
+        self.infrared.disconnect()
+    }
+
     /// Bytes the program has transmitted over the serial port.
     pub fn serial_output(&self) -> &[u8] {
         self.serial.output()
@@ -142,13 +254,7 @@ impl SystemBus {
                         | if self.key1_armed { 0x01 } else { 0x00 }
                 }
             }
-            io::RP => {
-                if self.mode == GBMode::DMG {
-                    0xFF
-                } else {
-                    self.rp | 0x3E
-                }
-            }
+            io::RP => self.infrared.read(a),
             io::SVBK => {
                 if self.mode == GBMode::DMG {
                     0xFF
@@ -177,11 +283,7 @@ impl SystemBus {
                 self.boot_rom_enabled = false;
                 self.boot_just_disabled = true;
             }
-            io::RP => {
-                if self.mode != GBMode::DMG {
-                    self.rp = v & 0xC1;
-                }
-            }
+            io::RP => self.infrared.write(a, v),
             io::SVBK => {
                 if self.mode != GBMode::DMG {
                     self.wram_bank = (v & 0x07) as usize;
@@ -195,8 +297,12 @@ impl SystemBus {
 }
 
 impl Chip for SystemBus {
-    fn advance(&mut self, _base_dot: bool) -> Ticked {
-        // Drain the untimed ports' interrupt requests.
+    fn advance(&mut self, base_dot: bool) -> Ticked {
+        if base_dot {
+            self.infrared.tick();
+        }
+
+        // Drain the ports' interrupt requests.
         let mut bits = self.serial.interrupts.bits();
         self.serial.interrupts = Interrupts::empty();
         bits |= self.joypad.interrupts.bits();
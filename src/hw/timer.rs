@@ -15,6 +15,7 @@ enum Reload {
     },
 }
 
+#[derive(Clone)]
 pub struct Timer {
     counter: u16,
     tima: u8,
@@ -101,7 +102,7 @@ impl Chip for Timer {
     fn bus(&mut self, pins: &mut Pins) -> Ticked {
         match pins.address {
             io::DIV if pins.selected(true) => match pins.dir {
-                BusDir::Read => pins.data = (self.counter >> 8) as u8,
+                BusDir::Read => pins.data = self.peek(pins.address),
                 // Writing DIV clears the whole counter; the drop can clock TIMA.
                 BusDir::Write => {
                     if self.set_counter(0) {
@@ -111,7 +112,7 @@ impl Chip for Timer {
                 BusDir::Idle => {}
             },
             io::TIMA if pins.selected(true) => match pins.dir {
-                BusDir::Read => pins.data = self.tima,
+                BusDir::Read => pins.data = self.peek(pins.address),
                 BusDir::Write => match self.reload {
                     // Cycle A: Write cancels the overflow entirely
                     Reload::Overflowed { .. } => {
@@ -125,7 +126,7 @@ impl Chip for Timer {
                 BusDir::Idle => {}
             },
             io::TMA if pins.selected(true) => match pins.dir {
-                BusDir::Read => pins.data = self.tma,
+                BusDir::Read => pins.data = self.peek(pins.address),
                 BusDir::Write => {
                     self.tma = pins.data;
                     // Cycle B holds TIMA's load line from TMA, so a TMA write
@@ -137,7 +138,7 @@ impl Chip for Timer {
                 BusDir::Idle => {}
             },
             io::TAC if pins.selected(true) => match pins.dir {
-                BusDir::Read => pins.data = 0xF8 | self.tac,
+                BusDir::Read => pins.data = self.peek(pins.address),
                 BusDir::Write => {
                     let before = self.mux();
                     self.tac = pins.data & 0x07;
@@ -154,8 +155,28 @@ impl Chip for Timer {
 }
 
 impl Timer {
+    /// DIV, TIMA, TMA or TAC as the CPU reads it.
+    pub fn peek(&self, a: u16) -> u8 {
+        match a {
+            io::DIV => (self.counter >> 8) as u8,
+            io::TIMA => self.tima,
+            io::TMA => self.tma,
+            _ => 0xF8 | self.tac,
+        }
+    }
+
     /// DIV register value, for the APU frame sequencer.
     pub fn div(&self) -> u8 {
         (self.counter >> 8) as u8
     }
+
+    /// The full 16-bit system divider, for the serial clock.
+    pub fn counter(&self) -> u16 {
+        self.counter
+    }
+
+    /// Put the system divider at `value` without clocking TIMA.
+    pub fn preset_counter(&mut self, value: u16) {
+        self.counter = value;
+    }
 }
@@ -4,9 +4,16 @@ extern crate num_derive;
 pub mod components;
 pub mod config;
 pub mod context;
+pub mod debugger;
+pub mod disasm;
 pub mod framebuffer;
 pub mod hw;
 pub mod mbc;
+pub mod overlay;
+pub mod runner;
+pub mod script;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub const CLOCK_FREQUENCY: u32 = 4_194_304;
 pub const STEP_TIME: u32 = 16;
@@ -1,16 +1,33 @@
-use crate::components::ppu::ppu::{SCREEN_H, SCREEN_W};
+use crate::components::link::bgb::BgbLink;
+use crate::components::link::ir_socket::IrSocket;
+use crate::components::link::printer::Printer;
 use crate::components::prelude::*;
 use crate::config::Config;
 use crate::context::Context;
-use crate::framebuffer::{FramebufferReader, create_framebuffer_pair};
+use crate::debugger::cdl::Cdl;
+use crate::debugger::dap::{self, Dap};
+use crate::debugger::profile::Profiler;
+use crate::debugger::repl::{Repl, parse_address};
+use crate::debugger::search::{Watch, WatchList};
+use crate::debugger::symbols::Symbols;
+use crate::debugger::timeline::Timeline;
+use crate::debugger::trace::{TraceOptions, Tracer};
+use crate::framebuffer::{FramebufferReader, create_framebuffer_pair, side_by_side, tile};
+use crate::hw::link::{FourPlayer, LinkedPair};
+use crate::hw::motherboard::Motherboard;
 use crate::mbc::header::{CGBFlag, Header};
-use clap::Parser;
+use crate::runner::{Runner, Until};
+use crate::script::script::Script;
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
 use pollster::FutureExt;
 use std::fs::File;
 use std::io::{BufWriter, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{Receiver, Sender};
 use std::time::{Duration, Instant};
 use std::{process, thread};
 use tetsuyu::*;
@@ -23,19 +40,416 @@ use winit::platform::modifier_supplement::KeyEventExtModifierSupplement;
 use winit::window::{Window, WindowId};
 
 #[derive(Parser)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 struct Args {
-    rom_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(required = true)]
+    rom_path: Option<String>,
     boot_rom: Option<String>,
+    /// Link a second Game Boy running this ROM, shown side by side.
+    #[arg(long)]
+    link: Option<String>,
+    /// Wait for a BGB link protocol peer (BGB, SameBoy, tetsuyu) on this
+    /// address, e.g. `127.0.0.1:8765`.
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["link", "bgb_connect"])]
+    bgb_listen: Option<String>,
+    /// Connect to a BGB link protocol peer listening on this address.
+    #[arg(long, value_name = "ADDR", conflicts_with = "link")]
+    bgb_connect: Option<String>,
+    /// Plug a Game Boy Printer into the serial port, saving each print as a
+    /// PNG in this directory.
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["link", "bgb_listen", "bgb_connect"])]
+    printer: Option<PathBuf>,
+    /// Plug this Game Boy and up to three more, running these ROMs, into a
+    /// Four Player Adapter, shown in a 2x2 grid.
+    #[arg(
+        long,
+        value_name = "ROM",
+        num_args = 1..=3,
+        conflicts_with_all = ["link", "bgb_listen", "bgb_connect", "printer"]
+    )]
+    four_player: Vec<String>,
+    /// Wait for another emulator's infrared port to connect on this address.
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["link", "ir_connect"])]
+    ir_listen: Option<String>,
+    /// Face the infrared port of an emulator listening on this address.
+    #[arg(long, value_name = "ADDR", conflicts_with = "link")]
+    ir_connect: Option<String>,
+    /// Run under the debugger, taking commands on standard input.
+    #[arg(long, conflicts_with_all = ["link", "four_player"])]
+    debug: bool,
+    /// Serve the Debug Adapter Protocol to an editor on this address, or on
+    /// standard input and output with `stdio`.
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["link", "four_player", "debug"])]
+    dap: Option<String>,
+    /// Watch a byte, or a little-endian word with `:16`, in RAM, logging the
+    /// watched values on frames where one changes. Takes
+    /// `[name=]<addr>[:16]` and can be given more than once.
+    #[arg(long, value_name = "SPEC")]
+    watch: Vec<String>,
+    /// Run this Rhai script alongside the game, to read and write memory,
+    /// press buttons, save and load states and draw over the screen. The
+    /// script reload key runs it again.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["debug", "dap"])]
+    script: Option<PathBuf>,
+    /// Log every instruction to this file in gameboy-doctor's format.
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+    /// Only log instructions in `[bank:]start[-end]` (hex).
+    #[arg(long, value_name = "RANGE", requires = "trace")]
+    trace_range: Option<String>,
+    /// Columns to add to each trace line.
+    #[arg(
+        long,
+        value_name = "COLUMNS",
+        value_delimiter = ',',
+        requires = "trace"
+    )]
+    trace_extra: Vec<TraceExtra>,
+    /// Labels for the debugger and traces, from an RGBDS, no$gmb or wla-dx
+    /// `.sym` file. Defaults to the ROM's path with a `.sym` extension, if
+    /// there is one.
+    #[arg(long, value_name = "FILE")]
+    sym: Option<PathBuf>,
+    /// Keep a code/data log of what each ROM byte is used for in this file,
+    /// adding to it if it exists. Code run from RAM goes in `<FILE>.ram.cdl`.
+    #[arg(long, value_name = "FILE")]
+    cdl: Option<PathBuf>,
+    /// Profile where the CPU's time goes, writing a report to this file and
+    /// stacks for flamegraph tools to `<FILE>.folded` on exit.
+    #[arg(long, value_name = "FILE")]
+    profile: Option<PathBuf>,
+    /// Record when interrupts, PPU modes, lines, DMA and PPU register writes
+    /// happen within frames, writing Chrome trace-event JSON for Perfetto or
+    /// chrome://tracing to this file on exit.
+    #[arg(long, value_name = "FILE")]
+    timeline: Option<PathBuf>,
+    /// Frames to record on the timeline.
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "timeline")]
+    timeline_frames: u32,
+    /// Frames to let pass before recording the timeline.
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "timeline")]
+    timeline_skip: u32,
+    /// Write the VRAM viewers (tile sheet, BG and window maps, OAM and
+    /// palettes) as PNGs to this directory on exit, and whenever the VRAM
+    /// key is pressed. The key writes to `./vram` without it.
+    #[arg(long, value_name = "DIR")]
+    vram: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum TraceExtra {
+    /// Base-clock cycles since power on.
+    Cycles,
+    Ly,
+    /// The ROM bank at $4000-$7FFF.
+    Bank,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Disassemble part of a ROM.
+    Disasm {
+        rom_path: String,
+        /// `[bank:]start[-end]` in hex; bank defaults to 1 and end to 64
+        /// bytes on.
+        #[arg(default_value = "0100-0150")]
+        range: String,
+        /// Labels to show, as for the emulator's `--sym`.
+        #[arg(long, value_name = "FILE")]
+        sym: Option<PathBuf>,
+    },
+    /// Run a ROM with no window or sound until a condition is met, then
+    /// write out where it got to. Exits with 0 once the condition is met, 1
+    /// on an error, 2 on bad arguments, 3 on a `--serial-fail` string and 4
+    /// on a timeout.
+    Run(RunArgs),
+}
+
+#[derive(clap::Args)]
+#[command(group(
+    ArgGroup::new("until")
+        .required(true)
+        .multiple(true)
+        .args(["frames", "magic_break", "serial", "serial_fail", "timeout"])
+))]
+struct RunArgs {
+    rom_path: PathBuf,
+    /// Settings to run with, instead of ./config.toml.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Boot ROM to use, instead of the one the config gives for the mode.
+    #[arg(long, value_name = "FILE")]
+    boot_rom: Option<String>,
+    #[arg(long, value_enum)]
+    mode: Option<RunMode>,
+    /// Rhai script to drive the game with, as for the emulator's `--script`.
+    #[arg(long, value_name = "FILE")]
+    script: Option<PathBuf>,
+    /// Stop after this many frames.
+    #[arg(long, value_name = "N")]
+    frames: Option<u64>,
+    /// Stop when the CPU runs `LD B,B`.
+    #[arg(long)]
+    magic_break: bool,
+    /// Stop when the serial output contains this; can be given more than once.
+    #[arg(long, value_name = "TEXT")]
+    serial: Vec<String>,
+    /// Stop and fail when the serial output contains this.
+    #[arg(long, value_name = "TEXT")]
+    serial_fail: Vec<String>,
+    /// Give up after this many seconds.
+    #[arg(long, value_name = "SECONDS", value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+    /// Write the last frame to this PNG.
+    #[arg(long, value_name = "FILE")]
+    screenshot: Option<PathBuf>,
+    /// Write the serial output to this file.
+    #[arg(long, value_name = "FILE")]
+    serial_out: Option<PathBuf>,
+    /// Write a JSON summary of the run to this file instead of standard
+    /// output.
+    #[arg(long, value_name = "FILE")]
+    summary: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RunMode {
+    Dmg,
+    Cgb,
 }
 
 struct App {
     header: Header,
     context: Option<Context>,
     config: Config,
-    input_tx: Sender<(JoypadButton, bool)>,
+    /// Player index (0 for the first machine), button and state.
+    input_tx: Sender<(usize, JoypadButton, bool)>,
     framebuffer_reader: FramebufferReader,
+    /// Screens of the other linked machines.
+    link_readers: Vec<FramebufferReader>,
+    /// All linked screens composed into one frame.
+    link_frame: Vec<u8>,
     occluded: bool,
     dump_frame: bool,
+    /// Asks the CPU thread to write the VRAM viewers.
+    dump_vram: Arc<AtomicBool>,
+    /// Asks the CPU thread to run the script again.
+    reload_script: Arc<AtomicBool>,
+}
+
+/// The machine(s) the CPU thread drives.
+enum Machine {
+    Single(Box<Motherboard>),
+    Linked(Box<LinkedPair>),
+    FourPlayer(Box<FourPlayer>),
+}
+
+impl Machine {
+    /// Run for a little while; returns the base-clock cycles elapsed.
+    fn step(&mut self) -> u32 {
+        match self {
+            Machine::Single(mb) => {
+                let cycles = mb.step();
+                // Pace against the base clock, which runs at a constant rate in
+                // both speed modes. `step` returns CPU T-cycles; in double speed
+                // those tick at twice the base rate, so two of them equal one unit
+                // of real-time budget. Without this the limiter throttles double
+                // speed to the base cycle throughput and the user sees half speed.
+                if mb.double_speed() {
+                    cycles / 2
+                } else {
+                    cycles
+                }
+            }
+            Machine::Linked(pair) => {
+                let before = pair.cycles();
+                pair.step_mcycle();
+                (pair.cycles() - before) as u32
+            }
+            Machine::FourPlayer(group) => {
+                let before = group.cycles();
+                group.step_mcycle();
+                (group.cycles() - before) as u32
+            }
+        }
+    }
+
+    /// The first player's machine, the one traced, logged and profiled.
+    fn first(&mut self) -> &mut Motherboard {
+        match self {
+            Machine::Single(mb) => mb,
+            Machine::Linked(pair) => &mut pair.a,
+            Machine::FourPlayer(group) => &mut group.machines[0],
+        }
+    }
+
+    fn joypad(&mut self, player: usize, button: JoypadButton, pressed: bool) {
+        let mb = match self {
+            Machine::Single(mb) if player == 0 => mb,
+            Machine::Linked(pair) if player == 0 => &mut pair.a,
+            Machine::Linked(pair) => &mut pair.b,
+            Machine::FourPlayer(group) => match group.machines.get_mut(player) {
+                Some(mb) => mb,
+                None => return,
+            },
+            Machine::Single(_) => return,
+        };
+        if pressed {
+            mb.joypad_down(button);
+        } else {
+            mb.joypad_up(button);
+        }
+    }
+}
+
+/// Write the VRAM viewers for `mb` to `dir`.
+fn dump_vram(mb: &Motherboard, dir: &Path) {
+    match mb.video().dump(dir) {
+        Ok(()) => println!("Wrote VRAM viewers to \"{}\"", dir.display()),
+        Err(err) => eprintln!("Failed to write \"{}\": {}", dir.display(), err),
+    }
+}
+
+/// Finish the trace, code/data log, profile and timeline being kept of `mb`,
+/// and write its VRAM viewers.
+fn shut_down(
+    mb: &mut Motherboard,
+    profile: Option<&Path>,
+    timeline: Option<&Path>,
+    vram: Option<&Path>,
+    symbols: &Symbols,
+) {
+    if let Some(mut tracer) = mb.set_tracer(None)
+        && let Err(err) = tracer.flush()
+    {
+        eprintln!("Failed to write trace: {}", err);
+    }
+    if let Some(mut cdl) = mb.set_cdl(None)
+        && let Err(err) = cdl.save()
+    {
+        eprintln!("Failed to save code/data log: {}", err);
+    }
+    if let (Some(path), Some(profiler)) = (profile, mb.set_profiler(None)) {
+        let folded = path.with_extension("folded");
+        for (path, text) in [
+            (path, profiler.report(symbols)),
+            (folded.as_path(), profiler.folded(symbols)),
+        ] {
+            if let Err(err) = std::fs::write(path, text) {
+                eprintln!("Failed to write \"{}\": {}", path.display(), err);
+            }
+        }
+    }
+    if let (Some(path), Some(timeline)) = (timeline, mb.set_timeline(None))
+        && let Err(err) = std::fs::write(path, timeline.chrome_json())
+    {
+        eprintln!("Failed to write \"{}\": {}", path.display(), err);
+    }
+    if let Some(dir) = vram {
+        dump_vram(mb, dir);
+    }
+}
+
+/// Keep `mb` to real time once it has run `cycles` more T-cycles, and pass
+/// it the buttons pressed since.
+fn pace(
+    mb: &mut Motherboard,
+    cycles: u64,
+    limiter: &mut Limiter,
+    input_rx: &Receiver<(usize, JoypadButton, bool)>,
+) {
+    let cycles = if mb.double_speed() {
+        cycles / 2
+    } else {
+        cycles
+    };
+    limiter.cycles += cycles as u32;
+    limiter.wait();
+    while let Ok((_, button, pressed)) = input_rx.try_recv() {
+        if pressed {
+            mb.joypad_down(button);
+        } else {
+            mb.joypad_up(button);
+        }
+    }
+}
+
+/// What to do between a debugger's slices of running a machine: keep it to
+/// real time and pass it the buttons pressed (unless headless), write its
+/// VRAM viewers when asked, and carry on only while the emulator is running.
+fn between_slices<'a>(
+    headless: bool,
+    limiter: &'a mut Limiter,
+    input_rx: &'a Receiver<(usize, JoypadButton, bool)>,
+    vram_requested: &'a AtomicBool,
+    vram_dir: &'a Path,
+    running: &'a AtomicBool,
+) -> impl FnMut(&mut Motherboard, u64) -> bool + 'a {
+    move |mb, cycles| {
+        if !headless {
+            pace(mb, cycles, limiter, input_rx);
+        }
+        if vram_requested.swap(false, Ordering::Relaxed) {
+            dump_vram(mb, vram_dir);
+        }
+        running.load(Ordering::Relaxed)
+    }
+}
+
+/// A debug adapter for `rom`, with sources found next to it.
+fn dap_server<W: Write>(
+    messages: Receiver<serde_json::Value>,
+    output: W,
+    rom: &[u8],
+    rom_path: &str,
+    symbols: &Symbols,
+) -> Dap<W> {
+    let mut dap = Dap::new(messages, output);
+    dap.symbols = symbols.clone();
+    dap.rom = rom.to_vec();
+    match Path::new(rom_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dap.sources.scan(dir),
+        _ => dap.sources.scan(Path::new(".")),
+    }
+    dap
+}
+
+/// Keeps the CPU thread to real time.
+struct Limiter {
+    cycles: u32,
+    zero: Instant,
+}
+
+impl Limiter {
+    fn new() -> Self {
+        Self {
+            cycles: 0,
+            zero: Instant::now(),
+        }
+    }
+
+    /// Sleep off a step's worth of cycles once they have been run.
+    fn wait(&mut self) {
+        // https://github.com/mohanson/gameboy/blob/master/src/cpu.rs#L13
+        if self.cycles > STEP_CYCLES {
+            self.cycles -= STEP_CYCLES;
+            let now = Instant::now();
+            let duration = now.duration_since(self.zero);
+            let milliseconds = STEP_TIME.saturating_sub(duration.as_millis() as u32);
+            // println!("[CPU] Sleeping {}ms", milliseconds);
+            thread::sleep(Duration::from_millis(milliseconds as u64));
+            self.zero = self
+                .zero
+                .checked_add(Duration::from_millis(u64::from(STEP_TIME)))
+                .unwrap();
+
+            if now.checked_duration_since(self.zero).is_some() {
+                self.zero = now;
+            }
+        }
+    }
 }
 
 impl ApplicationHandler for App {
@@ -62,12 +476,26 @@ impl ApplicationHandler for App {
 
         match event {
             WindowEvent::RedrawRequested if window_id == context.window().id() => {
-                let frame_data = self.framebuffer_reader.get_latest_frame();
+                let frame = self.framebuffer_reader.get_latest();
+                let (frame_data, width, height) = match &mut self.link_readers[..] {
+                    [] => (&frame.data[..], frame.width, frame.height),
+                    [link_reader] => {
+                        let (width, height) =
+                            side_by_side(frame, link_reader.get_latest(), &mut self.link_frame);
+                        (&self.link_frame[..], width, height)
+                    }
+                    link_readers => {
+                        let mut frames = vec![frame];
+                        frames.extend(link_readers.iter_mut().map(|r| r.get_latest()));
+                        let (width, height) = tile(&frames, 2, &mut self.link_frame);
+                        (&self.link_frame[..], width, height)
+                    }
+                };
 
                 if self.dump_frame {
                     let file = File::create("./frame.png").unwrap();
                     let w = &mut BufWriter::new(file);
-                    let mut encoder = png::Encoder::new(w, SCREEN_W as u32, SCREEN_H as u32);
+                    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
 
                     encoder.set_color(png::ColorType::Rgba);
                     encoder.set_depth(png::BitDepth::Eight);
@@ -78,7 +506,7 @@ impl ApplicationHandler for App {
                     self.dump_frame = false;
                 }
 
-                context.update(frame_data);
+                context.update(frame_data, width, height);
                 context.render();
             }
             WindowEvent::Resized(physical_size) => {
@@ -119,25 +547,180 @@ impl ApplicationHandler for App {
 
 impl App {
     pub fn send_input(&mut self, key: Key, pressed: bool) {
-        let input = &self.config.input;
-        let input_tx = &self.input_tx;
-
-        match key {
-            key if key == input.up => input_tx.send((JoypadButton::UP, pressed)).unwrap(),
-            key if key == input.left => input_tx.send((JoypadButton::LEFT, pressed)).unwrap(),
-            key if key == input.down => input_tx.send((JoypadButton::DOWN, pressed)).unwrap(),
-            key if key == input.right => input_tx.send((JoypadButton::RIGHT, pressed)).unwrap(),
-            key if key == input.a => input_tx.send((JoypadButton::A, pressed)).unwrap(),
-            key if key == input.b => input_tx.send((JoypadButton::B, pressed)).unwrap(),
-            key if key == input.select => input_tx.send((JoypadButton::SELECT, pressed)).unwrap(),
-            key if key == input.start => input_tx.send((JoypadButton::START, pressed)).unwrap(),
-            key if key == input.screenshot => self.dump_frame = true,
-            _ => (),
+        if key == self.config.input.screenshot {
+            self.dump_frame = true;
+            return;
+        }
+        if key == self.config.input.vram_dump {
+            if pressed {
+                self.dump_vram.store(true, Ordering::Relaxed);
+            }
+            return;
+        }
+        if key == self.config.input.script_reload {
+            if pressed {
+                self.reload_script.store(true, Ordering::Relaxed);
+            }
+            return;
+        }
+
+        let players = [
+            &self.config.input,
+            &self.config.link_input,
+            &self.config.player3_input,
+            &self.config.player4_input,
+        ];
+        let count = self.link_readers.len() + 1;
+        for (player, input) in players.into_iter().take(count).enumerate() {
+            let button = match &key {
+                key if *key == input.up => JoypadButton::UP,
+                key if *key == input.left => JoypadButton::LEFT,
+                key if *key == input.down => JoypadButton::DOWN,
+                key if *key == input.right => JoypadButton::RIGHT,
+                key if *key == input.a => JoypadButton::A,
+                key if *key == input.b => JoypadButton::B,
+                key if *key == input.select => JoypadButton::SELECT,
+                key if *key == input.start => JoypadButton::START,
+                _ => continue,
+            };
+            self.input_tx.send((player, button, pressed)).unwrap();
         }
     }
 }
 
+/// Split `[bank:]start[-end]` (hex) into its parts.
+fn parse_range(range: &str) -> Result<(Option<usize>, u16, Option<u16>), String> {
+    let (bank, range) = match range.split_once(':') {
+        Some((bank, range)) => (
+            Some(usize::from_str_radix(bank, 16).map_err(|_| format!("bad bank `{}`", bank))?),
+            range,
+        ),
+        None => (None, range),
+    };
+    match range.split_once('-') {
+        Some((start, end)) => Ok((bank, parse_address(start)?, Some(parse_address(end)?))),
+        None => Ok((bank, parse_address(range)?, None)),
+    }
+}
+
+/// A `--timeout` in seconds, which may be fractional but not negative.
+fn parse_timeout(seconds: &str) -> Result<Duration, String> {
+    let seconds: f64 = seconds.parse().map_err(|_| "not a number".to_string())?;
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|_| "must be a finite number of seconds, 0 or more".to_string())
+}
+
+/// Labels from `sym`, or from the `.sym` file next to the ROM if there is
+/// one and `sym` isn't given.
+fn load_symbols(rom_path: &str, sym: Option<&Path>) -> Result<Symbols, String> {
+    let default = Path::new(rom_path).with_extension("sym");
+    let path = match sym {
+        Some(path) => path,
+        None if default.is_file() => &default,
+        None => return Ok(Symbols::new()),
+    };
+    Symbols::load(path).map_err(|err| format!("Failed to read \"{}\": {}", path.display(), err))
+}
+
+/// Print a listing of `range` of the ROM at `rom_path`.
+fn disasm(rom_path: &str, range: &str, sym: Option<&Path>) -> Result<(), String> {
+    let rom = std::fs::read(rom_path)
+        .map_err(|err| format!("Failed to open ROM at \"{}\": {}", rom_path, err))?;
+    let symbols = load_symbols(rom_path, sym)?;
+    let (bank, start, end) = parse_range(range)?;
+    let bank = bank.unwrap_or(1);
+    let end = end.unwrap_or(start.saturating_add(0x40));
+    for line in disasm::listing(start, end, bank, &symbols, disasm::rom_reader(&rom, bank)) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Run a ROM headless for `tetsuyu run`, returning the exit status.
+fn run(args: &RunArgs) -> Result<i32, String> {
+    let config_path = args.config.as_deref().unwrap_or(Path::new("./config.toml"));
+    let mut config = match std::fs::read_to_string(config_path) {
+        Ok(config) => toml::from_str(&config)
+            .map_err(|err| format!("Failed to parse \"{}\": {}", config_path.display(), err))?,
+        Err(_) if args.config.is_none() => Config::default(),
+        Err(err) => {
+            return Err(format!(
+                "Failed to read \"{}\": {}",
+                config_path.display(),
+                err
+            ));
+        }
+    };
+    config.print_serial = false;
+    if let Some(mode) = args.mode {
+        config.mode = match mode {
+            RunMode::Dmg => GBMode::DMG,
+            RunMode::Cgb => GBMode::CGB,
+        };
+    }
+    if let Some(boot_rom) = &args.boot_rom {
+        match config.mode {
+            GBMode::DMG => config.dmg_boot_rom = boot_rom.clone(),
+            GBMode::CGB => config.cgb_boot_rom = boot_rom.clone(),
+        }
+    }
+
+    let mut runner = Runner::load(&args.rom_path, config)?;
+    if let Some(path) = &args.script {
+        runner.load_script(path)?;
+    }
+    let until = Until {
+        frames: args.frames,
+        magic_break: args.magic_break,
+        serial: args.serial.clone(),
+        serial_fail: args.serial_fail.clone(),
+        timeout: args.timeout,
+    };
+    let stop = runner.run(&until)?;
+
+    let write = |path: &Path, data: &[u8]| {
+        std::fs::write(path, data)
+            .map_err(|err| format!("Failed to write \"{}\": {}", path.display(), err))
+    };
+    if let Some(path) = &args.screenshot {
+        runner
+            .frame()
+            .write_png(path)
+            .map_err(|err| format!("Failed to write \"{}\": {}", path.display(), err))?;
+    }
+    if let Some(path) = &args.serial_out {
+        write(path, runner.machine().serial_output())?;
+    }
+    let summary = serde_json::to_string_pretty(&runner.summary(stop)).unwrap();
+    match &args.summary {
+        Some(path) => write(path, summary.as_bytes())?,
+        None => println!("{}", summary),
+    }
+    Ok(stop.status())
+}
+
 fn main() {
+    let args = Args::parse();
+    match &args.command {
+        Some(Command::Disasm {
+            rom_path,
+            range,
+            sym,
+        }) => {
+            if let Err(err) = disasm(rom_path, range, sym.as_deref()) {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Run(run_args)) => process::exit(run(run_args).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            1
+        })),
+        None => {}
+    }
+    let rom_path = args.rom_path.expect("clap requires a ROM path");
+
     let config = match File::open("./config.toml") {
         Ok(mut file) => {
             let mut config_data = String::new();
@@ -159,15 +742,10 @@ fn main() {
         }
     };
 
-    let args = Args::parse();
-    let mut file = match File::open(args.rom_path.clone()) {
+    let mut file = match File::open(rom_path.clone()) {
         Ok(file) => file,
         Err(err) => {
-            eprintln!(
-                "Failed to open ROM at \"{}\": {}",
-                args.rom_path.clone(),
-                err
-            );
+            eprintln!("Failed to open ROM at \"{}\": {}", rom_path.clone(), err);
             process::exit(1);
         }
     };
@@ -176,13 +754,139 @@ fn main() {
     file.read_to_end(&mut buffer).expect("Failed to read ROM!");
 
     let header = Header::new(buffer.clone());
-    println!("{}", header);
+    // Standard output carries the protocol for `--dap stdio`.
+    let dap_stdio = args.dap.as_deref() == Some("stdio");
+    if !dap_stdio {
+        println!("{}", header);
+    }
 
     assert!(
         (header.cgb_flag != CGBFlag::CGBOnly) || (config.mode != GBMode::DMG),
         "Cannot run CGB only game in DMG Mode!"
     );
 
+    let load_rom = |path: String| {
+        let buffer = std::fs::read(&path).unwrap_or_else(|err| {
+            eprintln!("Failed to open ROM at \"{}\": {}", path, err);
+            process::exit(1);
+        });
+        let header = Header::new(buffer.clone());
+        println!("{}", header);
+        (buffer, header)
+    };
+    let link_rom = args.link.map(load_rom);
+    let four_player_roms: Vec<_> = args.four_player.into_iter().map(load_rom).collect();
+
+    let bgb = match (&args.bgb_listen, &args.bgb_connect) {
+        (Some(addr), _) => {
+            println!("Waiting for a link partner on {}...", addr);
+            Some(BgbLink::listen(addr.as_str()))
+        }
+        (_, Some(addr)) => Some(BgbLink::connect(addr.as_str())),
+        _ => None,
+    }
+    .map(|link| {
+        link.unwrap_or_else(|err| {
+            eprintln!("Failed to set up the link cable: {}", err);
+            process::exit(1);
+        })
+    });
+
+    let ir = match (&args.ir_listen, &args.ir_connect) {
+        (Some(addr), _) => {
+            println!("Waiting for an infrared partner on {}...", addr);
+            Some(IrSocket::listen(addr.as_str()))
+        }
+        (_, Some(addr)) => Some(IrSocket::connect(addr.as_str())),
+        _ => None,
+    }
+    .map(|ir| {
+        ir.unwrap_or_else(|err| {
+            eprintln!("Failed to set up the infrared port: {}", err);
+            process::exit(1);
+        })
+    });
+
+    if let Some(dir) = &args.printer
+        && let Err(err) = std::fs::create_dir_all(dir)
+    {
+        eprintln!("Failed to create \"{}\": {}", dir.display(), err);
+        process::exit(1);
+    }
+    let printer = args.printer.map(|dir| Printer::new(Some(dir)));
+
+    let symbols = load_symbols(&rom_path, args.sym.as_deref()).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    let mut watches = WatchList::new();
+    for spec in &args.watch {
+        let watch = Watch::parse(spec, |at| match symbols.lookup(at) {
+            Some((_, address)) => Ok(address),
+            None => parse_address(at),
+        });
+        watches.add(watch.unwrap_or_else(|err| {
+            eprintln!("Bad watch `{}`: {}", spec, err);
+            process::exit(1);
+        }));
+    }
+
+    let tracer = args.trace.map(|path| {
+        let mut options = TraceOptions {
+            cycles: args.trace_extra.contains(&TraceExtra::Cycles),
+            ly: args.trace_extra.contains(&TraceExtra::Ly),
+            rom_bank: args.trace_extra.contains(&TraceExtra::Bank),
+            ..TraceOptions::default()
+        };
+        if let Some(range) = &args.trace_range {
+            let (bank, start, end) = parse_range(range).unwrap_or_else(|err| {
+                eprintln!("Bad trace range: {}", err);
+                process::exit(1);
+            });
+            options.bank = bank;
+            options.start = start;
+            options.end = end.unwrap_or(0xFFFF);
+        }
+        let mut tracer = Tracer::create(&path, options).unwrap_or_else(|err| {
+            eprintln!("Failed to create \"{}\": {}", path.display(), err);
+            process::exit(1);
+        });
+        if !symbols.is_empty() {
+            tracer.set_symbols(Some(symbols.clone()));
+        }
+        tracer
+    });
+
+    let cdl = args.cdl.map(|path| {
+        Cdl::open(&path, buffer.len()).unwrap_or_else(|err| {
+            eprintln!("Failed to read \"{}\": {}", path.display(), err);
+            process::exit(1);
+        })
+    });
+
+    // Editors connect on the accept thread and are served on the CPU thread.
+    let dap_clients = match &args.dap {
+        Some(addr) if !dap_stdio => {
+            let listener = TcpListener::bind(addr.as_str()).unwrap_or_else(|err| {
+                eprintln!("Failed to listen on {}: {}", addr, err);
+                process::exit(1);
+            });
+            println!("Waiting for a debug adapter client on {}...", addr);
+            let (tx, rx) = mpsc::channel();
+            thread::spawn(move || {
+                for stream in listener.incoming().map_while(Result::ok) {
+                    if tx.send(stream).is_err() {
+                        break;
+                    }
+                }
+            });
+            Some(rx)
+        }
+        _ => None,
+    };
+    let dap_rom = args.dap.is_some().then(|| buffer.clone());
+
     let panic = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
         panic(info);
@@ -192,74 +896,211 @@ fn main() {
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let (input_tx, input_rx) = mpsc::channel::<(JoypadButton, bool)>();
+    let (input_tx, input_rx) = mpsc::channel::<(usize, JoypadButton, bool)>();
     let (framebuffer_writer, framebuffer_reader) = create_framebuffer_pair();
+    let others = link_rom.iter().count() + four_player_roms.len();
+    let (mut link_writers, link_readers): (Vec<_>, Vec<_>) =
+        (0..others).map(|_| create_framebuffer_pair()).unzip();
+
+    let mut window_config = config.clone();
+    if !link_readers.is_empty() {
+        window_config.window_w *= 2;
+    }
+    if link_readers.len() > 1 {
+        window_config.window_h *= 2;
+    }
 
     let mut app = App {
         header: header.clone(),
         context: None,
-        config: config.clone(),
+        config: window_config,
         input_tx,
         framebuffer_reader,
+        link_readers,
+        link_frame: Vec::new(),
         occluded: false,
         dump_frame: false,
+        dump_vram: Arc::new(AtomicBool::new(false)),
+        reload_script: Arc::new(AtomicBool::new(false)),
     };
 
     // Start CPU
-    thread::spawn(move || {
-        let mut mb = hw::motherboard::Motherboard::from_config(
-            buffer,
-            header,
-            config.clone(),
-            framebuffer_writer,
-        );
-        let mut step_cycles = 0;
-        let mut step_zero = Instant::now();
-
-        loop {
-            if !config.headless {
-                // https://github.com/mohanson/gameboy/blob/master/src/cpu.rs#L13
-                if step_cycles > STEP_CYCLES {
-                    step_cycles -= STEP_CYCLES;
-                    let now = Instant::now();
-                    let duration = now.duration_since(step_zero);
-                    let milliseconds = STEP_TIME.saturating_sub(duration.as_millis() as u32);
-                    // println!("[CPU] Sleeping {}ms", milliseconds);
-                    thread::sleep(Duration::from_millis(milliseconds as u64));
-                    step_zero = step_zero
-                        .checked_add(Duration::from_millis(u64::from(STEP_TIME)))
-                        .unwrap();
-
-                    if now.checked_duration_since(step_zero).is_some() {
-                        step_zero = now;
+    let running = Arc::new(AtomicBool::new(true));
+    let cpu_running = running.clone();
+    let cpu_dump_vram = app.dump_vram.clone();
+    let cpu_reload_script = app.reload_script.clone();
+    let vram_dir = args.vram.clone().unwrap_or_else(|| PathBuf::from("./vram"));
+    let cpu = thread::spawn(move || {
+        let mut mb = Motherboard::from_config(buffer, header, config.clone(), framebuffer_writer);
+        if let Some(bgb) = bgb {
+            mb.connect_serial(Box::new(bgb));
+        }
+        if let Some(printer) = printer {
+            mb.connect_serial(Box::new(printer));
+        }
+        if let Some(ir) = ir {
+            mb.connect_infrared(Box::new(ir));
+        }
+        mb.set_tracer(tracer);
+        mb.set_cdl(cdl);
+        if args.profile.is_some() {
+            mb.set_profiler(Some(Profiler::new()));
+        }
+        if args.timeline.is_some() {
+            mb.set_timeline(Some(Timeline::new(
+                args.timeline_skip,
+                args.timeline_frames,
+            )));
+        }
+        let mut machine = if let Some((buffer, header)) = link_rom {
+            let writer = link_writers.remove(0);
+            let partner = Motherboard::from_config(buffer, header, config.clone(), writer);
+            Machine::Linked(Box::new(LinkedPair::new(mb, partner)))
+        } else if !four_player_roms.is_empty() {
+            let mut machines = vec![mb];
+            for ((buffer, header), writer) in four_player_roms.into_iter().zip(link_writers) {
+                machines.push(Motherboard::from_config(
+                    buffer,
+                    header,
+                    config.clone(),
+                    writer,
+                ));
+            }
+            Machine::FourPlayer(Box::new(FourPlayer::new(machines)))
+        } else {
+            Machine::Single(Box::new(mb))
+        };
+        let mut limiter = Limiter::new();
+        let finish = |mb: &mut Motherboard| {
+            shut_down(
+                mb,
+                args.profile.as_deref(),
+                args.timeline.as_deref(),
+                args.vram.as_deref(),
+                &symbols,
+            )
+        };
+
+        let mut script = args.script.as_deref().map(|path| {
+            Script::load(path, machine.first()).unwrap_or_else(|err| {
+                eprintln!("Failed to run {}: {}", path.display(), err);
+                process::exit(1);
+            })
+        });
+
+        if args.debug
+            && let Machine::Single(mb) = &mut machine
+        {
+            let (command_tx, command_rx) = mpsc::channel();
+            thread::spawn(move || {
+                for line in std::io::stdin().lines().map_while(Result::ok) {
+                    if command_tx.send(line).is_err() {
+                        break;
                     }
                 }
+            });
 
-                match input_rx.try_recv() {
-                    Ok(v) => {
-                        if v.1 {
-                            mb.joypad_down(v.0);
-                        } else {
-                            mb.joypad_up(v.0);
-                        }
-                    }
-                    Err(_) => {}
+            let mut repl = Repl::new(command_rx, std::io::stdout());
+            repl.symbols = symbols.clone();
+            repl.watches = std::mem::take(&mut watches);
+            repl.run(
+                mb,
+                between_slices(
+                    config.headless,
+                    &mut limiter,
+                    &input_rx,
+                    &cpu_dump_vram,
+                    &vram_dir,
+                    &cpu_running,
+                ),
+            );
+            finish(mb);
+            process::exit(0);
+        }
+
+        if dap_stdio && let Machine::Single(mb) = &mut machine {
+            let rom = dap_rom.as_deref().unwrap_or_default();
+            let mut dap = dap_server(
+                dap::spawn_reader(std::io::stdin()),
+                std::io::stdout(),
+                rom,
+                &rom_path,
+                &symbols,
+            );
+            dap.run(
+                mb,
+                between_slices(
+                    config.headless,
+                    &mut limiter,
+                    &input_rx,
+                    &cpu_dump_vram,
+                    &vram_dir,
+                    &cpu_running,
+                ),
+            );
+            finish(mb);
+            process::exit(0);
+        }
+
+        while cpu_running.load(Ordering::Relaxed) {
+            // Serve a client that has connected until it detaches.
+            if let Some(clients) = &dap_clients
+                && let Ok(stream) = clients.try_recv()
+                && let Machine::Single(mb) = &mut machine
+                && let Ok(output) = stream.try_clone()
+            {
+                let rom = dap_rom.as_deref().unwrap_or_default();
+                let messages = dap::spawn_reader(stream);
+                let mut dap = dap_server(messages, output, rom, &rom_path, &symbols);
+                let quit = dap.run(
+                    mb,
+                    between_slices(
+                        config.headless,
+                        &mut limiter,
+                        &input_rx,
+                        &cpu_dump_vram,
+                        &vram_dir,
+                        &cpu_running,
+                    ),
+                );
+                if quit {
+                    finish(mb);
+                    process::exit(0);
                 }
             }
 
-            let cycles = mb.step();
-            // Pace against the base clock, which runs at a constant rate in
-            // both speed modes. `step` returns CPU T-cycles; in double speed
-            // those tick at twice the base rate, so two of them equal one unit
-            // of real-time budget. Without this the limiter throttles double
-            // speed to the base cycle throughput and the user sees half speed.
-            step_cycles += if mb.double_speed() {
-                cycles / 2
-            } else {
-                cycles
-            };
+            if !config.headless {
+                limiter.wait();
+
+                if let Ok((player, button, pressed)) = input_rx.try_recv() {
+                    machine.joypad(player, button, pressed);
+                }
+            }
+
+            if cpu_dump_vram.swap(false, Ordering::Relaxed) {
+                dump_vram(machine.first(), &vram_dir);
+            }
+
+            limiter.cycles += machine.step();
+            if let Some(line) = watches.frame(machine.first()) {
+                println!("{}", line);
+            }
+            if let Some(script) = &mut script {
+                if cpu_reload_script.swap(false, Ordering::Relaxed) {
+                    match script.reload(machine.first()) {
+                        Ok(()) => println!("Reloaded {}", script.path().unwrap().display()),
+                        Err(err) => eprintln!("Script error: {}", err),
+                    }
+                }
+                if let Err(err) = script.step(machine.first()) {
+                    eprintln!("Script error: {}", err);
+                }
+            }
         }
+        finish(machine.first());
     });
 
     let _ = event_loop.run_app(&mut app);
+    running.store(false, Ordering::Relaxed);
+    let _ = cpu.join();
 }
@@ -1,6 +1,7 @@
 use crate::components::memory::Memory;
 use crate::mbc::mode::MBC;
 
+#[derive(Clone)]
 pub struct MBC1 {
     rom: Vec<u8>,
     ram: Vec<u8>,
@@ -54,7 +55,27 @@ impl Memory for MBC1 {
     }
 }
 
-impl MBC for MBC1 {}
+impl MBC for MBC1 {
+    fn box_clone(&self) -> Box<dyn MBC> {
+        Box::new(self.clone())
+    }
+
+    fn rom_bank(&self) -> usize {
+        MBC1::rom_bank(self)
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn ram_offset(&self, a: u16) -> Option<usize> {
+        Some(a as usize + self.ram_bank() * 0x2000 - 0xA000)
+    }
+}
 
 impl MBC1 {
     pub fn new(rom: Vec<u8>) -> Self {
@@ -87,6 +108,7 @@ impl MBC1 {
     }
 }
 
+#[derive(Clone)]
 enum BankMode {
     ROM,
     RAM,
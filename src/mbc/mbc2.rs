@@ -1,6 +1,7 @@
 use crate::components::memory::Memory;
 use crate::mbc::mode::MBC;
 
+#[derive(Clone)]
 pub struct MBC2 {
     rom: Vec<u8>,
     ram: Vec<u8>,
@@ -13,9 +14,10 @@ impl Memory for MBC2 {
         match a {
             0x0000..=0x3FFF => self.rom[a as usize],
             0x4000..=0x7FFF => self.rom[a as usize + self.rom_bank * 0x4000 - 0x4000],
-            0xA000..=0xA1FF => {
+            // The 512 half-bytes repeat through $A000-$BFFF.
+            0xA000..=0xBFFF => {
                 if self.ram_enabled {
-                    self.ram[(a - 0xA000) as usize]
+                    self.ram[(a as usize - 0xA000) % 0x200]
                 } else {
                     0x00
                 }
@@ -37,9 +39,9 @@ impl Memory for MBC2 {
                     self.rom_bank = v as usize;
                 }
             }
-            0xA000..=0xA1FF => {
+            0xA000..=0xBFFF => {
                 if self.ram_enabled {
-                    self.ram[(a - 0xa000) as usize] = v
+                    self.ram[(a as usize - 0xA000) % 0x200] = v
                 }
             }
             _ => panic!("Write to unsupported MBC2 address ({:#06x})!", a),
@@ -47,7 +49,27 @@ impl Memory for MBC2 {
     }
 }
 
-impl MBC for MBC2 {}
+impl MBC for MBC2 {
+    fn box_clone(&self) -> Box<dyn MBC> {
+        Box::new(self.clone())
+    }
+
+    fn rom_bank(&self) -> usize {
+        self.rom_bank
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn ram_offset(&self, a: u16) -> Option<usize> {
+        Some((a as usize - 0xA000) % 0x200)
+    }
+}
 
 impl MBC2 {
     pub fn new(rom: Vec<u8>) -> Self {
@@ -2,6 +2,7 @@ use crate::components::memory::Memory;
 use crate::mbc::mode::MBC;
 use std::time::SystemTime;
 
+#[derive(Clone)]
 pub struct MBC3 {
     rom: Vec<u8>,
     ram: Vec<u8>,
@@ -61,7 +62,28 @@ impl Memory for MBC3 {
     }
 }
 
-impl MBC for MBC3 {}
+impl MBC for MBC3 {
+    fn box_clone(&self) -> Box<dyn MBC> {
+        Box::new(self.clone())
+    }
+
+    fn rom_bank(&self) -> usize {
+        self.rom_bank
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn ram_offset(&self, a: u16) -> Option<usize> {
+        // Banks past 3 select the clock registers.
+        (self.ram_bank <= 0x03).then(|| a as usize + self.ram_bank * 0x2000 - 0xA000)
+    }
+}
 
 impl MBC3 {
     pub fn new(rom: Vec<u8>) -> Self {
@@ -76,6 +98,7 @@ impl MBC3 {
     }
 }
 
+#[derive(Clone)]
 struct RTC {
     s: u8,
     m: u8,
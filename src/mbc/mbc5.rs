@@ -1,6 +1,7 @@
 use crate::components::memory::Memory;
 use crate::mbc::mode::MBC;
 
+#[derive(Clone)]
 pub struct MBC5 {
     rom: Vec<u8>,
     ram: Vec<u8>,
@@ -45,7 +46,27 @@ impl Memory for MBC5 {
     }
 }
 
-impl MBC for MBC5 {}
+impl MBC for MBC5 {
+    fn box_clone(&self) -> Box<dyn MBC> {
+        Box::new(self.clone())
+    }
+
+    fn rom_bank(&self) -> usize {
+        self.rom_bank
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn ram_offset(&self, a: u16) -> Option<usize> {
+        Some(a as usize + self.ram_bank * 0x2000 - 0xA000)
+    }
+}
 
 impl MBC5 {
     pub fn new(rom: Vec<u8>) -> Self {
@@ -130,4 +130,33 @@ impl fmt::Display for MBCMode {
     }
 }
 
-pub trait MBC: Memory + Send {}
+pub trait MBC: Memory + Send {
+    /// A copy of the cartridge as it is now, for savestates.
+    fn box_clone(&self) -> Box<dyn MBC>;
+
+    /// The ROM bank mapped at $4000-$7FFF.
+    fn rom_bank(&self) -> usize {
+        1
+    }
+
+    /// Cartridge RAM, banks laid end to end.
+    fn ram(&self) -> &[u8] {
+        &[]
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+
+    /// Where `a` in $A000-$BFFF lands in `ram()` with the bank mapped now,
+    /// enabled or not; None if there's no RAM there.
+    fn ram_offset(&self, _a: u16) -> Option<usize> {
+        None
+    }
+}
+
+impl Clone for Box<dyn MBC> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
@@ -1,6 +1,7 @@
 use crate::components::memory::Memory;
 use crate::mbc::mode::MBC;
 
+#[derive(Clone)]
 pub struct ROMOnly {
     rom: Vec<u8>,
 }
@@ -9,14 +10,19 @@ impl Memory for ROMOnly {
     fn read(&self, a: u16) -> u8 {
         match a {
             0x0000..=0x7FFF => self.rom[a as usize],
-            _ => panic!("Read to unsupported ROM-only address ({:#06x})!", a),
+            // There is no cartridge RAM to answer.
+            _ => 0xFF,
         }
     }
 
     fn write(&mut self, _a: u16, _v: u8) {}
 }
 
-impl MBC for ROMOnly {}
+impl MBC for ROMOnly {
+    fn box_clone(&self) -> Box<dyn MBC> {
+        Box::new(self.clone())
+    }
+}
 
 impl ROMOnly {
     pub fn new(rom: Vec<u8>) -> Self {
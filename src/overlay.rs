@@ -0,0 +1,217 @@
+/// Glyphs for ' ' to '_', 3x5 pixels, one row per byte with the leftmost
+/// pixel in bit 2. Lowercase letters are drawn as capitals; anything else
+/// as '?'.
+#[rustfmt::skip]
+const FONT: [[u8; 5]; 64] = [
+    [0b000, 0b000, 0b000, 0b000, 0b000], // ' '
+    [0b010, 0b010, 0b010, 0b000, 0b010], // !
+    [0b101, 0b101, 0b000, 0b000, 0b000], // "
+    [0b101, 0b111, 0b101, 0b111, 0b101], // #
+    [0b011, 0b110, 0b010, 0b011, 0b110], // $
+    [0b101, 0b001, 0b010, 0b100, 0b101], // %
+    [0b010, 0b101, 0b010, 0b101, 0b011], // &
+    [0b010, 0b010, 0b000, 0b000, 0b000], // '
+    [0b001, 0b010, 0b010, 0b010, 0b001], // (
+    [0b100, 0b010, 0b010, 0b010, 0b100], // )
+    [0b000, 0b101, 0b010, 0b101, 0b000], // *
+    [0b000, 0b010, 0b111, 0b010, 0b000], // +
+    [0b000, 0b000, 0b000, 0b010, 0b100], // ,
+    [0b000, 0b000, 0b111, 0b000, 0b000], // -
+    [0b000, 0b000, 0b000, 0b000, 0b010], // .
+    [0b001, 0b001, 0b010, 0b100, 0b100], // /
+    [0b111, 0b101, 0b101, 0b101, 0b111], // 0
+    [0b010, 0b110, 0b010, 0b010, 0b111], // 1
+    [0b111, 0b001, 0b111, 0b100, 0b111], // 2
+    [0b111, 0b001, 0b111, 0b001, 0b111], // 3
+    [0b101, 0b101, 0b111, 0b001, 0b001], // 4
+    [0b111, 0b100, 0b111, 0b001, 0b111], // 5
+    [0b111, 0b100, 0b111, 0b101, 0b111], // 6
+    [0b111, 0b001, 0b001, 0b001, 0b001], // 7
+    [0b111, 0b101, 0b111, 0b101, 0b111], // 8
+    [0b111, 0b101, 0b111, 0b001, 0b111], // 9
+    [0b000, 0b010, 0b000, 0b010, 0b000], // :
+    [0b000, 0b010, 0b000, 0b010, 0b100], // ;
+    [0b001, 0b010, 0b100, 0b010, 0b001], // <
+    [0b000, 0b111, 0b000, 0b111, 0b000], // =
+    [0b100, 0b010, 0b001, 0b010, 0b100], // >
+    [0b111, 0b001, 0b011, 0b000, 0b010], // ?
+    [0b010, 0b101, 0b111, 0b100, 0b011], // @
+    [0b010, 0b101, 0b111, 0b101, 0b101], // A
+    [0b110, 0b101, 0b110, 0b101, 0b110], // B
+    [0b011, 0b100, 0b100, 0b100, 0b011], // C
+    [0b110, 0b101, 0b101, 0b101, 0b110], // D
+    [0b111, 0b100, 0b110, 0b100, 0b111], // E
+    [0b111, 0b100, 0b110, 0b100, 0b100], // F
+    [0b011, 0b100, 0b101, 0b101, 0b011], // G
+    [0b101, 0b101, 0b111, 0b101, 0b101], // H
+    [0b111, 0b010, 0b010, 0b010, 0b111], // I
+    [0b001, 0b001, 0b001, 0b101, 0b010], // J
+    [0b101, 0b101, 0b110, 0b101, 0b101], // K
+    [0b100, 0b100, 0b100, 0b100, 0b111], // L
+    [0b101, 0b111, 0b111, 0b101, 0b101], // M
+    [0b110, 0b101, 0b101, 0b101, 0b101], // N
+    [0b010, 0b101, 0b101, 0b101, 0b010], // O
+    [0b110, 0b101, 0b110, 0b100, 0b100], // P
+    [0b010, 0b101, 0b101, 0b110, 0b011], // Q
+    [0b110, 0b101, 0b110, 0b101, 0b101], // R
+    [0b011, 0b100, 0b010, 0b001, 0b110], // S
+    [0b111, 0b010, 0b010, 0b010, 0b010], // T
+    [0b101, 0b101, 0b101, 0b101, 0b111], // U
+    [0b101, 0b101, 0b101, 0b101, 0b010], // V
+    [0b101, 0b101, 0b111, 0b111, 0b101], // W
+    [0b101, 0b101, 0b010, 0b101, 0b101], // X
+    [0b101, 0b101, 0b010, 0b010, 0b010], // Y
+    [0b111, 0b001, 0b010, 0b100, 0b111], // Z
+    [0b011, 0b010, 0b010, 0b010, 0b011], // [
+    [0b100, 0b100, 0b010, 0b001, 0b001], // \
+    [0b110, 0b010, 0b010, 0b010, 0b110], // ]
+    [0b010, 0b101, 0b000, 0b000, 0b000], // ^
+    [0b000, 0b000, 0b000, 0b000, 0b111], // _
+];
+
+/// How far apart characters and lines of text are.
+pub const CHAR_W: i32 = 4;
+pub const LINE_H: i32 = 6;
+
+#[derive(Clone, Debug)]
+enum Shape {
+    Pixel(i32, i32),
+    Line(i32, i32, i32, i32),
+    Rect(i32, i32, i32, i32),
+    Fill(i32, i32, i32, i32),
+    Text(i32, i32, String),
+}
+
+/// Shapes drawn over the next frame sent to the screen, for HUDs. Colours
+/// are `0xRRGGBBAA`, blended with what is underneath.
+#[derive(Clone, Default, Debug)]
+pub struct Overlay {
+    shapes: Vec<(Shape, u32)>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pixel(&mut self, x: i32, y: i32, color: u32) {
+        self.shapes.push((Shape::Pixel(x, y), color));
+    }
+
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: u32) {
+        self.shapes.push((Shape::Line(x0, y0, x1, y1), color));
+    }
+
+    /// The outline of a `w` by `h` box.
+    pub fn rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: u32) {
+        self.shapes.push((Shape::Rect(x, y, w, h), color));
+    }
+
+    pub fn fill(&mut self, x: i32, y: i32, w: i32, h: i32, color: u32) {
+        self.shapes.push((Shape::Fill(x, y, w, h), color));
+    }
+
+    /// `text` in 3x5 capitals from its top-left corner, with `\n` starting
+    /// a new line.
+    pub fn text(&mut self, x: i32, y: i32, text: &str, color: u32) {
+        self.shapes
+            .push((Shape::Text(x, y, text.to_string()), color));
+    }
+
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    /// Draw everything onto an RGBA frame, clipping to its edges.
+    pub fn draw(&self, frame: &mut [u8], width: usize, height: usize) {
+        for (shape, color) in &self.shapes {
+            let mut plot = |x: i32, y: i32| {
+                if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+                    let i = 4 * (y as usize * width + x as usize);
+                    blend(&mut frame[i..i + 4], *color);
+                }
+            };
+            match shape {
+                Shape::Pixel(x, y) => plot(*x, *y),
+                Shape::Line(x0, y0, x1, y1) => line(*x0, *y0, *x1, *y1, plot),
+                Shape::Rect(x, y, w, h) => {
+                    for dx in 0..*w {
+                        plot(x + dx, *y);
+                        if *h > 1 {
+                            plot(x + dx, y + h - 1);
+                        }
+                    }
+                    for dy in 1..h - 1 {
+                        plot(*x, y + dy);
+                        if *w > 1 {
+                            plot(x + w - 1, y + dy);
+                        }
+                    }
+                }
+                Shape::Fill(x, y, w, h) => {
+                    for dy in 0..*h {
+                        for dx in 0..*w {
+                            plot(x + dx, y + dy);
+                        }
+                    }
+                }
+                Shape::Text(x, y, text) => {
+                    for (row, line) in text.split('\n').enumerate() {
+                        let top = y + LINE_H * row as i32;
+                        for (column, c) in line.chars().enumerate() {
+                            let left = x + CHAR_W * column as i32;
+                            for (dy, bits) in glyph(c).iter().enumerate() {
+                                for dx in 0..3 {
+                                    if bits & (0b100 >> dx) != 0 {
+                                        plot(left + dx, top + dy as i32);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn glyph(c: char) -> &'static [u8; 5] {
+    match c.to_ascii_uppercase() {
+        c @ ' '..='_' => &FONT[c as usize - ' ' as usize],
+        _ => &FONT['?' as usize - ' ' as usize],
+    }
+}
+
+/// Bresenham's line from one end to the other, both included.
+fn line(x0: i32, y0: i32, x1: i32, y1: i32, mut plot: impl FnMut(i32, i32)) {
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (sx, sy) = ((x1 - x0).signum(), (y1 - y0).signum());
+    let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+    loop {
+        plot(x, y);
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+fn blend(pixel: &mut [u8], color: u32) {
+    let [r, g, b, a] = color.to_be_bytes();
+    let a = a as u32;
+    for (channel, value) in pixel.iter_mut().zip([r, g, b]) {
+        *channel = ((value as u32 * a + *channel as u32 * (255 - a)) / 255) as u8;
+    }
+}
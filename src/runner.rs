@@ -0,0 +1,227 @@
+use crate::components::mode::GBMode;
+use crate::components::registers::Registers;
+use crate::config::Config;
+use crate::framebuffer::{Frame, FramebufferReader, create_framebuffer_pair};
+use crate::hw::motherboard::Motherboard;
+use crate::mbc::header::{CGBFlag, Header};
+use crate::script::script::Script;
+use serde::Serialize;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// M-cycles run between looks at the clock for the timeout.
+const CLOCK_CHECK: u32 = 0x4000;
+
+/// What ends a run: whichever comes first. With nothing set, a run only
+/// ends on an error.
+#[derive(Clone, Debug, Default)]
+pub struct Until {
+    /// Frames sent to the screen.
+    pub frames: Option<u64>,
+    /// The CPU running `LD B,B`.
+    pub magic_break: bool,
+    /// Any of these turning up in the serial output.
+    pub serial: Vec<String>,
+    /// Any of these turning up in the serial output, as a failure.
+    pub serial_fail: Vec<String>,
+    /// Real time, not emulated.
+    pub timeout: Option<Duration>,
+}
+
+impl Until {
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_none()
+            && !self.magic_break
+            && self.serial.is_empty()
+            && self.serial_fail.is_empty()
+            && self.timeout.is_none()
+    }
+}
+
+/// Why a run ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stop {
+    Frames,
+    MagicBreak,
+    Serial,
+    SerialFail,
+    Timeout,
+}
+
+impl Stop {
+    /// The exit status of a run that ended this way: 0 for what it was
+    /// waiting for, 3 for a failure in the serial output and 4 for a
+    /// timeout. 1 and 2 are left for errors and bad arguments.
+    pub fn status(self) -> i32 {
+        match self {
+            Stop::Frames | Stop::MagicBreak | Stop::Serial => 0,
+            Stop::SerialFail => 3,
+            Stop::Timeout => 4,
+        }
+    }
+}
+
+/// Where a run got to, as written out for CI.
+#[derive(Clone, Debug, Serialize)]
+pub struct Summary {
+    pub title: String,
+    pub stop: Stop,
+    pub status: i32,
+    pub frames: u64,
+    pub cycles: u64,
+    /// Real time spent running.
+    pub seconds: f64,
+    pub magic_break: bool,
+    pub registers: Registers,
+    /// Lossily decoded as UTF-8.
+    pub serial: String,
+}
+
+/// A machine run without a window or sound device, optionally driven by a
+/// script, until a condition in `Until` is met.
+pub struct Runner {
+    mb: Motherboard,
+    fb: FramebufferReader,
+    script: Option<Script>,
+    title: String,
+    cycles: u64,
+    elapsed: Duration,
+}
+
+impl Runner {
+    pub fn new(mb: Motherboard, fb: FramebufferReader, title: String) -> Self {
+        Self {
+            mb,
+            fb,
+            script: None,
+            title,
+            cycles: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Load the ROM at `path` into a machine set up by `config`, booting
+    /// from the boot ROM its paths give for its mode.
+    pub fn load(path: &Path, mut config: Config) -> Result<Self, String> {
+        let rom =
+            std::fs::read(path).map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+        let header = Header::new(rom.clone());
+        if header.cgb_flag == CGBFlag::CGBOnly && config.mode == GBMode::DMG {
+            return Err("can't run a CGB-only game in DMG mode".to_string());
+        }
+        let rom_is_cgb = matches!(
+            header.cgb_flag,
+            CGBFlag::CGBOnly | CGBFlag::BackwardsCompatible
+        );
+
+        let boot_path = match config.mode {
+            GBMode::DMG => &config.dmg_boot_rom,
+            GBMode::CGB => &config.cgb_boot_rom,
+        };
+        let boot = std::fs::read(boot_path)
+            .map_err(|err| format!("can't read boot ROM {}: {}", boot_path, err))?;
+        let mut boot_rom = [0u8; 0x900];
+        let end = boot.len().min(boot_rom.len());
+        boot_rom[..end].copy_from_slice(&boot[..end]);
+
+        config.headless = true;
+        let title = header.title.clone();
+        let (writer, fb) = create_framebuffer_pair();
+        let mb = Motherboard::new(rom, header, config, boot_rom, writer, rom_is_cgb);
+        Ok(Self::new(mb, fb, title))
+    }
+
+    /// Run the script in `path` alongside the machine.
+    pub fn load_script(&mut self, path: &Path) -> Result<(), String> {
+        if let Some(script) = self.script.take() {
+            script.unload(&mut self.mb);
+        }
+        self.script = Some(Script::load(path, &mut self.mb)?);
+        Ok(())
+    }
+
+    pub fn machine(&self) -> &Motherboard {
+        &self.mb
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Motherboard {
+        &mut self.mb
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.fb.frames_seen()
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// The last frame sent to the screen.
+    pub fn frame(&mut self) -> &Frame {
+        self.fb.get_latest()
+    }
+
+    /// Run until `until` is met, or the script fails.
+    pub fn run(&mut self, until: &Until) -> Result<Stop, String> {
+        let start = Instant::now();
+        let result = self.run_for(until, start);
+        self.elapsed += start.elapsed();
+        result
+    }
+
+    fn run_for(&mut self, until: &Until, start: Instant) -> Result<Stop, String> {
+        let mut serial_len = self.mb.serial_output().len();
+        let mut steps = 0u32;
+        // One M-cycle at a time rather than an instruction, so that a CPU
+        // halted for good still lets the frames and the clock be seen.
+        loop {
+            self.mb.step_mcycle();
+            self.cycles += 4;
+            let frames = self.fb.poll();
+            if let Some(script) = &mut self.script {
+                script.step(&mut self.mb)?;
+            }
+
+            if until.magic_break && self.mb.magic_break() {
+                return Ok(Stop::MagicBreak);
+            }
+            if until.frames.is_some_and(|n| frames >= n) {
+                return Ok(Stop::Frames);
+            }
+            let serial = self.mb.serial_output();
+            if serial.len() != serial_len {
+                serial_len = serial.len();
+                let contains =
+                    |s: &String| s.is_empty() || serial.windows(s.len()).any(|w| w == s.as_bytes());
+                if until.serial_fail.iter().any(contains) {
+                    return Ok(Stop::SerialFail);
+                }
+                if until.serial.iter().any(contains) {
+                    return Ok(Stop::Serial);
+                }
+            }
+            steps = steps.wrapping_add(1);
+            if let Some(timeout) = until.timeout
+                && steps.is_multiple_of(CLOCK_CHECK)
+                && start.elapsed() >= timeout
+            {
+                return Ok(Stop::Timeout);
+            }
+        }
+    }
+
+    pub fn summary(&self, stop: Stop) -> Summary {
+        Summary {
+            title: self.title.clone(),
+            stop,
+            status: stop.status(),
+            frames: self.frames(),
+            cycles: self.cycles,
+            seconds: self.elapsed.as_secs_f64(),
+            magic_break: self.mb.magic_break(),
+            registers: self.mb.cpu_regs(),
+            serial: String::from_utf8_lossy(self.mb.serial_output()).into_owned(),
+        }
+    }
+}
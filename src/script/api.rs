@@ -0,0 +1,203 @@
+//! What scripts can call. Numbers are Rhai's 64-bit integers; addresses must
+//! fit in 16 bits.
+//!
+//! - `read(addr)`, `read16(addr)`, `write(addr, v)`, `write16(addr, v)`:
+//!   memory as the debugger sees it, reaching VRAM and OAM at any time and
+//!   leaving ROM alone. Words are little-endian.
+//! - `reg(name)`, `set_reg(name, v)`: `A`-`L`, `AF`-`HL`, `SP` or `PC`.
+//! - `pc()`, `ly()`, `frame()`: frames are counted from when the script
+//!   started.
+//! - `press(button)`, `release(button)`: `A`, `B`, `SELECT`, `START`, `UP`,
+//!   `DOWN`, `LEFT` or `RIGHT`, held until released.
+//! - `save_state()`, `load_state(state)`: states are kept in memory.
+//! - `on_frame(f)`, `on_execute(addr, f)`, `on_read(start, end, f)`,
+//!   `on_write(start, end, f)`, `on_interrupt(f)`: `f` is called with the
+//!   frame number, the address executed, the address and value accessed, or
+//!   the interrupt's vector.
+//! - `pixel(x, y, color)`, `line(x0, y0, x1, y1, color)`,
+//!   `rect(x, y, w, h, color)`, `fill(x, y, w, h, color)`,
+//!   `text(x, y, string, color)`: drawn over the next frame only, in
+//!   `0xRRGGBBAA`.
+
+use crate::components::joypad::JoypadButton;
+use crate::components::prelude::Reg;
+use crate::hw::hooks::HookId;
+use crate::hw::motherboard::{Motherboard, SaveState};
+use crate::script::script::Host;
+use rhai::{Engine, EvalAltResult, FnPtr};
+use std::sync::atomic::Ordering;
+
+type Result<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+fn address(a: i64) -> Result<u16> {
+    u16::try_from(a).map_err(|_| format!("address {} out of range", a).into())
+}
+
+fn reg(name: &str) -> Result<Reg> {
+    Reg::parse(name).ok_or_else(|| format!("no register `{}`", name).into())
+}
+
+fn button(name: &str) -> Result<JoypadButton> {
+    JoypadButton::from_name(&name.to_uppercase())
+        .ok_or_else(|| format!("no button `{}`", name).into())
+}
+
+fn color(c: i64) -> u32 {
+    c as u32
+}
+
+pub(crate) fn register(engine: &mut Engine, host: &Host) {
+    engine.register_type_with_name::<SaveState>("SaveState");
+
+    let h = host.clone();
+    engine.register_fn("read", move |a: i64| -> Result<i64> {
+        let a = address(a)?;
+        h.with(|mb| mb.peek(a) as i64)
+    });
+    let h = host.clone();
+    engine.register_fn("read16", move |a: i64| -> Result<i64> {
+        let a = address(a)?;
+        h.with(|mb| u16::from_le_bytes([mb.peek(a), mb.peek(a.wrapping_add(1))]) as i64)
+    });
+    let h = host.clone();
+    engine.register_fn("write", move |a: i64, v: i64| -> Result<()> {
+        let a = address(a)?;
+        h.with(|mb| mb.poke(a, v as u8))
+    });
+    let h = host.clone();
+    engine.register_fn("write16", move |a: i64, v: i64| -> Result<()> {
+        let a = address(a)?;
+        h.with(|mb| mb.poke_range(a, &(v as u16).to_le_bytes()))
+    });
+
+    let h = host.clone();
+    engine.register_fn("reg", move |name: &str| -> Result<i64> {
+        let r = reg(name)?;
+        h.with(|mb| mb.register(r) as i64)
+    });
+    let h = host.clone();
+    engine.register_fn("set_reg", move |name: &str, v: i64| -> Result<()> {
+        let r = reg(name)?;
+        h.with(|mb| mb.set_register(r, v as u16))
+    });
+    let h = host.clone();
+    engine.register_fn("pc", move || h.with(|mb| mb.pc() as i64));
+    let h = host.clone();
+    engine.register_fn("ly", move || h.with(|mb| mb.ly() as i64));
+    let h = host.clone();
+    engine.register_fn("frame", move || h.frames.load(Ordering::Relaxed) as i64);
+
+    let h = host.clone();
+    engine.register_fn("press", move |name: &str| -> Result<()> {
+        let b = button(name)?;
+        h.with(|mb| mb.joypad_down(b))
+    });
+    let h = host.clone();
+    engine.register_fn("release", move |name: &str| -> Result<()> {
+        let b = button(name)?;
+        h.with(|mb| mb.joypad_up(b))
+    });
+
+    let h = host.clone();
+    engine.register_fn("save_state", move || h.with(|mb| mb.save_state()));
+    let h = host.clone();
+    engine.register_fn("load_state", move |state: SaveState| {
+        h.with(|mb| mb.load_state(&state))
+    });
+
+    register_callbacks(engine, host);
+    register_overlay(engine, host);
+}
+
+/// Register the hook on the machine and remember it, to take it off again.
+fn hook(host: &Host, add: impl FnOnce(&mut Motherboard) -> HookId) -> Result<()> {
+    let id = host.with(add)?;
+    host.hooks.borrow_mut().push(id);
+    Ok(())
+}
+
+fn register_callbacks(engine: &mut Engine, host: &Host) {
+    let h = host.clone();
+    engine.register_fn("on_frame", move |f: FnPtr| {
+        let queue = h.queue(h.callback(f));
+        let frames = h.frames.clone();
+        hook(&h, |mb| {
+            mb.on_frame(move |_, _| queue(vec![frames.load(Ordering::Relaxed) as i64]))
+        })
+    });
+    let h = host.clone();
+    engine.register_fn("on_execute", move |a: i64, f: FnPtr| {
+        let a = address(a)?;
+        let queue = h.queue(h.callback(f));
+        hook(&h, |mb| {
+            mb.on_execute(a, move |_, pc| queue(vec![pc as i64]))
+        })
+    });
+    let h = host.clone();
+    engine.register_fn("on_read", move |start: i64, end: i64, f: FnPtr| {
+        let range = address(start)?..=address(end)?;
+        let queue = h.queue(h.callback(f));
+        hook(&h, |mb| {
+            mb.on_read(range, move |_, access| {
+                queue(vec![access.address as i64, access.value as i64])
+            })
+        })
+    });
+    let h = host.clone();
+    engine.register_fn("on_write", move |start: i64, end: i64, f: FnPtr| {
+        let range = address(start)?..=address(end)?;
+        let queue = h.queue(h.callback(f));
+        hook(&h, |mb| {
+            mb.on_write(range, move |_, access| {
+                queue(vec![access.address as i64, access.value as i64])
+            })
+        })
+    });
+    let h = host.clone();
+    engine.register_fn("on_interrupt", move |f: FnPtr| {
+        let queue = h.queue(h.callback(f));
+        hook(&h, |mb| {
+            mb.on_interrupt(move |_, bit| {
+                queue(vec![0x40 + 8 * bit.bits().trailing_zeros() as i64])
+            })
+        })
+    });
+}
+
+fn register_overlay(engine: &mut Engine, host: &Host) {
+    let h = host.clone();
+    engine.register_fn("pixel", move |x: i64, y: i64, c: i64| {
+        h.with(|mb| mb.overlay_mut().pixel(x as i32, y as i32, color(c)))
+    });
+    let h = host.clone();
+    engine.register_fn("line", move |x0: i64, y0: i64, x1: i64, y1: i64, c: i64| {
+        h.with(|mb| {
+            mb.overlay_mut()
+                .line(x0 as i32, y0 as i32, x1 as i32, y1 as i32, color(c))
+        })
+    });
+    let h = host.clone();
+    engine.register_fn(
+        "rect",
+        move |x: i64, y: i64, width: i64, height: i64, c: i64| {
+            h.with(|mb| {
+                mb.overlay_mut()
+                    .rect(x as i32, y as i32, width as i32, height as i32, color(c))
+            })
+        },
+    );
+    let h = host.clone();
+    engine.register_fn(
+        "fill",
+        move |x: i64, y: i64, width: i64, height: i64, c: i64| {
+            h.with(|mb| {
+                mb.overlay_mut()
+                    .fill(x as i32, y as i32, width as i32, height as i32, color(c))
+            })
+        },
+    );
+    let h = host.clone();
+    engine.register_fn("text", move |x: i64, y: i64, s: &str, c: i64| {
+        h.with(|mb| mb.overlay_mut().text(x as i32, y as i32, s, color(c)))
+    });
+}
@@ -0,0 +1,189 @@
+use crate::config::Config;
+use crate::framebuffer::create_framebuffer_pair;
+use crate::hw::hooks::HookId;
+use crate::hw::motherboard::Motherboard;
+use crate::mbc::header::Header;
+use crate::script::api;
+use rhai::{AST, Dynamic, Engine, EvalAltResult, FnPtr};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A script callback to run, with its arguments, queued by a machine hook.
+pub(crate) struct Call {
+    pub callback: usize,
+    pub args: Vec<i64>,
+}
+
+/// What the functions scripts call share with the script running them.
+#[derive(Clone, Default)]
+pub(crate) struct Host {
+    /// The machine, lent for the length of a call into the script.
+    machine: Rc<RefCell<Option<Box<Motherboard>>>>,
+    pub callbacks: Rc<RefCell<Vec<FnPtr>>>,
+    /// The hooks the script's callbacks are registered with.
+    pub hooks: Rc<RefCell<Vec<HookId>>>,
+    pub pending: Arc<Mutex<Vec<Call>>>,
+    pub frames: Arc<AtomicU64>,
+}
+
+impl Host {
+    /// Run `f` on the machine the script is running against.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Motherboard) -> R) -> Result<R, Box<EvalAltResult>> {
+        let mut machine = self
+            .machine
+            .try_borrow_mut()
+            .map_err(|_| "the machine is already in use")?;
+        let mb = machine.as_mut().ok_or("no machine to run against")?;
+        Ok(f(mb))
+    }
+
+    /// Add a callback, returning the index its calls are queued with.
+    pub fn callback(&self, f: FnPtr) -> usize {
+        let mut callbacks = self.callbacks.borrow_mut();
+        callbacks.push(f);
+        callbacks.len() - 1
+    }
+
+    /// Queue a call of `callback` with `args`, from a machine hook.
+    pub fn queue(&self, callback: usize) -> impl Fn(Vec<i64>) + Send + 'static {
+        let pending = self.pending.clone();
+        move |args| pending.lock().unwrap().push(Call { callback, args })
+    }
+}
+
+/// A Rhai script driving the machine: reading and writing memory and
+/// registers, pressing buttons, saving and loading states and drawing over
+/// the screen, from callbacks run on frames, instructions, memory accesses
+/// and interrupts. See `api` for what scripts can call.
+///
+/// Callbacks run between instructions: after the one that caused them, or
+/// for `on_execute`, before the instruction at the address.
+pub struct Script {
+    path: Option<PathBuf>,
+    engine: Engine,
+    ast: AST,
+    host: Host,
+    /// Counts frames for `frame()`; kept across reloads.
+    frame_hook: HookId,
+    /// Left in the caller's hands while the machine is lent to the script.
+    stand_in: Option<Box<Motherboard>>,
+}
+
+impl Script {
+    /// Run the script in `path` against `mb`.
+    pub fn load(path: &Path, mb: &mut Motherboard) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+        let mut script = Self::new(&source, mb)?;
+        script.path = Some(path.to_path_buf());
+        Ok(script)
+    }
+
+    /// Run `source` against `mb`.
+    pub fn new(source: &str, mb: &mut Motherboard) -> Result<Self, String> {
+        let host = Host::default();
+        let mut engine = Engine::new();
+        api::register(&mut engine, &host);
+        let ast = engine.compile(source).map_err(|err| err.to_string())?;
+
+        let frames = host.frames.clone();
+        let frame_hook = mb.on_frame(move |_, _| {
+            frames.fetch_add(1, Ordering::Relaxed);
+        });
+        let mut script = Self {
+            path: None,
+            engine,
+            ast,
+            host,
+            frame_hook,
+            stand_in: Some(stand_in()),
+        };
+        if let Err(err) = script.lend(mb, |s| s.engine.run_ast(&s.ast)) {
+            script.unload(mb);
+            return Err(err.to_string());
+        }
+        Ok(script)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Read the script's file again and run it in place of the old one, whose
+    /// callbacks are dropped. If it doesn't compile, the old one carries on.
+    pub fn reload(&mut self, mb: &mut Motherboard) -> Result<(), String> {
+        let path = self.path.as_ref().ok_or("the script has no file")?;
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+        let ast = self.engine.compile(source).map_err(|err| err.to_string())?;
+        self.forget(mb);
+        self.ast = ast;
+        self.lend(mb, |s| s.engine.run_ast(&s.ast))
+            .map_err(|err| err.to_string())
+    }
+
+    /// Run the callbacks of what happened since the last call; call after
+    /// every instruction. Stops at the first error.
+    pub fn step(&mut self, mb: &mut Motherboard) -> Result<(), String> {
+        let calls = std::mem::take(&mut *self.host.pending.lock().unwrap());
+        if calls.is_empty() {
+            return Ok(());
+        }
+        self.lend(mb, |s| {
+            for call in calls {
+                let f = s.host.callbacks.borrow()[call.callback].clone();
+                // Whatever the callback gives back is thrown away.
+                let _: Dynamic = f
+                    .call(&s.engine, &s.ast, call.args)
+                    .map_err(|err| err.to_string())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Take the script's hooks off `mb`.
+    pub fn unload(mut self, mb: &mut Motherboard) {
+        self.forget(mb);
+        mb.remove_hook(self.frame_hook);
+    }
+
+    /// Drop the script's callbacks and any calls still queued for them.
+    fn forget(&mut self, mb: &mut Motherboard) {
+        for id in self.host.hooks.borrow_mut().drain(..) {
+            mb.remove_hook(id);
+        }
+        self.host.callbacks.borrow_mut().clear();
+        self.host.pending.lock().unwrap().clear();
+    }
+
+    /// Run `f` with the machine lent to the functions the script calls: it
+    /// is swapped into the host for the length of the call, with the
+    /// stand-in in its place meanwhile.
+    fn lend<R>(&mut self, mb: &mut Motherboard, f: impl FnOnce(&mut Self) -> R) -> R {
+        let mut lent = self.stand_in.take().expect("the machine is already lent");
+        std::mem::swap(mb, &mut lent);
+        *self.host.machine.borrow_mut() = Some(lent);
+        let result = f(self);
+        let mut stand_in = self.host.machine.take().expect("the machine was kept");
+        std::mem::swap(mb, &mut stand_in);
+        self.stand_in = Some(stand_in);
+        result
+    }
+}
+
+/// A blank headless machine, to hold the place of the one lent to a script.
+fn stand_in() -> Box<Motherboard> {
+    let rom = vec![0; 0x8000];
+    let header = Header::new(rom.clone());
+    let config = Config {
+        headless: true,
+        ..Config::default()
+    };
+    let (writer, _) = create_framebuffer_pair();
+    Box::new(Motherboard::new(
+        rom, header, config, [0; 0x900], writer, false,
+    ))
+}
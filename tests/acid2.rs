@@ -0,0 +1,40 @@
+use common::*;
+use std::path::Path;
+use tetsuyu::components::mode::GBMode;
+
+#[macro_use]
+mod common;
+
+/// Run an acid2 test until it signals it's done with `LD B,B`, then compare
+/// the next full frame to its reference image.
+pub fn run_acid2_test(rom: &str, expected: &str, mode: GBMode) {
+    let rom = format!("roms/acid2/{rom}");
+    let png = format!("roms/acid2/{expected}.png");
+    if !Path::new(&rom).exists() || !Path::new(&png).exists() {
+        return;
+    }
+
+    let Some(mut h) = setup_harness(&rom, mode) else {
+        return;
+    };
+    assert_eq!(
+        h.run_until(StopCondition::MagicBreak, 600 * FC),
+        RunOutcome::Met,
+        "acid2 test execution timed out."
+    );
+    let frames = h.frames() + 2;
+    h.run_until(StopCondition::Frames(frames), h.cycles() + 3 * FC);
+    let reference = RefImage::load_png(&png).expect("Failed to load the reference image");
+    assert_image_matches(expected, Some(h.compare(&reference)));
+}
+
+mod acid2 {
+    use super::*;
+
+    test_suite![
+        run_acid2_test,
+        dmg_acid2 => ("dmg-acid2.gb", "dmg-acid2", GBMode::DMG),
+        dmg_acid2_cgb => ("dmg-acid2.gb", "dmg-acid2-cgb", GBMode::CGB),
+        cgb_acid2 => ("cgb-acid2.gbc", "cgb-acid2", GBMode::CGB),
+    ];
+}
@@ -0,0 +1,100 @@
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::debugger::cdl::{Cdl, CdlFlags};
+
+mod common;
+use common::{test_machine, test_rom};
+
+#[rustfmt::skip]
+const PROGRAM: [u8; 25] = [
+    0xFA, 0x00, 0x02, // $0150: LD A,[$0200]
+    0x21, 0x80, 0xFF, // $0153: LD HL,$FF80
+    0x11, 0x10, 0x02, // $0156: LD DE,$0210
+    0x0E, 0x08,       // $0159: LD C,8
+    0x1A,             // $015B: .copy: LD A,[DE]
+    0x22,             // $015C: LD [HL+],A
+    0x13,             // $015D: INC DE
+    0x0D,             // $015E: DEC C
+    0x20, 0xFA,       // $015F: JR NZ,.copy
+    0x3E, 0x03,       // $0161: LD A,$03
+    0xCD, 0x80, 0xFF, // $0163: CALL $FF80
+    0x40,             // $0166: LD B,B
+    0x18, 0xFE,       // $0167: JR @
+];
+
+/// Copied to HRAM: start OAM DMA from $0300 and wait it out.
+#[rustfmt::skip]
+const DMA_ROUTINE: [u8; 8] = [
+    0xE0, 0x46,       // $FF80: LDH [$46],A
+    0x06, 0x28,       // $FF82: LD B,40
+    0x05,             // $FF84: .wait: DEC B
+    0x20, 0xFD,       // $FF85: JR NZ,.wait
+    0xC9,             // $FF87: RET
+];
+
+fn rom() -> Vec<u8> {
+    let mut rom = test_rom(&PROGRAM);
+    rom[0x0210..0x0218].copy_from_slice(&DMA_ROUTINE);
+    rom
+}
+
+fn run(cdl: Cdl) -> Cdl {
+    let mut mb = test_machine(rom(), GBMode::DMG);
+    mb.set_cdl(Some(cdl));
+    while !mb.magic_break() {
+        mb.step();
+    }
+    mb.set_cdl(None).unwrap()
+}
+
+#[test]
+fn marks_code_data_and_graphics() {
+    let cdl = run(Cdl::new(0x8000));
+
+    // The boot ROM's bytes aren't the cartridge's.
+    assert_eq!(cdl.rom(0x0000), CdlFlags::empty());
+    assert_eq!(cdl.rom(0x0004), CdlFlags::CODE);
+    // Opcodes and operands alike.
+    for offset in 0x0150..=0x0166 {
+        assert_eq!(cdl.rom(offset), CdlFlags::CODE, "${:04X}", offset);
+    }
+    assert_eq!(cdl.rom(0x0167), CdlFlags::empty());
+
+    assert_eq!(cdl.rom(0x0200), CdlFlags::DATA);
+    for offset in 0x0210..0x0218 {
+        assert_eq!(cdl.rom(offset), CdlFlags::DATA, "${:04X}", offset);
+    }
+    for offset in 0x0300..0x03A0 {
+        assert_eq!(cdl.rom(offset), CdlFlags::GRAPHICS, "${:04X}", offset);
+    }
+    assert_eq!(cdl.rom(0x03A0), CdlFlags::empty());
+
+    // The routine ran from HRAM.
+    for offset in 0x8000..0x8008 {
+        assert_eq!(cdl.ram(offset), CdlFlags::CODE, "${:04X}", offset);
+    }
+    assert_eq!(cdl.ram(0x8008), CdlFlags::empty());
+}
+
+#[test]
+fn saves_and_merges() {
+    let path = std::env::temp_dir().join(format!("tetsuyu-{}.cdl", std::process::id()));
+    let ram_path = path.with_extension("ram.cdl");
+    let mut old = vec![0u8; 0x11];
+    old[0x10] = CdlFlags::DATA.bits();
+    std::fs::write(&path, old).unwrap();
+
+    let mut cdl = run(Cdl::open(&path, 0x8000).unwrap());
+    // Carried over from the file.
+    assert_eq!(cdl.rom(0x0010), CdlFlags::DATA);
+    cdl.save().unwrap();
+
+    let rom_log = std::fs::read(&path).unwrap();
+    let ram_log = std::fs::read(&ram_path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&ram_path);
+    assert_eq!(rom_log.len(), 0x8000);
+    assert_eq!(rom_log, cdl.rom_log());
+    assert_eq!(rom_log[0x0150], 0x01);
+    assert_eq!(rom_log[0x0300], 0x40);
+    assert_eq!(ram_log[0x8000], 0x01);
+}
@@ -0,0 +1,287 @@
+use serde_json::{Value, json};
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::debugger::dap::{self, Dap};
+use tetsuyu::debugger::sources::SourceFile;
+use tetsuyu::debugger::symbols::Symbols;
+use tetsuyu::hw::motherboard::Motherboard;
+
+mod common;
+use common::{test_machine, test_rom};
+
+/// Calls `Func` over and over, which leaves A + 1 in B.
+#[rustfmt::skip]
+const PROGRAM: [u8; 16] = [
+    0x31, 0xFE, 0xDF, // $0150: LD SP,$DFFE
+    0x3E, 0x01,       // $0153: LD A,1
+    0xCD, 0x5D, 0x01, // $0155: CALL $015D
+    0x18, 0xF9,       // $0158: JR $0153
+    0x00, 0x00, 0x00,
+    0x3C,             // $015D: INC A
+    0x47,             // $015E: LD B,A
+    0xC9,             // $015F: RET
+];
+
+/// What `PROGRAM` was assembled from.
+const SOURCE: &str = "\
+SECTION \"main\", ROM0[$150]
+Main:
+    ld sp, $DFFE
+.loop
+    ld a, 1
+    call Func
+    jr .loop
+    ds 3
+
+Func:
+    inc a
+    ; the result
+    ld b, a
+    ret
+";
+
+const SYMBOLS: &str = "00:0150 Main\n00:0153 Main.loop\n00:015D Func\n";
+
+fn rom() -> Vec<u8> {
+    test_rom(&PROGRAM)
+}
+
+fn machine() -> Motherboard {
+    test_machine(rom(), GBMode::DMG)
+}
+
+/// The editor's end of a session.
+struct Client {
+    stream: TcpStream,
+    input: BufReader<TcpStream>,
+    seq: u64,
+    /// Events that came while waiting for a response.
+    events: Vec<Value>,
+}
+
+impl Client {
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let message = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        });
+        dap::write_message(&mut self.stream, &message).unwrap();
+        loop {
+            let message = dap::read_message(&mut self.input).unwrap().unwrap();
+            if message["type"] == "response" {
+                assert_eq!(message["request_seq"], self.seq);
+                assert_eq!(message["success"], true, "{}", message);
+                return message["body"].clone();
+            }
+            self.events.push(message);
+        }
+    }
+
+    fn event(&mut self, event: &str) -> Value {
+        if let Some(i) = self.events.iter().position(|m| m["event"] == event) {
+            return self.events.remove(i)["body"].clone();
+        }
+        loop {
+            let message = dap::read_message(&mut self.input).unwrap().unwrap();
+            if message["event"] == event {
+                return message["body"].clone();
+            }
+        }
+    }
+
+    fn variable(&mut self, reference: u64, name: &str) -> String {
+        let body = self.request("variables", json!({ "variablesReference": reference }));
+        let variables = body["variables"].as_array().unwrap();
+        let variable = variables.iter().find(|v| v["name"] == name).unwrap();
+        variable["value"].as_str().unwrap().to_string()
+    }
+
+    fn pc(&mut self) -> Value {
+        let body = self.request("stackTrace", json!({ "threadId": 1 }));
+        body["stackFrames"][0]["instructionPointerReference"].clone()
+    }
+}
+
+/// A server on a free port, with a client connected to it. The server
+/// thread gives back what `Dap::run` returned.
+fn session() -> (Client, thread::JoinHandle<bool>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut mb = machine();
+        let messages = dap::spawn_reader(stream.try_clone().unwrap());
+        let mut dap = Dap::new(messages, stream);
+        dap.symbols = Symbols::parse(SYMBOLS);
+        dap.rom = rom();
+        dap.run(&mut mb, |_, _| true)
+    });
+    let stream = TcpStream::connect(address).unwrap();
+    let input = BufReader::new(stream.try_clone().unwrap());
+    let client = Client {
+        stream,
+        input,
+        seq: 0,
+        events: Vec::new(),
+    };
+    (client, server)
+}
+
+#[test]
+fn source_breakpoints_stack_and_stepping() {
+    let dir = std::env::temp_dir().join(format!("tetsuyu-dap-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.asm");
+    std::fs::write(&path, SOURCE).unwrap();
+
+    let (mut client, server) = session();
+    let body = client.request("initialize", json!({ "adapterID": "tetsuyu" }));
+    assert_eq!(body["supportsReadMemoryRequest"], true);
+    assert_eq!(body["exceptionBreakpointFilters"][0]["filter"], "vblank");
+    client.event("initialized");
+    client.request("launch", json!({ "sourceDirs": [dir] }));
+
+    // The comment moves the breakpoint down to `ld b, a`.
+    let body = client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": path }, "breakpoints": [{ "line": 12 }, { "line": 8 }] }),
+    );
+    let breakpoints = body["breakpoints"].as_array().unwrap();
+    assert_eq!(breakpoints[0]["verified"], true);
+    assert_eq!(breakpoints[0]["line"], 13);
+    assert_eq!(breakpoints[0]["instructionReference"], "0x015E");
+    // `ds` can't be counted past; the next label stands in.
+    assert_eq!(breakpoints[1]["line"], 10);
+    assert_eq!(breakpoints[1]["instructionReference"], "0x015D");
+    let id = breakpoints[0]["id"].clone();
+
+    client.request("configurationDone", json!({}));
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["reason"], "breakpoint");
+    client.request("continue", json!({ "threadId": 1 }));
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["hitBreakpointIds"][0], id);
+
+    let body = client.request("stackTrace", json!({ "threadId": 1 }));
+    let frames = body["stackFrames"].as_array().unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0]["name"], "Func+1");
+    assert_eq!(frames[0]["line"], 13);
+    assert_eq!(frames[0]["source"]["path"], path.to_str().unwrap());
+    assert_eq!(frames[1]["instructionPointerReference"], "0x0158");
+    assert_eq!(frames[1]["line"], 7);
+
+    let body = client.request("scopes", json!({ "frameId": 0 }));
+    assert_eq!(body["scopes"][0]["name"], "Registers");
+    let registers = body["scopes"][0]["variablesReference"].as_u64().unwrap();
+    let io = body["scopes"][1]["variablesReference"].as_u64().unwrap();
+    assert_eq!(client.variable(registers, "A"), "$02");
+    assert_eq!(client.variable(registers, "SP"), "$DFFC");
+
+    client.request(
+        "setVariable",
+        json!({ "variablesReference": registers, "name": "A", "value": "$41" }),
+    );
+    client.request(
+        "setVariable",
+        json!({ "variablesReference": io, "name": "BGP", "value": "$E4" }),
+    );
+    assert_eq!(client.variable(io, "BGP"), "$E4");
+    let body = client.request("evaluate", json!({ "expression": "a + 1" }));
+    assert_eq!(body["result"], "66 ($42)");
+
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    assert_eq!(client.pc(), "0x015F");
+    assert_eq!(client.variable(registers, "B"), "$41");
+    client.request("stepOut", json!({ "threadId": 1 }));
+    client.event("stopped");
+    assert_eq!(client.pc(), "0x0158");
+
+    let body = client.request(
+        "readMemory",
+        json!({ "memoryReference": "0x0150", "offset": 3, "count": 5 }),
+    );
+    assert_eq!(body["address"], "0x0153");
+    assert_eq!(body["data"], "PgHNXQE=");
+
+    let body = client.request(
+        "disassemble",
+        json!({ "memoryReference": "0x0155", "instructionOffset": -1, "instructionCount": 3 }),
+    );
+    let instructions = body["instructions"].as_array().unwrap();
+    assert_eq!(instructions[0]["address"], "0x0153");
+    assert_eq!(instructions[0]["symbol"], "Main.loop");
+    assert_eq!(instructions[1]["instructionBytes"], "CD 5D 01");
+    assert_eq!(instructions[2]["line"], 7);
+
+    // Clearing the file's breakpoints lets it run until paused.
+    client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": path }, "breakpoints": [] }),
+    );
+    client.request("continue", json!({ "threadId": 1 }));
+    client.request("pause", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "pause");
+
+    client.request("disconnect", json!({}));
+    client.event("terminated");
+    assert!(server.join().unwrap());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn attach_function_breakpoints_and_entry() {
+    let (mut client, server) = session();
+    client.request("initialize", json!({ "linesStartAt1": false }));
+    client.request("attach", json!({ "stopOnEntry": true }));
+    let body = client.request(
+        "setFunctionBreakpoints",
+        json!({ "breakpoints": [{ "name": "Func", "condition": "a == 1" }, { "name": "$0158" }, { "name": "Nowhere" }] }),
+    );
+    let breakpoints = body["breakpoints"].as_array().unwrap();
+    assert_eq!(breakpoints[0]["instructionReference"], "0x015D");
+    assert_eq!(breakpoints[1]["instructionReference"], "0x0158");
+    assert_eq!(breakpoints[2]["verified"], false);
+
+    client.request("configurationDone", json!({}));
+    assert_eq!(client.event("stopped")["reason"], "entry");
+    client.request("continue", json!({ "threadId": 1 }));
+    client.event("stopped");
+    assert_eq!(client.pc(), "0x015D");
+
+    // Replacing them clears the old ones.
+    client.request("setFunctionBreakpoints", json!({ "breakpoints": [] }));
+    client.request(
+        "setInstructionBreakpoints",
+        json!({ "breakpoints": [{ "instructionReference": "0x0150", "offset": 5 }] }),
+    );
+    client.request("continue", json!({ "threadId": 1 }));
+    client.event("stopped");
+    assert_eq!(client.pc(), "0x0155");
+
+    // Detaching leaves the emulator running.
+    client.request("disconnect", json!({}));
+    assert!(!server.join().unwrap());
+}
+
+#[test]
+fn source_labels() {
+    let file = SourceFile::parse(SOURCE);
+    assert_eq!(file.len(), 14);
+    assert_eq!(file.label(1), Some("Main"));
+    assert_eq!(file.label(3), Some("Main.loop"));
+    assert_eq!(file.label(9), Some("Func"));
+    assert_eq!(file.label(0), None);
+
+    let symbols = Symbols::parse(SYMBOLS);
+    assert_eq!(file.resolve(4, &symbols, &rom()), Some((4, 0, 0x0153)));
+    assert_eq!(file.resolve(5, &symbols, &rom()), Some((5, 0, 0x0155)));
+    assert_eq!(file.locate(0, 0x0158, &symbols, &rom()), Some(6));
+    assert_eq!(file.locate(0, 0x015F, &symbols, &rom()), Some(13));
+}
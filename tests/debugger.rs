@@ -0,0 +1,258 @@
+use std::sync::mpsc::channel;
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::debugger::debugger::{Debugger, StopReason};
+use tetsuyu::debugger::expr::Expr;
+use tetsuyu::debugger::repl::Repl;
+use tetsuyu::hw::bus::{AccessKind, BusAccess};
+use tetsuyu::hw::interrupt::Interrupts;
+use tetsuyu::hw::motherboard::Motherboard;
+
+mod common;
+use common::{test_machine, test_rom};
+
+fn rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = test_rom(program);
+    rom[0x0050] = 0xD9; // RETI
+    rom
+}
+
+fn machine(rom: Vec<u8>) -> Motherboard {
+    test_machine(rom, GBMode::DMG)
+}
+
+/// Calls a countdown loop, then stores $42 to $C000.
+#[rustfmt::skip]
+const PROGRAM: [u8; 22] = [
+    0x31, 0xFE, 0xDF, // $0150: LD SP,$DFFE
+    0xCD, 0x60, 0x01, // $0153: CALL $0160
+    0x3E, 0x42,       // $0156: LD A,$42
+    0xEA, 0x00, 0xC0, // $0158: LD ($C000),A
+    0x40,             // $015B: LD B,B
+    0x18, 0xFE,       // $015C: JR @
+    0x00, 0x00,
+    0x06, 0x05,       // $0160: LD B,5
+    0x05,             // $0162: .loop: DEC B
+    0x20, 0xFD,       // $0163: JR NZ,.loop
+    0xC9,             // $0165: RET
+];
+
+/// Turns the LCD on and takes timer interrupts, handled by a RETI at $0050.
+#[rustfmt::skip]
+const TIMER: [u8; 13] = [
+    0x3E, 0x91,       // LD A,$91
+    0xE0, 0x40,       // LDH (LCDC),A
+    0x3E, 0x04,       // LD A,$04
+    0xE0, 0xFF,       // LDH (IE),A
+    0x3E, 0x05,       // LD A,$05
+    0xE0, 0x07,       // LDH (TAC),A
+    0xFB,             // EI
+];
+
+const MAX: u64 = 1_000_000;
+
+#[test]
+fn breakpoint_stops_before_instruction() {
+    let mut mb = machine(rom(&PROGRAM));
+    let mut debugger = Debugger::new();
+    let id = debugger.add_breakpoint(0x0158, None, None);
+
+    assert_eq!(debugger.run(&mut mb, MAX), StopReason::Breakpoint(id));
+    assert_eq!(mb.pc(), 0x0158);
+    assert_eq!(mb.cpu_regs().a, 0x42);
+    assert_ne!(mb.read_bus(0xC000), 0x42);
+
+    // Carrying on doesn't hit it again.
+    debugger.add_breakpoint(0x015C, None, None);
+    assert_ne!(debugger.run(&mut mb, MAX), StopReason::Breakpoint(id));
+}
+
+#[test]
+fn conditional_breakpoint() {
+    let mut mb = machine(rom(&PROGRAM));
+    let mut debugger = Debugger::new();
+    let id = debugger.add_breakpoint(0x0162, None, Some(Expr::parse("b == 2").unwrap()));
+
+    assert_eq!(debugger.run(&mut mb, MAX), StopReason::Breakpoint(id));
+    assert_eq!(mb.cpu_regs().b, 2);
+}
+
+#[test]
+fn bank_breakpoint() {
+    let mut rom = vec![0u8; 0x10000];
+    rom[0x0004..0x0007].copy_from_slice(&[0xC3, 0x50, 0x01]); // JP $0150
+    rom[0x0147] = 0x01; // MBC1
+    rom[0x0148] = 0x01; // 4 banks
+    rom[0x014B] = 0x01;
+    rom[0x0150..0x0158].copy_from_slice(&[0x3E, 0x02, 0xEA, 0x00, 0x20, 0xC3, 0x00, 0x40]);
+    rom[0x4000..0x4002].copy_from_slice(&[0x18, 0xFE]);
+    rom[0x8000..0x8003].copy_from_slice(&[0x40, 0x18, 0xFE]);
+
+    let mut mb = machine(rom);
+    let mut debugger = Debugger::new();
+    debugger.add_breakpoint(0x4000, Some(1), None);
+    let id = debugger.add_breakpoint(0x4000, Some(2), None);
+
+    assert_eq!(debugger.run(&mut mb, MAX), StopReason::Breakpoint(id));
+    assert_eq!(mb.rom_bank(), 2);
+}
+
+#[test]
+fn write_watchpoint() {
+    let mut mb = machine(rom(&PROGRAM));
+    let mut debugger = Debugger::new();
+    debugger.add_watchpoint(0xC000, 0xC000, true, false, None);
+    let id = debugger.add_watchpoint(0xC000, 0xC0FF, false, true, None);
+
+    let access = BusAccess {
+        address: 0xC000,
+        value: 0x42,
+        kind: AccessKind::Write,
+    };
+    assert_eq!(
+        debugger.run(&mut mb, MAX),
+        StopReason::Watchpoint(id, access)
+    );
+    assert_eq!(mb.pc(), 0x015B);
+}
+
+#[test]
+fn conditional_watchpoint_sees_value() {
+    let mut mb = machine(rom(&PROGRAM));
+    let mut debugger = Debugger::new();
+    debugger.add_watchpoint(
+        0xC000,
+        0xC000,
+        false,
+        true,
+        Some(Expr::parse("value == 1").unwrap()),
+    );
+    debugger.add_breakpoint(0x015B, None, None);
+
+    assert!(matches!(
+        debugger.run(&mut mb, MAX),
+        StopReason::Breakpoint(_)
+    ));
+}
+
+#[test]
+fn interrupt_break() {
+    let mut mb = machine(rom(&TIMER));
+    let mut debugger = Debugger::new();
+    debugger.break_on_interrupts(Interrupts::TIMER, true);
+
+    assert_eq!(
+        debugger.run(&mut mb, MAX),
+        StopReason::Interrupt(Interrupts::TIMER)
+    );
+    assert_eq!(mb.pc(), 0x0050);
+}
+
+#[test]
+fn run_to_scanline() {
+    let mut mb = machine(rom(&TIMER));
+    let mut debugger = Debugger::new();
+    debugger.run_to_scanline(&mb, 100);
+
+    assert_eq!(debugger.run(&mut mb, MAX), StopReason::Scanline(100));
+    assert_eq!(mb.ly(), 100);
+}
+
+#[test]
+fn step_over_and_out() {
+    let mut mb = machine(rom(&PROGRAM));
+    let mut debugger = Debugger::new();
+    debugger.add_breakpoint(0x0153, None, None);
+    debugger.run(&mut mb, MAX);
+
+    debugger.step_over(&mut mb);
+    assert_eq!(debugger.run(&mut mb, MAX), StopReason::Step);
+    assert_eq!(mb.pc(), 0x0156);
+    assert_eq!(mb.cpu_regs().b, 0);
+
+    let mut mb = machine(rom(&PROGRAM));
+    let mut debugger = Debugger::new();
+    let id = debugger.add_breakpoint(0x0162, None, None);
+    debugger.run(&mut mb, MAX);
+    debugger.remove(id);
+
+    debugger.step();
+    assert_eq!(debugger.run(&mut mb, MAX), StopReason::Step);
+    assert_eq!(mb.pc(), 0x0163);
+
+    debugger.step_out(&mut mb);
+    assert_eq!(debugger.run(&mut mb, MAX), StopReason::Step);
+    assert_eq!(mb.pc(), 0x0156);
+    assert_eq!(mb.cpu_regs().sp, 0xDFFE);
+}
+
+#[test]
+fn modify_registers_and_memory() {
+    let mut mb = machine(rom(&PROGRAM));
+    let mut debugger = Debugger::new();
+    debugger.add_breakpoint(0x0158, None, None);
+    debugger.run(&mut mb, MAX);
+
+    let mut regs = mb.cpu_regs();
+    regs.a = 0x99;
+    mb.set_cpu_regs(regs);
+    assert_eq!(mb.pc(), 0x0158);
+
+    mb.write_bus(0xC001, 0x12);
+    while !mb.magic_break() {
+        mb.step();
+    }
+    assert_eq!(mb.read_bus(0xC000), 0x99);
+    assert_eq!(mb.read_bus(0xC001), 0x12);
+
+    mb.jump(0x0156);
+    assert_eq!(mb.pc(), 0x0156);
+    mb.step();
+    assert_eq!(mb.cpu_regs().a, 0x42);
+}
+
+#[test]
+fn expressions() {
+    let mut mb = machine(rom(&PROGRAM));
+    while !mb.magic_break() {
+        mb.step();
+    }
+
+    let mut eval = |text: &str| Expr::parse(text).unwrap().eval(&mut mb);
+    assert_eq!(eval("a == $42 && [$C000] + 1 == 0x43"), Ok(1));
+    assert_eq!(eval("1 + 2 & 3 == 3"), Ok(1));
+    assert_eq!(eval("!(sp == 57342) || %101 == 4"), Ok(0));
+    assert_eq!(eval("{$C000} & $FF"), Ok(0x42));
+    assert_eq!(eval("pc"), Ok(0x015B));
+    assert_eq!(eval("LY"), Ok(0xFF44));
+    assert!(eval("nope").is_err());
+    assert!(Expr::parse("[1 + 2").is_err());
+    assert!(Expr::parse("1 +").is_err());
+}
+
+#[test]
+fn repl_commands() {
+    let mut mb = machine(rom(&PROGRAM));
+    let (commands, input) = channel();
+    let mut repl = Repl::new(input, Vec::new());
+    for line in [
+        "b 0158",
+        "c",
+        "p a",
+        "set a 7",
+        "",
+        "poke c000 $10",
+        "x $C000 2",
+        "q",
+    ] {
+        commands.send(line.to_string()).unwrap();
+    }
+    repl.run(&mut mb, |_, _| true);
+
+    let output = String::from_utf8(repl.output().clone()).unwrap();
+    assert!(output.contains("Breakpoint 1 at $0158"), "{}", output);
+    assert!(output.contains("PC=$0158 AF=$42"), "{}", output);
+    assert!(output.contains("66 ($42)"), "{}", output);
+    assert!(output.contains("AF=$07"), "{}", output);
+    assert!(output.contains("$C000: 10 00"), "{}", output);
+    assert_eq!(mb.cpu_regs().a, 7);
+}
@@ -0,0 +1,92 @@
+use tetsuyu::debugger::symbols::Symbols;
+use tetsuyu::disasm::{disassemble, line, listing, rom_reader};
+
+fn text(address: u16, bytes: &[u8]) -> String {
+    disassemble(address, |a| {
+        bytes
+            .get(a.wrapping_sub(address) as usize)
+            .copied()
+            .unwrap_or(0)
+    })
+    .text
+}
+
+#[test]
+fn rgbds_syntax() {
+    let cases: [(&[u8], &str); 24] = [
+        (&[0x00], "nop"),
+        (&[0x08, 0x34, 0x12], "ld [$1234], sp"),
+        (&[0x10, 0x00], "stop"),
+        (&[0x18, 0xFE], "jr $0150"),
+        (&[0x38, 0x02], "jr c, $0154"),
+        (&[0x21, 0x00, 0xC0], "ld hl, $C000"),
+        (&[0x22], "ld [hl+], a"),
+        (&[0x3A], "ld a, [hl-]"),
+        (&[0x34], "inc [hl]"),
+        (&[0x36, 0x7F], "ld [hl], $7F"),
+        (&[0x76], "halt"),
+        (&[0x7E], "ld a, [hl]"),
+        (&[0xAF], "xor a, a"),
+        (&[0xE0, 0x40], "ldh [$FF40], a"),
+        (&[0xF2], "ldh a, [c]"),
+        (&[0xE8, 0xFE], "add sp, -2"),
+        (&[0xF8, 0x05], "ld hl, sp+5"),
+        (&[0xF1], "pop af"),
+        (&[0xE9], "jp hl"),
+        (&[0xCA, 0x00, 0x40], "jp z, $4000"),
+        (&[0xCD, 0x00, 0x20], "call $2000"),
+        (&[0xFF], "rst $38"),
+        (&[0xCB, 0x37], "swap a"),
+        (&[0xDD], "db $DD"),
+    ];
+    for (bytes, expected) in cases {
+        assert_eq!(text(0x0150, bytes), expected, "{:02X?}", bytes);
+    }
+    assert_eq!(text(0x0150, &[0xCB, 0xBE]), "res 7, [hl]");
+    assert_eq!(text(0x0150, &[0xCB, 0x46]), "bit 0, [hl]");
+}
+
+#[test]
+fn sizes_and_cycles() {
+    let call = disassemble(0x0150, |a| [0xC4, 0x00, 0x20][a as usize - 0x0150]);
+    assert_eq!(call.size(), 3);
+    assert_eq!(call.bytes, [0xC4, 0x00, 0x20]);
+    assert_eq!((call.cycles, call.branch_cycles), (12, Some(24)));
+    assert_eq!(call.target, Some(0x2000));
+
+    let bit = disassemble(0, |a| [0xCB, 0x46, 0x00][a as usize]);
+    assert_eq!((bit.size(), bit.cycles, bit.branch_cycles), (2, 12, None));
+
+    let ret = disassemble(0, |_| 0xC9);
+    assert_eq!((ret.size(), ret.cycles), (1, 16));
+}
+
+#[test]
+fn every_opcode_decodes() {
+    for op in 0..=0xFFu8 {
+        let instruction = disassemble(0, |a| if a == 0 { op } else { 0x00 });
+        assert!(!instruction.text.is_empty());
+        assert!((1..=3).contains(&instruction.size()));
+        assert!(instruction.cycles >= 4 && instruction.cycles % 4 == 0);
+    }
+}
+
+#[test]
+fn banked_listing() {
+    let mut rom = vec![0u8; 0x10000];
+    rom[0x0150] = 0xC9;
+    rom[0x4000] = 0x3C;
+    rom[0x8000..0x8003].copy_from_slice(&[0xC3, 0x00, 0x40]);
+
+    let read = rom_reader(&rom, 2);
+    assert_eq!(read(0x0150), 0xC9);
+    assert_eq!(read(0x4000), 0xC3);
+    assert_eq!(read(0x8000), 0xFF);
+
+    let lines = listing(0x4000, 0x4004, 2, &Symbols::new(), rom_reader(&rom, 2));
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("02:4000  C3 00 40  jp $4000"));
+
+    let instruction = disassemble(0x0150, rom_reader(&rom, 1));
+    assert!(line(0, 0x0150, &instruction).starts_with("00:0150  C9"));
+}
@@ -0,0 +1,224 @@
+use common::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tetsuyu::components::mode::GBMode;
+
+#[macro_use]
+mod common;
+
+/// Frames a Gambatte test runs for once the boot ROM is done.
+const TEST_FRAMES: u64 = 15;
+
+/// What a Gambatte test should leave on the screen for a model.
+enum Expected {
+    /// Hex digits at the top left, from `_out<digits>` in the ROM's name.
+    Out(String),
+    /// The whole screen, from `<rom>_<model>.png` next to the ROM.
+    Image(PathBuf),
+}
+
+fn model_tag(mode: GBMode) -> &'static str {
+    match mode {
+        GBMode::DMG => "dmg08",
+        GBMode::CGB => "cgb04c",
+    }
+}
+
+/// What `rom` should show on `mode`, if it says. Names list the models a
+/// result is for before it, as in `_dmg08_cgb04c_out0` or
+/// `_dmg08_out2_cgb04c_out3`; audio results aren't checked.
+fn expected(rom: &Path, mode: GBMode) -> Option<Expected> {
+    let stem = rom.file_stem()?.to_str()?;
+    let tag = model_tag(mode);
+    let png = rom.with_file_name(format!("{stem}_{tag}.png"));
+    if png.exists() {
+        return Some(Expected::Image(png));
+    }
+
+    let parts: Vec<&str> = stem.split('_').collect();
+    let at = parts.iter().position(|part| *part == tag)?;
+    let out = parts[at + 1..]
+        .iter()
+        .find(|part| !matches!(**part, "dmg08" | "cgb04c"))?
+        .strip_prefix("out")?;
+    if out.is_empty() || !out.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(Expected::Out(out.to_ascii_uppercase()))
+}
+
+/// Whether the screen shows `out` from its top-left corner. The tests draw
+/// digit n with tile n, and a pixel of the digit must be black where the
+/// tile has one set and not black elsewhere.
+fn shows(h: &mut Harness, out: &str) -> bool {
+    let frame = h.framebuffer().to_vec();
+    out.chars().enumerate().all(|(i, c)| {
+        let tile = 0x8000 + 16 * c.to_digit(16).unwrap() as u16;
+        (0..8).all(|y| {
+            let row = h.peek(tile + 2 * y) | h.peek(tile + 2 * y + 1);
+            (0..8).all(|x| {
+                let at = 4 * (y as usize * 160 + 8 * i + x);
+                let black = frame[at..at + 3] == [0, 0, 0];
+                black == (row & (0x80 >> x) != 0)
+            })
+        })
+    })
+}
+
+/// The ROMs under `dir`, and under the directories in it.
+fn roms(dir: &Path, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        if path.is_dir() {
+            roms(&path, found);
+        } else if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("gb" | "gbc")
+        ) {
+            found.push(path);
+        }
+    }
+}
+
+/// Run every test in one of the corpus's directories that says what it
+/// should show on `mode`, failing with the ones that don't show it.
+pub fn run_gambatte_tests(dir: &str, mode: GBMode) {
+    let mut found = Vec::new();
+    roms(&Path::new("roms/gambatte").join(dir), &mut found);
+    found.sort();
+
+    let mut failures = Vec::new();
+    for rom in found {
+        let Some(expected) = expected(&rom, mode) else {
+            continue;
+        };
+        let Some(mut h) = setup_harness(rom.to_str().unwrap(), mode) else {
+            return;
+        };
+        let name = rom.file_stem().unwrap().to_string_lossy().into_owned();
+
+        // The corpus is timed from the end of the boot ROM.
+        if h.run_until(StopCondition::Pc(0x0100), 600 * FC) == RunOutcome::TimedOut {
+            failures.push(format!("{name}: the boot ROM didn't finish"));
+            continue;
+        }
+        let frames = h.frames() + TEST_FRAMES;
+        h.run_until(
+            StopCondition::Frames(frames),
+            h.cycles() + (TEST_FRAMES + 5) * FC,
+        );
+
+        match expected {
+            Expected::Out(out) => {
+                if !shows(&mut h, &out) {
+                    failures.push(format!("{name}: expected {out}"));
+                }
+            }
+            Expected::Image(png) => {
+                let result = RefImage::load_png(&png).and_then(|reference| h.compare(&reference));
+                match result {
+                    Ok(report) if report.first_diff.is_none() => {}
+                    Ok(report) => {
+                        let heatmap = std::env::temp_dir()
+                            .join(format!("{name}-{}-diff.png", model_tag(mode)));
+                        let _ = report.write_heatmap(&heatmap);
+                        failures.push(format!(
+                            "{name}: first diff at {}, heatmap in {}",
+                            report.format_diff(),
+                            heatmap.display()
+                        ));
+                    }
+                    Err(e) => failures.push(format!("{name}: {e}")),
+                }
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} Gambatte tests failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+mod dmg {
+    use super::*;
+
+    test_suite![
+        run_gambatte_tests,
+        bgen => ("bgen", GBMode::DMG),
+        bgtiledata => ("bgtiledata", GBMode::DMG),
+        bgtilemap => ("bgtilemap", GBMode::DMG),
+        display_startstate => ("display_startstate", GBMode::DMG),
+        div => ("div", GBMode::DMG),
+        dma => ("dma", GBMode::DMG),
+        dmgpalette_during_m3 => ("dmgpalette_during_m3", GBMode::DMG),
+        enable_display => ("enable_display", GBMode::DMG),
+        halt => ("halt", GBMode::DMG),
+        irq_precedence => ("irq_precedence", GBMode::DMG),
+        lcd_offset => ("lcd_offset", GBMode::DMG),
+        lcdirq_precedence => ("lcdirq_precedence", GBMode::DMG),
+        ly0 => ("ly0", GBMode::DMG),
+        lycenable => ("lycenable", GBMode::DMG),
+        lycint_ly => ("lycint_ly", GBMode::DMG),
+        lycm2int => ("lycm2int", GBMode::DMG),
+        lywrite => ("lywrite", GBMode::DMG),
+        m0enable => ("m0enable", GBMode::DMG),
+        m1 => ("m1", GBMode::DMG),
+        m2enable => ("m2enable", GBMode::DMG),
+        miscmstatirq => ("miscmstatirq", GBMode::DMG),
+        oam_access => ("oam_access", GBMode::DMG),
+        oamdma => ("oamdma", GBMode::DMG),
+        scx_during_m3 => ("scx_during_m3", GBMode::DMG),
+        scy => ("scy", GBMode::DMG),
+        serial => ("serial", GBMode::DMG),
+        sprites => ("sprites", GBMode::DMG),
+        tima => ("tima", GBMode::DMG),
+        undef_ops => ("undef_ops", GBMode::DMG),
+        vram_m3 => ("vram_m3", GBMode::DMG),
+        window => ("window", GBMode::DMG),
+    ];
+}
+
+mod cgb {
+    use super::*;
+
+    test_suite![
+        run_gambatte_tests,
+        bgen => ("bgen", GBMode::CGB),
+        bgtiledata => ("bgtiledata", GBMode::CGB),
+        bgtilemap => ("bgtilemap", GBMode::CGB),
+        cgbpal_m3 => ("cgbpal_m3", GBMode::CGB),
+        display_startstate => ("display_startstate", GBMode::CGB),
+        div => ("div", GBMode::CGB),
+        dma => ("dma", GBMode::CGB),
+        enable_display => ("enable_display", GBMode::CGB),
+        halt => ("halt", GBMode::CGB),
+        irq_precedence => ("irq_precedence", GBMode::CGB),
+        lcd_offset => ("lcd_offset", GBMode::CGB),
+        lcdirq_precedence => ("lcdirq_precedence", GBMode::CGB),
+        ly0 => ("ly0", GBMode::CGB),
+        lycenable => ("lycenable", GBMode::CGB),
+        lycint_ly => ("lycint_ly", GBMode::CGB),
+        lycm2int => ("lycm2int", GBMode::CGB),
+        lywrite => ("lywrite", GBMode::CGB),
+        m0enable => ("m0enable", GBMode::CGB),
+        m1 => ("m1", GBMode::CGB),
+        m2enable => ("m2enable", GBMode::CGB),
+        miscmstatirq => ("miscmstatirq", GBMode::CGB),
+        oam_access => ("oam_access", GBMode::CGB),
+        oamdma => ("oamdma", GBMode::CGB),
+        scx_during_m3 => ("scx_during_m3", GBMode::CGB),
+        scy => ("scy", GBMode::CGB),
+        serial => ("serial", GBMode::CGB),
+        speedchange => ("speedchange", GBMode::CGB),
+        sprites => ("sprites", GBMode::CGB),
+        tima => ("tima", GBMode::CGB),
+        undef_ops => ("undef_ops", GBMode::CGB),
+        vram_m3 => ("vram_m3", GBMode::CGB),
+        window => ("window", GBMode::CGB),
+    ];
+}
@@ -0,0 +1,132 @@
+use std::sync::{Arc, Mutex};
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::hw::bus::{AccessKind, BusAccess};
+use tetsuyu::hw::interrupt::Interrupts;
+use tetsuyu::hw::motherboard::Motherboard;
+
+mod common;
+use common::{run_to_program, test_machine, test_rom};
+
+/// Takes the VBlank interrupt, and copies $42 to $C000 and $C001 to A over
+/// and over.
+#[rustfmt::skip]
+const PROGRAM: [u8; 18] = [
+    0x31, 0xFE, 0xDF,   // $0150: LD SP,$DFFE
+    0x3E, 0x01,         // $0153: LD A,$01
+    0xE0, 0xFF,         // $0155: LDH (IE),A
+    0xFB,               // $0157: EI
+    0x3E, 0x42,         // $0158: LD A,$42
+    0xEA, 0x00, 0xC0,   // $015A: LD ($C000),A
+    0xFA, 0x01, 0xC0,   // $015D: LD A,($C001)
+    0x18, 0xF6,         // $0160: JR $0158
+];
+
+/// A machine past the boot ROM with the LCD on, about to run `PROGRAM`.
+fn machine() -> Motherboard {
+    let mut rom = test_rom(&PROGRAM);
+    rom[0x0040] = 0xD9; // RETI
+    let mut mb = test_machine(rom, GBMode::DMG);
+    run_to_program(&mut mb);
+    mb.write_bus(0xFF40, 0x91);
+    mb
+}
+
+#[test]
+fn execute_and_access_hooks() {
+    let mut mb = machine();
+    let executed = Arc::new(Mutex::new(0));
+    let accesses = Arc::new(Mutex::new(Vec::<BusAccess>::new()));
+
+    let count = executed.clone();
+    let store = mb.on_execute(0x015A, move |mb, pc| {
+        assert_eq!(pc, 0x015A);
+        assert_eq!(mb.cpu_regs().a, 0x42);
+        *count.lock().unwrap() += 1;
+    });
+    let log = accesses.clone();
+    mb.on_write(0xC000..=0xC0FF, move |_, access| {
+        log.lock().unwrap().push(access)
+    });
+    let log = accesses.clone();
+    mb.on_read(0xC001..=0xC001, move |_, access| {
+        log.lock().unwrap().push(access)
+    });
+    mb.write_bus(0xC001, 0x99);
+
+    // One lap of the loop, then the LD ($C000),A of the next.
+    while *executed.lock().unwrap() < 2 {
+        mb.step();
+    }
+    assert_eq!(
+        *accesses.lock().unwrap(),
+        [
+            BusAccess {
+                address: 0xC000,
+                value: 0x42,
+                kind: AccessKind::Write,
+            },
+            BusAccess {
+                address: 0xC001,
+                value: 0x99,
+                kind: AccessKind::Read,
+            },
+        ]
+    );
+
+    // The debugger's own accesses aren't the CPU's.
+    mb.write_bus(0xC000, 0x00);
+    mb.read_bus(0xC001);
+    assert_eq!(accesses.lock().unwrap().len(), 2);
+
+    assert!(mb.remove_hook(store));
+    assert!(!mb.remove_hook(store));
+    assert_eq!(mb.hooks().unwrap().len(), 2);
+    for _ in 0..100 {
+        mb.step();
+    }
+    assert_eq!(*executed.lock().unwrap(), 2);
+}
+
+#[test]
+fn frame_scanline_and_interrupt_hooks() {
+    let mut mb = machine();
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let interrupts = Arc::new(Mutex::new(Vec::new()));
+
+    let log = frames.clone();
+    let frame_hook = mb.on_frame(move |mb, frame| {
+        assert_eq!(mb.ly(), 144);
+        log.lock().unwrap().push(frame);
+    });
+    let log = lines.clone();
+    let scanline = mb.on_scanline(move |_, ly| log.lock().unwrap().push(ly));
+    let log = interrupts.clone();
+    let dispatched = mb.on_interrupt(move |mb, bit| log.lock().unwrap().push((bit, mb.ly())));
+
+    // The interrupt is dispatched a few M-cycles into its frame.
+    while interrupts.lock().unwrap().len() < 3 {
+        mb.step();
+    }
+    assert_eq!(*frames.lock().unwrap(), [1, 2, 3]);
+    assert_eq!(mb.hooks().unwrap().frames(), 3);
+
+    // Every line, in order, from wherever the LCD started.
+    let lines = lines.lock().unwrap();
+    let start = lines.iter().position(|&ly| ly == 0).unwrap();
+    let frame: Vec<u8> = (0..154).collect();
+    assert_eq!(lines[start..start + 154], frame);
+    assert_eq!(lines[start + 154..start + 154 + 145], frame[..145]);
+
+    let interrupts = interrupts.lock().unwrap();
+    assert_eq!(interrupts.len(), 3);
+    assert!(interrupts.iter().all(|&i| i == (Interrupts::V_BLANK, 144)));
+    drop(interrupts);
+
+    // With the last hook gone, there are no hooks to run at all.
+    assert!(mb.remove_hook(frame_hook));
+    assert!(mb.remove_hook(scanline));
+    assert!(mb.hooks().is_some());
+    assert!(mb.remove_hook(dispatched));
+    assert!(mb.hooks().is_none());
+}
@@ -0,0 +1,102 @@
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+use tetsuyu::components::link::infrared::{InfraredLink, infrared_pair};
+use tetsuyu::components::link::ir_socket::IrSocket;
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::hw::link::LinkedPair;
+
+mod common;
+use common::{test_machine, test_rom};
+
+/// Turns the LED on and spins.
+#[rustfmt::skip]
+const LED_ON: [u8; 6] = [
+    0x3E, 0x01,       // LD A,$01
+    0xE0, 0x56,       // LDH (RP),A
+    0x18, 0xFE,       // JR @
+];
+
+/// Enables reading, waits a little, then copies RP into B.
+#[rustfmt::skip]
+const READ_RP: [u8; 15] = [
+    0x3E, 0xC0,       // LD A,$C0
+    0xE0, 0x56,       // LDH (RP),A
+    0x06, 0x40,       // LD B,$40
+    0x05,             // .wait: DEC B
+    0x20, 0xFD,       // JR NZ,.wait
+    0xF0, 0x56,       // LDH A,(RP)
+    0x47,             // LD B,A
+    0x40,             // LD B,B
+    0x18, 0xFE,       // JR @
+];
+
+#[test]
+fn pair_sees_switches_in_time() {
+    let (mut a, mut b) = infrared_pair();
+    a.send(100, true);
+    a.send(200, false);
+    assert!(!b.receive(99));
+    assert!(b.receive(100));
+    assert!(b.receive(150));
+    assert!(!b.receive(200));
+    assert!(!a.receive(1_000));
+}
+
+#[test]
+fn unread_switches_keep_the_newest_level() {
+    let (mut a, mut b) = infrared_pair();
+    for time in 0..100_000 {
+        a.send(time, time % 2 == 0);
+    }
+    a.send(100_000, true);
+    assert!(b.receive(200_000));
+    a.send(200_001, false);
+    assert!(!b.receive(200_001));
+}
+
+#[test]
+fn socket_carries_switches() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let peer = thread::spawn(move || IrSocket::new(listener.accept().unwrap().0).unwrap());
+    let mut a = IrSocket::new(TcpStream::connect(addr).unwrap()).unwrap();
+    let mut b = peer.join().unwrap();
+
+    a.send(1_000, true);
+    a.send(1_500, false);
+
+    thread::sleep(Duration::from_millis(100));
+
+    // Both switches play back a frame after they are first seen, 500
+    // cycles apart.
+    let mut time = 0;
+    while !b.receive(time) {
+        assert!(time < 1_000_000, "light never arrived");
+        time += 1;
+    }
+    assert_eq!(time, 70_224);
+    assert!(b.receive(time + 499));
+    assert!(!b.receive(time + 500));
+}
+
+#[test]
+fn rp_reads_partner_led() {
+    let lit = test_machine(test_rom(&LED_ON), GBMode::CGB);
+    let reader = test_machine(test_rom(&READ_RP), GBMode::CGB);
+    let mut pair = LinkedPair::new(lit, reader);
+
+    assert!(pair.run_until(100_000, |p| p.b.magic_break()));
+    assert_eq!(pair.b.cpu_regs().b & 0xC3, 0xC0);
+}
+
+#[test]
+fn rp_reads_no_light_alone() {
+    let mut mb = test_machine(test_rom(&READ_RP), GBMode::CGB);
+    let mut cycles = 0;
+    while !mb.magic_break() && cycles < 100_000 {
+        cycles += mb.step();
+    }
+    assert!(mb.magic_break());
+    assert_eq!(mb.cpu_regs().b & 0xC3, 0xC2);
+}
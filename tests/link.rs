@@ -0,0 +1,305 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::{Duration, Instant};
+use tetsuyu::components::link::adapter::{AdapterPort, FourPlayerAdapter};
+use tetsuyu::components::link::bgb::BgbLink;
+use tetsuyu::components::link::link::SerialLink;
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::hw::link::{FourPlayer, LinkedPair};
+use tetsuyu::hw::motherboard::Motherboard;
+
+mod common;
+use common::{test_machine, test_rom};
+
+/// Loads `sb` into SB, starts a transfer with `sc`, waits for SC bit 7 to
+/// clear and then copies SB into B before hitting the `LD B,B` breakpoint.
+fn transfer_program(sb: u8, sc: u8) -> [u8; 20] {
+    #[rustfmt::skip]
+    let program = [
+        0x3E, sb,         // LD A,sb
+        0xE0, 0x01,       // LDH (SB),A
+        0x3E, sc,         // LD A,sc
+        0xE0, 0x02,       // LDH (SC),A
+        0xF0, 0x02,       // .wait: LDH A,(SC)
+        0xCB, 0x7F,       // BIT 7,A
+        0x20, 0xFA,       // JR NZ,.wait
+        0xF0, 0x01,       // LDH A,(SB)
+        0x47,             // LD B,A
+        0x40,             // LD B,B
+        0x18, 0xFE,       // JR @
+    ];
+    program
+}
+
+/// A ROM-only cartridge running [`transfer_program`].
+fn transfer_rom(sb: u8, sc: u8) -> Vec<u8> {
+    test_rom(&transfer_program(sb, sc))
+}
+
+fn machine(rom: Vec<u8>) -> Motherboard {
+    test_machine(rom, GBMode::DMG)
+}
+
+#[test]
+fn unconnected_transfer_reads_ff() {
+    let mut mb = machine(transfer_rom(0x42, 0x81));
+    let mut cycles = 0;
+    while !mb.magic_break() && cycles < 100_000 {
+        cycles += mb.step();
+    }
+    assert!(mb.magic_break(), "transfer never completed");
+    assert_eq!(mb.cpu_regs().b, 0xFF);
+    assert_eq!(mb.serial_output(), &[0x42]);
+}
+
+#[test]
+fn linked_transfer_swaps_bytes() {
+    let master = machine(transfer_rom(0x42, 0x81));
+    let slave = machine(transfer_rom(0x99, 0x80));
+    let mut pair = LinkedPair::new(master, slave);
+
+    let done = pair.run_until(100_000, |p| p.a.magic_break() && p.b.magic_break());
+    assert!(done, "transfer never completed");
+    assert_eq!(pair.a.cpu_regs().b, 0x99);
+    assert_eq!(pair.b.cpu_regs().b, 0x42);
+    assert_eq!(pair.b.serial_output(), &[0x99]);
+}
+
+#[test]
+fn bgb_transfer_swaps_bytes() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (ready_tx, ready_rx) = channel();
+
+    let slave = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut mb = machine(transfer_rom(0x99, 0x80));
+        mb.connect_serial(Box::new(BgbLink::new(stream).unwrap()));
+        // Get SB staged before the master starts clocking.
+        let mut cycles = 0;
+        while cycles < 10_000 {
+            cycles += mb.step();
+        }
+        ready_tx.send(()).unwrap();
+        while !mb.magic_break() && cycles < 10_000_000 {
+            cycles += mb.step();
+        }
+        (mb.magic_break(), mb.cpu_regs().b)
+    });
+
+    let mut mb = machine(transfer_rom(0x42, 0x81));
+    mb.connect_serial(Box::new(
+        BgbLink::new(TcpStream::connect(addr).unwrap()).unwrap(),
+    ));
+    ready_rx.recv().unwrap();
+    let mut cycles = 0;
+    while !mb.magic_break() && cycles < 10_000_000 {
+        cycles += mb.step();
+    }
+    assert!(mb.magic_break(), "master transfer never completed");
+    assert_eq!(mb.cpu_regs().b, 0x99);
+
+    let (done, b) = slave.join().unwrap();
+    assert!(done, "slave transfer never completed");
+    assert_eq!(b, 0x42);
+}
+
+/// Plays BGB by hand to see what goes over the wire.
+#[test]
+fn bgb_sync_packets() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Switches to double speed, then sends on the fast clock.
+    let emulator = thread::spawn(move || {
+        #[rustfmt::skip]
+        let mut program = vec![
+            0x3E, 0x01,       // LD A,$01
+            0xE0, 0x4D,       // LDH (KEY1),A
+            0x10, 0x00,       // STOP
+        ];
+        program.extend_from_slice(&transfer_program(0x42, 0x83));
+        let mut mb = test_machine(test_rom(&program), GBMode::CGB);
+        mb.connect_serial(Box::new(BgbLink::connect(addr).unwrap()));
+        let mut cycles = 0;
+        while !mb.magic_break() && cycles < 10_000_000 {
+            cycles += mb.step();
+        }
+        (mb.magic_break(), mb.cpu_regs().b)
+    });
+
+    let (mut peer, _) = listener.accept().unwrap();
+    let mut packet = [0u8; 8];
+    peer.read_exact(&mut packet).unwrap();
+    assert_eq!(packet[..4], [1, 1, 4, 0]);
+    peer.write_all(&[1, 1, 4, 0, 0, 0, 0, 0]).unwrap();
+
+    // With no transfer started on the external clock, our byte isn't taken.
+    peer.write_all(&[104, 0x55, 0x81, 0, 0, 0, 0, 0]).unwrap();
+    let (mut not_ready, mut sync1) = (false, None);
+    while !not_ready || sync1.is_none() {
+        peer.read_exact(&mut packet).unwrap();
+        match packet[..2] {
+            [106, 1] => not_ready = true,
+            [104, _] => {
+                sync1 = Some(packet);
+                peer.write_all(&[105, 0x99, 0x80, 0, 0, 0, 0, 0]).unwrap();
+            }
+            // Status and timestamps.
+            _ => {}
+        }
+    }
+    // Internal, fast clock, double speed.
+    assert_eq!(sync1.unwrap()[..4], [104, 0x42, 0x87, 0]);
+    assert_eq!(emulator.join().unwrap(), (true, 0x99));
+}
+
+/// A peer that sends one timestamp and then goes quiet, without hanging up.
+#[test]
+fn bgb_timestamps_and_stalled_peer() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let peer = thread::spawn(move || {
+        let (mut peer, _) = listener.accept().unwrap();
+        let mut packet = [0u8; 8];
+        peer.read_exact(&mut packet).unwrap();
+        peer.write_all(&[1, 1, 4, 0, 0, 0, 0, 0]).unwrap();
+        peer.write_all(&[106, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        // The last timestamp sent before the emulator goes quiet too.
+        peer.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let mut last = 0;
+        while peer.read_exact(&mut packet).is_ok() {
+            if packet[..2] == [106, 0] {
+                last = u32::from_le_bytes(packet[4..].try_into().unwrap());
+            }
+        }
+        last
+    });
+
+    #[rustfmt::skip]
+    let program = [
+        0x3E, 0x01,       // LD A,$01
+        0xE0, 0x4D,       // LDH (KEY1),A
+        0x10, 0x00,       // STOP
+        0x18, 0xFE,       // JR @
+    ];
+    let mut mb = test_machine(test_rom(&program), GBMode::CGB);
+    mb.connect_serial(Box::new(BgbLink::connect(addr).unwrap()));
+    let start = Instant::now();
+    while mb.dots() < 5 * 70_224 {
+        mb.step();
+    }
+    // Waited on the peer once, not every poll from then on.
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert!(mb.double_speed());
+    let dots = mb.dots();
+    drop(mb);
+
+    // Timestamps are in 2MiHz units, double speed or not.
+    let last = peer.join().unwrap() as u64;
+    assert!(
+        last <= dots / 2 && last + 0x2000 > dots / 2,
+        "{last} after {dots} dots"
+    );
+}
+
+/// A cartridge that answers the adapter's first two ping bytes with `88`,
+/// keeping the header in B and the STAT byte in C.
+fn ping_rom() -> Vec<u8> {
+    #[rustfmt::skip]
+    let transfer = [
+        0x3E, 0x88,       // LD A,$88
+        0xE0, 0x01,       // LDH (SB),A
+        0x3E, 0x80,       // LD A,$80
+        0xE0, 0x02,       // LDH (SC),A
+        0xF0, 0x02,       // .wait: LDH A,(SC)
+        0xCB, 0x7F,       // BIT 7,A
+        0x20, 0xFA,       // JR NZ,.wait
+        0xF0, 0x01,       // LDH A,(SB)
+    ];
+    let mut program = transfer.to_vec();
+    program.push(0x47); // LD B,A
+    program.extend_from_slice(&transfer);
+    program.extend_from_slice(&[0x4F, 0x40, 0x18, 0xFE]); // LD C,A; LD B,B; JR @
+    test_rom(&program)
+}
+
+#[test]
+fn four_player_adapter_pings() {
+    let machines = (0..4).map(|_| machine(ping_rom())).collect();
+    let mut group = FourPlayer::new(machines);
+
+    let done = group.run_until(1_000_000, |g| g.machines.iter().all(|mb| mb.magic_break()));
+    assert!(done, "ping never completed");
+    for (player, mb) in group.machines.iter().enumerate() {
+        assert_eq!(mb.cpu_regs().b, 0xFE);
+        assert_eq!(mb.cpu_regs().c, 0xF0 | (player as u8 + 1));
+    }
+}
+
+/// Puts each player's byte in its port, runs the adapter until it clocks a
+/// byte through, and returns what each player shifted in.
+fn adapter_byte(
+    adapter: &mut FourPlayerAdapter,
+    ports: &mut [AdapterPort],
+    out: [u8; 4],
+) -> [u8; 4] {
+    for (port, &byte) in ports.iter_mut().zip(&out) {
+        port.publish(byte, 0x80, false);
+    }
+    let mut first = None;
+    for _ in 0..100_000 {
+        adapter.advance(1);
+        first = ports[0].receive();
+        if first.is_some() {
+            break;
+        }
+    }
+    let first = first.expect("the adapter never clocked a byte");
+
+    let mut received = [0; 4];
+    for (player, (port, byte)) in ports.iter_mut().zip(&mut received).enumerate() {
+        let bits = (player == 0).then_some(first).into_iter();
+        let bits = bits.chain(std::iter::from_fn(|| port.receive())).take(8);
+        *byte = bits.fold(0, |byte, bit| (byte << 1) | bit as u8);
+    }
+    received
+}
+
+#[test]
+fn four_player_adapter_relays_packets() {
+    let mut adapter = FourPlayerAdapter::new();
+    let mut ports: Vec<_> = (0..4).map(|p| adapter.port(p)).collect();
+    let mut byte = |out: [u8; 4]| adapter_byte(&mut adapter, &mut ports, out);
+
+    // Everyone joins; player 1 asks for the fastest rate and 1-byte packets.
+    assert_eq!(byte([0x88; 4]), [0xFE; 4]);
+    byte([0x88; 4]);
+    byte([0x00; 4]);
+    byte([0x01, 0x00, 0x00, 0x00]);
+
+    // Player 1 starts the game with a ping packet of $AA, which the adapter
+    // acknowledges with four $CC.
+    assert_eq!(byte([0xAA, 0x00, 0x00, 0x00]), [0xFE; 4]);
+    for _ in 1..4 {
+        byte([0xAA, 0x00, 0x00, 0x00]);
+    }
+    for _ in 0..4 {
+        assert_eq!(byte([0x00; 4]), [0xCC; 4]);
+    }
+
+    // Each cycle relays the packets sent in the one before to everyone.
+    let packets = [0x10, 0x11, 0x12, 0x13];
+    assert_eq!(byte(packets), [0x00; 4]);
+    for _ in 1..4 {
+        assert_eq!(byte([0x00; 4]), [0x00; 4]);
+    }
+    for &packet in &packets {
+        assert_eq!(byte([0xFF; 4]), [packet; 4]);
+    }
+
+    // A cycle of $FF from everyone goes back to pinging.
+    assert_eq!(byte([0x88; 4]), [0xFE; 4]);
+}
@@ -0,0 +1,155 @@
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::components::prelude::Reg;
+use tetsuyu::hw::motherboard::{Motherboard, Region};
+
+mod common;
+use common::{run_to_program, test_machine, test_rom};
+
+/// A machine past the boot ROM, spinning at $0150, with the cartridge type
+/// at $0147 being `cart_type`.
+fn machine(mode: GBMode, cart_type: u8) -> Motherboard {
+    let mut rom = test_rom(&[0x18, 0xFE]); // JR @
+    rom[0x0147] = cart_type;
+    let mut mb = test_machine(rom, mode);
+    run_to_program(&mut mb);
+    mb
+}
+
+#[test]
+fn poke_reaches_memory_the_cpu_is_locked_out_of() {
+    let mut mb = machine(GBMode::DMG, 0x00);
+    mb.write_bus(0xFF40, 0x91);
+    while mb.read_bus(0xFF41) & 0x03 != 0x03 {
+        mb.step_mcycle();
+    }
+
+    // Drawing: the CPU can't get at VRAM or OAM.
+    mb.write_bus(0x8000, 0x12);
+    mb.write_bus(0xFE00, 0x34);
+    assert_ne!(mb.peek(0x8000), 0x12);
+    assert_ne!(mb.peek(0xFE00), 0x34);
+
+    mb.poke(0x8000, 0x12);
+    mb.poke(0xFE00, 0x34);
+    assert_eq!(mb.peek(0x8000), 0x12);
+    assert_eq!(mb.peek(0xFE00), 0x34);
+    assert_eq!(mb.region(Region::Vram(0)).unwrap()[0], 0x12);
+    assert_eq!(mb.region(Region::Oam).unwrap()[0], 0x34);
+    // Poking takes no time.
+    assert_eq!(mb.read_bus(0xFF41) & 0x03, 0x03);
+}
+
+#[test]
+fn peek_reads_io_registers_as_the_cpu_does() {
+    let mut mb = machine(GBMode::CGB, 0x00);
+    mb.write_bus(0xFF40, 0x91);
+    mb.write_bus(0xFF06, 0x42);
+    mb.write_bus(0xFF07, 0x05);
+    mb.write_bus(0xFF24, 0x77);
+    mb.write_bus(0xFF70, 0x03);
+    mb.write_bus(0xFFFF, 0x1F);
+    while mb.read_bus(0xFF41) & 0x03 != 0x03 {
+        mb.step_mcycle();
+    }
+
+    for a in (0xFF00..=0xFF7F).chain([0xFFFF]) {
+        assert_eq!(mb.peek(a), mb.read_bus(a), "${a:04X}");
+    }
+    assert_eq!(mb.peek(0xFF06), 0x42);
+    assert_eq!(mb.peek(0xFF41) & 0x03, 0x03);
+}
+
+#[test]
+fn poke_leaves_rom_and_banking_alone() {
+    let mut mb = machine(GBMode::DMG, 0x01);
+    mb.poke(0x0150, 0x00);
+    mb.poke(0x2000, 0x05);
+    assert_eq!(mb.peek(0x0150), 0x18);
+    assert_eq!(mb.rom_bank(), 1);
+
+    // IO registers are written as usual.
+    mb.poke(0xFF47, 0xE4);
+    assert_eq!(mb.read_bus(0xFF47), 0xE4);
+
+    mb.poke_range(0xFFFD, &[1, 2, 3]);
+    assert_eq!(mb.peek_range(0xFFFD, 2), [1, 2]);
+    assert_eq!(mb.region(Region::Hram).unwrap()[0x7D..], [1, 2]);
+    assert_eq!(mb.read_bus(0xFFFF) & 0x1F, 3);
+}
+
+#[test]
+fn cartridge_ram_by_bank() {
+    // MBC1+RAM+BATTERY.
+    let mut mb = machine(GBMode::DMG, 0x03);
+    assert_eq!(mb.region(Region::CartRam(3)).unwrap().len(), 0x2000);
+    assert!(mb.region(Region::CartRam(4)).is_none());
+
+    // Disabled RAM can still be poked.
+    mb.poke(0xA000, 0x56);
+    assert_eq!(mb.read_bus(0xA000), 0x00);
+    assert_eq!(mb.region(Region::CartRam(0)).unwrap()[0], 0x56);
+    mb.write_bus(0x0000, 0x0A);
+    assert_eq!(mb.read_bus(0xA000), 0x56);
+
+    mb.region_mut(Region::CartRam(2)).unwrap()[5] = 0x77;
+    mb.write_bus(0x6000, 0x01);
+    mb.write_bus(0x4000, 0x02);
+    assert_eq!(mb.read_bus(0xA005), 0x77);
+    mb.poke(0xA001, 0x88);
+    assert_eq!(mb.region(Region::CartRam(2)).unwrap()[1], 0x88);
+
+    // No cartridge RAM at all.
+    let mb = machine(GBMode::DMG, 0x00);
+    assert!(mb.region(Region::CartRam(0)).is_none());
+}
+
+#[test]
+fn cgb_banks() {
+    let mut mb = machine(GBMode::CGB, 0x00);
+    mb.write_bus(0xFF40, 0x00);
+
+    mb.region_mut(Region::Wram(3)).unwrap()[0] = 0x11;
+    mb.write_bus(0xFF70, 0x03);
+    assert_eq!(mb.peek(0xD000), 0x11);
+    mb.poke(0xD001, 0x22);
+    assert_eq!(mb.region(Region::Wram(3)).unwrap()[1], 0x22);
+    assert!(mb.region(Region::Wram(8)).is_none());
+
+    mb.write_bus(0xFF4F, 0x01);
+    mb.poke(0x8000, 0x99);
+    assert_eq!(mb.region(Region::Vram(1)).unwrap()[0], 0x99);
+    assert_eq!(mb.region(Region::Vram(0)).unwrap()[0], 0x00);
+    assert_eq!(mb.peek(0x8000), 0x99);
+    assert!(mb.region(Region::Vram(2)).is_none());
+}
+
+#[test]
+fn registers() {
+    let mut mb = machine(GBMode::DMG, 0x00);
+    mb.set_register(Reg::B, 0x1234);
+    assert_eq!(mb.cpu_regs().b, 0x34);
+    mb.set_register(Reg::AF, 0x12FF);
+    assert_eq!(mb.register(Reg::AF), 0x12F0);
+    mb.set_register(Reg::SP, 0xDFFE);
+    assert_eq!(mb.register(Reg::SP), 0xDFFE);
+
+    mb.set_register(Reg::PC, 0x0100);
+    assert_eq!(mb.register(Reg::PC), 0x0100);
+    assert_eq!(mb.pc(), 0x0100);
+
+    // Setting PC only redirects the next instruction; no time passes.
+    mb.write_bus(0xFF40, 0x91);
+    for _ in 0..1000 {
+        mb.step();
+    }
+    let before = (mb.dots(), mb.read_bus(0xFF04), mb.ly());
+    mb.set_register(Reg::PC, 0x0150);
+    assert_eq!((mb.dots(), mb.read_bus(0xFF04), mb.ly()), before);
+    assert_eq!(mb.pc(), 0x0150);
+    mb.step();
+    assert_eq!(mb.pc(), 0x0150);
+
+    assert_eq!(Reg::parse("HL"), Some(Reg::HL));
+    assert_eq!(Reg::parse("pc"), Some(Reg::PC));
+    assert_eq!(Reg::parse("ix"), None);
+}
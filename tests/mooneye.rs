@@ -1,6 +1,6 @@
 use common::*;
 use std::path::Path;
-use tetsuyu::components::mode::GBMode;
+use tetsuyu::components::mode::{GBMode, Model};
 
 #[macro_use]
 mod common;
@@ -12,16 +12,40 @@ pub fn run_mooneye_test(sub_path: &str, mode: GBMode) {
     }
 
     let mut h = setup_harness(&rom, mode).expect("Harness initialization failed");
+    assert_passed(&mut h, "");
+}
+
+/// Run `roms/moonsuite/{sub_path}.gb` without a boot ROM, from the state
+/// each model its name is for would be left in, or the DMG's and CGB's when
+/// it doesn't say.
+pub fn run_mooneye_model_test(sub_path: &str) {
+    let rom = format!("roms/moonsuite/{sub_path}.gb");
+    if !Path::new(&rom).exists() {
+        return;
+    }
+
+    let models = mooneye_models(sub_path).unwrap_or_else(|| vec![Model::Dmg, Model::Cgb]);
+    for model in models {
+        let mut h = Harness::builder(&rom)
+            .model(model)
+            .skip_boot()
+            .build()
+            .expect("Harness initialization failed");
+        assert_passed(&mut h, &format!(" on {model:?}"));
+    }
+}
+
+fn assert_passed(h: &mut Harness, on: &str) {
     assert_ne!(
         h.run_until(StopCondition::MagicBreak, 240 * FC),
         RunOutcome::TimedOut,
-        "Mooneye test execution timed out."
+        "Mooneye test execution timed out{on}."
     );
 
     let regs = h.cpu_regs();
     assert!(
         regs.b == 3 && regs.c == 5 && regs.d == 8 && regs.e == 13 && regs.h == 21 && regs.l == 34,
-        "Mooneye register verification signature failed: {regs}"
+        "Mooneye register verification signature failed{on}: {regs}"
     );
 }
 
@@ -178,3 +202,95 @@ mod cgb {
         timer_tma_write_reloading => ("timer/tma_write_reloading", GBMode::CGB),
     ];
 }
+
+/// What each model's boot ROM leaves behind.
+mod boot {
+    use super::*;
+
+    test_suite![
+        run_mooneye_model_test,
+        #[ignore = "the post-boot divider is only known for the DMG and MGB"]
+        boot_div_dmg0 => ("acceptance/boot_div-dmg0"),
+        boot_div_dmg_abc_mgb => ("acceptance/boot_div-dmgABCmgb"),
+        #[ignore = "the post-boot divider is only known for the DMG and MGB"]
+        boot_div_s => ("acceptance/boot_div-S"),
+        #[ignore = "the post-boot divider is only known for the DMG and MGB"]
+        boot_div2_s => ("acceptance/boot_div2-S"),
+        boot_hwio_dmg0 => ("acceptance/boot_hwio-dmg0"),
+        boot_hwio_dmg_abc_mgb => ("acceptance/boot_hwio-dmgABCmgb"),
+        boot_hwio_s => ("acceptance/boot_hwio-S"),
+        boot_regs_dmg0 => ("acceptance/boot_regs-dmg0"),
+        boot_regs_dmg_abc => ("acceptance/boot_regs-dmgABC"),
+        boot_regs_mgb => ("acceptance/boot_regs-mgb"),
+        boot_regs_sgb => ("acceptance/boot_regs-sgb"),
+        boot_regs_sgb2 => ("acceptance/boot_regs-sgb2"),
+    ];
+}
+
+/// The CGB tests; the AGB and CGB0 ones are for models that aren't
+/// emulated.
+mod misc {
+    use super::*;
+
+    test_suite![
+        run_mooneye_model_test,
+        #[ignore = "the post-boot divider is only known for the DMG and MGB"]
+        boot_div_cgb_abcde => ("misc/boot_div-cgbABCDE"),
+        boot_hwio_c => ("misc/boot_hwio-C"),
+        boot_regs_cgb => ("misc/boot_regs-cgb"),
+        bits_unused_hwio_c => ("misc/bits/unused_hwio-C"),
+        ppu_vblank_stat_intr_c => ("misc/ppu/vblank_stat_intr-C"),
+    ];
+}
+
+mod mbc1 {
+    use super::*;
+
+    test_suite![
+        run_mooneye_model_test,
+        bits_bank1 => ("emulator-only/mbc1/bits_bank1"),
+        bits_bank2 => ("emulator-only/mbc1/bits_bank2"),
+        bits_mode => ("emulator-only/mbc1/bits_mode"),
+        bits_ramg => ("emulator-only/mbc1/bits_ramg"),
+        multicart_rom_8mb => ("emulator-only/mbc1/multicart_rom_8Mb"),
+        ram_64kb => ("emulator-only/mbc1/ram_64kb"),
+        ram_256kb => ("emulator-only/mbc1/ram_256kb"),
+        rom_512kb => ("emulator-only/mbc1/rom_512kb"),
+        rom_1mb => ("emulator-only/mbc1/rom_1Mb"),
+        rom_2mb => ("emulator-only/mbc1/rom_2Mb"),
+        rom_4mb => ("emulator-only/mbc1/rom_4Mb"),
+        rom_8mb => ("emulator-only/mbc1/rom_8Mb"),
+        rom_16mb => ("emulator-only/mbc1/rom_16Mb"),
+    ];
+}
+
+mod mbc2 {
+    use super::*;
+
+    test_suite![
+        run_mooneye_model_test,
+        bits_ramg => ("emulator-only/mbc2/bits_ramg"),
+        bits_romb => ("emulator-only/mbc2/bits_romb"),
+        bits_unused => ("emulator-only/mbc2/bits_unused"),
+        ram => ("emulator-only/mbc2/ram"),
+        rom_512kb => ("emulator-only/mbc2/rom_512kb"),
+        rom_1mb => ("emulator-only/mbc2/rom_1Mb"),
+        rom_2mb => ("emulator-only/mbc2/rom_2Mb"),
+    ];
+}
+
+mod mbc5 {
+    use super::*;
+
+    test_suite![
+        run_mooneye_model_test,
+        rom_512kb => ("emulator-only/mbc5/rom_512kb"),
+        rom_1mb => ("emulator-only/mbc5/rom_1Mb"),
+        rom_2mb => ("emulator-only/mbc5/rom_2Mb"),
+        rom_4mb => ("emulator-only/mbc5/rom_4Mb"),
+        rom_8mb => ("emulator-only/mbc5/rom_8Mb"),
+        rom_16mb => ("emulator-only/mbc5/rom_16Mb"),
+        rom_32mb => ("emulator-only/mbc5/rom_32Mb"),
+        rom_64mb => ("emulator-only/mbc5/rom_64Mb"),
+    ];
+}
@@ -0,0 +1,97 @@
+use tetsuyu::components::link::link::SerialLink;
+use tetsuyu::components::link::printer::{PRINT_WIDTH, Printer};
+
+/// Clock `v` through the printer MSB first; returns the byte shifted back.
+fn send_byte(printer: &mut Printer, v: u8) -> u8 {
+    let mut reply = 0;
+    for bit in (0..8).rev() {
+        reply = (reply << 1) | printer.exchange(v & (1 << bit) != 0) as u8;
+    }
+    reply
+}
+
+/// Send one packet; returns the device ID and status bytes.
+fn send_packet(printer: &mut Printer, command: u8, compressed: bool, data: &[u8]) -> (u8, u8) {
+    let length = (data.len() as u16).to_le_bytes();
+    let mut body = vec![command, compressed as u8, length[0], length[1]];
+    body.extend_from_slice(data);
+    let checksum = body.iter().fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+
+    for &b in [0x88, 0x33]
+        .iter()
+        .chain(&body)
+        .chain(&checksum.to_le_bytes())
+    {
+        send_byte(printer, b);
+    }
+    (send_byte(printer, 0x00), send_byte(printer, 0x00))
+}
+
+/// Two tile rows whose pixels all have colour index 3.
+fn black_rows() -> Vec<u8> {
+    vec![0xFF; 640]
+}
+
+#[test]
+fn prints_a_job() {
+    let mut printer = Printer::new(None);
+    let prints = printer.prints();
+
+    assert_eq!(send_packet(&mut printer, 0x01, false, &[]), (0x81, 0x00));
+    assert_eq!(
+        send_packet(&mut printer, 0x04, false, &black_rows()),
+        (0x81, 0x08)
+    );
+    // Compressed: a run of 640 $00 bytes (five runs of 128).
+    let compressed = [0xFE, 0x00].repeat(5);
+    assert_eq!(
+        send_packet(&mut printer, 0x04, true, &compressed),
+        (0x81, 0x08)
+    );
+    send_packet(&mut printer, 0x04, false, &[]);
+
+    // One sheet, no margin before and three lines after, palette $E4.
+    let (id, status) = send_packet(&mut printer, 0x02, false, &[0x01, 0x03, 0xE4, 0x40]);
+    assert_eq!(id, 0x81);
+    assert_eq!(status & 0x02, 0x02, "printer should be busy");
+    let mut polls = 0;
+    while send_packet(&mut printer, 0x0F, false, &[]).1 & 0x02 != 0 {
+        polls += 1;
+        assert!(polls < 100, "printer never finished");
+    }
+
+    let prints = prints.lock().unwrap();
+    assert_eq!(prints.len(), 1);
+    let print = &prints[0];
+    assert_eq!((print.width, print.height), (PRINT_WIDTH, 32));
+    assert_eq!(print.pixels[0], 0x00);
+    assert_eq!(print.pixels[16 * PRINT_WIDTH], 0xFF);
+}
+
+#[test]
+fn bad_checksum_is_reported() {
+    let mut printer = Printer::new(None);
+    for &b in &[0x88, 0x33, 0x0F, 0x00, 0x00, 0x00, 0x12, 0x34] {
+        send_byte(&mut printer, b);
+    }
+    assert_eq!(send_byte(&mut printer, 0x00), 0x81);
+    assert_eq!(send_byte(&mut printer, 0x00) & 0x01, 0x01);
+}
+
+#[test]
+fn writes_png() {
+    let dir = std::env::temp_dir().join(format!("tetsuyu-printer-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut printer = Printer::new(Some(dir.clone()));
+    send_packet(&mut printer, 0x01, false, &[]);
+    send_packet(&mut printer, 0x04, false, &black_rows());
+    send_packet(&mut printer, 0x02, false, &[0x01, 0x01, 0xE4, 0x40]);
+
+    let path = dir.join("print_000.png");
+    let decoder = png::Decoder::new(std::io::BufReader::new(std::fs::File::open(&path).unwrap()));
+    let reader = decoder.read_info().unwrap();
+    assert_eq!(reader.info().width, PRINT_WIDTH as u32);
+    assert_eq!(reader.info().height, 16);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,104 @@
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::debugger::profile::Profiler;
+use tetsuyu::debugger::symbols::Symbols;
+use tetsuyu::hw::interrupt::Interrupts;
+
+mod common;
+use common::{test_machine, test_rom};
+
+#[rustfmt::skip]
+const VBLANK: [u8; 2] = [
+    0x3C,             // $0040: INC A
+    0xD9,             // $0041: RETI
+];
+
+#[rustfmt::skip]
+const PROGRAM: [u8; 26] = [
+    0x3E, 0x91,       // $0150: LD A,$91
+    0xE0, 0x40,       // $0152: LDH [LCDC],A
+    0x3E, 0x01,       // $0154: LD A,$01
+    0xE0, 0xFF,       // $0156: LDH [IE],A
+    0xAF,             // $0158: XOR A
+    0xE0, 0x0F,       // $0159: LDH [IF],A
+    0xFB,             // $015B: EI
+    0xCD, 0x00, 0x02, // $015C: CALL Work
+    0x76,             // $015F: .wait: HALT
+    0x00,             // $0160: NOP
+    0xFE, 0x02,       // $0161: CP 2
+    0x20, 0xFA,       // $0163: JR NZ,.wait
+    0x40,             // $0165: LD B,B
+    0x18, 0xFE,       // $0166: JR @
+    0x00, 0x00,
+];
+
+#[rustfmt::skip]
+const WORK: [u8; 6] = [
+    0x06, 0x10,       // $0200: Work: LD B,16
+    0x05,             // $0202: .loop: DEC B
+    0x20, 0xFD,       // $0203: JR NZ,.loop
+    0xC9,             // $0205: RET
+];
+
+fn profile() -> Profiler {
+    let mut rom = test_rom(&PROGRAM);
+    rom[0x0040..0x0042].copy_from_slice(&VBLANK);
+    rom[0x0200..0x0206].copy_from_slice(&WORK);
+    let mut mb = test_machine(rom, GBMode::DMG);
+    mb.set_profiler(Some(Profiler::new()));
+    while !mb.magic_break() {
+        mb.step();
+    }
+    mb.set_profiler(None).unwrap()
+}
+
+fn symbols() -> Symbols {
+    Symbols::parse("00:0040 VBlank\n00:0150 Main\n00:015f Main.wait\n00:0200 Work\n")
+}
+
+#[test]
+fn cycles_per_address() {
+    let profiler = profile();
+    assert_eq!(profiler.cycles(0, 0x0200), 8);
+    // DEC B, 16 times.
+    assert_eq!(profiler.cycles(0, 0x0202), 64);
+    // JR NZ taken 15 times and not once.
+    assert_eq!(profiler.cycles(0, 0x0203), 15 * 12 + 8);
+    assert_eq!(profiler.cycles(0, 0x0205), 16);
+    assert_eq!(profiler.cycles(0, 0x0165), 0);
+}
+
+#[test]
+fn halted_and_handlers() {
+    let profiler = profile();
+    // Most of two frames is spent waiting.
+    assert!(profiler.halted() > 100_000, "{}", profiler.halted());
+    assert!(profiler.executing() < 1_000, "{}", profiler.executing());
+    // Twice: a 20-cycle dispatch, INC A and RETI.
+    assert_eq!(profiler.handler(Interrupts::V_BLANK), 2 * (20 + 4 + 16));
+    assert_eq!(profiler.handler(Interrupts::TIMER), 0);
+}
+
+#[test]
+fn report_and_folded_stacks() {
+    let profiler = profile();
+    let symbols = symbols();
+
+    let report = profiler.report(&symbols);
+    assert!(report.contains("\nFunctions:\n"), "{}", report);
+    assert!(report.contains("%  Work\n"), "{}", report);
+    assert!(report.contains("%  vblank\n"), "{}", report);
+    assert!(report.contains("%  00:0203  Work+3\n"), "{}", report);
+    let hotspots = report.split("Hotspots:\n").nth(1).unwrap();
+    assert!(hotspots.lines().next().unwrap().ends_with("Work+3"));
+
+    let folded = profiler.folded(&symbols);
+    // LD B, the loop and RET; the CALL is Main's.
+    assert!(folded.contains("[top];Work 276\n"), "{}", folded);
+    assert!(folded.contains("[top];[vblank] 80\n"), "{}", folded);
+    assert!(folded.contains("[top];[halted] "), "{}", folded);
+    assert!(
+        folded
+            .lines()
+            .all(|l| l.rsplit_once(' ').unwrap().1.parse::<u64>().is_ok())
+    );
+}
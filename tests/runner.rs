@@ -0,0 +1,180 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::config::Config;
+use tetsuyu::runner::{Runner, Stop, Until};
+
+mod common;
+use common::{BOOT, test_machine_with_screen, test_rom};
+
+/// Turns the LCD on, sends "OK" over the serial port and hits the magic
+/// breakpoint.
+#[rustfmt::skip]
+const PROGRAM: [u8; 17] = [
+    0x31, 0xFE, 0xDF,   // $0150: LD SP,$DFFE
+    0x3E, 0x91,         // $0153: LD A,$91
+    0xE0, 0x40,         // $0155: LDH (LCDC),A
+    0x3E, 0x4F,         // $0157: LD A,'O'
+    0xCD, 0x70, 0x01,   // $0159: CALL $0170
+    0x3E, 0x4B,         // $015C: LD A,'K'
+    0xCD, 0x70, 0x01,   // $015E: CALL $0170
+];
+
+/// Sends A over the serial port and waits for it to go.
+#[rustfmt::skip]
+const SEND: [u8; 13] = [
+    0xE0, 0x01,         // $0170: LDH (SB),A
+    0x3E, 0x81,         // $0172: LD A,$81
+    0xE0, 0x02,         // $0174: LDH (SC),A
+    0xF0, 0x02,         // $0176: LDH A,(SC)
+    0xCB, 0x7F,         // $0178: BIT 7,A
+    0x20, 0xFA,         // $017A: JR NZ,$0176
+    0xC9,               // $017C: RET
+];
+
+fn rom() -> Vec<u8> {
+    let mut rom = test_rom(&PROGRAM);
+    // $0161: LD B,B then JR $0162, spinning.
+    rom[0x0161..0x0164].copy_from_slice(&[0x40, 0x18, 0xFE]);
+    rom[0x0170..0x0170 + SEND.len()].copy_from_slice(&SEND);
+    rom
+}
+
+/// Turns the LCD on and halts with interrupts off, never to wake.
+#[rustfmt::skip]
+const HALT: [u8; 6] = [
+    0x3E, 0x91,         // $0150: LD A,$91
+    0xE0, 0x40,         // $0152: LDH (LCDC),A
+    0xF3,               // $0154: DI
+    0x76,               // $0155: HALT
+];
+
+fn runner() -> Runner {
+    runner_with(rom())
+}
+
+fn runner_with(rom: Vec<u8>) -> Runner {
+    let (mb, reader) = test_machine_with_screen(rom, GBMode::DMG);
+    Runner::new(mb, reader, "TEST".to_string())
+}
+
+#[test]
+fn stop_conditions() {
+    let mut r = runner();
+    let serial = Until {
+        serial: vec!["K".to_string()],
+        ..Until::default()
+    };
+    assert_eq!(r.run(&serial), Ok(Stop::Serial));
+    assert_eq!(r.machine().serial_output(), b"OK");
+    assert!(!r.machine().magic_break());
+
+    let magic_break = Until {
+        magic_break: true,
+        ..Until::default()
+    };
+    assert_eq!(r.run(&magic_break), Ok(Stop::MagicBreak));
+    let summary = r.summary(Stop::MagicBreak);
+    assert_eq!((summary.status, summary.serial.as_str()), (0, "OK"));
+
+    let frames = Until {
+        frames: Some(r.frames() + 3),
+        ..Until::default()
+    };
+    assert_eq!(r.run(&frames), Ok(Stop::Frames));
+    let timeout = Until {
+        timeout: Some(Duration::from_millis(50)),
+        ..Until::default()
+    };
+    assert_eq!(r.run(&timeout), Ok(Stop::Timeout));
+
+    // A failure string wins over a success one seen at the same time.
+    let mut r = runner();
+    let both = Until {
+        serial: vec!["OK".to_string()],
+        serial_fail: vec!["K".to_string()],
+        ..Until::default()
+    };
+    assert_eq!(r.run(&both), Ok(Stop::SerialFail));
+    assert_eq!(r.summary(Stop::SerialFail).status, 3);
+}
+
+#[test]
+fn halted_for_good() {
+    let mut r = runner_with(test_rom(&HALT));
+    let frames = Until {
+        frames: Some(3),
+        ..Until::default()
+    };
+    assert_eq!(r.run(&frames), Ok(Stop::Frames));
+    assert_eq!(r.machine().pc(), 0x0155);
+    let timeout = Until {
+        timeout: Some(Duration::from_millis(50)),
+        ..Until::default()
+    };
+    assert_eq!(r.run(&timeout), Ok(Stop::Timeout));
+}
+
+#[test]
+fn run_subcommand() {
+    let dir = std::env::temp_dir().join(format!("tetsuyu-run-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str| -> PathBuf { dir.join(name) };
+    std::fs::write(file("test.gb"), rom()).unwrap();
+    std::fs::write(file("boot.bin"), BOOT).unwrap();
+    std::fs::write(
+        file("config.toml"),
+        toml::to_string(&Config::default()).unwrap(),
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_tetsuyu"))
+            .arg("run")
+            .arg(file("test.gb"))
+            .arg("--config")
+            .arg(file("config.toml"))
+            .arg("--boot-rom")
+            .arg(file("boot.bin"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let screenshot = file("shot.png");
+    let output = run(&[
+        "--magic-break",
+        "--screenshot",
+        screenshot.to_str().unwrap(),
+        "--serial-out",
+        file("serial.txt").to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(summary["stop"], "magic_break");
+    assert_eq!(summary["serial"], "OK");
+    assert_eq!(summary["registers"]["pc"], 0x0162);
+    assert_eq!(std::fs::read(file("serial.txt")).unwrap(), b"OK");
+    let png = std::fs::read(&screenshot).unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+
+    let output = run(&[
+        "--timeout",
+        "0.1",
+        "--summary",
+        file("summary.json").to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(4));
+    let summary: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(file("summary.json")).unwrap()).unwrap();
+    assert_eq!(summary["stop"], "timeout");
+
+    // Nothing to stop on is refused, as is a timeout that isn't one.
+    assert_eq!(run(&[]).status.code(), Some(2));
+    for timeout in ["-1", "NaN", "inf", "soon"] {
+        let arg = format!("--timeout={timeout}");
+        assert_eq!(run(&[&arg]).status.code(), Some(2));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,118 @@
+use common::*;
+use std::path::Path;
+use tetsuyu::components::mode::GBMode;
+
+#[macro_use]
+mod common;
+
+/// SameSuite tests end on `LD B,B` with the Fibonacci numbers in B-L on a
+/// pass, or $42 in each on a failure, as mooneye's do.
+pub fn run_samesuite_test(sub_path: &str, mode: GBMode) {
+    let rom = format!("roms/samesuite/{sub_path}.gb");
+    if !Path::new(&rom).exists() {
+        return;
+    }
+
+    let Some(mut h) = setup_harness(&rom, mode) else {
+        return;
+    };
+    assert_ne!(
+        h.run_until(StopCondition::MagicBreak, 600 * FC),
+        RunOutcome::TimedOut,
+        "SameSuite test execution timed out."
+    );
+
+    let regs = h.cpu_regs();
+    assert!(
+        [regs.b, regs.c, regs.d, regs.e, regs.h, regs.l] != [0x42; 6],
+        "SameSuite test reported a failure: {regs}"
+    );
+    assert!(
+        regs.b == 3 && regs.c == 5 && regs.d == 8 && regs.e == 13 && regs.h == 21 && regs.l == 34,
+        "SameSuite register verification signature failed: {regs}"
+    );
+}
+
+mod cgb {
+    use super::*;
+
+    test_suite![
+        run_samesuite_test,
+        // APU
+        apu_div_trigger_volume_10 => ("apu/div_trigger_volume_10", GBMode::CGB),
+        apu_div_write_trigger => ("apu/div_write_trigger", GBMode::CGB),
+        apu_div_write_trigger_10 => ("apu/div_write_trigger_10", GBMode::CGB),
+        apu_div_write_trigger_volume => ("apu/div_write_trigger_volume", GBMode::CGB),
+        apu_div_write_trigger_volume_10 => ("apu/div_write_trigger_volume_10", GBMode::CGB),
+        // APU channel 1
+        ch1_align => ("apu/channel_1/channel_1_align", GBMode::CGB),
+        ch1_align_cpu => ("apu/channel_1/channel_1_align_cpu", GBMode::CGB),
+        ch1_delay => ("apu/channel_1/channel_1_delay", GBMode::CGB),
+        ch1_duty => ("apu/channel_1/channel_1_duty", GBMode::CGB),
+        ch1_duty_delay => ("apu/channel_1/channel_1_duty_delay", GBMode::CGB),
+        ch1_freq_change => ("apu/channel_1/channel_1_freq_change", GBMode::CGB),
+        ch1_nrx2_glitch => ("apu/channel_1/channel_1_nrx2_glitch", GBMode::CGB),
+        ch1_nrx2_speed_change => ("apu/channel_1/channel_1_nrx2_speed_change", GBMode::CGB),
+        ch1_restart => ("apu/channel_1/channel_1_restart", GBMode::CGB),
+        ch1_restart_nrx2_glitch => ("apu/channel_1/channel_1_restart_nrx2_glitch", GBMode::CGB),
+        ch1_stop_div => ("apu/channel_1/channel_1_stop_div", GBMode::CGB),
+        ch1_stop_restart => ("apu/channel_1/channel_1_stop_restart", GBMode::CGB),
+        ch1_sweep => ("apu/channel_1/channel_1_sweep", GBMode::CGB),
+        ch1_sweep_restart => ("apu/channel_1/channel_1_sweep_restart", GBMode::CGB),
+        ch1_sweep_restart_2 => ("apu/channel_1/channel_1_sweep_restart_2", GBMode::CGB),
+        ch1_volume => ("apu/channel_1/channel_1_volume", GBMode::CGB),
+        ch1_volume_div => ("apu/channel_1/channel_1_volume_div", GBMode::CGB),
+        // APU channel 2
+        ch2_align => ("apu/channel_2/channel_2_align", GBMode::CGB),
+        ch2_align_cpu => ("apu/channel_2/channel_2_align_cpu", GBMode::CGB),
+        ch2_delay => ("apu/channel_2/channel_2_delay", GBMode::CGB),
+        ch2_duty => ("apu/channel_2/channel_2_duty", GBMode::CGB),
+        ch2_duty_delay => ("apu/channel_2/channel_2_duty_delay", GBMode::CGB),
+        ch2_freq_change => ("apu/channel_2/channel_2_freq_change", GBMode::CGB),
+        ch2_nrx2_glitch => ("apu/channel_2/channel_2_nrx2_glitch", GBMode::CGB),
+        ch2_nrx2_speed_change => ("apu/channel_2/channel_2_nrx2_speed_change", GBMode::CGB),
+        ch2_restart => ("apu/channel_2/channel_2_restart", GBMode::CGB),
+        ch2_restart_nrx2_glitch => ("apu/channel_2/channel_2_restart_nrx2_glitch", GBMode::CGB),
+        ch2_stop_div => ("apu/channel_2/channel_2_stop_div", GBMode::CGB),
+        ch2_stop_restart => ("apu/channel_2/channel_2_stop_restart", GBMode::CGB),
+        ch2_volume => ("apu/channel_2/channel_2_volume", GBMode::CGB),
+        ch2_volume_div => ("apu/channel_2/channel_2_volume_div", GBMode::CGB),
+        // APU channel 3
+        ch3_and_glitch => ("apu/channel_3/channel_3_and_glitch", GBMode::CGB),
+        ch3_delay => ("apu/channel_3/channel_3_delay", GBMode::CGB),
+        ch3_first_sample => ("apu/channel_3/channel_3_first_sample", GBMode::CGB),
+        ch3_freq_change_delay => ("apu/channel_3/channel_3_freq_change_delay", GBMode::CGB),
+        ch3_restart_delay => ("apu/channel_3/channel_3_restart_delay", GBMode::CGB),
+        ch3_restart_during_delay => ("apu/channel_3/channel_3_restart_during_delay", GBMode::CGB),
+        ch3_restart_stop_delay => ("apu/channel_3/channel_3_restart_stop_delay", GBMode::CGB),
+        ch3_shift_delay => ("apu/channel_3/channel_3_shift_delay", GBMode::CGB),
+        ch3_shift_skip_delay => ("apu/channel_3/channel_3_shift_skip_delay", GBMode::CGB),
+        ch3_stop_delay => ("apu/channel_3/channel_3_stop_delay", GBMode::CGB),
+        ch3_stop_div => ("apu/channel_3/channel_3_stop_div", GBMode::CGB),
+        ch3_wave_ram_dac_on_rw => ("apu/channel_3/channel_3_wave_ram_dac_on_rw", GBMode::CGB),
+        ch3_wave_ram_locked_write => ("apu/channel_3/channel_3_wave_ram_locked_write", GBMode::CGB),
+        ch3_wave_ram_sync => ("apu/channel_3/channel_3_wave_ram_sync", GBMode::CGB),
+        // APU channel 4
+        ch4_align => ("apu/channel_4/channel_4_align", GBMode::CGB),
+        ch4_delay => ("apu/channel_4/channel_4_delay", GBMode::CGB),
+        ch4_equivalent_frequencies => ("apu/channel_4/channel_4_equivalent_frequencies", GBMode::CGB),
+        ch4_freq_change => ("apu/channel_4/channel_4_freq_change", GBMode::CGB),
+        ch4_frequency_alignment => ("apu/channel_4/channel_4_frequency_alignment", GBMode::CGB),
+        ch4_lfsr => ("apu/channel_4/channel_4_lfsr", GBMode::CGB),
+        ch4_lfsr15 => ("apu/channel_4/channel_4_lfsr15", GBMode::CGB),
+        ch4_lfsr_15_7 => ("apu/channel_4/channel_4_lfsr_15_7", GBMode::CGB),
+        ch4_lfsr_7_15 => ("apu/channel_4/channel_4_lfsr_7_15", GBMode::CGB),
+        ch4_lfsr_restart => ("apu/channel_4/channel_4_lfsr_restart", GBMode::CGB),
+        ch4_lfsr_restart_fast => ("apu/channel_4/channel_4_lfsr_restart_fast", GBMode::CGB),
+        ch4_volume_div => ("apu/channel_4/channel_4_volume_div", GBMode::CGB),
+        // DMA
+        dma_gbc_dma_cont => ("dma/gbc_dma_cont", GBMode::CGB),
+        dma_gdma_addr_mask => ("dma/gdma_addr_mask", GBMode::CGB),
+        dma_hdma_lcd_off => ("dma/hdma_lcd_off", GBMode::CGB),
+        dma_hdma_mode0 => ("dma/hdma_mode0", GBMode::CGB),
+        // Interrupts
+        interrupt_ei_delay_halt => ("interrupt/ei_delay_halt", GBMode::CGB),
+        // PPU
+        ppu_blocking_bgpi_increase => ("ppu/blocking_bgpi_increase", GBMode::CGB),
+    ];
+}
@@ -0,0 +1,208 @@
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::framebuffer::FramebufferReader;
+use tetsuyu::hw::motherboard::Motherboard;
+use tetsuyu::script::script::Script;
+
+mod common;
+use common::{run_to_program, test_machine_with_screen, test_rom};
+
+/// Counts up at $C000 forever, taking the VBlank interrupt.
+#[rustfmt::skip]
+const PROGRAM: [u8; 15] = [
+    0x31, 0xFE, 0xDF,   // $0150: LD SP,$DFFE
+    0x3E, 0x01,         // $0153: LD A,$01
+    0xE0, 0xFF,         // $0155: LDH (IE),A
+    0xFB,               // $0157: EI
+    0xFA, 0x00, 0xC0,   // $0158: LD A,($C000)
+    0x3C,               // $015B: INC A
+    0xEA, 0x00, 0xC0,   // $015C: LD ($C000),A
+];
+
+/// A machine past the boot ROM with the LCD on, about to run `PROGRAM`, and
+/// its screen.
+fn machine() -> (Motherboard, FramebufferReader) {
+    let mut rom = test_rom(&PROGRAM);
+    rom[0x0040] = 0xD9; // RETI
+    rom[0x015F..0x0161].copy_from_slice(&[0x18, 0xF7]); // JR $0158
+    let (mut mb, reader) = test_machine_with_screen(rom, GBMode::DMG);
+    run_to_program(&mut mb);
+    mb.write_bus(0xFF40, 0x91);
+    (mb, reader)
+}
+
+/// Run `steps` instructions, running the script's callbacks after each.
+fn run(mb: &mut Motherboard, script: &mut Script, steps: usize) {
+    for _ in 0..steps {
+        mb.step();
+        script.step(mb).unwrap();
+    }
+}
+
+#[test]
+fn save_states() {
+    let (mut mb, _reader) = machine();
+    for _ in 0..1000 {
+        mb.step();
+    }
+    let state = mb.save_state();
+    let (pc, count) = (mb.pc(), mb.peek(0xC000));
+
+    for _ in 0..5000 {
+        mb.step();
+    }
+    assert_ne!(mb.peek(0xC000), count);
+    mb.load_state(&state);
+    assert_eq!((mb.pc(), mb.peek(0xC000)), (pc, count));
+
+    // Running on from a state goes the same way each time.
+    let after = |mb: &mut Motherboard| {
+        for _ in 0..20000 {
+            mb.step();
+        }
+        (mb.pc(), mb.ly(), mb.peek(0xC000), mb.cpu_regs().sp)
+    };
+    let first = after(&mut mb);
+    mb.load_state(&state);
+    assert_eq!(after(&mut mb), first);
+}
+
+#[test]
+fn memory_registers_and_input() {
+    let (mut mb, _reader) = machine();
+    let source = r#"
+        write(0xC100, 0x12);
+        write16(0xC102, 0xBEEF);
+        write(0xC104, read(0xC100) + read16(0xC102));
+        set_reg("B", 0x34);
+        write(0xC105, reg("b"));
+        press("start");
+        press("Down");
+    "#;
+    let script = Script::new(source, &mut mb).unwrap();
+    assert_eq!(
+        mb.peek_range(0xC100, 6),
+        [0x12, 0x00, 0xEF, 0xBE, 0x01, 0x34]
+    );
+    assert_eq!(mb.cpu_regs().b, 0x34);
+
+    // Start and Down read as pressed (0) with both button groups selected.
+    mb.write_bus(0xFF00, 0x00);
+    assert_eq!(mb.read_bus(0xFF00) & 0x0F, 0b0111);
+    script.unload(&mut mb);
+    assert!(mb.hooks().is_none());
+
+    assert!(Script::new("write(0x10000, 1);", &mut mb).is_err());
+    assert!(Script::new("press(\"turbo\");", &mut mb).is_err());
+    assert!(Script::new("let x = ;", &mut mb).is_err());
+    assert!(mb.hooks().is_none());
+}
+
+#[test]
+fn callbacks() {
+    let (mut mb, _reader) = machine();
+    let source = r#"
+        let frames = 0;
+        on_frame(|n| { frames += 1; write(0xC200, n); write(0xC201, frames); });
+        on_execute(0x015B, |pc| write(0xC202, read(0xC202) + 1));
+        on_write(0xC000, 0xC000, |a, v| if v == 100 { write(0xC203, a >> 8); });
+        on_interrupt(|vector| write(0xC204, vector));
+    "#;
+    let mut script = Script::new(source, &mut mb).unwrap();
+    while mb.peek(0xC200) < 2 {
+        run(&mut mb, &mut script, 1);
+    }
+    assert_eq!(mb.peek(0xC201), 2);
+    while mb.pc() != 0x0158 {
+        run(&mut mb, &mut script, 1);
+    }
+    assert_eq!(mb.peek(0xC202), mb.peek(0xC000));
+    assert_eq!(mb.peek(0xC203), 0xC0);
+    run(&mut mb, &mut script, 100);
+    assert_eq!(mb.peek(0xC204), 0x40);
+
+    // A failing callback stops the script's step with its error.
+    let (mut mb, _reader) = machine();
+    let mut script = Script::new("on_execute(0x0158, |pc| read(-1));", &mut mb).unwrap();
+    let err = loop {
+        mb.step();
+        if let Err(err) = script.step(&mut mb) {
+            break err;
+        }
+    };
+    assert_eq!(mb.pc(), 0x0158);
+    assert!(err.contains("out of range"));
+}
+
+#[test]
+fn callbacks_do_not_reenter() {
+    let (mut mb, _reader) = machine();
+    // Each store of the count clears it again and goes round from the top,
+    // hooking the count's reads the first time; none of which runs the
+    // machine, so none of it calls back into the script mid-callback.
+    let source = r#"
+        on_write(0xC000, 0xC000, |a, v| {
+            write(0xC000, 0);
+            set_reg("PC", 0x0158);
+            write(0xC100, read(0xC100) | (1 << v));
+            if read(0xC101) == 0 {
+                on_read(0xC000, 0xC000, |a, v| write(0xC101, read(0xC101) + 1));
+            }
+        });
+    "#;
+    let mut script = Script::new(source, &mut mb).unwrap();
+    run(&mut mb, &mut script, 400);
+    // Only the program's stores of 1 were seen, not the callback's own 0s.
+    assert_eq!(mb.peek(0xC100), 0b10);
+    assert!(mb.peek(0xC101) > 1);
+    assert!(mb.peek(0xC000) <= 1);
+}
+
+#[test]
+fn save_states_from_scripts() {
+    let (mut mb, _reader) = machine();
+    let source = r#"
+        let state = save_state();
+        let loaded = false;
+        on_write(0xC000, 0xC000, |a, v| {
+            if v == 50 && !loaded { loaded = true; load_state(state); }
+        });
+    "#;
+    let mut script = Script::new(source, &mut mb).unwrap();
+    run(&mut mb, &mut script, 400);
+    // Back to 0 at 50, then on from there: 4 instructions a count.
+    assert!(mb.peek(0xC000) < 60);
+}
+
+#[test]
+fn overlay_and_reload() {
+    let (mut mb, mut reader) = machine();
+    let path = std::env::temp_dir().join(format!("tetsuyu-script-{}.rhai", std::process::id()));
+    std::fs::write(&path, "on_frame(|n| fill(0, 0, 2, 2, 0xFF0000FF));").unwrap();
+    let mut script = Script::load(&path, &mut mb).unwrap();
+
+    // Drawn on one frame and shown on the next.
+    let pixel = |mb: &mut Motherboard, script: &mut Script, reader: &mut FramebufferReader| {
+        let seen = reader.poll();
+        while reader.poll() < seen + 3 {
+            run(mb, script, 1);
+        }
+        reader.get_latest_frame()[..4].to_vec()
+    };
+    assert_eq!(
+        pixel(&mut mb, &mut script, &mut reader),
+        [0xFF, 0x00, 0x00, 0xFF]
+    );
+
+    std::fs::write(&path, "on_frame(|n| text(0, 0, \"8\", 0x0000FF80));").unwrap();
+    script.reload(&mut mb).unwrap();
+    let [r, g, b, _] = pixel(&mut mb, &mut script, &mut reader)[..] else {
+        unreachable!()
+    };
+    assert!(b > r && b > g);
+
+    // A reload that doesn't compile leaves the old script running.
+    std::fs::write(&path, "on_frame(|n| ").unwrap();
+    assert!(script.reload(&mut mb).is_err());
+    assert_eq!(mb.hooks().unwrap().len(), 2);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,139 @@
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::debugger::repl::parse_address;
+use tetsuyu::debugger::search::{Compare, Search, Watch, WatchList, Width, gameshark};
+use tetsuyu::hw::motherboard::Motherboard;
+
+mod common;
+use common::{run_to_program, test_machine, test_rom};
+
+/// A machine past the boot ROM, spinning at $0150.
+fn machine(mode: GBMode) -> Motherboard {
+    let mut mb = test_machine(test_rom(&[0x18, 0xFE]), mode); // JR @
+    run_to_program(&mut mb);
+    mb
+}
+
+fn addresses(search: &Search) -> Vec<u16> {
+    search.candidates().iter().map(|c| c.address).collect()
+}
+
+#[test]
+fn narrowing_down_bytes() {
+    let mut mb = machine(GBMode::DMG);
+    mb.write_bus(0xC100, 5);
+    mb.write_bus(0xFF90, 5);
+
+    let mut search = Search::new(&mb, Width::Byte);
+    assert_eq!(search.len(), 0x2000 + 0x2000 + 0x7F);
+
+    // A life lost at $C100; HRAM goes the other way.
+    mb.write_bus(0xC100, 4);
+    mb.write_bus(0xFF90, 6);
+    assert_eq!(search.filter(&mb, Compare::Changed), 2);
+    assert_eq!(search.filter(&mb, Compare::Unchanged), 2);
+    assert_eq!(search.filter(&mb, Compare::Equal(4)), 1);
+    assert_eq!(addresses(&search), [0xC100]);
+
+    mb.write_bus(0xC100, 3);
+    search.filter(&mb, Compare::DecreasedBy(1));
+    let candidate = search.candidates()[0];
+    assert_eq!((candidate.previous, candidate.value), (4, 3));
+
+    // Wrapping around from 0 counts as taking one away.
+    mb.write_bus(0xC100, 0);
+    assert_eq!(search.filter(&mb, Compare::Decreased), 1);
+    mb.write_bus(0xC100, 0xFF);
+    assert_eq!(search.filter(&mb, Compare::DecreasedBy(1)), 1);
+    assert_eq!(search.filter(&mb, Compare::Increased), 0);
+    assert!(search.is_empty());
+}
+
+#[test]
+fn words_and_gameshark_codes() {
+    let mut mb = machine(GBMode::DMG);
+    let mut search = Search::new(&mb, Width::Word);
+    // The last byte of each area has no second byte to pair with.
+    assert_eq!(search.len(), 0x1FFF + 0x1FFF + 0x7E);
+
+    mb.write_bus(0xC200, 0x34);
+    mb.write_bus(0xC201, 0x12);
+    search.filter(&mb, Compare::IncreasedBy(0x1234));
+    assert_eq!(addresses(&search), [0xC200]);
+    mb.write_bus(0xC200, 0x00);
+    mb.write_bus(0xC201, 0x13);
+    search.filter(&mb, Compare::Greater(0x1234));
+    assert_eq!(search.candidates()[0].value, 0x1300);
+
+    assert_eq!(gameshark(&mb, 0xC100, 0x63, Width::Byte), ["016300C1"]);
+    assert_eq!(
+        gameshark(&mb, 0xC200, 0x1234, Width::Word),
+        ["013400C2", "011201C2"]
+    );
+    // SVBK means nothing on the DMG.
+    assert_eq!(gameshark(&mb, 0xD000, 0x01, Width::Byte), ["010100D0"]);
+
+    let mut mb = machine(GBMode::CGB);
+    mb.write_bus(0xFF70, 3);
+    assert_eq!(gameshark(&mb, 0xD000, 0x01, Width::Byte), ["930100D0"]);
+    assert_eq!(gameshark(&mb, 0xC000, 0x01, Width::Byte), ["010100C0"]);
+}
+
+#[test]
+fn parsing_comparisons() {
+    assert_eq!(Compare::parse("= 10"), Ok(Compare::Equal(10)));
+    assert_eq!(Compare::parse("$0A"), Ok(Compare::Equal(10)));
+    assert_eq!(Compare::parse("!=$FF"), Ok(Compare::NotEqual(0xFF)));
+    assert_eq!(Compare::parse("< 3"), Ok(Compare::Less(3)));
+    assert_eq!(Compare::parse("+1"), Ok(Compare::IncreasedBy(1)));
+    assert_eq!(Compare::parse("- 2"), Ok(Compare::DecreasedBy(2)));
+    assert_eq!(Compare::parse("same"), Ok(Compare::Unchanged));
+    assert_eq!(Compare::parse("down"), Ok(Compare::Decreased));
+    assert!(Compare::parse("> lots").is_err());
+
+    let watch = Watch::parse("hp=C200:16", parse_address).unwrap();
+    assert_eq!((watch.address, watch.width), (0xC200, Width::Word));
+    assert_eq!(watch.to_string(), "hp");
+    let watch = Watch::parse("$FF90", parse_address).unwrap();
+    assert_eq!((watch.address, watch.width), (0xFF90, Width::Byte));
+    assert_eq!(watch.to_string(), "$FF90");
+    assert!(Watch::parse("C200:32", parse_address).is_err());
+}
+
+#[test]
+fn watches_log_frames_that_change() {
+    let mut mb = machine(GBMode::DMG);
+    mb.write_bus(0xFF40, 0x80);
+    mb.write_bus(0xC100, 3);
+    let mut watches = WatchList::new();
+    watches.add(Watch::parse("lives=C100", parse_address).unwrap());
+    watches.add(Watch::parse("C200:16", parse_address).unwrap());
+    assert_eq!(watches.show(&mb), "lives=$03 $C200=$0000");
+
+    // Run to the start of the next VBlank, giving back what was logged.
+    let next_frame = |mb: &mut Motherboard, watches: &mut WatchList| {
+        let mut logged = None;
+        while mb.ly() == 144 {
+            mb.step();
+            logged = logged.or(watches.frame(mb));
+        }
+        while mb.ly() != 144 {
+            mb.step();
+            logged = logged.or(watches.frame(mb));
+        }
+        logged
+    };
+    assert_eq!(
+        next_frame(&mut mb, &mut watches).as_deref(),
+        Some("frame 1: lives=$03 $C200=$0000")
+    );
+    assert_eq!(next_frame(&mut mb, &mut watches), None);
+    mb.write_bus(0xC201, 0x01);
+    assert_eq!(
+        next_frame(&mut mb, &mut watches).as_deref(),
+        Some("frame 3: lives=$03 $C200=$0100")
+    );
+
+    assert!(watches.remove(0xC100));
+    assert!(!watches.remove(0xC100));
+    assert_eq!(watches.len(), 1);
+}
@@ -0,0 +1,57 @@
+use tetsuyu::components::memory::Memory;
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::components::serial::Serial;
+use tetsuyu::hw::interrupt::Interrupts;
+
+/// Starts a transfer with `sc` and ticks the port with a divider counting
+/// from zero, one T-cycle per tick, until SC bit 7 clears. Gives up after
+/// `limit` cycles.
+fn transfer(mode: GBMode, sc: u8, limit: u32) -> (Serial, Option<u32>) {
+    let mut serial = Serial::new(false, mode);
+    serial.write(0xFF01, 0x42);
+    serial.write(0xFF02, sc);
+    for cycles in 1..=limit {
+        serial.tick(cycles as u16);
+        if serial.read(0xFF02) & 0x80 == 0 {
+            return (serial, Some(cycles));
+        }
+    }
+    (serial, None)
+}
+
+#[test]
+fn internal_clock_takes_8192hz() {
+    let (serial, cycles) = transfer(GBMode::DMG, 0x81, 10_000);
+    assert_eq!(cycles, Some(8 * 512));
+    assert!(serial.interrupts.contains(Interrupts::SERIAL));
+    assert_eq!(serial.read(0xFF01), 0xFF);
+}
+
+#[test]
+fn cgb_fast_clock_takes_262144hz() {
+    let (serial, cycles) = transfer(GBMode::CGB, 0x83, 10_000);
+    assert_eq!(cycles, Some(8 * 16));
+    assert!(serial.interrupts.contains(Interrupts::SERIAL));
+}
+
+#[test]
+fn dmg_ignores_fast_clock() {
+    let (_, cycles) = transfer(GBMode::DMG, 0x83, 10_000);
+    assert_eq!(cycles, Some(8 * 512));
+}
+
+#[test]
+fn interrupt_waits_for_the_last_bit() {
+    let (serial, cycles) = transfer(GBMode::DMG, 0x81, 8 * 512 - 1);
+    assert_eq!(cycles, None);
+    assert_eq!(serial.read(0xFF02) & 0x80, 0x80);
+    assert!(!serial.interrupts.contains(Interrupts::SERIAL));
+}
+
+#[test]
+fn external_clock_alone_never_completes() {
+    let (serial, cycles) = transfer(GBMode::DMG, 0x80, 1 << 20);
+    assert_eq!(cycles, None);
+    assert!(!serial.interrupts.contains(Interrupts::SERIAL));
+    assert_eq!(serial.read(0xFF01), 0x42);
+}
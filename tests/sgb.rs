@@ -0,0 +1,407 @@
+use tetsuyu::CLOCK_FREQUENCY;
+use tetsuyu::components::joypad::Joypad;
+use tetsuyu::components::memory::Memory;
+use tetsuyu::components::ppu::ppu::{SCREEN_H, SCREEN_W};
+use tetsuyu::components::sgb::packet::{Command, CommandData, PacketReader};
+use tetsuyu::components::sgb::sgb::{Mask, SGB_SCREEN_H, SGB_SCREEN_W, Sgb};
+use tetsuyu::components::spc::spc::Spc;
+use tetsuyu::framebuffer::{FramebufferReader, FramebufferWriter, create_framebuffer_pair};
+
+/// Bit-bang `packet` through `reader` the way a game drives `JOYP`.
+fn send_packet(reader: &mut PacketReader, packet: &[u8; 16]) -> Option<CommandData> {
+    reader.write(0x00);
+    reader.write(0x30);
+    for i in 0..128 {
+        let bit = packet[i / 8] >> (i % 8) & 1 != 0;
+        reader.write(if bit { 0x10 } else { 0x20 });
+        reader.write(0x30);
+    }
+    let command = reader.write(0x20);
+    reader.write(0x30);
+    command
+}
+
+#[test]
+fn packet_single() {
+    let mut reader = PacketReader::new();
+    let mut packet = [0u8; 16];
+    packet[0] = (0x11 << 3) | 1;
+    packet[1] = 0x03;
+
+    let command = send_packet(&mut reader, &packet).expect("no command decoded");
+    assert_eq!(command.command, Command::MltReq);
+    assert_eq!(command.byte(1), 0x03);
+}
+
+#[test]
+fn packet_multi() {
+    let mut reader = PacketReader::new();
+    let mut first = [0u8; 16];
+    first[0] = (0x04 << 3) | 2;
+    let mut second = [0u8; 16];
+    second[0] = 0xAB;
+
+    assert!(send_packet(&mut reader, &first).is_none());
+    let command = send_packet(&mut reader, &second).expect("no command decoded");
+    assert_eq!(command.command, Command::AttrBlk);
+    assert_eq!(command.data.len(), 32);
+    assert_eq!(command.byte(16), 0xAB);
+}
+
+/// A one-packet command with `params` after the header byte.
+fn command(command: Command, params: &[u8]) -> CommandData {
+    let mut data = vec![0; 16];
+    data[0] = (command as u8) << 3 | 1;
+    data[1..=params.len()].copy_from_slice(params);
+    CommandData { command, data }
+}
+
+/// Little-endian bytes of `words`, for palette commands.
+fn words(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+#[test]
+fn pal01_and_pal23_set_screen_palettes() {
+    let mut sgb = Sgb::new();
+    let pal01 = words(&[0x1000, 0x0001, 0x0002, 0x0003, 0x0011, 0x0012, 0x0013]);
+    sgb.command(command(Command::Pal01, &pal01));
+    let pal23 = words(&[0x2000, 0x0021, 0x0022, 0x0023, 0x0031, 0x0032, 0x0033]);
+    sgb.command(command(Command::Pal23, &pal23));
+
+    for palette in 0..4 {
+        // Colour 0 is shared, and the last command sent sets it.
+        assert_eq!(sgb.palette_color(palette, 0), 0x2000);
+        for i in 1..4 {
+            assert_eq!(sgb.palette_color(palette, i), (palette * 0x10 + i) as u16);
+        }
+    }
+}
+
+#[test]
+fn attr_blk_paints_inside_line_and_outside() {
+    let mut sgb = Sgb::new();
+    // One block from cell (2,2) to (5,6): inside 1, line 2, outside 3.
+    sgb.command(command(Command::AttrBlk, &[1, 0x07, 0x39, 2, 2, 5, 6]));
+
+    assert_eq!(sgb.attribute(3, 4), 1);
+    assert_eq!(sgb.attribute(2, 4), 2);
+    assert_eq!(sgb.attribute(5, 6), 2);
+    assert_eq!(sgb.attribute(4, 2), 2);
+    assert_eq!(sgb.attribute(0, 0), 3);
+    assert_eq!(sgb.attribute(6, 6), 3);
+    assert_eq!(sgb.attribute(3, 7), 3);
+}
+
+#[test]
+fn attr_lin_paints_rows_and_columns() {
+    let mut sgb = Sgb::new();
+    // Row 3 in palette 1, then column 4 in palette 2.
+    sgb.command(command(
+        Command::AttrLin,
+        &[2, 0x80 | 1 << 5 | 3, 2 << 5 | 4],
+    ));
+
+    assert_eq!(sgb.attribute(0, 3), 1);
+    assert_eq!(sgb.attribute(19, 3), 1);
+    assert_eq!(sgb.attribute(4, 0), 2);
+    assert_eq!(sgb.attribute(4, 3), 2);
+    assert_eq!(sgb.attribute(4, 17), 2);
+    assert_eq!(sgb.attribute(0, 0), 0);
+}
+
+#[test]
+fn attr_div_splits_the_screen() {
+    let mut sgb = Sgb::new();
+    // Above row 5 in palette 1, row 5 itself in 2 and below it in 3.
+    sgb.command(command(Command::AttrDiv, &[0x40 | 2 << 4 | 1 << 2 | 3, 5]));
+    for x in 0..20 {
+        assert_eq!(sgb.attribute(x, 4), 1);
+        assert_eq!(sgb.attribute(x, 5), 2);
+        assert_eq!(sgb.attribute(x, 6), 3);
+    }
+
+    // The same split on columns.
+    sgb.command(command(Command::AttrDiv, &[2 << 4 | 1 << 2 | 3, 5]));
+    for y in 0..18 {
+        assert_eq!(sgb.attribute(4, y), 1);
+        assert_eq!(sgb.attribute(5, y), 2);
+        assert_eq!(sgb.attribute(6, y), 3);
+    }
+}
+
+/// Draws `data` as the 256 tiles of a VRAM transfer, 20 to a row.
+fn show(sgb: &mut Sgb, data: &[u8]) {
+    for (tile, bytes) in data.chunks(16).enumerate() {
+        let (tx, ty) = (tile % 20, tile / 20);
+        for (row, pair) in bytes.chunks(2).enumerate() {
+            for px in 0..8 {
+                let bit = |b: u8| (b >> (7 - px)) & 1;
+                let shade = bit(pair[0]) | bit(pair[1]) << 1;
+                sgb.set_shade(tx * 8 + px, ty * 8 + row, shade);
+            }
+        }
+    }
+}
+
+fn fill(sgb: &mut Sgb, shade: u8) {
+    for y in 0..SCREEN_H {
+        for x in 0..SCREEN_W {
+            sgb.set_shade(x, y, shade);
+        }
+    }
+}
+
+/// Colours come out with the 15-bit value in red and green.
+fn map(c: u16) -> [u8; 3] {
+    [c as u8, (c >> 8) as u8, 0x55]
+}
+
+fn screen() -> (FramebufferWriter, FramebufferReader) {
+    let (mut writer, reader) = create_framebuffer_pair();
+    writer.resize(SGB_SCREEN_W, SGB_SCREEN_H);
+    (writer, reader)
+}
+
+/// Renders a frame and returns the colour at (`x`, `y`) of the 256×224
+/// picture.
+fn render(
+    sgb: &mut Sgb,
+    screen: &mut (FramebufferWriter, FramebufferReader),
+    x: usize,
+    y: usize,
+) -> u16 {
+    sgb.render(&mut screen.0, map);
+    screen.0.submit_frame();
+    let frame = screen.1.get_latest();
+    let i = (y * frame.width + x) * 4;
+    assert_eq!(frame.data[i + 2], 0x55);
+    frame.data[i] as u16 | (frame.data[i + 1] as u16) << 8
+}
+
+#[test]
+fn pal_trn_then_pal_set_applies_system_palettes() {
+    let mut sgb = Sgb::new();
+    let mut screen = screen();
+
+    // System palette `i` colour `j` is `i * 4 + j`.
+    let data = words(&(0..2048).collect::<Vec<u16>>());
+    show(&mut sgb, &data);
+    sgb.command(command(Command::PalTrn, &[]));
+    render(&mut sgb, &mut screen, 0, 0);
+
+    sgb.command(command(Command::PalSet, &words(&[5, 6, 7, 0x1FF])));
+    assert_eq!(sgb.palette_color(0, 0), 20);
+    for (palette, system) in [5, 6, 7, 0x1FF].into_iter().enumerate() {
+        for i in 1..4 {
+            assert_eq!(sgb.palette_color(palette, i), system * 4 + i as u16);
+        }
+    }
+}
+
+#[test]
+fn mask_en_freezes_and_blanks_the_screen() {
+    let mut sgb = Sgb::new();
+    let mut screen = screen();
+    let pal01 = words(&[0x0100, 0x0101, 0x0102, 0x0103, 0x0201, 0x0202, 0x0203]);
+    sgb.command(command(Command::Pal01, &pal01));
+    let centre = (SGB_SCREEN_W / 2, SGB_SCREEN_H / 2);
+    let mut render = |sgb: &mut Sgb| render(sgb, &mut screen, centre.0, centre.1);
+
+    fill(&mut sgb, 3);
+    assert_eq!(render(&mut sgb), 0x0103);
+
+    // Frozen: the last frame stays up whatever is drawn.
+    sgb.command(command(Command::MaskEn, &[1]));
+    assert_eq!(sgb.mask(), Mask::Freeze);
+    fill(&mut sgb, 1);
+    assert_eq!(render(&mut sgb), 0x0103);
+
+    sgb.command(command(Command::MaskEn, &[2]));
+    assert_eq!(sgb.mask(), Mask::Black);
+    assert_eq!(render(&mut sgb), 0x0000);
+
+    sgb.command(command(Command::MaskEn, &[3]));
+    assert_eq!(sgb.mask(), Mask::Color0);
+    assert_eq!(render(&mut sgb), 0x0100);
+
+    sgb.command(command(Command::MaskEn, &[0]));
+    assert_eq!(sgb.mask(), Mask::None);
+    assert_eq!(render(&mut sgb), 0x0101);
+}
+
+#[test]
+fn chr_trn_and_pct_trn_draw_the_border() {
+    let mut sgb = Sgb::new();
+    let mut screen = screen();
+    let backdrop = sgb.palette_color(0, 0);
+    assert_eq!(render(&mut sgb, &mut screen, 0, 0), backdrop);
+
+    // Border tile 1 has its top-left pixel in colour 1.
+    let mut tiles = vec![0; 0x1000];
+    tiles[32] = 0x80;
+    show(&mut sgb, &tiles);
+    sgb.command(command(Command::ChrTrn, &[0]));
+    render(&mut sgb, &mut screen, 0, 0);
+
+    // The top-left map entry uses that tile with palette 4, whose colour 1
+    // is $7C1F.
+    let mut map = vec![0; 0x1000];
+    map[0] = 0x01;
+    map[0x802..0x804].copy_from_slice(&0x7C1Fu16.to_le_bytes());
+    show(&mut sgb, &map);
+    sgb.command(command(Command::PctTrn, &[]));
+
+    assert_eq!(render(&mut sgb, &mut screen, 0, 0), 0x7C1F);
+    // Colour 0 is see-through.
+    assert_eq!(render(&mut sgb, &mut screen, 1, 0), backdrop);
+}
+
+#[test]
+fn mlt_req_cycles_joypad_ids() {
+    let mut joypad = Joypad::new(true);
+    let mut write = |v| joypad.write(0xFF00, v);
+    let mut packet = [0u8; 16];
+    packet[0] = (Command::MltReq as u8) << 3 | 1;
+    packet[1] = 0x03;
+
+    // The same bit-banging as `send_packet`, through JOYP.
+    write(0x00);
+    write(0x30);
+    for i in 0..128 {
+        let bit = packet[i / 8] >> (i % 8) & 1 != 0;
+        write(if bit { 0x10 } else { 0x20 });
+        write(0x30);
+    }
+    write(0x20);
+    write(0x30);
+    assert!(joypad.take_sgb_command().is_some());
+
+    // Each pulse on P15 moves on to the next of the four controllers, whose
+    // ID reads back with neither line selected.
+    let mut ids = Vec::new();
+    for _ in 0..5 {
+        ids.push(joypad.read(0xFF00) & 0x0F);
+        joypad.write(0xFF00, 0x10);
+        joypad.write(0xFF00, 0x30);
+    }
+    assert_eq!(ids, [0x0F, 0x0E, 0x0D, 0x0C, 0x0F]);
+}
+
+/// SOU_TRN data: a program at $0200 that sets up voice 0 to loop a constant
+/// positive BRR sample at full volume, the sample directory and the sample.
+/// With `on_sound`, the voice is only keyed on once a SOUND command puts a
+/// sound effect A code in port 0.
+fn sound_upload(on_sound: bool) -> Vec<u8> {
+    let dsp_writes: [(u8, u8); 11] = [
+        (0x0C, 0x7F), // MVOLL
+        (0x1C, 0x7F), // MVOLR
+        (0x6C, 0x20), // FLG: unmuted, echo writes off
+        (0x5D, 0x03), // DIR = $0300
+        (0x00, 0x7F), // V0 VOLL
+        (0x01, 0x7F), // V0 VOLR
+        (0x02, 0x00), // V0 PITCHL
+        (0x03, 0x10), // V0 PITCHH
+        (0x04, 0x00), // V0 SRCN
+        (0x05, 0x00), // V0 ADSR1: GAIN mode
+        (0x07, 0x7F), // V0 GAIN: direct, maximum
+    ];
+    let mut program = Vec::new();
+    for (reg, value) in dsp_writes {
+        program.extend_from_slice(&[0x8F, reg, 0xF2, 0x8F, value, 0xF3]);
+    }
+    if on_sound {
+        program.extend_from_slice(&[0xE4, 0xF4, 0xF0, 0xFC]); // MOV A,$F4; BEQ *-2
+    }
+    program.extend_from_slice(&[0x8F, 0x4C, 0xF2, 0x8F, 0x01, 0xF3]); // KON
+    program.extend_from_slice(&[0x2F, 0xFE]); // BRA *
+
+    let mut data = Vec::new();
+    let mut block = |address: u16, bytes: &[u8]| {
+        data.extend_from_slice(&(bytes.len() as u16).to_le_bytes());
+        data.extend_from_slice(&address.to_le_bytes());
+        data.extend_from_slice(bytes);
+    };
+    block(0x0200, &program);
+    block(0x0300, &[0x00, 0x04, 0x00, 0x04]);
+    block(0x0400, &[0xB3, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77]);
+    data.extend_from_slice(&[0x00, 0x00, 0x00, 0x02]);
+    data
+}
+
+#[test]
+fn spc_upload_plays_sample() {
+    let mut spc = Spc::new();
+    for _ in 0..CLOCK_FREQUENCY / 100 {
+        spc.advance();
+    }
+    assert_eq!(spc.output(), (0, 0));
+
+    spc.upload(&sound_upload(false));
+    for _ in 0..CLOCK_FREQUENCY / 10 {
+        spc.advance();
+    }
+    let (left, right) = spc.output();
+    assert!(left > 0x1000, "left output {left:#x}");
+    assert_eq!(left, right);
+}
+
+/// The loudest left output over the next 10ms.
+fn peak(spc: &mut Spc) -> i32 {
+    let mut peak = 0;
+    for _ in 0..CLOCK_FREQUENCY / 100 {
+        spc.advance();
+        peak = peak.max((spc.output().0 as i32).abs());
+    }
+    peak
+}
+
+#[test]
+fn sound_codes_play_built_in_effects() {
+    let mut spc = Spc::new();
+    assert_eq!(peak(&mut spc), 0);
+
+    // Every sound effect A and B code plays, and $80 stops it again.
+    for a in 0x01..=0x30 {
+        spc.write_ports([a, 0x00, 0x00, 0x00]);
+        let loudness = peak(&mut spc);
+        assert!(loudness > 0x1000, "effect A {a:#04x}: {loudness:#x}");
+        spc.write_ports([0x80, 0x00, 0x00, 0x00]);
+        peak(&mut spc);
+        assert_eq!(peak(&mut spc), 0, "effect A {a:#04x} didn't stop");
+    }
+    for b in 0x01..=0x19 {
+        spc.write_ports([0x00, b, 0x00, 0x00]);
+        let loudness = peak(&mut spc);
+        assert!(loudness > 0x1000, "effect B {b:#04x}: {loudness:#x}");
+        spc.write_ports([0x00, 0x80, 0x00, 0x00]);
+        peak(&mut spc);
+        assert_eq!(peak(&mut spc), 0, "effect B {b:#04x} didn't stop");
+    }
+
+    // The volume attributes turn effects down, and mute them.
+    spc.write_ports([0x01, 0x00, 0x08, 0x00]);
+    let low = peak(&mut spc);
+    assert!(low > 0 && low < 0x1000, "low volume {low:#x}");
+    spc.write_ports([0x80, 0x01, 0xC0, 0x00]);
+    peak(&mut spc);
+    assert_eq!(peak(&mut spc), 0);
+}
+
+#[test]
+fn uploaded_driver_replaces_built_in_one() {
+    let run = |spc: &mut Spc| {
+        for _ in 0..CLOCK_FREQUENCY / 100 {
+            spc.advance();
+        }
+        spc.output()
+    };
+
+    let mut spc = Spc::new();
+    spc.upload(&sound_upload(true));
+    assert_eq!(run(&mut spc), (0, 0));
+    spc.write_ports([0x01, 0x00, 0x00, 0x00]);
+    run(&mut spc);
+    let (left, _) = run(&mut spc);
+    assert!(left > 0x1000, "left output {left:#x}");
+}
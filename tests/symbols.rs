@@ -0,0 +1,130 @@
+use std::sync::mpsc::channel;
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::debugger::repl::Repl;
+use tetsuyu::debugger::symbols::Symbols;
+use tetsuyu::debugger::trace::{TraceOptions, Tracer};
+use tetsuyu::disasm::{disassemble, listing, rom_reader};
+use tetsuyu::hw::motherboard::Motherboard;
+
+mod common;
+use common::{test_machine, test_rom};
+
+#[rustfmt::skip]
+const PROGRAM: [u8; 12] = [
+    0x06, 0x03,       // $0150: Main: LD B,3
+    0x05,             // $0152: .loop: DEC B
+    0x20, 0xFD,       // $0153: JR NZ,.loop
+    0xCD, 0x5A, 0x01, // $0155: CALL Done
+    0x18, 0xFE,       // $0158: JR @
+    0x40,             // $015A: Done: LD B,B
+    0xC9,             // $015B: RET
+];
+
+const SYM: &str = "\
+; File generated by rgblink
+00:0150 Main
+00:0152 Main.loop
+00:015a Done
+01:4000 BankedOne
+02:4000 BankedTwo
+00:c000 wCounter
+";
+
+fn machine() -> Motherboard {
+    test_machine(test_rom(&PROGRAM), GBMode::DMG)
+}
+
+#[test]
+fn parse_and_describe() {
+    let symbols = Symbols::parse(SYM);
+    assert_eq!(symbols.len(), 6);
+    assert_eq!(symbols.lookup("Main.loop"), Some((0, 0x0152)));
+    assert_eq!(symbols.lookup("BankedTwo"), Some((2, 0x4000)));
+    assert_eq!(symbols.lookup("main"), None);
+
+    assert_eq!(symbols.label(0, 0x0150), Some("Main"));
+    assert_eq!(symbols.describe(0, 0x0153).as_deref(), Some("Main.loop+1"));
+    // WRAM labels match whatever bank is mapped; ROM banks don't.
+    assert_eq!(symbols.describe(5, 0xC004).as_deref(), Some("wCounter+4"));
+    assert_eq!(symbols.describe(2, 0x4010).as_deref(), Some("BankedTwo+16"));
+    assert_eq!(symbols.describe(3, 0x4010), None);
+    // Nothing reaches past the end of a memory area.
+    assert_eq!(symbols.describe(0, 0x8000), None);
+    assert_eq!(symbols.describe(0, 0x0100), None);
+}
+
+#[test]
+fn wla_dx_sections() {
+    let symbols = Symbols::parse(
+        "[labels]\n0000:0150 main\n0001:4000 banked\n\n\
+         [definitions]\n00000010 _sizeof_main\n\n\
+         [information]\nversion 2\n",
+    );
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols.lookup("main"), Some((0, 0x0150)));
+    assert_eq!(symbols.lookup("banked"), Some((1, 0x4000)));
+}
+
+#[test]
+fn labelled_disassembly() {
+    let symbols = Symbols::parse(SYM);
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0150..0x0150 + PROGRAM.len()].copy_from_slice(&PROGRAM);
+
+    let mut call = disassemble(0x0155, rom_reader(&rom, 1));
+    call.name_target(&symbols, 1);
+    assert_eq!(call.text, "call Done");
+    let mut jr = disassemble(0x0153, rom_reader(&rom, 1));
+    jr.name_target(&symbols, 1);
+    assert_eq!(jr.text, "jr nz, Main.loop");
+
+    let lines = listing(0x0150, 0x0155, 1, &symbols, rom_reader(&rom, 1));
+    assert_eq!(lines[0], "Main:");
+    assert!(lines[1].starts_with("00:0150  06 03"), "{}", lines[1]);
+    assert_eq!(lines[2], "Main.loop:");
+    assert_eq!(lines.len(), 5);
+}
+
+#[test]
+fn labelled_trace() {
+    let out = std::env::temp_dir().join("tetsuyu-symbols-trace.log");
+    let mut tracer = Tracer::create(&out, TraceOptions::default()).unwrap();
+    tracer.set_symbols(Some(Symbols::parse(SYM)));
+    let mut mb = machine();
+    mb.set_tracer(Some(tracer));
+    while !mb.magic_break() {
+        mb.step();
+    }
+    mb.set_tracer(None);
+
+    let text = std::fs::read_to_string(&out).unwrap();
+    let _ = std::fs::remove_file(&out);
+    let lines: Vec<_> = text.lines().collect();
+    // $0004 comes before any label.
+    assert!(!lines[0].contains("SYM:"), "{}", lines[0]);
+    assert!(lines[1].ends_with("PC:0150 PCMEM:06,03,05,20 SYM:Main"));
+    assert!(lines[3].ends_with(" SYM:Main.loop+1"), "{}", lines[3]);
+}
+
+#[test]
+fn break_at_label() {
+    let mut mb = machine();
+    let (commands, input) = channel();
+    let mut repl = Repl::new(input, Vec::new());
+    repl.symbols = Symbols::parse(SYM);
+    for line in ["b Done", "c", "dis Main 3", "q"] {
+        commands.send(line.to_string()).unwrap();
+    }
+    repl.run(&mut mb, |_, _| true);
+
+    let output = String::from_utf8(repl.output().clone()).unwrap();
+    assert!(
+        output.contains("Breakpoint 1 at $015A <Done>"),
+        "{}",
+        output
+    );
+    assert!(output.contains("PC=$015A <Done> AF="), "{}", output);
+    assert!(output.contains("Main:\n00:0150"), "{}", output);
+    assert!(output.contains("jr nz, Main.loop"), "{}", output);
+    assert_eq!(mb.pc(), 0x015A);
+}
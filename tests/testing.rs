@@ -0,0 +1,232 @@
+use tetsuyu::components::mode::{GBMode, Model};
+use tetsuyu::config::{Color, Config};
+use tetsuyu::testing::*;
+
+mod common;
+use common::{BOOT, test_rom};
+
+/// Shows a blank screen in shade 0, sends "Passed" over the serial port and
+/// hits the magic breakpoint.
+#[rustfmt::skip]
+const PROGRAM: [u8; 22] = [
+    0x31, 0xFE, 0xDF,   // $0150: LD SP,$DFFE
+    0xAF,               // $0153: XOR A
+    0xE0, 0x47,         // $0154: LDH (BGP),A
+    0x3E, 0x91,         // $0156: LD A,$91
+    0xE0, 0x40,         // $0158: LDH (LCDC),A
+    0x21, 0x00, 0x02,   // $015A: LD HL,$0200
+    0x2A,               // $015D: LD A,(HL+)
+    0xB7,               // $015E: OR A
+    0x28, 0x05,         // $015F: JR Z,$0166
+    0xCD, 0x70, 0x01,   // $0161: CALL $0170
+    0x18, 0xF7,         // $0164: JR $015D
+];
+
+/// Sends A over the serial port and waits for it to go.
+#[rustfmt::skip]
+const SEND: [u8; 13] = [
+    0xE0, 0x01,         // $0170: LDH (SB),A
+    0x3E, 0x81,         // $0172: LD A,$81
+    0xE0, 0x02,         // $0174: LDH (SC),A
+    0xF0, 0x02,         // $0176: LDH A,(SC)
+    0xCB, 0x7F,         // $0178: BIT 7,A
+    0x20, 0xFA,         // $017A: JR NZ,$0176
+    0xC9,               // $017C: RET
+];
+
+fn rom() -> Vec<u8> {
+    let mut rom = test_rom(&PROGRAM);
+    // $0166: LD B,B then JR $0167, spinning.
+    rom[0x0166..0x0169].copy_from_slice(&[0x40, 0x18, 0xFE]);
+    rom[0x0170..0x0170 + SEND.len()].copy_from_slice(&SEND);
+    rom[0x0200..0x0207].copy_from_slice(b"Passed\0");
+    rom
+}
+
+fn harness(builder: HarnessBuilder) -> Harness {
+    builder.boot_rom_bytes(BOOT.to_vec()).build().unwrap()
+}
+
+#[test]
+fn stop_conditions() {
+    let mut h = harness(HarnessBuilder::from_rom(rom()));
+    assert_eq!(
+        h.run_until(StopCondition::Pc(0x0170), 20 * FRAME_CYCLES),
+        RunOutcome::Met
+    );
+    assert_eq!(h.serial(), b"");
+    assert_eq!(
+        h.run_until(
+            StopCondition::SerialEndsWithAny(&["Passed"]),
+            20 * FRAME_CYCLES
+        ),
+        RunOutcome::Met
+    );
+    assert_eq!(h.serial(), b"Passed");
+    assert_eq!(
+        h.run_until(StopCondition::MagicBreak, 20 * FRAME_CYCLES),
+        RunOutcome::Met
+    );
+    assert_eq!(h.cpu_regs().pc, 0x0167);
+
+    let budget = h.cycles() + 2 * FRAME_CYCLES;
+    assert_eq!(
+        h.run_until(StopCondition::Frames(u64::MAX), budget),
+        RunOutcome::TimedOut
+    );
+    assert!(h.cycles() >= budget);
+}
+
+#[test]
+fn builder() {
+    // Without a boot ROM there is nothing to start from; nothing is read
+    // from the working directory to find one.
+    let err = HarnessBuilder::from_rom(rom()).build().err().unwrap();
+    assert_eq!(err, "no boot ROM given");
+    assert!(Harness::builder("no/such/rom.gb").build().is_err());
+
+    // The reference palette by default, or any other.
+    let frame = |builder: HarnessBuilder| {
+        let mut h = harness(builder);
+        h.run_until(StopCondition::Frames(3), 10 * FRAME_CYCLES);
+        h.framebuffer()[..4].to_vec()
+    };
+    assert_eq!(
+        frame(HarnessBuilder::from_rom(rom())),
+        [0xFF, 0xFF, 0xFF, 0xFF]
+    );
+    let mut palette = reference_palette();
+    palette.light = Color::new(0x123456);
+    assert_eq!(
+        frame(HarnessBuilder::from_rom(rom()).palette(palette)),
+        [0x12, 0x34, 0x56, 0xFF]
+    );
+    // Set before or after a config, the palette wins over the config's.
+    for builder in [
+        HarnessBuilder::from_rom(rom())
+            .palette(palette)
+            .config(Config::default()),
+        HarnessBuilder::from_rom(rom())
+            .config(Config::default())
+            .palette(palette),
+    ] {
+        assert_eq!(frame(builder), [0x12, 0x34, 0x56, 0xFF]);
+    }
+
+    // A config's boot ROM path is used when none is given.
+    let path = std::env::temp_dir().join(format!("tetsuyu-boot-{}.bin", std::process::id()));
+    std::fs::write(&path, BOOT).unwrap();
+    let config = Config {
+        dmg_boot_rom: path.to_str().unwrap().to_string(),
+        ..Config::default()
+    };
+    let h = HarnessBuilder::from_rom(rom())
+        .config(config)
+        .mode(GBMode::DMG)
+        .build();
+    std::fs::remove_file(&path).unwrap();
+    assert!(h.is_ok());
+}
+
+#[test]
+fn skip_boot() {
+    let start = |model: Model| {
+        let mut h = HarnessBuilder::from_rom(rom())
+            .model(model)
+            .skip_boot()
+            .build()
+            .unwrap();
+        let regs = h.cpu_regs();
+        let start = (
+            h.machine().pc(),
+            regs.get_af(),
+            regs.get_de(),
+            regs.get_hl(),
+            regs.sp,
+        );
+        let mb = h.machine_mut();
+        assert_eq!((mb.read_bus(0xFF40), mb.read_bus(0xFF47)), (0x91, 0xFC));
+        // It runs on from $0100 like any other start.
+        assert_eq!(
+            h.run_until(StopCondition::MagicBreak, 20 * FRAME_CYCLES),
+            RunOutcome::Met
+        );
+        assert_eq!(h.serial(), b"Passed");
+        start
+    };
+
+    // The header checksum is 0, so the DMG's boot ROM leaves Z alone set.
+    let regs = start(Model::Dmg);
+    assert_eq!(regs, (0x0100, 0x0180, 0x00D8, 0x014D, 0xFFFE));
+    let mut h = HarnessBuilder::from_rom(rom()).skip_boot().build().unwrap();
+    assert_eq!(h.machine_mut().read_bus(0xFF04), 0xAB);
+
+    let regs = start(Model::Sgb);
+    assert_eq!(regs, (0x0100, 0x0100, 0x0000, 0xC060, 0xFFFE));
+    // A DMG game on the CGB.
+    let regs = start(Model::Cgb);
+    assert_eq!(regs, (0x0100, 0x1180, 0x0008, 0x007C, 0xFFFE));
+}
+
+#[test]
+fn mooneye_names() {
+    assert_eq!(mooneye_models("boot_regs-dmgABC"), Some(vec![Model::Dmg]));
+    assert_eq!(
+        mooneye_models("acceptance/boot_div-dmgABCmgb"),
+        Some(vec![Model::Dmg, Model::Mgb])
+    );
+    assert_eq!(
+        mooneye_models("ppu/intr_1_2_timing-GS"),
+        Some(vec![Model::Dmg, Model::Mgb, Model::Sgb, Model::Sgb2])
+    );
+    assert_eq!(mooneye_models("boot_regs-sgb2"), Some(vec![Model::Sgb2]));
+    assert_eq!(mooneye_models("boot_div-cgbABCDE"), Some(vec![Model::Cgb]));
+    // Only for models that aren't emulated.
+    assert_eq!(mooneye_models("boot_regs-A"), Some(vec![]));
+    assert_eq!(mooneye_models("boot_div-cgb0"), Some(vec![]));
+    // For every model; only the file name is looked at.
+    assert_eq!(mooneye_models("emulator-only/mbc1/rom_1Mb"), None);
+    assert_eq!(mooneye_models("ei_sequence"), None);
+    assert_eq!(
+        mooneye_models("emulator-only/boot-dmgABC"),
+        Some(vec![Model::Dmg])
+    );
+    assert_eq!(mooneye_models("boot-GS/ei_sequence"), None);
+    // A '-' without models after it.
+    assert_eq!(mooneye_models("oam_dma-timing"), None);
+    assert_eq!(mooneye_models("boot_regs-"), None);
+}
+
+#[test]
+fn reference_images() {
+    let mut h = harness(HarnessBuilder::from_rom(rom()));
+    h.run_until(StopCondition::Frames(3), 10 * FRAME_CYCLES);
+    let mut reference = RefImage {
+        width: 160,
+        height: 144,
+        rgba: h.framebuffer().to_vec(),
+    };
+    let report = h.compare(&reference).unwrap();
+    assert_eq!(
+        (report.matched, report.format_diff()),
+        (report.total, "exact".into())
+    );
+
+    let i = 4 * (10 * 160 + 20);
+    reference.rgba[i..i + 3].copy_from_slice(&[0x00, 0x00, 0x00]);
+    let report = h.compare(&reference).unwrap();
+    assert_eq!(report.first_diff, Some((20, 10)));
+    assert_eq!(report.matched, report.total - 1);
+    assert_eq!(report.heatmap.pixel(20, 10), [0xFF, 0x00, 0x00, 0xFF]);
+    assert_eq!(report.heatmap.pixel(0, 0), [0x3F, 0x3F, 0x3F, 0xFF]);
+
+    let path = std::env::temp_dir().join(format!("tetsuyu-diff-{}.png", std::process::id()));
+    report.write_heatmap(&path).unwrap();
+    let heatmap = RefImage::load_png(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!((heatmap.width, heatmap.height), (160, 144));
+    assert_eq!(heatmap.rgba, report.heatmap.data);
+
+    reference.width = 256;
+    assert!(h.compare(&reference).is_err());
+}
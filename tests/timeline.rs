@@ -0,0 +1,124 @@
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::debugger::timeline::{Event, Timeline};
+use tetsuyu::hw::interrupt::Interrupts;
+
+mod common;
+use common::{test_machine, test_rom};
+
+#[rustfmt::skip]
+const PROGRAM: [u8; 26] = [
+    0x3E, 0x91,       // $0150: LD A,$91
+    0xE0, 0x40,       // $0152: LDH [LCDC],A
+    0xF0, 0x44,       // $0154: .frame: LDH A,[LY]
+    0xFE, 0x40,       // $0156: CP 64
+    0x20, 0xFA,       // $0158: JR NZ,.frame
+    0x3E, 0x10,       // $015A: LD A,16
+    0xE0, 0x43,       // $015C: LDH [SCX],A
+    0x3E, 0xC0,       // $015E: LD A,$C0
+    0xE0, 0x46,       // $0160: LDH [DMA],A
+    0xF0, 0x44,       // $0162: .line: LDH A,[LY]
+    0xFE, 0x40,       // $0164: CP 64
+    0x28, 0xFA,       // $0166: JR Z,.line
+    0x18, 0xEA,       // $0168: JR .frame
+];
+
+fn record(skip: u32, frames: u32) -> Timeline {
+    let mut mb = test_machine(test_rom(&PROGRAM), GBMode::DMG);
+    mb.set_timeline(Some(Timeline::new(skip, frames)));
+    for _ in 0..(skip + frames + 2) * 70_224 / 4 {
+        if mb.timeline().unwrap().done() {
+            break;
+        }
+        mb.step();
+    }
+    let timeline = mb.set_timeline(None).unwrap();
+    assert!(timeline.done());
+    timeline
+}
+
+#[test]
+fn one_frame() {
+    let timeline = record(0, 1);
+    let records = timeline.records();
+
+    assert_eq!(records[0].event, Event::Line(0));
+    let lines: Vec<_> = records
+        .iter()
+        .filter_map(|r| match r.event {
+            Event::Line(ly) => Some(ly),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(lines, (0..=153).collect::<Vec<_>>());
+
+    // OAM scan, drawing and HBlank on each visible line, then VBlank.
+    let modes = |ly: u8| -> Vec<u8> {
+        records
+            .iter()
+            .filter(|r| r.ly == ly)
+            .filter_map(|r| match r.event {
+                Event::Mode(mode) => Some(mode),
+                _ => None,
+            })
+            .collect()
+    };
+    assert_eq!(modes(10), [2, 3, 0]);
+    assert_eq!(modes(144), [1]);
+
+    let vblank = records
+        .iter()
+        .find(|r| r.event == Event::Interrupt(Interrupts::V_BLANK))
+        .unwrap();
+    assert_eq!((vblank.ly, vblank.line_dot), (144, 0));
+
+    let scx = records
+        .iter()
+        .find(|r| {
+            r.event
+                == Event::Write {
+                    address: 0xFF43,
+                    value: 0x10,
+                }
+        })
+        .unwrap();
+    assert_eq!(scx.ly, 64);
+
+    let start = records
+        .iter()
+        .find(|r| r.event == Event::OamDmaStart(0xC000))
+        .unwrap();
+    let end = records
+        .iter()
+        .find(|r| r.event == Event::OamDmaEnd)
+        .unwrap();
+    // 160 bytes, one per M-cycle.
+    assert_eq!(end.dots - start.dots, 159 * 4);
+    assert!(start.dots > scx.dots);
+}
+
+#[test]
+fn skips_frames() {
+    let first = record(0, 1);
+    let third = record(2, 1);
+    assert_eq!(third.records().len(), first.records().len());
+    assert_eq!(
+        third.records()[0].dots - first.records()[0].dots,
+        2 * 70_224
+    );
+}
+
+#[test]
+fn chrome_trace_json() {
+    let json = record(0, 1).chrome_json();
+    assert!(json.starts_with("{\"traceEvents\":["));
+    assert!(json.ends_with("],\"displayTimeUnit\":\"ns\"}\n"));
+    assert!(json.contains(r#""name":"thread_name","args":{"name":"PPU mode"}"#));
+    assert!(json.contains(r#""ph":"X","pid":1,"tid":1,"name":"Mode 3 (Drawing)""#));
+    assert!(json.contains(r#""name":"LY 64""#));
+    assert!(json.contains(r#""ph":"i","s":"t","pid":1,"tid":3,"name":"vblank""#));
+    assert!(json.contains(r#""name":"SCX = $10""#));
+    assert!(json.contains(r#""name":"OAM DMA from $C000""#));
+    assert!(json.contains(r#""args":{"ly":144,"dot":0,"#));
+    assert_eq!(json.matches('{').count(), json.matches('}').count());
+    assert_eq!(json.matches('[').count(), json.matches(']').count());
+}
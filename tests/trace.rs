@@ -0,0 +1,101 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::debugger::trace::{TraceOptions, Tracer};
+use tetsuyu::hw::motherboard::Motherboard;
+
+mod common;
+use common::{test_machine, test_rom};
+
+#[rustfmt::skip]
+const PROGRAM: [u8; 9] = [
+    0x06, 0x03,       // $0150: LD B,3
+    0x05,             // $0152: .loop: DEC B
+    0x20, 0xFD,       // $0153: JR NZ,.loop
+    0x40,             // $0155: LD B,B
+    0x18, 0xFE,       // $0156: JR @
+    0x00,
+];
+
+fn machine() -> Motherboard {
+    test_machine(test_rom(&PROGRAM), GBMode::DMG)
+}
+
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn trace(options: TraceOptions) -> Vec<String> {
+    let out = Shared::default();
+    let mut mb = machine();
+    mb.set_tracer(Some(Tracer::new(Box::new(out.clone()), options)));
+    while !mb.magic_break() {
+        mb.step();
+    }
+    mb.set_tracer(None);
+
+    let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    text.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn gameboy_doctor_format() {
+    let lines = trace(TraceOptions::default());
+    // The boot ROM is left out.
+    assert_eq!(
+        lines[0],
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0004 PCMEM:C3,50,01,00"
+    );
+    assert_eq!(
+        lines[1],
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0150 PCMEM:06,03,05,20"
+    );
+    assert!(lines[3].starts_with("A:01 F:50 B:02 "), "{}", lines[3]);
+    assert!(lines.last().unwrap().contains("PC:0155 PCMEM:40,18,FE,00"));
+    assert_eq!(lines.len(), 9);
+}
+
+#[test]
+fn range_and_extras() {
+    let lines = trace(TraceOptions {
+        start: 0x0152,
+        end: 0x0153,
+        cycles: true,
+        ly: true,
+        rom_bank: true,
+        ..TraceOptions::default()
+    });
+    assert_eq!(lines.len(), 6);
+    assert!(
+        lines
+            .iter()
+            .all(|l| l.contains("PC:0152") || l.contains("PC:0153"))
+    );
+    assert!(lines[0].ends_with(" LY:00 BANK:01"), "{}", lines[0]);
+
+    let cycles: Vec<u64> = lines
+        .iter()
+        .map(|l| {
+            l.split(" CY:")
+                .nth(1)
+                .unwrap()
+                .split(' ')
+                .next()
+                .unwrap()
+                .parse()
+                .unwrap()
+        })
+        .collect();
+    // DEC B takes 4 cycles and a taken JR NZ 12.
+    assert_eq!(cycles[1] - cycles[0], 4);
+    assert_eq!(cycles[2] - cycles[1], 12);
+}
@@ -0,0 +1,168 @@
+use tetsuyu::components::mode::GBMode;
+use tetsuyu::config::Config;
+use tetsuyu::hw::motherboard::Motherboard;
+
+mod common;
+use common::{run_to_program, test_machine, test_rom};
+
+const RED: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+
+/// A machine past the boot ROM, spinning at $0150 with the LCD off so VRAM
+/// and OAM can be written freely.
+fn machine(mode: GBMode) -> Motherboard {
+    let mut mb = test_machine(test_rom(&[0x18, 0xFE]), mode); // JR @
+    run_to_program(&mut mb);
+    mb.write_bus(0xFF40, 0x00);
+    mb
+}
+
+fn write(mb: &mut Motherboard, address: u16, data: &[u8]) {
+    for (i, &byte) in data.iter().enumerate() {
+        mb.write_bus(address + i as u16, byte);
+    }
+}
+
+/// Tile 1: colour 1 across the top row, colour 2 across the next, and a
+/// single colour 3 pixel at the left of the third.
+const TILE: [u8; 6] = [0xFF, 0x00, 0x00, 0xFF, 0x80, 0x80];
+
+#[test]
+fn dmg_tiles_and_bg_map() {
+    let mut mb = machine(GBMode::DMG);
+    let palette = Config::default().ppu_config.palette;
+    let shade = |color: tetsuyu::config::Color| [color.r(), color.g(), color.b(), 0xFF];
+
+    write(&mut mb, 0x8010, &TILE);
+    write(&mut mb, 0x8800, &TILE);
+    mb.write_bus(0x9801, 0x01);
+    mb.write_bus(0xFF42, 100); // SCY
+    mb.write_bus(0xFF43, 100); // SCX
+    mb.write_bus(0xFF47, 0x1B); // BGP, reversed
+    mb.write_bus(0xFF40, 0x11);
+
+    let tiles = mb.video().tiles();
+    assert_eq!((tiles.width, tiles.height), (256, 192));
+    // The sheet ignores BGP.
+    assert_eq!(tiles.pixel(8, 0), shade(palette.light_gray));
+    assert_eq!(tiles.pixel(15, 1), shade(palette.dark_gray));
+    assert_eq!(tiles.pixel(8, 2), shade(palette.dark));
+    assert_eq!(tiles.pixel(9, 2), shade(palette.light));
+    // $8800 is tile 128, the first of the ninth row.
+    assert_eq!(tiles.pixel(0, 64), shade(palette.light_gray));
+
+    let map = mb.video().bg_map();
+    assert_eq!((map.width, map.height), (256, 256));
+    assert_eq!(map.pixel(8, 0), shade(palette.dark_gray));
+    assert_eq!(map.pixel(8, 2), shade(palette.light));
+    assert_eq!(map.pixel(0, 0), shade(palette.dark));
+    // The viewport, wrapping around to the left and top.
+    assert_eq!(map.pixel(100, 150), RED);
+    assert_eq!(map.pixel(3, 150), RED);
+    assert_eq!(map.pixel(150, 243), RED);
+    assert_ne!(map.pixel(4, 150), RED);
+
+    // With $8800 addressing, index $80 is the tile at $8800.
+    mb.write_bus(0x9801, 0x80);
+    mb.write_bus(0xFF40, 0x01);
+    assert_eq!(mb.video().bg_map().pixel(8, 0), shade(palette.dark_gray));
+}
+
+#[test]
+fn window_viewport() {
+    let mut mb = machine(GBMode::DMG);
+    mb.write_bus(0xFF4A, 72); // WY
+    mb.write_bus(0xFF4B, 87); // WX
+    mb.write_bus(0xFF40, 0x41);
+    assert!(!mb.video().window_map().data.chunks(4).any(|p| p == RED));
+
+    mb.write_bus(0xFF40, 0x61);
+    let map = mb.video().window_map();
+    assert_eq!(map.pixel(0, 10), RED);
+    assert_eq!(map.pixel(79, 10), RED);
+    assert_eq!(map.pixel(40, 71), RED);
+    assert_ne!(map.pixel(80, 10), RED);
+    assert_ne!(map.pixel(40, 72), RED);
+}
+
+#[test]
+fn cgb_attributes_sprites_and_palettes() {
+    let mut mb = machine(GBMode::CGB);
+    // BG palette 2, colour 3: red; OBJ palette 3, colour 1: blue.
+    mb.write_bus(0xFF68, 0x80 | (2 * 8 + 6));
+    write(&mut mb, 0xFF69, &[0x1F]);
+    write(&mut mb, 0xFF69, &[0x00]);
+    mb.write_bus(0xFF6A, 0x80 | (3 * 8 + 2));
+    write(&mut mb, 0xFF6B, &[0x00]);
+    write(&mut mb, 0xFF6B, &[0x7C]);
+
+    // Tile 1 in bank 1, flipped horizontally in palette 2.
+    mb.write_bus(0xFF4F, 1);
+    write(&mut mb, 0x8010, &TILE);
+    mb.write_bus(0x9800, 0x08 | 0x20 | 0x02);
+    mb.write_bus(0xFF4F, 0);
+    mb.write_bus(0x9800, 0x01);
+
+    // Sprite 0: that tile, flipped vertically in palette 3.
+    write(&mut mb, 0xFE00, &[16, 8, 0x01, 0x40 | 0x08 | 0x03]);
+    // Keep the viewport outline out of the way.
+    mb.write_bus(0xFF42, 128); // SCY
+    mb.write_bus(0xFF43, 128); // SCX
+    mb.write_bus(0xFF40, 0x13);
+
+    let video = mb.video();
+    assert_eq!(video.cgb_raw(false, 2, 3), 0x001F);
+    assert_eq!(video.cgb_raw(true, 3, 1), 0x7C00);
+    let palettes = video.palettes();
+    assert_eq!((palettes.width, palettes.height), (72, 64));
+    let red = palettes.pixel(24, 16);
+    let blue = palettes.pixel(40 + 8, 24);
+    assert!(red[0] > 0xC0 && red[2] < 0x40);
+    assert!(blue[2] > 0xC0 && blue[0] < 0x40);
+
+    let tiles = video.tiles();
+    // Tile 1 is only in bank 1, on the right.
+    assert_eq!(tiles.pixel(8, 2), tiles.pixel(0, 0));
+    assert_ne!(tiles.pixel(128 + 8, 2), tiles.pixel(0, 0));
+
+    let map = video.bg_map();
+    assert_eq!(map.pixel(7, 2), red);
+    assert_eq!(map.pixel(0, 2), palettes.pixel(0, 16));
+
+    let sprites = video.sprites();
+    assert_eq!(sprites.len(), 40);
+    let sprite = sprites[0];
+    assert!(sprite.y_flip() && !sprite.x_flip() && !sprite.behind_bg());
+    assert_eq!((sprite.bank(), sprite.cgb_palette()), (1, 3));
+    assert!(
+        sprite
+            .to_string()
+            .starts_with("00  Y= 16 X=  8  tile=$01  attr=$4B  -V-")
+    );
+
+    let oam = video.oam();
+    assert_eq!((oam.width, oam.height), (64, 80));
+    // The top row, flipped to the bottom; colour 0 is see-through.
+    assert_eq!(oam.pixel(0, 7), blue);
+    assert_eq!(oam.pixel(0, 0)[3], 0);
+    assert_eq!(oam.pixel(8, 0)[3], 0);
+}
+
+#[test]
+fn dump_writes_pngs() {
+    let mb = machine(GBMode::DMG);
+    let dir = std::env::temp_dir().join(format!("tetsuyu-vram-{}", std::process::id()));
+    mb.video().dump(&dir).unwrap();
+    for name in [
+        "tiles.png",
+        "bg_map.png",
+        "window_map.png",
+        "oam.png",
+        "palettes.png",
+    ] {
+        let data = std::fs::read(dir.join(name)).unwrap();
+        assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n", "{}", name);
+    }
+    let table = std::fs::read_to_string(dir.join("oam.txt")).unwrap();
+    assert_eq!(table.lines().count(), 40);
+    std::fs::remove_dir_all(&dir).unwrap();
+}